@@ -0,0 +1,3 @@
+-- "To:" lines for a customer, used when a quotation / proforma names the customer on its own
+
+ALTER TABLE customer_profiles ADD COLUMN address JSONB;
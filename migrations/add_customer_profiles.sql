@@ -0,0 +1,13 @@
+-- Standing terms for regular customers, filled into their quotations / proformas.
+-- customer_key is a lower-cased customer name, an E.164 phone number or "telegram:<id>"
+
+CREATE TABLE customer_profiles (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    customer_key TEXT UNIQUE NOT NULL,
+    terms_and_conditions JSONB,
+    discount REAL,
+    delivery_charges REAL,
+    estimated_delivery TEXT,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
@@ -0,0 +1,10 @@
+-- Every freshly scraped metal price, so today's rate can be compared with yesterday's close
+
+CREATE TABLE metal_prices (
+    id BIGSERIAL PRIMARY KEY,
+    metal TEXT NOT NULL,
+    price DOUBLE PRECISION NOT NULL,
+    fetched_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_metal_prices_metal_fetched_at ON metal_prices(metal, fetched_at);
@@ -0,0 +1,19 @@
+-- Quotations / proformas above quotation.approval_threshold, held until an admin approves them.
+-- recipient is a Telegram chat id or a "whatsapp:+91..." number on the request's platform
+
+CREATE TABLE quotation_approvals (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id),
+    platform TEXT NOT NULL,
+    recipient TEXT NOT NULL,
+    document_reference TEXT NOT NULL,
+    grand_total REAL NOT NULL,
+    text TEXT NOT NULL,
+    files JSONB NOT NULL DEFAULT '[]',
+    status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'approved', 'rejected')),
+    decided_by TEXT,
+    decided_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE INDEX idx_quotation_approvals_status ON quotation_approvals(status);
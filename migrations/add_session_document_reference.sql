@@ -0,0 +1,5 @@
+-- Quotation / proforma number (eg. Q-20250821-1234) generated by each session, for support lookups
+
+ALTER TABLE query_sessions ADD COLUMN document_reference TEXT;
+
+CREATE INDEX idx_query_sessions_document_reference ON query_sessions(document_reference);
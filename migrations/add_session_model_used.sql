@@ -0,0 +1,5 @@
+-- Track which LLM produced the final parse for each session (after any fallback)
+
+ALTER TABLE query_sessions ADD COLUMN model_used TEXT;
+
+CREATE INDEX idx_query_sessions_created_at ON query_sessions(created_at);
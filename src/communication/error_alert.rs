@@ -1,52 +1,253 @@
+use crate::communication::quiet_hours::QuietHours;
 use crate::configuration::Context;
+use crate::core::alert::{Alert, AlertCategory};
 use crate::core::service_manager::{Error as ServiceManagerError, ServiceWithReceiver};
 use async_trait::async_trait;
 use dotenvy::dotenv;
+use std::collections::HashMap;
 use std::env;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use teloxide::prelude::*;
 use tokio::sync::{mpsc, Mutex};
 use tracing::error;
 
+// How often held and repeated alerts are checked for flushing
+const ALERT_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
 pub struct ErrorAlertService {
     bot: Bot,
-    receiver: Option<Arc<Mutex<mpsc::Receiver<String>>>>,
+    receiver: Option<Arc<Mutex<mpsc::Receiver<Alert>>>>,
     channel_id: i64,
+    cost_channel_id: i64,
+    quiet_hours: Option<QuietHours>,
+    dedup_window: Duration,
 }
 
 #[async_trait]
 impl ServiceWithReceiver for ErrorAlertService {
     type Context = Context;
+    type Message = Alert;
 
-    async fn new(context: Context, receiver: Option<Arc<Mutex<mpsc::Receiver<String>>>>) -> Self {
+    async fn new(context: Context, receiver: Option<Arc<Mutex<mpsc::Receiver<Alert>>>>) -> Self {
         dotenv().ok();
         let error_bot_token = env::var("ERROR_BOT_TOKEN").expect("ERROR_BOT_TOKEN not found");
         let bot = Bot::new(error_bot_token);
         let channel_id = context.config.telegram.error_channel_id;
+        let cost_channel_id = context
+            .config
+            .telegram
+            .cost_channel_id
+            .unwrap_or(channel_id);
+        let quiet_hours = QuietHours::from_config(context.config.quiet_hours.as_ref());
 
         Self {
             bot,
             receiver,
             channel_id,
+            cost_channel_id,
+            quiet_hours,
+            dedup_window: Duration::from_secs(context.config.telegram.error_dedup_window_secs),
         }
     }
 
     async fn run(self) -> Result<(), ServiceManagerError> {
         if let Some(receiver) = &self.receiver {
+            let mut held_alerts = 0usize;
+            let mut deduplicator = AlertDeduplicator::new(self.dedup_window);
+            let mut check_interval = tokio::time::interval(ALERT_FLUSH_INTERVAL);
             loop {
-                let mut rx = receiver.lock().await;
-                if let Some(error_message) = rx.recv().await {
-                    drop(rx);
-                    if let Err(e) = self
-                        .bot
-                        .send_message(ChatId(self.channel_id), &error_message)
-                        .await
-                    {
-                        error!(error = %e, "Failed to send error alert");
+                let received = {
+                    let mut rx = receiver.lock().await;
+                    tokio::select! {
+                        message = rx.recv() => message,
+                        _ = check_interval.tick() => None,
+                    }
+                };
+
+                if let Some(alert) = received {
+                    if self.is_quiet() && !alert.needs_attention() {
+                        held_alerts += 1;
+                        continue;
                     }
+                    // Informational alerts are distinct by nature so only failures are collapsed
+                    if !alert.needs_attention() || deduplicator.record(&alert, Instant::now()) {
+                        self.send(&alert).await;
+                    }
+                }
+
+                for summary in deduplicator.drain_expired(Instant::now()) {
+                    self.send(&summary).await;
+                }
+
+                if held_alerts > 0 && !self.is_quiet() {
+                    self.send(&Alert::info(format!(
+                        "🌙 {} non-critical alerts were held during quiet hours",
+                        held_alerts
+                    )))
+                    .await;
+                    held_alerts = 0;
                 }
             }
         }
         Ok(())
     }
 }
+
+impl ErrorAlertService {
+    fn is_quiet(&self) -> bool {
+        self.quiet_hours
+            .as_ref()
+            .is_some_and(|quiet_hours| quiet_hours.is_active())
+    }
+
+    // Only alerts that need attention notify the people watching the channel
+    async fn send(&self, alert: &Alert) {
+        let channel_id = match alert.category {
+            AlertCategory::Error => self.channel_id,
+            AlertCategory::Usage => self.cost_channel_id,
+        };
+        if let Err(e) = self
+            .bot
+            .send_message(ChatId(channel_id), alert.to_string())
+            .disable_notification(!alert.needs_attention())
+            .await
+        {
+            error!(error = %e, "Failed to send error alert");
+        }
+    }
+}
+
+// Collapses bursts of the same error - the first occurrence is sent straight away and any
+// repeats within the window are reported once as a count when the window closes
+struct AlertDeduplicator {
+    window: Duration,
+    recent: HashMap<String, RecentAlert>,
+}
+
+struct RecentAlert {
+    alert: Alert,
+    first_seen: Instant,
+    repeats: u32,
+}
+
+impl AlertDeduplicator {
+    fn new(window: Duration) -> Self {
+        Self {
+            window,
+            recent: HashMap::new(),
+        }
+    }
+
+    // Returns true when the alert should be sent now
+    fn record(&mut self, alert: &Alert, now: Instant) -> bool {
+        if self.window.is_zero() {
+            return true;
+        }
+        let key = similarity_key(&alert.message);
+        match self.recent.get_mut(&key) {
+            Some(recent) if now.duration_since(recent.first_seen) < self.window => {
+                recent.repeats += 1;
+                false
+            }
+            _ => {
+                self.recent.insert(
+                    key,
+                    RecentAlert {
+                        alert: alert.clone(),
+                        first_seen: now,
+                        repeats: 0,
+                    },
+                );
+                true
+            }
+        }
+    }
+
+    // Summaries for windows that have closed with repeats in them
+    fn drain_expired(&mut self, now: Instant) -> Vec<Alert> {
+        let window = self.window;
+        let expired: Vec<String> = self
+            .recent
+            .iter()
+            .filter(|(_, recent)| now.duration_since(recent.first_seen) >= window)
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        expired
+            .into_iter()
+            .filter_map(|key| self.recent.remove(&key))
+            .filter(|recent| recent.repeats > 0)
+            .map(|recent| {
+                Alert::new(
+                    recent.alert.severity,
+                    format!(
+                        "🔁 ×{} in the last {}s: {}",
+                        recent.repeats + 1,
+                        window.as_secs(),
+                        recent.alert.message
+                    ),
+                )
+            })
+            .collect()
+    }
+}
+
+// Digits are masked so messages differing only in ids, counts or timings count as the same
+fn similarity_key(message: &str) -> String {
+    message
+        .chars()
+        .map(|c| if c.is_ascii_digit() { '#' } else { c })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::alert::Severity;
+
+    #[test]
+    fn test_repeats_within_window_are_collapsed() {
+        let mut deduplicator = AlertDeduplicator::new(Duration::from_secs(60));
+        let start = Instant::now();
+        let alert = Alert::error("Groq API error: 503");
+
+        assert!(deduplicator.record(&alert, start));
+        for i in 1..14 {
+            assert!(!deduplicator.record(&alert, start + Duration::from_secs(i)));
+        }
+        assert!(deduplicator
+            .drain_expired(start + Duration::from_secs(30))
+            .is_empty());
+
+        let summaries = deduplicator.drain_expired(start + Duration::from_secs(60));
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].severity, Severity::Error);
+        assert_eq!(
+            summaries[0].message,
+            "🔁 ×14 in the last 60s: Groq API error: 503"
+        );
+        assert!(deduplicator.record(&alert, start + Duration::from_secs(61)));
+    }
+
+    #[test]
+    fn test_similar_messages_share_a_window() {
+        let mut deduplicator = AlertDeduplicator::new(Duration::from_secs(60));
+        let start = Instant::now();
+
+        assert!(deduplicator.record(&Alert::error("Stock request 12 timed out"), start));
+        assert!(!deduplicator.record(&Alert::error("Stock request 13 timed out"), start));
+        assert!(deduplicator.record(&Alert::error("Failed to save conversation message"), start));
+    }
+
+    #[test]
+    fn test_single_alert_has_no_summary() {
+        let mut deduplicator = AlertDeduplicator::new(Duration::from_secs(60));
+        let start = Instant::now();
+
+        assert!(deduplicator.record(&Alert::critical("Database unreachable"), start));
+        assert!(deduplicator
+            .drain_expired(start + Duration::from_secs(120))
+            .is_empty());
+    }
+}
@@ -1,5 +1,5 @@
-use crate::communication::telegram::Response;
-use crate::query::QueryError;
+use crate::query::{QueryError, QueryResponse};
+use crate::quotation::QuotationError;
 
 pub fn map_query_error_to_user_message(error: &QueryError) -> String {
     match error {
@@ -9,17 +9,41 @@ pub fn map_query_error_to_user_message(error: &QueryError) -> String {
         QueryError::QuotationServiceError => {
             "Error generating quotation - please check whether items are valid".to_string()
         }
+        QueryError::QuotationFailed(QuotationError::NoItems) => {
+            "No items to quote - please list the items with their specifications".to_string()
+        }
+        QueryError::QuotationFailed(QuotationError::NoItemsPriced(items)) => format!(
+            "I couldn't find prices for any of the items: {} - please check item/specifications",
+            items.join(", ")
+        ),
+        QueryError::QuotationFailed(QuotationError::UnpricedItems(items)) => format!(
+            "I couldn't find prices for: {} - please check item/specifications",
+            items.join(", ")
+        ),
+        QueryError::QuotationFailed(QuotationError::ImplausibleAmount(reason)) => format!(
+            "This quotation looks wrong ({}) - please re-check the items, quantities and prices",
+            reason
+        ),
+        QueryError::QuotationFailed(QuotationError::InvalidItemParameters(reason)) => format!(
+            "Invalid parameters ({}) - discounts and loadings are fractions eg. 0.1 for 10%",
+            reason
+        ),
+        QueryError::QuotationFailed(QuotationError::InvalidCharges(reason)) => format!(
+            "Invalid charges ({}) - please re-check the packing & forwarding charges",
+            reason
+        ),
         QueryError::LLMError(_) => "Unable to understand query correctly".to_string(),
         QueryError::OcrError(_) => "Could not process image - please try again with clearer image".to_string(),
         QueryError::TranscriptionError(_) => "Could not process audio - please try again with clearer audio".to_string(),
+        QueryError::Timeout(_) => "This is taking too long - please try again".to_string(),
+        QueryError::MediaTooLarge(_, limit) => format!(
+            "That file is too large - please send media under {} MB",
+            limit.div_ceil(1024 * 1024)
+        ),
         _ => "Could not service request - please try again later".to_string(),
     }
 }
 
-pub fn create_error_response(error: &QueryError) -> Response {
-    Response {
-        text: map_query_error_to_user_message(error),
-        file: None,
-        query_metadata: None,
-    }
+pub fn create_error_response(error: &QueryError) -> QueryResponse {
+    QueryResponse::new(map_query_error_to_user_message(error))
 }
\ No newline at end of file
@@ -1,6 +1,7 @@
 pub mod error_alert;
 pub mod error_handler;
 pub mod price_alert;
+pub mod quiet_hours;
 pub mod session_helpers;
 pub mod telegram;
 pub mod websocket;
@@ -1,3 +1,4 @@
+use crate::communication::quiet_hours::{QuietHours, QUIET_HOURS_CHECK_INTERVAL};
 use crate::configuration::Context;
 use crate::core::http::RetryableClient;
 use crate::core::service_manager::{Error as ServiceManagerError, ServiceWithReceiver};
@@ -20,6 +21,28 @@ pub struct PriceAlert {
     pub timestamp: String,
     pub copper_price: f64,
     pub aluminum_price: f64,
+    // Percentage moves since the previous alert, absent for the first one
+    #[serde(default)]
+    pub copper_change_percent: Option<f64>,
+    #[serde(default)]
+    pub aluminum_change_percent: Option<f64>,
+}
+
+impl PriceAlert {
+    pub fn copper_text(&self) -> String {
+        price_with_change(self.copper_price, self.copper_change_percent)
+    }
+
+    pub fn aluminum_text(&self) -> String {
+        price_with_change(self.aluminum_price, self.aluminum_change_percent)
+    }
+}
+
+fn price_with_change(price: f64, change_percent: Option<f64>) -> String {
+    match change_percent {
+        Some(change) => format!("Rs. {:.2} ({:+.2}%)", price, change),
+        None => format!("Rs. {:.2}", price),
+    }
 }
 
 pub struct PriceAlertService {
@@ -34,11 +57,13 @@ pub struct PriceAlertService {
     twilio_from_number: String,
     template_sid: String,
     database: Arc<DatabaseService>,
+    quiet_hours: Option<QuietHours>,
 }
 
 #[async_trait]
 impl ServiceWithReceiver for PriceAlertService {
     type Context = Context;
+    type Message = String;
 
     async fn new(context: Context, receiver: Option<Arc<Mutex<mpsc::Receiver<String>>>>) -> Self {
         let bot = Bot::from_env();
@@ -58,19 +83,34 @@ impl ServiceWithReceiver for PriceAlertService {
             twilio_from_number: whatsapp_config.twilio_from_number.clone(),
             template_sid: whatsapp_config.template_sid.clone(),
             database: context.database.clone(),
+            quiet_hours: QuietHours::from_config(context.config.quiet_hours.as_ref()),
         }
     }
 
     async fn run(self) -> Result<(), ServiceManagerError> {
         if let Some(receiver) = &self.receiver {
+            // Only the latest price matters, so quiet hours keep one alert to send in the morning
+            let mut held_alert: Option<PriceAlert> = None;
+            let mut check_interval = tokio::time::interval(QUIET_HOURS_CHECK_INTERVAL);
             loop {
-                let mut rx = receiver.lock().await;
-                if let Some(message) = rx.recv().await {
-                    drop(rx);
-
+                let received = {
+                    let mut rx = receiver.lock().await;
+                    tokio::select! {
+                        message = rx.recv() => message,
+                        _ = check_interval.tick() => None,
+                    }
+                };
+
+                if let Some(message) = received {
                     let alert: PriceAlert = serde_json::from_str(&message)
                         .map_err(|_| ServiceManagerError::new("Failed to parse price alert"))?;
+                    held_alert = Some(alert);
+                }
 
+                if self.is_quiet() {
+                    continue;
+                }
+                if let Some(alert) = held_alert.take() {
                     // Send to Telegram subscribers
                     self.send_telegram_alerts(&alert).await;
 
@@ -84,10 +124,18 @@ impl ServiceWithReceiver for PriceAlertService {
 }
 
 impl PriceAlertService {
+    fn is_quiet(&self) -> bool {
+        self.quiet_hours
+            .as_ref()
+            .is_some_and(|quiet_hours| quiet_hours.is_active())
+    }
+
     async fn send_telegram_alerts(&self, alert: &PriceAlert) {
         let message = format!(
-            "🔔 Metal Price Update\n  {}\n\n🟤 Copper: Rs. {:.2}\n⚪ Aluminium: Rs. {:.2}",
-            alert.timestamp, alert.copper_price, alert.aluminum_price
+            "🔔 Metal Price Update\n  {}\n\n🟤 Copper: {}\n⚪ Aluminium: {}",
+            alert.timestamp,
+            alert.copper_text(),
+            alert.aluminum_text()
         );
 
         for &chat_id in &self.telegram_subscribers {
@@ -122,8 +170,8 @@ impl PriceAlertService {
             "ContentSid": self.template_sid,
             "ContentVariables": json!({
                 "1": alert.timestamp,
-                "2": format!("Rs. {:.2}", alert.copper_price),
-                "3": format!("Rs. {:.2}", alert.aluminum_price)
+                "2": alert.copper_text(),
+                "3": alert.aluminum_text()
             }).to_string()
         });
 
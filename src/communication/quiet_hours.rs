@@ -0,0 +1,75 @@
+use crate::configuration::QuietHoursConfig;
+use chrono::{Timelike, Utc};
+use chrono_tz::Asia::Kolkata;
+use std::time::Duration;
+
+// How often alert services check whether a quiet window has ended
+pub const QUIET_HOURS_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone)]
+pub struct QuietHours {
+    start_hour: u32,
+    end_hour: u32,
+}
+
+impl QuietHours {
+    pub fn from_config(config: Option<&QuietHoursConfig>) -> Option<Self> {
+        config.map(|config| Self {
+            start_hour: config.start_hour % 24,
+            end_hour: config.end_hour % 24,
+        })
+    }
+
+    pub fn contains(&self, hour: u32) -> bool {
+        if self.start_hour <= self.end_hour {
+            hour >= self.start_hour && hour < self.end_hour
+        } else {
+            hour >= self.start_hour || hour < self.end_hour
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.contains(Utc::now().with_timezone(&Kolkata).hour())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Alert;
+
+    fn quiet_hours(start_hour: u32, end_hour: u32) -> QuietHours {
+        QuietHours::from_config(Some(&QuietHoursConfig {
+            start_hour,
+            end_hour,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_window_wrapping_midnight() {
+        let window = quiet_hours(22, 7);
+        assert!(window.contains(22));
+        assert!(window.contains(0));
+        assert!(window.contains(6));
+        assert!(!window.contains(7));
+        assert!(!window.contains(12));
+    }
+
+    #[test]
+    fn test_window_within_day() {
+        let window = quiet_hours(13, 15);
+        assert!(window.contains(13));
+        assert!(window.contains(14));
+        assert!(!window.contains(15));
+        assert!(!window.contains(9));
+    }
+
+    // Quiet hours hold back what doesn't need attention - the usage reports, not failures
+    #[test]
+    fn test_cost_notifications_are_not_critical() {
+        assert!(!Alert::usage("💰 Query Cost Alert\n\nPlatform: telegram").needs_attention());
+        assert!(!Alert::usage("LLM query parsing took: 1.20s").needs_attention());
+        assert!(Alert::error("Scheduled conversation purge failed: timeout").needs_attention());
+    }
+}
@@ -1,6 +1,6 @@
-use crate::communication::telegram::Response;
-use crate::database::{DatabaseService, SessionContext, SessionResult, User};
-use crate::query::QueryError;
+use crate::core::Alert;
+use crate::database::{DatabaseService, QuotationApproval, SessionContext, SessionResult, User};
+use crate::query::{QueryError, QueryResponse};
 use std::sync::Arc;
 use tokio::sync::mpsc;
 
@@ -12,12 +12,27 @@ pub fn create_whatsapp_session_context(user: &User, phone: &str) -> SessionConte
     SessionContext::new(user.id, "whatsapp").with_phone(phone.to_string())
 }
 
+// Sent with the documents of a held quotation once an admin approves it
+pub fn approval_released_text(approval: &QuotationApproval) -> String {
+    format!(
+        "✅ {} approved\n{}",
+        approval.document_reference, approval.text
+    )
+}
+
+pub fn approval_rejected_text(approval: &QuotationApproval) -> String {
+    format!(
+        "❌ {} was not approved. Please get in touch with us to discuss it.",
+        approval.document_reference
+    )
+}
+
 pub async fn create_session_or_error(
     database: &Arc<DatabaseService>,
     context: &SessionContext,
     query_text: &str,
     query_type: &str,
-    error_sender: &mpsc::Sender<String>,
+    error_sender: &mpsc::Sender<Alert>,
 ) -> Result<(), ()> {
     if database
         .create_session_with_context(context, query_text, query_type)
@@ -25,7 +40,7 @@ pub async fn create_session_or_error(
         .is_err()
     {
         let _ = error_sender
-            .send("Failed to create session".to_string())
+            .send(Alert::critical("Failed to create session"))
             .await;
         return Err(());
     }
@@ -38,10 +53,10 @@ pub async fn complete_session_with_error(
     error: &QueryError,
     query_text: &str,
     start_time: std::time::Instant,
-    error_sender: &mpsc::Sender<String>,
+    error_sender: &mpsc::Sender<Alert>,
 ) {
-    let error_msg = format!("❌ Query Failed\n\nQuery: {}\nError: {}", query_text, error);
-    let _ = error_sender.send(error_msg).await;
+    let error_msg = format!("Query Failed\n\nQuery: {}\nError: {}", query_text, error);
+    let _ = error_sender.send(Alert::error(error_msg)).await;
 
     let result = SessionResult {
         success: false,
@@ -58,10 +73,10 @@ pub async fn complete_session_with_error(
 pub async fn complete_session_with_success(
     database: &Arc<DatabaseService>,
     context: &SessionContext,
-    response: &Response,
+    response: &QueryResponse,
     query_text: &str,
     start_time: std::time::Instant,
-    error_sender: &mpsc::Sender<String>,
+    error_sender: &mpsc::Sender<Alert>,
 ) {
     let result = SessionResult {
         success: true,
@@ -1,24 +1,49 @@
 use crate::communication::error_handler::create_error_response;
 use crate::communication::session_helpers::{
-    complete_session_with_error, complete_session_with_success, create_session_context,
-    create_session_or_error,
+    approval_rejected_text, approval_released_text, complete_session_with_error,
+    complete_session_with_success, create_session_context, create_session_or_error,
 };
+use crate::core::http::{retry_stats, HostRetryStats};
 use crate::core::service_manager::{Error as ServiceManagerError, ServiceWithErrorSender};
+use crate::core::Alert;
 use crate::database::DatabaseService;
-use crate::database::SessionContext;
-use crate::query::QueryError;
+use crate::database::{
+    ApprovalStatus, CustomerProfile, ModelUsage, QuotationApproval, SessionContext, User,
+};
+use crate::pdf::{format_amount, release_document, replace_header_image};
+use crate::query::{
+    check_media_size, default_load_test_request, QueryError, QueryResponse,
+    MAX_LOAD_TEST_CONCURRENCY, MAX_LOAD_TEST_REQUESTS, PENDING_APPROVAL_KEY,
+};
+use crate::quotation::QuotationRequest;
 use crate::{configuration::Context, query::QueryFulfilment};
 use async_trait::async_trait;
+use std::collections::BTreeMap;
 use std::fs;
+use std::future::IntoFuture;
 use std::sync::Arc;
+use std::time::Duration;
 use teloxide::net::Download;
 use teloxide::prelude::*;
 use teloxide::types::InputFile;
 use teloxide::types::PhotoSize;
+use teloxide::types::{
+    CallbackQuery, InlineKeyboardButton, InlineKeyboardMarkup, MessageId, Update,
+};
+use teloxide::RequestError;
 use thiserror::Error;
-use tokio::sync::mpsc;
 use tokio::sync::mpsc::Sender;
-use tracing::error;
+use tokio::sync::{broadcast, mpsc};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+// Sent as the caption of a photo / JPEG file by an admin to replace the PDF letterhead
+const SET_HEADER_COMMAND: &str = "/setheader";
+// Follow-up used by /prompt when the admin doesn't give one
+const DEFAULT_PROMPT_PREVIEW_QUERY: &str = "make it 200 M and add 50 M of 4C x 4 sqmm armoured";
+// Callback data of the admin's buttons on a held quotation, followed by the approval id
+const APPROVE_CALLBACK_PREFIX: &str = "approval:approve:";
+const REJECT_CALLBACK_PREFIX: &str = "approval:reject:";
 
 #[derive(Debug, Error)]
 pub enum TelegramError {
@@ -28,26 +53,47 @@ pub enum TelegramError {
     QueryFulfilmentInitError(String),
     #[error("Image processing error: {0}")]
     ImageProcessingError(String),
+    #[error("Media of {0} bytes is over the {1} byte limit")]
+    MediaTooLarge(u64, u64),
 }
 
 pub struct TelegramService {
     bot: Bot,
     query_fulfilment: QueryFulfilment,
-    error_sender: mpsc::Sender<String>,
+    error_sender: mpsc::Sender<Alert>,
     database: Arc<DatabaseService>,
+    approvals: broadcast::Sender<QuotationApproval>,
+    // Subscribed up front so no approval requested while the bot starts up is missed
+    approval_events: broadcast::Receiver<QuotationApproval>,
 }
 
+#[derive(Default)]
 pub struct Response {
     pub text: String,
     pub file: Option<String>,
     pub query_metadata: Option<serde_json::Value>,
+    // Sent as further documents after `file`
+    pub attachments: Vec<String>,
+}
+
+// Follow-up buttons come from the query metadata (see follow_up_keyboard), so the QueryResponse
+// buttons aren't carried over
+impl From<QueryResponse> for Response {
+    fn from(response: QueryResponse) -> Self {
+        Self {
+            text: response.text,
+            file: response.file,
+            query_metadata: response.query_metadata,
+            attachments: response.attachments,
+        }
+    }
 }
 
 #[async_trait]
 impl ServiceWithErrorSender for TelegramService {
     type Context = Context;
 
-    async fn new(context: Context, error_sender: mpsc::Sender<String>) -> Self {
+    async fn new(context: Context, error_sender: mpsc::Sender<Alert>) -> Self {
         let query_fulfilment = QueryFulfilment::new(context.clone()).await.unwrap();
         let bot = Bot::from_env();
 
@@ -56,6 +102,8 @@ impl ServiceWithErrorSender for TelegramService {
             query_fulfilment,
             error_sender,
             database: context.database.clone(),
+            approvals: context.approvals.clone(),
+            approval_events: context.approvals.subscribe(),
         }
     }
 
@@ -63,22 +111,60 @@ impl ServiceWithErrorSender for TelegramService {
         let query_fulfilment = Arc::new(self.query_fulfilment);
         let error_sender = Arc::new(self.error_sender);
         let database = self.database;
-        teloxide::repl(self.bot, move |bot: Bot, msg: Message| {
-            let query_fulfilment = Arc::clone(&query_fulfilment);
-            let error_sender = Arc::clone(&error_sender);
-            let database = Arc::clone(&database);
-            async move {
-                tokio::spawn(Self::handle_message(
-                    bot,
-                    msg,
-                    query_fulfilment,
-                    error_sender,
-                    database,
-                ));
-                respond(())
-            }
-        })
-        .await;
+        let approvals = self.approvals;
+        tokio::spawn(Self::forward_approvals(
+            self.bot.clone(),
+            self.approval_events,
+            database.clone(),
+            error_sender.clone(),
+        ));
+        let handler = dptree::entry()
+            .branch(Update::filter_message().endpoint(
+                |bot: Bot,
+                 msg: Message,
+                 query_fulfilment: Arc<QueryFulfilment>,
+                 error_sender: Arc<mpsc::Sender<Alert>>,
+                 database: Arc<DatabaseService>| async move {
+                    tokio::spawn(Self::handle_message(
+                        bot,
+                        msg,
+                        query_fulfilment,
+                        error_sender,
+                        database,
+                    ));
+                    respond(())
+                },
+            ))
+            .branch(Update::filter_callback_query().endpoint(
+                |bot: Bot,
+                 callback: CallbackQuery,
+                 query_fulfilment: Arc<QueryFulfilment>,
+                 error_sender: Arc<mpsc::Sender<Alert>>,
+                 database: Arc<DatabaseService>,
+                 approvals: broadcast::Sender<QuotationApproval>| async move {
+                    tokio::spawn(Self::handle_callback(
+                        bot,
+                        callback,
+                        query_fulfilment,
+                        error_sender,
+                        database,
+                        approvals,
+                    ));
+                    respond(())
+                },
+            ));
+
+        Dispatcher::builder(self.bot, handler)
+            .dependencies(dptree::deps![
+                query_fulfilment,
+                error_sender,
+                database,
+                approvals
+            ])
+            .enable_ctrlc_handler()
+            .build()
+            .dispatch()
+            .await;
         Ok(())
     }
 }
@@ -88,7 +174,7 @@ impl TelegramService {
         bot: Bot,
         msg: Message,
         query_fulfilment: Arc<QueryFulfilment>,
-        error_sender: Arc<mpsc::Sender<String>>,
+        error_sender: Arc<mpsc::Sender<Alert>>,
         database: Arc<DatabaseService>,
     ) -> ResponseResult<()> {
         let chat_id = msg.chat.id;
@@ -112,7 +198,10 @@ impl TelegramService {
                 // Create pending user for Telegram
                 if let Err(e) = database.create_pending_telegram_user(&telegram_id).await {
                     let _ = error_sender
-                        .send(format!("Failed to create pending user: {}", e))
+                        .send(Alert::error(format!(
+                            "Failed to create pending user: {}",
+                            e
+                        )))
                         .await;
                 }
                 bot.send_message(
@@ -124,10 +213,10 @@ impl TelegramService {
             }
             Err(e) => {
                 let _ = error_sender
-                    .send(format!(
+                    .send(Alert::critical(format!(
                         "Database error for telegram_id {}: {}",
                         telegram_id, e
-                    ))
+                    )))
                     .await;
                 bot.send_message(chat_id, "System error. Please try again later.")
                     .await?;
@@ -137,8 +226,30 @@ impl TelegramService {
 
         if let Some(photo) = msg.photo() {
             let caption = msg.caption().unwrap_or("").trim();
+            if caption == SET_HEADER_COMMAND {
+                // Telegram recompresses photos - the largest size is closest to the original
+                let largest = photo
+                    .iter()
+                    .max_by_key(|p| p.width as u64 * p.height as u64);
+                let reply = match largest {
+                    Some(largest) => {
+                        Self::set_header_image(
+                            &bot,
+                            &database,
+                            &query_fulfilment.branding().header_image_path,
+                            &telegram_id,
+                            &largest.file.id,
+                        )
+                        .await
+                    }
+                    None => "❌ No photo found".to_string(),
+                };
+                bot.send_message(chat_id, reply).await?;
+                return Ok(());
+            }
 
-            bot.send_message(chat_id, "Processing request... please wait ⏳")
+            let ack = bot
+                .send_message(chat_id, "Processing request... please wait ⏳")
                 .await?;
             let start_time = std::time::Instant::now();
             let mut context = create_session_context(&user, &telegram_id);
@@ -170,15 +281,14 @@ impl TelegramService {
                         &error_sender,
                     )
                     .await;
-                    bot.send_message(chat_id, response.text).await?;
-                    if let Some(file_path) = response.file {
-                        bot.send_document(chat_id, InputFile::file(&file_path))
-                            .await?;
-                        // Clean up generated files
-                        if !file_path.contains("assets") {
-                            let _ = fs::remove_file(&file_path);
-                        }
-                    }
+                    Self::deliver_response(
+                        &bot,
+                        chat_id,
+                        Some(ack.id),
+                        response.into(),
+                        &error_sender,
+                    )
+                    .await?;
                 }
                 Err(e) => {
                     // Convert TelegramError to QueryError for consistent error handling
@@ -186,6 +296,9 @@ impl TelegramService {
                         TelegramError::ImageProcessingError(_) => {
                             QueryError::OcrError(e.to_string())
                         }
+                        TelegramError::MediaTooLarge(size, limit) => {
+                            QueryError::MediaTooLarge(size, limit)
+                        }
                         _ => QueryError::LLMError(e.to_string()),
                     };
                     complete_session_with_error(
@@ -197,8 +310,15 @@ impl TelegramService {
                         &error_sender,
                     )
                     .await;
-                    let error_response = create_error_response(&query_error);
-                    bot.send_message(chat_id, error_response.text).await?;
+                    let error_response = Response::from(create_error_response(&query_error));
+                    Self::deliver_response(
+                        &bot,
+                        chat_id,
+                        Some(ack.id),
+                        error_response,
+                        &error_sender,
+                    )
+                    .await?;
                 }
             }
             return Ok(());
@@ -212,11 +332,13 @@ impl TelegramService {
                             .to_string(),
                     file: None,
                     query_metadata: None,
+                    ..Default::default()
                 },
                 "/help" => Response {
-                    text: QueryFulfilment::get_help_text(),
+                    text: QueryFulfilment::get_help_text(None),
                     file: None,
                     query_metadata: None,
+                    ..Default::default()
                 },
                 text if text.starts_with("/approve_telegram ") => {
                     if database.is_admin(&telegram_id).await {
@@ -226,6 +348,7 @@ impl TelegramService {
                                 text: format!("✅ Approved user: {}", target_id),
                                 file: None,
                                 query_metadata: None,
+                                ..Default::default()
                             },
                             Ok(false) => Response {
                                 text: format!(
@@ -234,11 +357,13 @@ impl TelegramService {
                                 ),
                                 file: None,
                                 query_metadata: None,
+                                ..Default::default()
                             },
                             Err(e) => Response {
                                 text: format!("❌ Error approving user: {}", e),
                                 file: None,
                                 query_metadata: None,
+                                ..Default::default()
                             },
                         }
                     } else {
@@ -246,6 +371,7 @@ impl TelegramService {
                             text: "❌ Admin access required".to_string(),
                             file: None,
                             query_metadata: None,
+                            ..Default::default()
                         }
                     }
                 }
@@ -257,11 +383,13 @@ impl TelegramService {
                                 text: format!("✅ Approved WhatsApp user: {}", phone),
                                 file: None,
                                 query_metadata: None,
+                                ..Default::default()
                             },
                             Err(e) => Response {
                                 text: format!("❌ Error approving WhatsApp user: {}", e),
                                 file: None,
                                 query_metadata: None,
+                                ..Default::default()
                             },
                         }
                     } else {
@@ -269,6 +397,7 @@ impl TelegramService {
                             text: "❌ Admin access required".to_string(),
                             file: None,
                             query_metadata: None,
+                            ..Default::default()
                         }
                     }
                 }
@@ -281,6 +410,7 @@ impl TelegramService {
                                         text: "No pending approvals".to_string(),
                                         file: None,
                                         query_metadata: None,
+                                        ..Default::default()
                                     }
                                 } else {
                                     let mut msg = "📋 Pending Approvals:\n\n".to_string();
@@ -293,6 +423,7 @@ impl TelegramService {
                                         text: msg,
                                         file: None,
                                         query_metadata: None,
+                                        ..Default::default()
                                     }
                                 }
                             }
@@ -300,6 +431,265 @@ impl TelegramService {
                                 text: format!("❌ Error fetching pending users: {}", e),
                                 file: None,
                                 query_metadata: None,
+                                ..Default::default()
+                            },
+                        }
+                    } else {
+                        Response {
+                            text: "❌ Admin access required".to_string(),
+                            file: None,
+                            query_metadata: None,
+                            ..Default::default()
+                        }
+                    }
+                }
+
+                text if text == "/model_usage" || text.starts_with("/model_usage ") => {
+                    if database.is_admin(&telegram_id).await {
+                        let days = text.strip_prefix("/model_usage").unwrap().trim();
+                        // Defaults to the last week
+                        let days = if days.is_empty() {
+                            Ok(7)
+                        } else {
+                            days.parse::<i64>()
+                        };
+                        match days {
+                            Ok(days) if days > 0 => {
+                                let since = chrono::Utc::now() - chrono::Duration::days(days);
+                                match database.get_model_usage(since).await {
+                                    Ok(usage) => Response {
+                                        text: format_model_usage(&usage, days),
+                                        file: None,
+                                        query_metadata: None,
+                                        ..Default::default()
+                                    },
+                                    Err(e) => Response {
+                                        text: format!("❌ Error fetching model usage: {}", e),
+                                        file: None,
+                                        query_metadata: None,
+                                        ..Default::default()
+                                    },
+                                }
+                            }
+                            _ => Response {
+                                text: "❌ Invalid period. Use: /model_usage <days>".to_string(),
+                                file: None,
+                                query_metadata: None,
+                                ..Default::default()
+                            },
+                        }
+                    } else {
+                        Response {
+                            text: "❌ Admin access required".to_string(),
+                            file: None,
+                            query_metadata: None,
+                            ..Default::default()
+                        }
+                    }
+                }
+
+                "/status" => {
+                    if database.is_admin(&telegram_id).await {
+                        let metrics = query_fulfilment.stock_metrics().await;
+                        let warehouses = if metrics.connected_warehouses.is_empty() {
+                            "none".to_string()
+                        } else {
+                            metrics.connected_warehouses.join(", ")
+                        };
+                        let latency = metrics
+                            .average_latency_ms
+                            .map(|ms| format!("{:.0} ms", ms))
+                            .unwrap_or_else(|| "n/a".to_string());
+                        Response {
+                            text: format!(
+                                "📊 Stock service\nTally connected: {}\nPending requests: {}\nServed: {}\nTimeouts: {}\nAvg round trip: {}\n\n{}",
+                                warehouses,
+                                metrics.pending_requests,
+                                metrics.total_served,
+                                metrics.timeouts,
+                                latency,
+                                format_retry_stats(&retry_stats())
+                            ),
+                            file: None,
+                            query_metadata: None,
+                            ..Default::default()
+                        }
+                    } else {
+                        Response {
+                            text: "❌ Admin access required".to_string(),
+                            file: None,
+                            query_metadata: None,
+                            ..Default::default()
+                        }
+                    }
+                }
+                text if text.starts_with("/export_user ") => {
+                    if database.is_admin(&telegram_id).await {
+                        let target_id = text.strip_prefix("/export_user ").unwrap().trim();
+                        Self::export_user_data(&database, target_id).await
+                    } else {
+                        Response {
+                            text: "❌ Admin access required".to_string(),
+                            file: None,
+                            query_metadata: None,
+                            ..Default::default()
+                        }
+                    }
+                }
+
+                text if text.starts_with("/testquote ") => {
+                    if database.is_admin(&telegram_id).await {
+                        let request_json = text.strip_prefix("/testquote ").unwrap().trim();
+                        match serde_json::from_str::<QuotationRequest>(request_json) {
+                            Ok(request) => match query_fulfilment.test_quotation(request) {
+                                Ok(response) => response.into(),
+                                Err(e) => Response {
+                                    text: format!("❌ Test quotation failed: {}", e),
+                                    file: None,
+                                    query_metadata: None,
+                                    ..Default::default()
+                                },
+                            },
+                            Err(e) => Response {
+                                text: format!("❌ Invalid quotation request JSON: {}", e),
+                                file: None,
+                                query_metadata: None,
+                                ..Default::default()
+                            },
+                        }
+                    } else {
+                        Response {
+                            text: "❌ Admin access required".to_string(),
+                            file: None,
+                            query_metadata: None,
+                            ..Default::default()
+                        }
+                    }
+                }
+
+                text if text == "/loadtest" || text.starts_with("/loadtest ") => {
+                    if database.is_admin(&telegram_id).await {
+                        match parse_load_test_args(text.strip_prefix("/loadtest").unwrap()) {
+                            Ok((requests, concurrency, request)) => {
+                                info!(
+                                    "Admin {} started a load test: {} quotations, {} concurrent",
+                                    telegram_id, requests, concurrency
+                                );
+                                // Pricing and PDF rendering are synchronous, keep them off the
+                                // runtime's worker while they run
+                                let report = tokio::task::block_in_place(|| {
+                                    query_fulfilment.load_test_quotations(
+                                        &request,
+                                        requests,
+                                        concurrency,
+                                    )
+                                });
+                                Response {
+                                    text: report.summary(),
+                                    file: None,
+                                    query_metadata: None,
+                                    ..Default::default()
+                                }
+                            }
+                            Err(e) => Response {
+                                text: format!("❌ {}", e),
+                                file: None,
+                                query_metadata: None,
+                                ..Default::default()
+                            },
+                        }
+                    } else {
+                        Response {
+                            text: "❌ Admin access required".to_string(),
+                            file: None,
+                            query_metadata: None,
+                            ..Default::default()
+                        }
+                    }
+                }
+
+                text if text.starts_with("/parse ") => {
+                    if database.is_admin(&telegram_id).await {
+                        Self::run_dry_parse(
+                            text.strip_prefix("/parse ").unwrap().trim(),
+                            &user,
+                            &telegram_id,
+                            &query_fulfilment,
+                            &database,
+                            &error_sender,
+                        )
+                        .await
+                    } else {
+                        Response {
+                            text: "❌ Admin access required".to_string(),
+                            file: None,
+                            query_metadata: None,
+                            ..Default::default()
+                        }
+                    }
+                }
+
+                text if text == "/prompt" || text.starts_with("/prompt ") => {
+                    if database.is_admin(&telegram_id).await {
+                        let sample_query = text.strip_prefix("/prompt").unwrap().trim();
+                        Self::preview_prompt(&query_fulfilment, sample_query).await
+                    } else {
+                        Response {
+                            text: "❌ Admin access required".to_string(),
+                            file: None,
+                            query_metadata: None,
+                            ..Default::default()
+                        }
+                    }
+                }
+
+                text if text == "/customer" || text.starts_with("/customer ") => {
+                    if database.is_admin(&telegram_id).await {
+                        let args = text.strip_prefix("/customer").unwrap();
+                        Self::manage_customer_profiles(&database, args).await
+                    } else {
+                        Response {
+                            text: "❌ Admin access required".to_string(),
+                            file: None,
+                            query_metadata: None,
+                            ..Default::default()
+                        }
+                    }
+                }
+
+                text if text.starts_with("/purge_conversations ") => {
+                    if database.is_admin(&telegram_id).await {
+                        let days = text.strip_prefix("/purge_conversations ").unwrap().trim();
+                        match days.parse::<i64>() {
+                            Ok(days) if days > 0 => {
+                                let cutoff = chrono::Utc::now() - chrono::Duration::days(days);
+                                match database.purge_conversations_before(cutoff).await {
+                                    Ok(report) => Response {
+                                        text: format!(
+                                            "✅ Purged conversations older than {} days\nConversations deleted: {}\nMessages deleted: {}\nSessions unlinked: {}",
+                                            days,
+                                            report.conversations_deleted,
+                                            report.messages_deleted,
+                                            report.sessions_unlinked
+                                        ),
+                                        file: None,
+                                        query_metadata: None,
+                                        ..Default::default()
+                                    },
+                                    Err(e) => Response {
+                                        text: format!("❌ Error purging conversations: {}", e),
+                                        file: None,
+                                        query_metadata: None,
+                                        ..Default::default()
+                                    },
+                                }
+                            }
+                            _ => Response {
+                                text: "❌ Invalid period. Use: /purge_conversations <days>"
+                                    .to_string(),
+                                file: None,
+                                query_metadata: None,
+                                ..Default::default()
                             },
                         }
                     } else {
@@ -307,6 +697,7 @@ impl TelegramService {
                             text: "❌ Admin access required".to_string(),
                             file: None,
                             query_metadata: None,
+                            ..Default::default()
                         }
                     }
                 }
@@ -316,17 +707,19 @@ impl TelegramService {
                         let model = text.strip_prefix("/llm ").unwrap().trim();
                         match model {
                             "claude" | "groq" => {
-                                query_fulfilment.set_primary_model(model);
+                                query_fulfilment.set_primary_model(model).await;
                                 Response {
                                     text: format!("✅ Primary LLM switched to: {}", model),
                                     file: None,
                                     query_metadata: None,
+                                    ..Default::default()
                                 }
                             }
                             _ => Response {
                                 text: "❌ Invalid model. Use: /llm claude or /llm groq".to_string(),
                                 file: None,
                                 query_metadata: None,
+                                ..Default::default()
                             },
                         }
                     } else {
@@ -334,66 +727,51 @@ impl TelegramService {
                             text: "❌ Admin access required".to_string(),
                             file: None,
                             query_metadata: None,
+                            ..Default::default()
                         }
                     }
                 }
 
-                text => {
-                    let start_time = std::time::Instant::now();
-                    let mut context = create_session_context(&user, &telegram_id);
-                    if create_session_or_error(&database, &context, text, "text", &error_sender)
-                        .await
-                        .is_err()
-                    {
-                        bot.send_message(chat_id, "System error").await?;
-                        return Ok(());
-                    }
-                    match query_fulfilment
-                        .fulfil_query(text, &mut context, &error_sender)
-                        .await
-                    {
-                        Ok(response) => {
-                            complete_session_with_success(
-                                &database,
-                                &context,
-                                &response,
-                                text,
-                                start_time,
-                                &error_sender,
-                            )
-                            .await;
-                            response
-                        }
-                        Err(e) => {
-                            complete_session_with_error(
-                                &database,
-                                &context,
-                                &e,
-                                text,
-                                start_time,
-                                &error_sender,
-                            )
-                            .await;
-                            create_error_response(&e)
+                "/approvals" => {
+                    if database.is_admin(&telegram_id).await {
+                        Self::list_pending_approvals(&bot, &database).await
+                    } else {
+                        Response {
+                            text: "❌ Admin access required".to_string(),
+                            file: None,
+                            query_metadata: None,
+                            ..Default::default()
                         }
                     }
                 }
-            };
 
-            bot.send_message(chat_id, response.text).await?;
-            if let Some(file_path) = response.file {
-                bot.send_document(chat_id, InputFile::file(&file_path))
-                    .await?;
+                SET_HEADER_COMMAND => Response {
+                    text: format!(
+                        "Send the new letterhead as a JPEG photo or file with the caption {}",
+                        SET_HEADER_COMMAND
+                    ),
+                    file: None,
+                    query_metadata: None,
+                    ..Default::default()
+                },
 
-                // Clean up the PDF file - only quotations - after successful send
-                if !&file_path.contains("assets") {
-                    if let Err(e) = fs::remove_file(&file_path) {
-                        error!("Warning: Failed to delete PDF file {}: {}", file_path, e);
-                    }
+                text => {
+                    Self::run_text_query(
+                        text,
+                        &user,
+                        &telegram_id,
+                        &query_fulfilment,
+                        &database,
+                        &error_sender,
+                    )
+                    .await
                 }
-            }
+            };
+
+            Self::deliver_response(&bot, chat_id, None, response, &error_sender).await?;
         } else if let Some(voice) = msg.voice() {
-            bot.send_message(chat_id, "Processing audio... please wait ⏳")
+            let ack = bot
+                .send_message(chat_id, "Processing audio... please wait ⏳")
                 .await?;
             let start_time = std::time::Instant::now();
             let mut context = create_session_context(&user, &telegram_id);
@@ -424,14 +802,14 @@ impl TelegramService {
                         &error_sender,
                     )
                     .await;
-                    bot.send_message(chat_id, response.text).await?;
-                    if let Some(file_path) = response.file {
-                        bot.send_document(chat_id, InputFile::file(&file_path))
-                            .await?;
-                        if !file_path.contains("assets") {
-                            let _ = fs::remove_file(&file_path);
-                        }
-                    }
+                    Self::deliver_response(
+                        &bot,
+                        chat_id,
+                        Some(ack.id),
+                        response.into(),
+                        &error_sender,
+                    )
+                    .await?;
                 }
                 Err(e) => {
                     // Convert TelegramError to QueryError for consistent error handling
@@ -439,6 +817,9 @@ impl TelegramService {
                         TelegramError::ImageProcessingError(_) => {
                             QueryError::TranscriptionError(e.to_string())
                         }
+                        TelegramError::MediaTooLarge(size, limit) => {
+                            QueryError::MediaTooLarge(size, limit)
+                        }
                         _ => QueryError::LLMError(e.to_string()),
                     };
                     complete_session_with_error(
@@ -450,14 +831,33 @@ impl TelegramService {
                         &error_sender,
                     )
                     .await;
-                    let error_response = create_error_response(&query_error);
-                    bot.send_message(chat_id, error_response.text).await?;
+                    let error_response = Response::from(create_error_response(&query_error));
+                    Self::deliver_response(
+                        &bot,
+                        chat_id,
+                        Some(ack.id),
+                        error_response,
+                        &error_sender,
+                    )
+                    .await?;
                 }
             }
             return Ok(());
-        } else if msg.document().is_some() {
-            bot.send_message(chat_id, "I received a document! 📄")
-                .await?;
+        } else if let Some(document) = msg.document() {
+            if msg.caption().map(str::trim) == Some(SET_HEADER_COMMAND) {
+                let reply = Self::set_header_image(
+                    &bot,
+                    &database,
+                    &query_fulfilment.branding().header_image_path,
+                    &telegram_id,
+                    &document.file.id,
+                )
+                .await;
+                bot.send_message(chat_id, reply).await?;
+            } else {
+                bot.send_message(chat_id, "I received a document! 📄")
+                    .await?;
+            }
         } else {
             bot.send_message(
                 chat_id,
@@ -468,18 +868,595 @@ impl TelegramService {
         Ok(())
     }
 
+    async fn run_text_query(
+        text: &str,
+        user: &User,
+        telegram_id: &str,
+        query_fulfilment: &QueryFulfilment,
+        database: &Arc<DatabaseService>,
+        error_sender: &Sender<Alert>,
+    ) -> Response {
+        let start_time = std::time::Instant::now();
+        let mut context = create_session_context(user, telegram_id);
+        if create_session_or_error(database, &context, text, "text", error_sender)
+            .await
+            .is_err()
+        {
+            return Response {
+                text: "System error".to_string(),
+                file: None,
+                query_metadata: None,
+                ..Default::default()
+            };
+        }
+        match query_fulfilment
+            .fulfil_query(text, &mut context, error_sender)
+            .await
+        {
+            Ok(response) => {
+                complete_session_with_success(
+                    database,
+                    &context,
+                    &response,
+                    text,
+                    start_time,
+                    error_sender,
+                )
+                .await;
+                response.into()
+            }
+            Err(e) => {
+                complete_session_with_error(database, &context, &e, text, start_time, error_sender)
+                    .await;
+                create_error_response(&e).into()
+            }
+        }
+    }
+
+    async fn manage_customer_profiles(database: &DatabaseService, args: &str) -> Response {
+        let text = match parse_customer_command(args) {
+            Err(usage) => usage,
+            Ok(CustomerCommand::List) => match database.list_customer_profiles().await {
+                Ok(profiles) if profiles.is_empty() => "No customer profiles saved".to_string(),
+                Ok(profiles) => profiles
+                    .iter()
+                    .map(format_customer_profile)
+                    .collect::<Vec<_>>()
+                    .join("\n\n"),
+                Err(e) => format!("❌ Error listing customer profiles: {}", e),
+            },
+            Ok(CustomerCommand::Show(key)) => {
+                let keys = [key];
+                match database.find_customer_profile(&keys).await {
+                    Ok(Some(profile)) => format_customer_profile(&profile),
+                    Ok(None) => format!("No customer profile for {}", keys[0]),
+                    Err(e) => format!("❌ Error loading customer profile: {}", e),
+                }
+            }
+            Ok(CustomerCommand::Delete(key)) => {
+                match database.delete_customer_profile(&key).await {
+                    Ok(true) => format!("✅ Deleted customer profile for {}", key),
+                    Ok(false) => format!("No customer profile for {}", key),
+                    Err(e) => format!("❌ Error deleting customer profile: {}", e),
+                }
+            }
+            Ok(CustomerCommand::Set(profile)) => {
+                match database.save_customer_profile(&profile).await {
+                    Ok(saved) => format!("✅ Saved\n{}", format_customer_profile(&saved)),
+                    Err(e) => format!("❌ Error saving customer profile: {}", e),
+                }
+            }
+        };
+
+        Response {
+            text,
+            file: None,
+            query_metadata: None,
+            ..Default::default()
+        }
+    }
+
+    // Sent as a file - the system prompt alone is longer than a Telegram message allows
+    async fn preview_prompt(query_fulfilment: &QueryFulfilment, sample_query: &str) -> Response {
+        let sample_query = if sample_query.is_empty() {
+            DEFAULT_PROMPT_PREVIEW_QUERY
+        } else {
+            sample_query
+        };
+        let preview = query_fulfilment.preview_prompt(sample_query).await;
+
+        // Kept out of artifacts/, which is publicly served over the webhook
+        let file_path = std::env::temp_dir().join(format!("prompt_preview_{}.txt", Uuid::new_v4()));
+        if let Err(e) = fs::write(&file_path, &preview) {
+            return Response {
+                text: format!("❌ Error writing prompt preview: {}", e),
+                file: None,
+                query_metadata: None,
+                ..Default::default()
+            };
+        }
+
+        Response {
+            text: format!("🧾 Prompt preview for: {}", sample_query),
+            file: Some(file_path.to_string_lossy().to_string()),
+            query_metadata: None,
+            ..Default::default()
+        }
+    }
+
+    // Admin only - the query is parsed in its own session so the LLM cost is still recorded,
+    // and parse errors are shown as is rather than as the user facing message
+    async fn run_dry_parse(
+        text: &str,
+        user: &User,
+        telegram_id: &str,
+        query_fulfilment: &QueryFulfilment,
+        database: &Arc<DatabaseService>,
+        error_sender: &Sender<Alert>,
+    ) -> Response {
+        let start_time = std::time::Instant::now();
+        let mut context = create_session_context(user, telegram_id);
+        if create_session_or_error(database, &context, text, "dry_parse", error_sender)
+            .await
+            .is_err()
+        {
+            return Response {
+                text: "System error".to_string(),
+                file: None,
+                query_metadata: None,
+                ..Default::default()
+            };
+        }
+        match query_fulfilment
+            .dry_parse(text, &mut context, error_sender)
+            .await
+        {
+            Ok(response) => {
+                complete_session_with_success(
+                    database,
+                    &context,
+                    &response,
+                    text,
+                    start_time,
+                    error_sender,
+                )
+                .await;
+                response.into()
+            }
+            Err(e) => {
+                complete_session_with_error(database, &context, &e, text, start_time, error_sender)
+                    .await;
+                Response {
+                    text: format!("❌ Parse failed: {}", e),
+                    file: None,
+                    query_metadata: None,
+                    ..Default::default()
+                }
+            }
+        }
+    }
+
+    // A tapped follow-up button runs its canned query as if the user had typed it, so the
+    // usual conversation context carries over
+    async fn handle_callback(
+        bot: Bot,
+        callback: CallbackQuery,
+        query_fulfilment: Arc<QueryFulfilment>,
+        error_sender: Arc<mpsc::Sender<Alert>>,
+        database: Arc<DatabaseService>,
+        approvals: broadcast::Sender<QuotationApproval>,
+    ) -> ResponseResult<()> {
+        bot.answer_callback_query(callback.id.clone()).await?;
+        if let Some((status, id)) = callback.data.as_deref().and_then(approval_decision) {
+            return Self::decide_approval(
+                &bot,
+                &callback.from.id.0.to_string(),
+                callback.message,
+                status,
+                id,
+                &database,
+                &approvals,
+                &error_sender,
+            )
+            .await;
+        }
+        let (Some(query), Some(message)) = (
+            callback.data.as_deref().and_then(follow_up_query),
+            callback.message,
+        ) else {
+            return Ok(());
+        };
+
+        let chat_id = message.chat.id;
+        let telegram_id = chat_id.0.to_string();
+        let user = match database.get_user_by_telegram(&telegram_id).await {
+            Ok(Some(user)) => user,
+            _ => return Ok(()),
+        };
+        if !database.is_user_authorized(&user).await {
+            return Ok(());
+        }
+
+        let ack = bot
+            .send_message(chat_id, "Processing request... please wait ⏳")
+            .await?;
+        let response = Self::run_text_query(
+            query,
+            &user,
+            &telegram_id,
+            &query_fulfilment,
+            &database,
+            &error_sender,
+        )
+        .await;
+        Self::deliver_response(&bot, chat_id, Some(ack.id), response, &error_sender).await
+    }
+
+    // Admin only - the decision is published so the requester's platform can send the documents
+    // on, or tell them it was turned down
+    #[allow(clippy::too_many_arguments)]
+    async fn decide_approval(
+        bot: &Bot,
+        telegram_id: &str,
+        message: Option<Message>,
+        status: ApprovalStatus,
+        id: Uuid,
+        database: &DatabaseService,
+        approvals: &broadcast::Sender<QuotationApproval>,
+        error_sender: &Sender<Alert>,
+    ) -> ResponseResult<()> {
+        if !database.is_admin(telegram_id).await {
+            return Ok(());
+        }
+        let reply = match database
+            .decide_quotation_approval(id, status, telegram_id)
+            .await
+        {
+            Ok(Some(mut approval)) => {
+                let reply = match status {
+                    ApprovalStatus::Rejected => {
+                        for file_path in &approval.files {
+                            if let Err(e) = fs::remove_file(file_path) {
+                                error!("Warning: Failed to delete PDF file {}: {}", file_path, e);
+                            }
+                        }
+                        format!("❌ Rejected {}", approval.document_reference)
+                    }
+                    _ => {
+                        // Back into artifacts/, where both platforms send documents from
+                        for file_path in &mut approval.files {
+                            match release_document(file_path) {
+                                Ok(released) => *file_path = released,
+                                Err(e) => {
+                                    error!("Failed to release PDF file {}: {}", file_path, e)
+                                }
+                            }
+                        }
+                        format!("✅ Approved {}", approval.document_reference)
+                    }
+                };
+                let _ = approvals.send(approval);
+                reply
+            }
+            Ok(None) => "This quotation has already been approved or rejected".to_string(),
+            Err(e) => {
+                let _ = error_sender
+                    .send(Alert::error(format!(
+                        "Failed to record decision on approval {}: {}",
+                        id, e
+                    )))
+                    .await;
+                format!("❌ Error recording the decision: {}", e)
+            }
+        };
+        // Replacing the text also drops the buttons
+        if let Some(message) = message {
+            bot.edit_message_text(message.chat.id, message.id, reply)
+                .await?;
+        }
+        Ok(())
+    }
+
+    // Asks the admin about newly held quotations, and sends decided ones that came in on
+    // Telegram on to the customer
+    async fn forward_approvals(
+        bot: Bot,
+        mut events: broadcast::Receiver<QuotationApproval>,
+        database: Arc<DatabaseService>,
+        error_sender: Arc<mpsc::Sender<Alert>>,
+    ) {
+        loop {
+            let approval = match events.recv().await {
+                Ok(approval) => approval,
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!(
+                        "Missed {} approval events, /approvals lists what is pending",
+                        missed
+                    );
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            };
+            let id = approval.id;
+            let result = match approval.status {
+                ApprovalStatus::Pending => Self::notify_admin(&bot, &database, &approval).await,
+                _ if approval.platform == "telegram" => {
+                    Self::release_approval(&bot, approval, &error_sender).await
+                }
+                _ => Ok(()),
+            };
+            if let Err(e) = result {
+                let _ = error_sender
+                    .send(Alert::error(format!(
+                        "Failed to forward approval {}: {}",
+                        id, e
+                    )))
+                    .await;
+            }
+        }
+    }
+
+    // The documents go first so the admin can check them before pressing a button. They are
+    // sent as is - the files are still needed for the customer.
+    async fn notify_admin(
+        bot: &Bot,
+        database: &DatabaseService,
+        approval: &QuotationApproval,
+    ) -> ResponseResult<()> {
+        let Ok(admin_chat) = database.admin_telegram_id().parse::<i64>().map(ChatId) else {
+            warn!(
+                "Admin telegram id is not a chat id, approval {} not sent",
+                approval.id
+            );
+            return Ok(());
+        };
+        for file_path in &approval.files {
+            send_with_retry(|| bot.send_document(admin_chat, InputFile::file(file_path))).await?;
+        }
+        send_with_retry(|| {
+            bot.send_message(admin_chat, approval_request_text(approval))
+                .reply_markup(approval_keyboard(approval.id))
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn release_approval(
+        bot: &Bot,
+        approval: QuotationApproval,
+        error_sender: &Sender<Alert>,
+    ) -> ResponseResult<()> {
+        let Ok(chat_id) = approval.recipient.parse::<i64>().map(ChatId) else {
+            warn!("Approval {} has no Telegram chat to send to", approval.id);
+            return Ok(());
+        };
+        match approval.status {
+            ApprovalStatus::Approved => {
+                let text = approval_released_text(&approval);
+                let mut files = approval.files.into_iter();
+                let response = Response {
+                    text,
+                    file: files.next(),
+                    attachments: files.collect(),
+                    ..Default::default()
+                };
+                Self::deliver_response(bot, chat_id, None, response, error_sender).await
+            }
+            ApprovalStatus::Rejected => {
+                send_with_retry(|| bot.send_message(chat_id, approval_rejected_text(&approval)))
+                    .await?;
+                Ok(())
+            }
+            ApprovalStatus::Pending => Ok(()),
+        }
+    }
+
+    // Admin only - asks about every pending approval again, eg. after a restart lost the buttons
+    async fn list_pending_approvals(bot: &Bot, database: &DatabaseService) -> Response {
+        let text = match database.list_pending_quotation_approvals().await {
+            Ok(pending) if pending.is_empty() => "No quotations waiting for approval".to_string(),
+            Ok(pending) => {
+                let mut failed = 0;
+                for approval in &pending {
+                    if let Err(e) = Self::notify_admin(bot, database, approval).await {
+                        warn!(error = %e, "Failed to resend approval {}", approval.id);
+                        failed += 1;
+                    }
+                }
+                match failed {
+                    0 => format!("{} quotation(s) waiting for approval", pending.len()),
+                    _ => format!(
+                        "{} quotation(s) waiting for approval, {} could not be shown",
+                        pending.len(),
+                        failed
+                    ),
+                }
+            }
+            Err(e) => format!("❌ Error listing pending approvals: {}", e),
+        };
+        Response {
+            text,
+            ..Default::default()
+        }
+    }
+
+    // Sends the reply and any attached documents, alerting only once retries are exhausted.
+    // When there is a "processing..." ack it is edited into the reply instead of adding a message.
+    async fn deliver_response(
+        bot: &Bot,
+        chat_id: ChatId,
+        ack: Option<MessageId>,
+        response: Response,
+        error_sender: &Sender<Alert>,
+    ) -> ResponseResult<()> {
+        let keyboard = follow_up_keyboard(response.query_metadata.as_ref());
+        let edited = match ack {
+            Some(ack_id) => {
+                let request = bot.edit_message_text(chat_id, ack_id, response.text.clone());
+                let request = match keyboard.clone() {
+                    Some(keyboard) => request.reply_markup(keyboard),
+                    None => request,
+                };
+                match request.await {
+                    Ok(message) => Some(message.id),
+                    Err(e) => {
+                        warn!(error = %e, "Failed to edit ack message, sending a new one");
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+        let reply_id = match edited {
+            Some(message_id) => message_id,
+            None => {
+                let sent = send_with_retry(|| {
+                    let request = bot.send_message(chat_id, response.text.clone());
+                    match keyboard.clone() {
+                        Some(keyboard) => request.reply_markup(keyboard),
+                        None => request,
+                    }
+                })
+                .await;
+                match sent {
+                    Ok(message) => message.id,
+                    Err(e) => {
+                        let _ = error_sender
+                            .send(Alert::error(format!(
+                                "Failed to deliver Telegram reply to chat {}: {}",
+                                chat_id.0, e
+                            )))
+                            .await;
+                        return Err(e);
+                    }
+                }
+            }
+        };
+
+        for file_path in response.file.into_iter().chain(response.attachments) {
+            if let Err(e) = send_with_retry(|| {
+                bot.send_document(chat_id, InputFile::file(&file_path))
+                    .reply_to_message_id(reply_id)
+            })
+            .await
+            {
+                let _ = error_sender
+                    .send(Alert::error(format!(
+                        "Failed to deliver document {} to chat {}: {}",
+                        file_path, chat_id.0, e
+                    )))
+                    .await;
+                return Err(e);
+            }
+
+            // Clean up the PDF file - only quotations - after successful send
+            if !file_path.contains("assets") {
+                if let Err(e) = fs::remove_file(&file_path) {
+                    error!("Warning: Failed to delete PDF file {}: {}", file_path, e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn export_user_data(database: &DatabaseService, target_id: &str) -> Response {
+        let user_id = match Uuid::parse_str(target_id) {
+            Ok(id) => id,
+            Err(_) => {
+                return Response {
+                    text: "❌ Invalid user id. Use: /export_user <user uuid>".to_string(),
+                    file: None,
+                    query_metadata: None,
+                    ..Default::default()
+                }
+            }
+        };
+
+        let export = match database.export_user_data(user_id).await {
+            Ok(export) => export,
+            Err(e) => {
+                return Response {
+                    text: format!("❌ Error exporting user data: {}", e),
+                    file: None,
+                    query_metadata: None,
+                    ..Default::default()
+                }
+            }
+        };
+
+        // Written outside artifacts/ since that directory is publicly served over the webhook
+        let file_path = std::env::temp_dir().join(format!("user_export_{}.json", user_id));
+        let json = serde_json::to_string_pretty(&export).unwrap_or_default();
+        if let Err(e) = fs::write(&file_path, json) {
+            return Response {
+                text: format!("❌ Error writing export file: {}", e),
+                file: None,
+                query_metadata: None,
+                ..Default::default()
+            };
+        }
+
+        Response {
+            text: format!(
+                "✅ Exported {} sessions, {} conversations, {} messages, {} cost events",
+                export.sessions.len(),
+                export.conversations.len(),
+                export.conversation_messages.len(),
+                export.cost_events.len()
+            ),
+            file: Some(file_path.to_string_lossy().to_string()),
+            query_metadata: None,
+            ..Default::default()
+        }
+    }
+
+    // Admin only - the new header is used from the next quotation / proforma onwards
+    async fn set_header_image(
+        bot: &Bot,
+        database: &DatabaseService,
+        header_path: &str,
+        telegram_id: &str,
+        file_id: &str,
+    ) -> String {
+        if !database.is_admin(telegram_id).await {
+            return "❌ Admin access required".to_string();
+        }
+
+        let file_info = match bot.get_file(file_id).await {
+            Ok(file_info) => file_info,
+            Err(e) => return format!("❌ Failed to get header image: {}", e),
+        };
+        let mut image_data = Vec::new();
+        if let Err(e) = bot.download_file(&file_info.path, &mut image_data).await {
+            return format!("❌ Failed to download header image: {}", e);
+        }
+
+        match replace_header_image(header_path, &image_data) {
+            Ok(Some(backup)) => format!(
+                "✅ Header image updated. Previous header saved to {}",
+                backup.display()
+            ),
+            Ok(None) => "✅ Header image updated".to_string(),
+            Err(e) => format!("❌ Header image not updated: {}", e),
+        }
+    }
+
     async fn process_image_query(
         bot: &Bot,
         photos: &[PhotoSize],
         caption: &str,
         query_fulfilment: &QueryFulfilment,
         context: &mut SessionContext,
-        error_sender: &Sender<String>,
-    ) -> Result<Response, TelegramError> {
-        // Get the largest photo size
-        let photo = photos.iter().max_by_key(|p| p.width * p.height).ok_or(
+        error_sender: &Sender<Alert>,
+    ) -> Result<QueryResponse, TelegramError> {
+        let photo = select_photo(photos, query_fulfilment.max_image_pixels()).ok_or(
             TelegramError::ImageProcessingError("No photo found".to_string()),
         )?;
+        info!("Selected photo resolution {}x{}", photo.width, photo.height);
+        check_telegram_media_size(
+            photo.file.size,
+            query_fulfilment.media_limits().max_image_bytes,
+        )?;
 
         // Download image
         let file_info = bot.get_file(&photo.file.id).await.map_err(|e| {
@@ -505,8 +1482,13 @@ impl TelegramService {
         voice: &teloxide::types::Voice,
         query_fulfilment: &QueryFulfilment,
         context: &mut SessionContext,
-        error_sender: &Sender<String>,
-    ) -> Result<Response, TelegramError> {
+        error_sender: &Sender<Alert>,
+    ) -> Result<QueryResponse, TelegramError> {
+        check_telegram_media_size(
+            voice.file.size,
+            query_fulfilment.media_limits().max_audio_bytes,
+        )?;
+
         // Use voice.file.id for download
         let file_info = bot.get_file(&voice.file.id).await.map_err(|e| {
             TelegramError::ImageProcessingError(format!("Failed to get voice file info: {}", e))
@@ -525,3 +1507,482 @@ impl TelegramService {
             .map_err(|e| TelegramError::ImageProcessingError(e.to_string()))
     }
 }
+
+// Telegram reports the file size with the message, so oversized media is never downloaded
+fn check_telegram_media_size(size: u32, limit: u64) -> Result<(), TelegramError> {
+    check_media_size(u64::from(size), limit)
+        .map_err(|_| TelegramError::MediaTooLarge(size.into(), limit))
+}
+
+fn format_model_usage(usage: &ModelUsage, days: i64) -> String {
+    if usage.total() == 0 {
+        return format!("🤖 No LLM parsed queries in the last {} days", days);
+    }
+    let mut lines = vec![format!("🤖 LLM usage - last {} days", days)];
+    for (model, sessions) in &usage.sessions_by_model {
+        lines.push(format!(
+            "{}: {} ({:.1}%)",
+            model,
+            sessions,
+            usage.share(model) * 100.0
+        ));
+    }
+    lines.push(format!("Total: {}", usage.total()));
+    lines.join("\n")
+}
+
+// "/loadtest <requests> [concurrency] [request json]" - the built-in sample request is used
+// when no JSON is given
+fn parse_load_test_args(args: &str) -> Result<(usize, usize, QuotationRequest), String> {
+    const USAGE: &str = "Usage: /loadtest <requests> [concurrency] [request json]";
+    let mut parts = args.trim().splitn(3, char::is_whitespace);
+
+    let requests = match parts.next().map(str::parse::<usize>) {
+        Some(Ok(n)) if (1..=MAX_LOAD_TEST_REQUESTS).contains(&n) => n,
+        _ => return Err(format!("{} (requests 1-{})", USAGE, MAX_LOAD_TEST_REQUESTS)),
+    };
+    let concurrency = match parts.next().map(str::parse::<usize>) {
+        None => 1,
+        Some(Ok(n)) if (1..=MAX_LOAD_TEST_CONCURRENCY).contains(&n) => n,
+        _ => {
+            return Err(format!(
+                "{} (concurrency 1-{})",
+                USAGE, MAX_LOAD_TEST_CONCURRENCY
+            ))
+        }
+    };
+    let request = match parts.next().map(str::trim) {
+        Some(json) if !json.is_empty() => serde_json::from_str::<QuotationRequest>(json)
+            .map_err(|e| format!("Invalid quotation request JSON: {}", e))?,
+        _ => default_load_test_request(),
+    };
+    Ok((requests, concurrency, request))
+}
+
+#[derive(Debug, PartialEq)]
+enum CustomerCommand {
+    List,
+    Show(String),
+    Delete(String),
+    Set(CustomerProfile),
+}
+
+// "/customer list | show <key> | delete <key> | set <key> <profile json>"
+fn parse_customer_command(args: &str) -> Result<CustomerCommand, String> {
+    const USAGE: &str = "Usage: /customer list | show <key> | delete <key> | set <key> <profile json>\nA key is a customer name, phone number or telegram:<id>";
+    let args = args.trim();
+    let (subcommand, rest) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+    let rest = rest.trim();
+
+    match subcommand {
+        "list" if rest.is_empty() => Ok(CustomerCommand::List),
+        "show" if !rest.is_empty() => Ok(CustomerCommand::Show(rest.to_string())),
+        "delete" if !rest.is_empty() => Ok(CustomerCommand::Delete(rest.to_string())),
+        "set" => {
+            // The key runs up to the JSON, so customer names can contain spaces
+            let (key, json) = rest.split_at(rest.find('{').unwrap_or(rest.len()));
+            if key.trim().is_empty() || json.is_empty() {
+                return Err(USAGE.to_string());
+            }
+            let mut profile: CustomerProfile = serde_json::from_str(json)
+                .map_err(|e| format!("Invalid customer profile JSON: {}", e))?;
+            profile.customer_key = key.trim().to_string();
+            Ok(CustomerCommand::Set(profile))
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn format_customer_profile(profile: &CustomerProfile) -> String {
+    let mut lines = vec![format!("👤 {}", profile.customer_key)];
+    if let Some(address) = &profile.address {
+        lines.push(format!("To: {}", address.join(", ")));
+    }
+    if let Some(discount) = profile.discount {
+        lines.push(format!("Discount: {:.1}%", discount * 100.0));
+    }
+    if let Some(delivery_charges) = profile.delivery_charges {
+        lines.push(format!("Delivery charges: Rs.{:.2}", delivery_charges));
+    }
+    if let Some(estimated_delivery) = &profile.estimated_delivery {
+        lines.push(format!("Delivery: {}", estimated_delivery));
+    }
+    if let Some(terms) = &profile.terms_and_conditions {
+        lines.push("Terms:".to_string());
+        lines.extend(terms.iter().map(|term| format!("- {}", term)));
+    }
+    lines.join("\n")
+}
+
+fn format_retry_stats(stats: &BTreeMap<String, HostRetryStats>) -> String {
+    if stats.is_empty() {
+        return "🔁 HTTP retries: none".to_string();
+    }
+    let mut lines = vec!["🔁 HTTP retries".to_string()];
+    for (host, host_stats) in stats {
+        lines.push(format!(
+            "{}: {} retried, {} gave up",
+            host, host_stats.retries, host_stats.exhausted
+        ));
+    }
+    lines.join("\n")
+}
+
+// Largest photo within the pixel cap. If every size is above the cap, take the smallest one
+// and let OCR downscale it.
+fn select_photo(photos: &[PhotoSize], max_pixels: u64) -> Option<&PhotoSize> {
+    let pixels = |p: &PhotoSize| p.width as u64 * p.height as u64;
+    photos
+        .iter()
+        .filter(|p| pixels(p) <= max_pixels)
+        .max_by_key(|p| pixels(p))
+        .or_else(|| photos.iter().min_by_key(|p| pixels(p)))
+}
+
+// Follow-up buttons offered after a response: (label, callback data)
+fn follow_up_buttons(
+    query_metadata: Option<&serde_json::Value>,
+) -> Vec<(&'static str, &'static str)> {
+    // A held quotation can't be followed up on until it is approved
+    if query_metadata.is_some_and(|metadata| metadata.get(PENDING_APPROVAL_KEY).is_some()) {
+        return Vec::new();
+    }
+    // Unit variants serialize as a plain string, the rest as an object keyed by variant
+    let is_query = |query_type: &str| match query_metadata {
+        Some(serde_json::Value::String(value)) => value == query_type,
+        Some(serde_json::Value::Object(map)) => map.contains_key(query_type),
+        _ => false,
+    };
+    if is_query("MetalPricing") {
+        vec![("🔄 Refresh prices", "followup:refresh_prices")]
+    } else if is_query("GetQuotation") {
+        vec![
+            ("📋 Convert to Proforma", "followup:proforma"),
+            ("💲 Get prices only", "followup:prices_only"),
+        ]
+    } else {
+        Vec::new()
+    }
+}
+
+fn follow_up_query(callback_data: &str) -> Option<&'static str> {
+    match callback_data {
+        "followup:refresh_prices" => Some("send metal prices"),
+        "followup:proforma" => Some("convert the last quotation into a proforma invoice"),
+        "followup:prices_only" => Some("give prices only for the items in the last quotation"),
+        _ => None,
+    }
+}
+
+fn follow_up_keyboard(query_metadata: Option<&serde_json::Value>) -> Option<InlineKeyboardMarkup> {
+    let buttons: Vec<InlineKeyboardButton> = follow_up_buttons(query_metadata)
+        .into_iter()
+        .map(|(label, data)| InlineKeyboardButton::callback(label, data))
+        .collect();
+    (!buttons.is_empty()).then(|| InlineKeyboardMarkup::new([buttons]))
+}
+
+fn approval_decision(callback_data: &str) -> Option<(ApprovalStatus, Uuid)> {
+    let (status, id) = match callback_data.strip_prefix(APPROVE_CALLBACK_PREFIX) {
+        Some(id) => (ApprovalStatus::Approved, id),
+        None => (
+            ApprovalStatus::Rejected,
+            callback_data.strip_prefix(REJECT_CALLBACK_PREFIX)?,
+        ),
+    };
+    Some((status, Uuid::parse_str(id).ok()?))
+}
+
+fn approval_keyboard(id: Uuid) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new([[
+        InlineKeyboardButton::callback("✅ Approve", format!("{}{}", APPROVE_CALLBACK_PREFIX, id)),
+        InlineKeyboardButton::callback("❌ Reject", format!("{}{}", REJECT_CALLBACK_PREFIX, id)),
+    ]])
+}
+
+fn approval_request_text(approval: &QuotationApproval) -> String {
+    format!(
+        "🔔 Approval needed: {} for Rs.{}\nRequested on {} by {}",
+        approval.document_reference,
+        format_amount(approval.grand_total),
+        approval.platform,
+        approval.recipient
+    )
+}
+
+const SEND_ATTEMPTS: u32 = 3;
+// Flood control asking for a longer wait than this fails the send rather than stall the handler
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+// Telegram sends don't go through RetryableClient, so transient failures are retried here
+async fn send_with_retry<R, T>(mut send: impl FnMut() -> R) -> Result<T, RequestError>
+where
+    R: IntoFuture<Output = Result<T, RequestError>>,
+{
+    let mut attempt = 1;
+    loop {
+        match send().await {
+            Err(e) if attempt < SEND_ATTEMPTS && is_transient_send_error(&e) => {
+                warn!(error = %e, "Telegram send attempt {} failed, retrying", attempt);
+                // Flood control tells us how long to back off; anything sooner is rejected again
+                let delay = match e {
+                    RequestError::RetryAfter(retry_after) => retry_after,
+                    _ => Duration::from_secs(2u64.pow(attempt - 1)),
+                };
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn is_transient_send_error(error: &RequestError) -> bool {
+    match error {
+        RequestError::Network(_) | RequestError::Io(_) => true,
+        RequestError::RetryAfter(retry_after) => *retry_after <= MAX_RETRY_AFTER,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use teloxide::ApiError;
+
+    fn photo(width: u32, height: u32) -> PhotoSize {
+        serde_json::from_value(serde_json::json!({
+            "file_id": format!("file_{}x{}", width, height),
+            "file_unique_id": format!("unique_{}x{}", width, height),
+            "file_size": 1024,
+            "width": width,
+            "height": height,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_select_photo_prefers_largest_under_cap() {
+        let photos = vec![photo(90, 60), photo(800, 600), photo(4000, 3000)];
+
+        let selected = select_photo(&photos, 1_000_000).unwrap();
+        assert_eq!((selected.width, selected.height), (800, 600));
+
+        let selected = select_photo(&photos, 100).unwrap();
+        assert_eq!((selected.width, selected.height), (90, 60));
+
+        assert!(select_photo(&[], 1_000_000).is_none());
+    }
+
+    #[test]
+    fn test_follow_up_buttons_by_query_type() {
+        let metal = serde_json::json!("MetalPricing");
+        assert_eq!(
+            follow_up_buttons(Some(&metal)),
+            vec![("🔄 Refresh prices", "followup:refresh_prices")]
+        );
+
+        let quotation = serde_json::json!({"GetQuotation": {"items": []}, "quotation_totals": {}});
+        let data: Vec<_> = follow_up_buttons(Some(&quotation))
+            .into_iter()
+            .map(|(_, data)| data)
+            .collect();
+        assert_eq!(data, vec!["followup:proforma", "followup:prices_only"]);
+        assert!(data.iter().all(|data| follow_up_query(data).is_some()));
+
+        let stock = serde_json::json!({"GetStock": {"query": "4 C x 2.5"}});
+        assert!(follow_up_buttons(Some(&stock)).is_empty());
+        let held = serde_json::json!({"GetQuotation": {"items": []}, "pending_approval": "id"});
+        assert!(follow_up_buttons(Some(&held)).is_empty());
+        assert!(follow_up_keyboard(None).is_none());
+        assert!(follow_up_query("followup:unknown").is_none());
+    }
+
+    #[test]
+    fn test_approval_decision_round_trips_button_data() {
+        let id = Uuid::new_v4();
+        let keyboard = approval_keyboard(id);
+        let decisions: Vec<_> = keyboard.inline_keyboard[0]
+            .iter()
+            .map(|button| match &button.kind {
+                teloxide::types::InlineKeyboardButtonKind::CallbackData(data) => {
+                    // Telegram's limit on callback data
+                    assert!(data.len() <= 64);
+                    approval_decision(data)
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            decisions,
+            vec![
+                Some((ApprovalStatus::Approved, id)),
+                Some((ApprovalStatus::Rejected, id))
+            ]
+        );
+        assert_eq!(approval_decision("approval:approve:not-a-uuid"), None);
+        assert_eq!(approval_decision("followup:proforma"), None);
+    }
+
+    #[tokio::test]
+    async fn test_send_with_retry_recovers_from_transient_failures() {
+        let mut attempts = 0;
+        let result = send_with_retry(|| {
+            attempts += 1;
+            let outcome = if attempts < 2 {
+                Err(RequestError::Io(std::io::ErrorKind::ConnectionReset.into()))
+            } else {
+                Ok(attempts)
+            };
+            std::future::ready(outcome)
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_send_with_retry_waits_for_retry_after() {
+        tokio::time::pause();
+        let start = tokio::time::Instant::now();
+        let mut attempts = 0;
+        let result = send_with_retry(|| {
+            attempts += 1;
+            let outcome = if attempts < 2 {
+                Err(RequestError::RetryAfter(Duration::from_secs(20)))
+            } else {
+                Ok(attempts)
+            };
+            std::future::ready(outcome)
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), 2);
+        assert!(start.elapsed() >= Duration::from_secs(20));
+
+        // A longer wait than we are willing to hold the handler for is not retried
+        let mut attempts = 0;
+        let result: Result<(), _> = send_with_retry(|| {
+            attempts += 1;
+            std::future::ready(Err(RequestError::RetryAfter(Duration::from_secs(300))))
+        })
+        .await;
+        assert!(matches!(result, Err(RequestError::RetryAfter(_))));
+        assert_eq!(attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn test_send_with_retry_gives_up_on_api_errors() {
+        let mut attempts = 0;
+        let result: Result<(), _> = send_with_retry(|| {
+            attempts += 1;
+            std::future::ready(Err(RequestError::Api(ApiError::BotBlocked)))
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn test_parse_customer_command() {
+        assert_eq!(parse_customer_command(" list "), Ok(CustomerCommand::List));
+        assert_eq!(
+            parse_customer_command("show BTL EPC Ltd"),
+            Ok(CustomerCommand::Show("BTL EPC Ltd".to_string()))
+        );
+        assert_eq!(
+            parse_customer_command("delete telegram:12345"),
+            Ok(CustomerCommand::Delete("telegram:12345".to_string()))
+        );
+
+        let Ok(CustomerCommand::Set(profile)) = parse_customer_command(
+            r#"set BTL EPC Ltd {"discount": 0.05, "terms_and_conditions": ["30 days credit"]}"#,
+        ) else {
+            panic!("set should parse");
+        };
+        assert_eq!(profile.customer_key, "BTL EPC Ltd");
+        assert_eq!(profile.discount, Some(0.05));
+        assert_eq!(profile.delivery_charges, None);
+        assert!(
+            format_customer_profile(&profile).contains("Discount: 5.0%\nTerms:\n- 30 days credit")
+        );
+
+        for args in [
+            "",
+            "show",
+            "set BTL",
+            r#"set {"discount": 0.05}"#,
+            "list all",
+        ] {
+            assert!(parse_customer_command(args).is_err(), "{:?}", args);
+        }
+        assert!(parse_customer_command("set BTL {not json")
+            .unwrap_err()
+            .starts_with("Invalid customer profile JSON"));
+    }
+
+    #[test]
+    fn test_format_retry_stats() {
+        assert_eq!(
+            format_retry_stats(&BTreeMap::new()),
+            "🔁 HTTP retries: none"
+        );
+
+        let stats = BTreeMap::from([
+            (
+                "api.groq.com".to_string(),
+                HostRetryStats {
+                    retries: 4,
+                    exhausted: 1,
+                },
+            ),
+            (
+                "api.anthropic.com".to_string(),
+                HostRetryStats {
+                    retries: 2,
+                    exhausted: 0,
+                },
+            ),
+        ]);
+        assert_eq!(
+            format_retry_stats(&stats),
+            "🔁 HTTP retries\napi.anthropic.com: 2 retried, 0 gave up\napi.groq.com: 4 retried, 1 gave up"
+        );
+    }
+
+    #[test]
+    fn test_format_model_usage() {
+        let mut usage = ModelUsage::default();
+        assert_eq!(
+            format_model_usage(&usage, 7),
+            "🤖 No LLM parsed queries in the last 7 days"
+        );
+
+        usage.sessions_by_model.insert("groq".to_string(), 3);
+        usage.sessions_by_model.insert("claude".to_string(), 1);
+        assert_eq!(
+            format_model_usage(&usage, 7),
+            "🤖 LLM usage - last 7 days\nclaude: 1 (25.0%)\ngroq: 3 (75.0%)\nTotal: 4"
+        );
+    }
+
+    #[test]
+    fn test_parse_load_test_args() {
+        let (requests, concurrency, request) = parse_load_test_args(" 50").unwrap();
+        assert_eq!((requests, concurrency), (50, 1));
+        assert_eq!(request.items.len(), 10);
+
+        let (requests, concurrency, request) = parse_load_test_args(
+            r#" 20 4 {"items": [], "delivery_charges": 0, "to": null, "terms_and_conditions": null, "estimated_delivery": null, "internal_note": null}"#,
+        )
+        .unwrap();
+        assert_eq!((requests, concurrency), (20, 4));
+        assert!(request.items.is_empty());
+
+        assert!(parse_load_test_args("").is_err());
+        assert!(parse_load_test_args(" 0").is_err());
+        assert!(parse_load_test_args(" 10 100").is_err());
+        assert!(parse_load_test_args(" 10 2 {not json").is_err());
+    }
+}
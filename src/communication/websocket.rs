@@ -1,38 +1,66 @@
 use crate::prices::utils::get_local_time;
-use crate::stock::StockService;
+use crate::stock::{StockService, TallyClient, DEFAULT_WAREHOUSE};
 use axum::{
     extract::{
         ws::{Message, WebSocket},
-        State, WebSocketUpgrade,
+        Query, State, WebSocketUpgrade,
     },
     response::Response,
 };
 use futures_util::SinkExt;
 use futures_util::StreamExt;
+use serde::Deserialize;
 use std::sync::Arc;
 use tokio::sync::{mpsc, Mutex};
 use tracing::{error, info};
+use uuid::Uuid;
+
+// Tally clients connect to /ws?warehouse=<id> - older clients without the parameter are
+// treated as the default warehouse
+#[derive(Debug, Deserialize)]
+pub struct TallyConnectParams {
+    pub warehouse: Option<String>,
+}
 
 // Handles websocket upgrade request forwarded by the webserver
 // We cannot create a different webserver listening on port 8081 because DO app platform lets us use only 1 port
 pub async fn websocket_handler(
     ws: WebSocketUpgrade,
     State(stock_service): State<StockService>,
+    Query(params): Query<TallyConnectParams>,
 ) -> Response {
-    ws.on_upgrade(move |socket| handle_connection(socket, stock_service))
+    let warehouse = params
+        .warehouse
+        .map(|w| w.trim().to_lowercase())
+        .filter(|w| !w.is_empty())
+        .unwrap_or_else(|| DEFAULT_WAREHOUSE.to_string());
+    ws.on_upgrade(move |socket| handle_connection(socket, stock_service, warehouse))
 }
 
-pub async fn handle_connection(socket: WebSocket, stock_service: StockService) {
+pub async fn handle_connection(socket: WebSocket, stock_service: StockService, warehouse: String) {
     let (ws_sender, mut ws_receiver) = socket.split();
     // Create mpsc channel - the sender will be provided to the stock service which will
     // use it to return stock query responses after talking to the tally client
     let (tx, mut rx) = mpsc::channel::<String>(100);
     let ws_sender = Arc::new(Mutex::new(ws_sender));
-    // Register this connection as THE Tally client - whenever there is a new call to handle_connection
-    // It means that either this is the first connection or previous connection got broken
-    // hence we can overwrite the sender in the stock service
-    *stock_service.tally_sender.lock().await = Some(tx);
-    info!("Tally sender registered at:{}", get_local_time());
+    // Register this connection as the Tally client for its warehouse - whenever there is a new call to
+    // handle_connection it means that either this is the first connection or the previous connection
+    // from that warehouse got broken, hence we can overwrite its sender in the stock service
+    let connection_id = Uuid::new_v4();
+    stock_service
+        .register_tally_client(
+            &warehouse,
+            TallyClient {
+                connection_id,
+                sender: tx,
+            },
+        )
+        .await;
+    info!(
+        "Tally sender registered for warehouse {} at:{}",
+        warehouse,
+        get_local_time()
+    );
     // Handle outgoing messages to Tally
     let sender = Arc::clone(&ws_sender);
     tokio::spawn(async move {
@@ -77,4 +105,9 @@ pub async fn handle_connection(socket: WebSocket, stock_service: StockService) {
             break;
         }
     }
+
+    // Fail anything still waiting on this connection so users aren't left on the timeout
+    stock_service
+        .handle_tally_disconnect(&warehouse, connection_id)
+        .await;
 }
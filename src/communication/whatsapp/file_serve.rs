@@ -44,7 +44,7 @@ pub async fn serve_file(
             .unwrap()),
         Err(e) => {
             let error_msg = format!("❌ File Serve Error\n\nFile: {}\nError: {}", file_path, e);
-            let _ = state.error_sender.try_send(error_msg);
+            let _ = state.error_sender.try_send(error_msg.into());
             Err(StatusCode::NOT_FOUND)
         }
     }
@@ -71,7 +71,7 @@ pub async fn serve_assets_file(
             .unwrap()),
         Err(e) => {
             let error_msg = format!("❌ File Serve Error\n\nFile: {}\nError: {}", file_path, e);
-            let _ = state.error_sender.try_send(error_msg);
+            let _ = state.error_sender.try_send(error_msg.into());
             Err(StatusCode::NOT_FOUND)
         }
     }
@@ -1,4 +1,5 @@
 use super::AppState;
+use crate::core::Alert;
 use crate::database::SessionContext;
 use axum::{
     http::{header::CONTENT_TYPE, StatusCode},
@@ -23,7 +24,7 @@ pub async fn send_whatsapp_message_with_media(
         ("MediaUrl", media_url),
     ];
 
-    let response = state
+    let response = match state
         .http_client
         .execute_with_retry(
             state
@@ -32,7 +33,19 @@ pub async fn send_whatsapp_message_with_media(
                 .basic_auth(&state.twilio_account_sid, Some(&state.twilio_auth_token))
                 .form(&params),
         )
-        .await?;
+        .await
+    {
+        Ok(response) => response,
+        Err(e) => {
+            // execute_with_retry has already retried, so this delivery is lost
+            let error_msg = format!(
+                "Failed to deliver whatsapp message with media : {}, to:{} - {}",
+                media_url, to, e
+            );
+            let _ = state.error_sender.try_send(Alert::error(error_msg));
+            return Err(e.into());
+        }
+    };
 
     if !response.status().is_success() {
         error!(
@@ -43,7 +56,7 @@ pub async fn send_whatsapp_message_with_media(
             "❌ Error sending whatsapp message with media : {}, to:{}",
             media_url, to
         );
-        let _ = state.error_sender.try_send(error_msg);
+        let _ = state.error_sender.try_send(error_msg.into());
     }
 
     let _ = state
@@ -72,7 +85,7 @@ pub async fn send_whatsapp_message(
         ("Body", message),
     ];
 
-    let response = state
+    let response = match state
         .http_client
         .execute_with_retry(
             state
@@ -81,7 +94,18 @@ pub async fn send_whatsapp_message(
                 .basic_auth(&state.twilio_account_sid, Some(&state.twilio_auth_token))
                 .form(&params),
         )
-        .await?;
+        .await
+    {
+        Ok(response) => response,
+        Err(e) => {
+            let error_msg = format!(
+                "Failed to deliver whatsapp message after retries, to:{} - {}",
+                to, e
+            );
+            let _ = state.error_sender.try_send(Alert::error(error_msg));
+            return Err(e.into());
+        }
+    };
 
     if !response.status().is_success() {
         let error_msg = format!(
@@ -89,7 +113,7 @@ pub async fn send_whatsapp_message(
             response.status(),
             to
         );
-        let _ = state.error_sender.try_send(error_msg);
+        let _ = state.error_sender.try_send(error_msg.into());
         error!("Failed to send WhatsApp message: {}", response.status());
     }
 
@@ -1,13 +1,14 @@
 use crate::communication::session_helpers::{
     create_session_or_error, create_whatsapp_session_context,
 };
-use crate::communication::websocket::websocket_handler;
+use crate::communication::websocket::{websocket_handler, TallyConnectParams};
 use crate::configuration::Context;
-use crate::core::http::RetryableClient;
+use crate::core::http::{RetryConfig, RetryableClient};
 use crate::core::service_manager::{Error as ServiceManagerError, ServiceWithErrorSender};
+use crate::core::Alert;
 use crate::database::DatabaseService;
-use crate::database::{SessionContext, User};
-use crate::query::QueryFulfilment;
+use crate::database::{normalize_phone, ApprovalStatus, QuotationApproval, SessionContext, User};
+use crate::query::{check_media_size, QueryError, QueryFulfilment, QueryResponse};
 use crate::stock::StockService;
 use async_trait::async_trait;
 use axum::extract::WebSocketUpgrade;
@@ -22,9 +23,9 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use thiserror::Error;
 use tokio::net::TcpListener;
-use tokio::sync::mpsc;
 use tokio::sync::mpsc::Sender;
-use tracing::{error, info};
+use tokio::sync::{broadcast, mpsc};
+use tracing::{error, info, warn};
 use uuid::Uuid;
 
 mod file_serve;
@@ -36,7 +37,8 @@ use file_serve::{serve_assets_file, serve_file};
 use message_sender::send_text_response;
 use webhook_validation::validate_twilio_signature;
 use whatsapp_helpers::{
-    convert_whatsapp_error_to_query_error, process_query_response, QueryProcessingParams,
+    convert_whatsapp_error_to_query_error, process_query_response, release_approval,
+    QueryProcessingParams,
 };
 
 #[derive(Debug, Error)]
@@ -45,16 +47,29 @@ pub enum WhatsAppError {
     QueryFulfilmentInitError(String),
     #[error("Image processing error: {0}")]
     ImageProcessingError(String),
+    #[error("Media of {0} bytes is over the {1} byte limit")]
+    MediaTooLarge(u64, u64),
+}
+
+impl From<QueryError> for WhatsAppError {
+    fn from(error: QueryError) -> Self {
+        match error {
+            QueryError::MediaTooLarge(size, limit) => Self::MediaTooLarge(size, limit),
+            e => Self::QueryFulfilmentInitError(e.to_string()),
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct AppState {
     pub query_fulfilment: Arc<QueryFulfilment>,
-    pub error_sender: mpsc::Sender<String>,
+    pub error_sender: mpsc::Sender<Alert>,
     pub file_base_url: String,
     pub twilio_account_sid: String,
     pub twilio_auth_token: String,
     pub http_client: RetryableClient,
+    // Twilio media downloads, which are worth more patience than a reply
+    pub media_client: RetryableClient,
     pub database: Arc<DatabaseService>,
     pub stock_service: Arc<StockService>,
 }
@@ -62,20 +77,22 @@ pub struct AppState {
 pub struct WhatsAppService {
     port: u16,
     query_fulfilment: QueryFulfilment,
-    error_sender: mpsc::Sender<String>,
+    error_sender: mpsc::Sender<Alert>,
     file_base_url: String,
     twilio_account_sid: String,
     twilio_auth_token: String,
     http_client: RetryableClient,
+    media_client: RetryableClient,
     database: Arc<DatabaseService>,
     stock_service: Arc<StockService>,
+    approval_events: broadcast::Receiver<QuotationApproval>,
 }
 
 #[async_trait]
 impl ServiceWithErrorSender for WhatsAppService {
     type Context = Context;
 
-    async fn new(context: Context, error_sender: mpsc::Sender<String>) -> Self {
+    async fn new(context: Context, error_sender: mpsc::Sender<Alert>) -> Self {
         let query_fulfilment = QueryFulfilment::new(context.clone()).await.unwrap();
         let twilio_account_sid = std::env::var("TWILIO_ACCOUNT_SID").unwrap();
         let twilio_auth_token = std::env::var("TWILIO_AUTH_TOKEN").unwrap();
@@ -87,8 +104,13 @@ impl ServiceWithErrorSender for WhatsAppService {
             twilio_account_sid,
             twilio_auth_token,
             http_client: RetryableClient::new(),
+            media_client: RetryableClient::with_config(RetryConfig {
+                max_retries: 5,
+                ..RetryConfig::default()
+            }),
             database: context.database.clone(),
             stock_service: context.stock_service.clone(),
+            approval_events: context.approvals.subscribe(),
         }
     }
 
@@ -100,9 +122,11 @@ impl ServiceWithErrorSender for WhatsAppService {
             twilio_account_sid: self.twilio_account_sid,
             twilio_auth_token: self.twilio_auth_token,
             http_client: self.http_client,
+            media_client: self.media_client,
             database: self.database,
             stock_service: self.stock_service.clone(),
         };
+        tokio::spawn(forward_approvals(state.clone(), self.approval_events));
 
         let app = Router::new()
             .route("/health", get(health_check))
@@ -124,12 +148,32 @@ impl ServiceWithErrorSender for WhatsAppService {
     }
 }
 
+// Approvals are decided on Telegram - this sends the ones requested on WhatsApp on
+async fn forward_approvals(state: AppState, mut events: broadcast::Receiver<QuotationApproval>) {
+    loop {
+        match events.recv().await {
+            Ok(approval)
+                if approval.platform == "whatsapp"
+                    && approval.status != ApprovalStatus::Pending =>
+            {
+                release_approval(&state, approval).await
+            }
+            Ok(_) => {}
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                warn!("Missed {} approval events", missed)
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        }
+    }
+}
+
 async fn whatsapp_websocket_handler(
     ws: WebSocketUpgrade,
     State(app_state): State<AppState>,
+    params: axum::extract::Query<TallyConnectParams>,
 ) -> Response {
     let stock_service = app_state.stock_service.as_ref().clone();
-    websocket_handler(ws, axum::extract::State(stock_service)).await
+    websocket_handler(ws, axum::extract::State(stock_service), params).await
 }
 
 async fn health_check() -> (StatusCode, &'static str) {
@@ -180,13 +224,16 @@ async fn webhook_handler(
     let from = payload.get("From").unwrap_or(&"".to_string()).clone();
     let body = payload.get("Body").unwrap_or(&"".to_string()).clone();
 
-    let phone = from.strip_prefix("whatsapp:").unwrap_or(&from);
+    let phone = normalize_phone(&from).unwrap_or_else(|| {
+        warn!("Webhook sender is not a valid phone number: {}", from);
+        from.strip_prefix("whatsapp:").unwrap_or(&from).to_string()
+    });
 
     // Define default IDs for unauthorized users
     let default_user_id = Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap();
     let default_context =
-        SessionContext::new(default_user_id, "whatsapp").with_phone(phone.to_string());
-    let user = match state.database.get_user_by_phone(phone).await {
+        SessionContext::new(default_user_id, "whatsapp").with_phone(phone.clone());
+    let user = match state.database.get_user_by_phone(&phone).await {
         Ok(Some(user)) => {
             if !state.database.is_user_authorized(&user).await {
                 // Log cost for unauthorized user
@@ -230,7 +277,7 @@ async fn webhook_handler(
         .await;
 
     if body.trim() == "/help" || body.trim() == "help" {
-        return send_text_response(&QueryFulfilment::get_help_text(), &state, &context).await;
+        return send_text_response(&QueryFulfilment::get_help_text(None), &state, &context).await;
     }
 
     if let Some(media_url) = payload.get("MediaUrl0") {
@@ -306,9 +353,8 @@ async fn create_session_context(
 ) -> SessionContext {
     let phone = user
         .phone_number
-        .as_ref()
-        .and_then(|p| p.strip_prefix("whatsapp:"))
-        .map(|p| p.to_string())
+        .as_deref()
+        .and_then(normalize_phone)
         .unwrap_or_default();
 
     let context = create_whatsapp_session_context(user, &phone);
@@ -328,14 +374,14 @@ async fn download_and_process_image(
     media_url: &str,
     user_text: &str,
     context: &mut SessionContext,
-    error_sender: &Sender<String>,
-) -> Result<crate::communication::telegram::Response, WhatsAppError> {
+    error_sender: &Sender<Alert>,
+) -> Result<QueryResponse, WhatsAppError> {
     // Download image from Twilio media URL
     let response = state
-        .http_client
+        .media_client
         .execute_with_retry(
             state
-                .http_client
+                .media_client
                 .get(media_url)
                 .basic_auth(&state.twilio_account_sid, Some(&state.twilio_auth_token)),
         )
@@ -348,6 +394,12 @@ async fn download_and_process_image(
         ));
     }
 
+    // Twilio sends the media's length up front - stop before reading an oversized body
+    let max_image_bytes = state.query_fulfilment.media_limits().max_image_bytes;
+    if let Some(length) = response.content_length() {
+        check_media_size(length, max_image_bytes)?;
+    }
+
     let image_data = response
         .bytes()
         .await
@@ -358,7 +410,7 @@ async fn download_and_process_image(
         .query_fulfilment
         .fulfil_image_query(&image_data, user_text, context, error_sender)
         .await
-        .map_err(|e| WhatsAppError::QueryFulfilmentInitError(e.to_string()))
+        .map_err(WhatsAppError::from)
 }
 
 #[cfg(test)]
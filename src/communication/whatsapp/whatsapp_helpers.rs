@@ -1,10 +1,9 @@
 use crate::communication::error_handler::map_query_error_to_user_message;
-use crate::communication::session_helpers::{complete_session_with_error, complete_session_with_success};
-use crate::communication::telegram::Response;
+use crate::communication::session_helpers::{approval_rejected_text, approval_released_text, complete_session_with_error, complete_session_with_success};
 use crate::communication::whatsapp::message_sender::{send_whatsapp_message, send_whatsapp_message_with_media};
 use crate::communication::whatsapp::AppState;
-use crate::database::SessionContext;
-use crate::query::QueryError;
+use crate::database::{ApprovalStatus, QuotationApproval, SessionContext};
+use crate::query::{QueryError, QueryResponse};
 use urlencoding::encode;
 
 pub struct QueryProcessingParams {
@@ -17,7 +16,7 @@ pub struct QueryProcessingParams {
 
 pub async fn process_query_response(
     params: QueryProcessingParams,
-    result: Result<Response, QueryError>,
+    result: Result<QueryResponse, QueryError>,
 ) {
     let QueryProcessingParams { state, from, query_text, context, start_time } = params;
 
@@ -25,15 +24,14 @@ pub async fn process_query_response(
         Ok(response) => {
             complete_session_with_success(&state.database, &context, &response, &query_text, start_time, &state.error_sender).await;
 
-            if let Some(file_path) = response.file {
-                let parts: Vec<&str> = file_path.split('/').collect();
-                let encoded_parts: Vec<String> = parts.iter().map(|part| encode(part).to_string()).collect();
-                let encoded_path = encoded_parts.join("/");
-                let file_url = format!("{}/{}", state.file_base_url, encoded_path);
-                let _ = send_whatsapp_message_with_media(&state, &from, &file_url, &context).await;
-            } else {
+            if response.file.is_none() {
                 let _ = send_whatsapp_message(&state, &from, &response.text, &context).await;
             }
+            // One message per document, the main one first
+            for file_path in response.files() {
+                let file_url = file_url(&state.file_base_url, file_path);
+                let _ = send_whatsapp_message_with_media(&state, &from, &file_url, &context).await;
+            }
         }
         Err(e) => {
             complete_session_with_error(&state.database, &context, &e, &query_text, start_time, &state.error_sender).await;
@@ -43,9 +41,37 @@ pub async fn process_query_response(
     }
 }
 
+// Sends a quotation held for approval on to the customer once an admin has decided on it
+pub async fn release_approval(state: &AppState, approval: QuotationApproval) {
+    let to = approval.recipient.as_str();
+    let phone = to.trim_start_matches("whatsapp:").to_string();
+    let context = SessionContext::new(approval.user_id, "whatsapp").with_phone(phone);
+
+    match approval.status {
+        ApprovalStatus::Approved => {
+            let _ = send_whatsapp_message(state, to, &approval_released_text(&approval), &context).await;
+            for file_path in &approval.files {
+                let file_url = file_url(&state.file_base_url, file_path);
+                let _ = send_whatsapp_message_with_media(state, to, &file_url, &context).await;
+            }
+        }
+        ApprovalStatus::Rejected => {
+            let _ = send_whatsapp_message(state, to, &approval_rejected_text(&approval), &context).await;
+        }
+        ApprovalStatus::Pending => {}
+    }
+}
+
+// Twilio fetches media from a URL, so each path segment is encoded
+fn file_url(base_url: &str, file_path: &str) -> String {
+    let encoded_parts: Vec<String> = file_path.split('/').map(|part| encode(part).to_string()).collect();
+    format!("{}/{}", base_url, encoded_parts.join("/"))
+}
+
 pub fn convert_whatsapp_error_to_query_error(error: crate::communication::whatsapp::WhatsAppError) -> QueryError {
     match error {
         crate::communication::whatsapp::WhatsAppError::ImageProcessingError(_) => QueryError::OcrError(error.to_string()),
+        crate::communication::whatsapp::WhatsAppError::MediaTooLarge(size, limit) => QueryError::MediaTooLarge(size, limit),
         _ => QueryError::LLMError(error.to_string()),
     }
 }
\ No newline at end of file
@@ -1,9 +1,14 @@
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
 use std::fs;
+use std::num::NonZeroU32;
 use std::sync::Arc;
 use thiserror::Error;
+use tokio::sync::broadcast;
 
-use crate::database::DatabaseService;
+use crate::database::{DatabaseService, QuotationApproval};
 use crate::stock::StockService;
 
 #[derive(Debug, Error)]
@@ -13,6 +18,19 @@ pub enum ConfigError {
 
     #[error("Deserialization error:{0}")]
     DeserializationError(String),
+
+    #[error("Invalid \"{section}\" section: {message}{}", display_hint(.hint))]
+    InvalidSection {
+        section: String,
+        message: String,
+        hint: Option<String>,
+    },
+}
+
+fn display_hint(hint: &Option<String>) -> String {
+    hint.as_ref()
+        .map(|hint| format!(" - {}", hint))
+        .unwrap_or_default()
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -22,25 +40,297 @@ pub struct Config {
     pub pdf_pricelists: Vec<PdfPriceListConfig>,
     pub metal_pricing: MetalPricingConfig,
     pub claude: ClaudeConfig,
+    #[serde(default)]
+    pub groq: GroqConfig,
     pub telegram: TelegramConfig,
     pub whatsapp: WhatsappConfig,
+    #[serde(default)]
+    pub retention: Option<RetentionConfig>,
+    #[serde(default)]
+    pub quotation: QuotationConfig,
+    #[serde(default)]
+    pub ocr: OcrConfig,
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHoursConfig>,
+    #[serde(default)]
+    pub transcription: TranscriptionConfig,
+    #[serde(default)]
+    pub confirm_intent: ConfirmIntentConfig,
+    #[serde(default)]
+    pub features: FeaturesConfig,
+    #[serde(default)]
+    pub query: QueryConfig,
+    #[serde(default)]
+    pub media: MediaConfig,
+    #[serde(default)]
+    pub branding: BrandingConfig,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct QueryConfig {
+    // Outer deadline on a whole query - OCR / transcription, parsing and the response together.
+    // Each stage still has its own, shorter timeouts.
+    #[serde(default = "default_query_timeout_secs")]
+    pub timeout_secs: u64,
+    // Follow-ups resend the whole conversation, so once a conversation has cost this much the
+    // user is asked to start afresh. No limit when unset.
+    #[serde(default)]
+    pub max_session_cost_usd: Option<f64>,
+}
+
+impl Default for QueryConfig {
+    fn default() -> Self {
+        Self {
+            timeout_secs: default_query_timeout_secs(),
+            max_session_cost_usd: None,
+        }
+    }
+}
+
+fn default_query_timeout_secs() -> u64 {
+    180
+}
+
+// Largest image / voice note accepted from Telegram or WhatsApp. Checked against the size the
+// platform reports, before anything is downloaded.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+pub struct MediaConfig {
+    #[serde(default = "default_max_image_bytes")]
+    pub max_image_bytes: u64,
+    #[serde(default = "default_max_audio_bytes")]
+    pub max_audio_bytes: u64,
+}
+
+impl Default for MediaConfig {
+    fn default() -> Self {
+        Self {
+            max_image_bytes: default_max_image_bytes(),
+            max_audio_bytes: default_max_audio_bytes(),
+        }
+    }
+}
+
+fn default_max_image_bytes() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_max_audio_bytes() -> u64 {
+    20 * 1024 * 1024
+}
+
+// Letterhead and footer line printed on every quotation / proforma page
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct BrandingConfig {
+    #[serde(default = "default_header_image_path")]
+    pub header_image_path: String,
+    // The footer reads "{footer_prefix}{footer_emphasis}", with the emphasis in blue
+    #[serde(default = "default_footer_prefix")]
+    pub footer_prefix: String,
+    #[serde(default = "default_footer_emphasis")]
+    pub footer_emphasis: String,
+}
+
+impl Default for BrandingConfig {
+    fn default() -> Self {
+        Self {
+            header_image_path: default_header_image_path(),
+            footer_prefix: default_footer_prefix(),
+            footer_emphasis: default_footer_emphasis(),
+        }
+    }
+}
+
+fn default_header_image_path() -> String {
+    crate::pdf::HEADER_IMAGE_PATH.to_string()
+}
+
+fn default_footer_prefix() -> String {
+    "Prepared using ".to_string()
+}
+
+fn default_footer_emphasis() -> String {
+    "AGL Intelligent Commercial Automation".to_string()
+}
+
+// Optional extras that each cost latency or LLM spend - all on unless switched off
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct FeaturesConfig {
+    // Carry earlier messages into follow-up queries
+    #[serde(default = "default_true")]
+    pub conversation_continuation: bool,
+    // Ask the LLM whether a query follows up on the last one - without it, only queries sent
+    // shortly after the previous reply continue the conversation
+    #[serde(default = "default_true")]
+    pub conversation_classifier: bool,
+    // Report the cost and timing of every successful query
+    #[serde(default = "default_true")]
+    pub cost_notifications: bool,
+}
+
+impl Default for FeaturesConfig {
+    fn default() -> Self {
+        Self {
+            conversation_continuation: true,
+            conversation_classifier: true,
+            cost_notifications: true,
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct MetalPricingConfig {
     pub al_url: String,
     pub cu_url: String,
+    // Tried in order when the main url is down or no longer shows a readable price
+    #[serde(default)]
+    pub al_fallbacks: Vec<MetalPriceSource>,
+    #[serde(default)]
+    pub cu_fallbacks: Vec<MetalPriceSource>,
+    // Any other traded metal by name (e.g. "lead"), with its sources in the order tried
+    #[serde(default)]
+    pub metals: HashMap<String, Vec<MetalPriceSource>>,
+    // Scheduled alerts are skipped unless copper or aluminium moved by more than this since
+    // the last one; every alert is sent when unset
+    #[serde(default)]
+    pub alert_threshold_percent: Option<f64>,
+    // IST times of the scheduled price alerts
+    #[serde(default = "default_alert_times")]
+    pub alert_times: Vec<AlertTime>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AlertTime {
+    pub hour: u32,
+    pub minute: u32,
+}
+
+fn default_alert_times() -> Vec<AlertTime> {
+    vec![
+        AlertTime {
+            hour: 10,
+            minute: 28,
+        },
+        AlertTime {
+            hour: 15,
+            minute: 9,
+        },
+    ]
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct MetalPriceSource {
+    pub url: String,
+    #[serde(default = "default_price_selector")]
+    pub selector: String,
+}
+
+fn default_price_selector() -> String {
+    "div.commodity-page__value".to_string()
+}
+
+impl MetalPriceSource {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            selector: default_price_selector(),
+        }
+    }
+}
+
+impl MetalPricingConfig {
+    pub fn aluminium_sources(&self) -> Vec<MetalPriceSource> {
+        std::iter::once(MetalPriceSource::new(&self.al_url))
+            .chain(self.al_fallbacks.iter().cloned())
+            .collect()
+    }
+
+    pub fn copper_sources(&self) -> Vec<MetalPriceSource> {
+        std::iter::once(MetalPriceSource::new(&self.cu_url))
+            .chain(self.cu_fallbacks.iter().cloned())
+            .collect()
+    }
+
+    /// Every configured metal, keyed by its lowercase name
+    pub fn metal_sources(&self) -> HashMap<String, Vec<MetalPriceSource>> {
+        let mut sources: HashMap<String, Vec<MetalPriceSource>> = self
+            .metals
+            .iter()
+            .map(|(metal, sources)| (metal.to_lowercase(), sources.clone()))
+            .collect();
+        sources.insert("aluminium".to_string(), self.aluminium_sources());
+        sources.insert("copper".to_string(), self.copper_sources());
+        sources
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct ClaudeConfig {
     pub system_prompt: String,
+    // Changed here, without a rebuild, when Anthropic retires a model
+    #[serde(default = "default_claude_model")]
+    pub model: String,
+    #[serde(default = "default_claude_max_tokens")]
+    pub max_tokens: u32,
+    // Retries when Claude is overloaded, backing off 1s, 2s, 4s... before falling back to Groq
+    #[serde(default = "default_claude_overload_retries")]
+    pub overload_retries: u32,
+}
+
+pub(crate) fn default_claude_model() -> String {
+    "claude-sonnet-4-20250514".to_string()
+}
+
+pub(crate) fn default_claude_max_tokens() -> u32 {
+    10240
+}
+
+pub(crate) fn default_claude_overload_retries() -> u32 {
+    3
+}
+
+// Groq shares Claude's system prompt, only the model is its own
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct GroqConfig {
+    #[serde(default = "default_groq_model")]
+    pub model: String,
+    #[serde(default = "default_groq_max_tokens")]
+    pub max_tokens: u32,
+}
+
+impl Default for GroqConfig {
+    fn default() -> Self {
+        Self {
+            model: default_groq_model(),
+            max_tokens: default_groq_max_tokens(),
+        }
+    }
+}
+
+fn default_groq_model() -> String {
+    "moonshotai/kimi-k2-instruct-0905".to_string()
+}
+
+fn default_groq_max_tokens() -> u32 {
+    8192
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct PriceListConfig {
     pub pricelist: String,
     pub brand: String,
+    // Standing discount for the brand, used when a quote item doesn't give one
+    #[serde(default)]
+    pub default_discount: Option<f32>,
+    // Tags that point at the brand's current prices - quoting off any other tag raises a warning
+    #[serde(default)]
+    pub current_tags: Vec<String>,
+    // Standing margin over listed cost eg. 0.08 means 8%, applied after discount and loadings
+    #[serde(default)]
+    pub markup: Option<f32>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -54,7 +344,17 @@ pub struct PdfPriceListConfig {
 pub struct TelegramConfig {
     pub price_alert_subscribers: Vec<i64>,
     pub error_channel_id: i64,
+    // Query cost and timing reports - falls back to the error channel when unset
+    #[serde(default)]
+    pub cost_channel_id: Option<i64>,
     pub admin_telegram_id: String,
+    // Repeats of the same error within this window are collapsed into one summary
+    #[serde(default = "default_error_dedup_window_secs")]
+    pub error_dedup_window_secs: u64,
+}
+
+fn default_error_dedup_window_secs() -> u64 {
+    60
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -66,11 +366,323 @@ pub struct WhatsappConfig {
     pub template_sid: String,
 }
 
+#[derive(Debug, Deserialize, Clone)]
+pub struct QuotationConfig {
+    #[serde(default)]
+    pub rounding: RoundingConfig,
+    // How users write amounts, used to clean up user given prices before they are quoted
+    #[serde(default)]
+    pub number_locale: NumberLocale,
+    // Brands tried in order for items that don't name one, and as fallbacks for items missing
+    // from the named brand's pricelists
+    #[serde(default)]
+    pub brand_preference: Vec<String>,
+    // Never price an item from a brand other than the one named
+    #[serde(default)]
+    pub strict_brand: bool,
+    // Print the customer's original enquiry on a closing page of quotations / proformas
+    #[serde(default)]
+    pub attach_enquiry: bool,
+    // Extra discount off the sub total for large orders, on top of any per item discounts
+    #[serde(default)]
+    pub volume_discounts: Vec<VolumeDiscountTier>,
+    #[serde(default)]
+    pub sanity: SanityConfig,
+    // Width of the item description column in mm (60 - 95, default 90), including the 16mm HSN
+    // column at its right. A wider column wraps long descriptions less, at the cost of narrower
+    // Qty / List Rate / Disc % / Rate / Amount columns. Widths above 95mm, which fitted before the
+    // List Rate and Disc % columns were added, are drawn at 95mm and logged at startup.
+    #[serde(default)]
+    pub description_column_mm: Option<f64>,
+    // Quotations / proformas with a grand total above this are held until an admin approves
+    // them on Telegram
+    #[serde(default)]
+    pub approval_threshold: Option<f32>,
+    // Our GST registration state eg. "Maharashtra" - buyers in the same state are charged
+    // CGST + SGST, others IGST
+    #[serde(default)]
+    pub seller_state: Option<String>,
+    // Printed on proforma invoices so the customer can pay against them
+    #[serde(default)]
+    pub bank_details: Option<BankDetails>,
+    // How long the "standard" terms say a quotation holds - metal prices move fast
+    #[serde(default = "default_validity_days")]
+    pub validity_days: u32,
+}
+
+impl Default for QuotationConfig {
+    fn default() -> Self {
+        Self {
+            rounding: RoundingConfig::default(),
+            number_locale: NumberLocale::default(),
+            brand_preference: Vec::new(),
+            strict_brand: false,
+            attach_enquiry: false,
+            volume_discounts: Vec::new(),
+            sanity: SanityConfig::default(),
+            description_column_mm: None,
+            approval_threshold: None,
+            seller_state: None,
+            bank_details: None,
+            validity_days: default_validity_days(),
+        }
+    }
+}
+
+fn default_validity_days() -> u32 {
+    3
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct BankDetails {
+    pub account_name: String,
+    pub account_number: String,
+    pub ifsc: String,
+    pub bank: String,
+    #[serde(default)]
+    pub branch: Option<String>,
+    #[serde(default)]
+    pub upi_id: Option<String>,
+}
+
+// When an order reaches more than one tier, the largest discount applies
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct VolumeDiscountTier {
+    pub basis: VolumeDiscountBasis,
+    // The tier applies once the order's total quantity / value is above this
+    pub above: f32,
+    // eg. 0.03 means 3%
+    pub discount: f32,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum VolumeDiscountBasis {
+    // Sum of the item quantities, with lengths counted in metres and pieces as they are
+    Quantity,
+    // Sub total before the volume discount
+    Value,
+}
+
+// Bounds a priced quotation has to stay within before a document is made from it. Figures
+// beyond them come from a mis-parsed request far more often than from a real order.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+pub struct SanityConfig {
+    // Highest believable rate for an item sold by the metre
+    #[serde(default = "default_max_price_per_metre")]
+    pub max_price_per_metre: f32,
+}
+
+impl Default for SanityConfig {
+    fn default() -> Self {
+        Self {
+            max_price_per_metre: default_max_price_per_metre(),
+        }
+    }
+}
+
+fn default_max_price_per_metre() -> f32 {
+    20000.0
+}
+
+// Line amounts and subtotals are always rounded to paise so the printed figures add up;
+// only the final payable amount is configurable
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct RoundingConfig {
+    #[serde(default)]
+    pub grand_total: GrandTotalRounding,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GrandTotalRounding {
+    #[default]
+    NearestRupee,
+    Paise,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum NumberLocale {
+    // 1,234.56 or 1,23,456.78
+    #[default]
+    Indian,
+    // 1.234,56
+    European,
+}
+
+impl NumberLocale {
+    pub fn grouping_separator(&self) -> char {
+        match self {
+            Self::Indian => ',',
+            Self::European => '.',
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RetentionConfig {
+    // Non zero, a cutoff of today or later would purge every conversation on each run
+    pub conversation_retention_days: NonZeroU32,
+    #[serde(default = "default_purge_interval_hours")]
+    pub purge_interval_hours: u64,
+}
+
+fn default_purge_interval_hours() -> u64 {
+    24
+}
+
+// Hours are in IST, the window may wrap past midnight eg. 22 -> 7
+#[derive(Debug, Deserialize, Clone)]
+pub struct QuietHoursConfig {
+    pub start_hour: u32,
+    pub end_hour: u32,
+}
+
+// Forces the spoken language for single-language deployments - unset means Whisper auto-detects
+#[derive(Debug, Deserialize, Clone)]
+pub struct TranscriptionConfig {
+    #[serde(default)]
+    pub language: Option<String>,
+    // Per platform override eg. {"whatsapp": "hi"}
+    #[serde(default)]
+    pub platform_languages: HashMap<String, String>,
+    // Audio above the upload limit is split into chunks of this length with ffmpeg
+    #[serde(default = "default_max_upload_bytes")]
+    pub max_upload_bytes: usize,
+    #[serde(default = "default_chunk_seconds")]
+    pub chunk_seconds: u32,
+    #[serde(default = "default_ffmpeg_command")]
+    pub ffmpeg_command: String,
+    #[serde(default = "default_transcription_url")]
+    pub api_url: String,
+}
+
+impl Default for TranscriptionConfig {
+    fn default() -> Self {
+        Self {
+            language: None,
+            platform_languages: HashMap::new(),
+            max_upload_bytes: default_max_upload_bytes(),
+            chunk_seconds: default_chunk_seconds(),
+            ffmpeg_command: default_ffmpeg_command(),
+            api_url: default_transcription_url(),
+        }
+    }
+}
+
+// Groq rejects uploads above 25 MB
+fn default_max_upload_bytes() -> usize {
+    24 * 1024 * 1024
+}
+
+fn default_chunk_seconds() -> u32 {
+    600
+}
+
+fn default_ffmpeg_command() -> String {
+    "ffmpeg".to_string()
+}
+
+fn default_transcription_url() -> String {
+    "https://api.groq.com/openai/v1/audio/transcriptions".to_string()
+}
+
+impl TranscriptionConfig {
+    pub fn language_for(&self, platform: &str) -> Option<&str> {
+        self.platform_languages
+            .get(platform)
+            .or(self.language.as_ref())
+            .map(|language| language.as_str())
+            .filter(|language| !language.is_empty() && *language != "auto")
+    }
+}
+
+// Asks the user to confirm before a quotation / proforma invoice is generated. Users are
+// matched on telegram id or phone number.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct ConfirmIntentConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    // Always asked, even when not enabled for everyone
+    #[serde(default)]
+    pub users: Vec<String>,
+    // Never asked - for users who want zero friction
+    #[serde(default)]
+    pub exempt_users: Vec<String>,
+}
+
+impl ConfirmIntentConfig {
+    pub fn applies_to(&self, telegram_id: Option<&str>, phone: Option<&str>) -> bool {
+        let listed = |users: &[String]| {
+            users
+                .iter()
+                .any(|user| Some(user.as_str()) == telegram_id || Some(user.as_str()) == phone)
+        };
+        if listed(&self.exempt_users) {
+            return false;
+        }
+        self.enabled || listed(&self.users)
+    }
+}
+
+// Caps the resolution of images sent for OCR, on every platform
+#[derive(Debug, Deserialize, Clone)]
+pub struct OcrConfig {
+    #[serde(default = "default_max_image_megapixels")]
+    pub max_image_megapixels: f32,
+    // Tried in order - later providers are fallbacks when earlier ones fail
+    #[serde(default = "default_ocr_providers")]
+    pub providers: Vec<OcrProviderKind>,
+    #[serde(default = "default_tesseract_command")]
+    pub tesseract_command: String,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OcrProviderKind {
+    Textract,
+    Tesseract,
+}
+
+impl Default for OcrConfig {
+    fn default() -> Self {
+        Self {
+            max_image_megapixels: default_max_image_megapixels(),
+            providers: default_ocr_providers(),
+            tesseract_command: default_tesseract_command(),
+        }
+    }
+}
+
+impl OcrConfig {
+    pub fn max_image_pixels(&self) -> u64 {
+        (self.max_image_megapixels.max(0.1) * 1_000_000.0) as u64
+    }
+}
+
+fn default_max_image_megapixels() -> f32 {
+    8.0
+}
+
+fn default_ocr_providers() -> Vec<OcrProviderKind> {
+    vec![OcrProviderKind::Textract]
+}
+
+fn default_tesseract_command() -> String {
+    "tesseract".to_string()
+}
+
+// Approvals waiting on an admin are few, so a small buffer is plenty
+const APPROVAL_EVENTS_CAPACITY: usize = 32;
+
 #[derive(Clone)]
 pub struct Context {
     pub config: Config,
     pub database: Arc<DatabaseService>,
     pub stock_service: Arc<StockService>,
+    // Held quotations as they are requested and decided, for the platform services to act on
+    pub approvals: broadcast::Sender<QuotationApproval>,
 }
 
 impl Context {
@@ -78,11 +690,13 @@ impl Context {
         let config = Config::new(config_file)?;
         let database = DatabaseService::new(config.telegram.admin_telegram_id.clone()).map_err(|e| {
             ConfigError::DeserializationError(format!("Database init failed: {}", e))
-        })?;
+        })?
+        .with_cost_notifications(config.features.cost_notifications);
         Ok(Self {
             config,
             database: Arc::new(database),
             stock_service,
+            approvals: broadcast::channel(APPROVAL_EVENTS_CAPACITY).0,
         })
     }
 }
@@ -90,8 +704,290 @@ impl Context {
 impl Config {
     pub fn new(config_file: &str) -> Result<Self, ConfigError> {
         let config_str = fs::read_to_string(config_file).map_err(|_| ConfigError::FileError)?;
-        let config: Config = serde_json::from_str(&config_str)
-            .map_err(|e| ConfigError::DeserializationError(e.to_string()))?;
-        Ok(config)
+        Self::from_json(&config_str)
+    }
+
+    pub fn from_json(config_str: &str) -> Result<Self, ConfigError> {
+        serde_json::from_str(config_str).map_err(|e| {
+            // serde only reports a line and column, so work out which section is at fault
+            match serde_json::from_str::<Value>(config_str) {
+                Ok(root) => find_invalid_section(&root)
+                    .unwrap_or_else(|| ConfigError::DeserializationError(e.to_string())),
+                Err(_) => ConfigError::DeserializationError(format!(
+                    "{} - the file is not valid JSON, check for a missing comma or quote",
+                    e
+                )),
+            }
+        })
+    }
+}
+
+type SectionCheck = fn(&Value) -> Result<(), serde_json::Error>;
+
+fn check<T: DeserializeOwned>(value: &Value) -> Result<(), serde_json::Error> {
+    T::deserialize(value).map(|_| ())
+}
+
+// (section, required, check) for every field of Config, in file order
+const SECTIONS: &[(&str, bool, SectionCheck)] = &[
+    ("log_level", true, check::<String>),
+    ("pricelists", true, check::<Vec<PriceListConfig>>),
+    ("pdf_pricelists", true, check::<Vec<PdfPriceListConfig>>),
+    ("metal_pricing", true, check::<MetalPricingConfig>),
+    ("claude", true, check::<ClaudeConfig>),
+    ("groq", false, check::<GroqConfig>),
+    ("telegram", true, check::<TelegramConfig>),
+    ("whatsapp", true, check::<WhatsappConfig>),
+    ("retention", false, check::<Option<RetentionConfig>>),
+    ("quotation", false, check::<QuotationConfig>),
+    ("ocr", false, check::<OcrConfig>),
+    ("quiet_hours", false, check::<Option<QuietHoursConfig>>),
+    ("transcription", false, check::<TranscriptionConfig>),
+    ("confirm_intent", false, check::<ConfirmIntentConfig>),
+    ("features", false, check::<FeaturesConfig>),
+    ("query", false, check::<QueryConfig>),
+    ("media", false, check::<MediaConfig>),
+    ("branding", false, check::<BrandingConfig>),
+];
+
+fn find_invalid_section(root: &Value) -> Option<ConfigError> {
+    let Some(root) = root.as_object() else {
+        return Some(ConfigError::DeserializationError(
+            "the config must be a JSON object".to_string(),
+        ));
+    };
+    SECTIONS.iter().find_map(|(section, required, check)| {
+        let invalid = |message: String, hint: Option<String>| ConfigError::InvalidSection {
+            section: section.to_string(),
+            message,
+            hint,
+        };
+        match root.get(*section) {
+            None if *required => Some(invalid(
+                "section is missing".to_string(),
+                Some(format!("add \"{}\" at the top level of the config", section)),
+            )),
+            None => None,
+            Some(value) => check(value).err().map(|e| {
+                let message = e.to_string();
+                let hint = section_hint(&message);
+                invalid(message, hint)
+            }),
+        }
+    })
+}
+
+// Suggestions for the mistakes that come up on deploys
+fn section_hint(message: &str) -> Option<String> {
+    if let Some(field) = message
+        .strip_prefix("missing field `")
+        .and_then(|rest| rest.split('`').next())
+    {
+        Some(format!("add the required \"{}\" field", field))
+    } else if let Some(expected) = message
+        .strip_prefix("invalid type: ")
+        .and_then(|rest| rest.split_once(", expected "))
+        .map(|(_, expected)| expected)
+    {
+        Some(format!("the value should be {}", expected))
+    } else if message.starts_with("unknown variant") {
+        Some("use one of the expected values listed".to_string())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transcription_language_resolution() {
+        let config = TranscriptionConfig {
+            language: Some("en".to_string()),
+            platform_languages: HashMap::from([
+                ("whatsapp".to_string(), "hi".to_string()),
+                ("telegram".to_string(), "auto".to_string()),
+            ]),
+            ..TranscriptionConfig::default()
+        };
+        assert_eq!(config.language_for("whatsapp"), Some("hi"));
+        assert_eq!(config.language_for("telegram"), None);
+        assert_eq!(config.language_for("web"), Some("en"));
+        assert_eq!(TranscriptionConfig::default().language_for("whatsapp"), None);
+    }
+
+    #[test]
+    fn test_confirm_intent_per_user() {
+        let config = ConfirmIntentConfig {
+            enabled: false,
+            users: vec!["1234".to_string()],
+            exempt_users: vec!["+919800000000".to_string()],
+        };
+        assert!(config.applies_to(Some("1234"), None));
+        assert!(!config.applies_to(Some("5678"), None));
+
+        let config = ConfirmIntentConfig {
+            enabled: true,
+            ..config
+        };
+        assert!(config.applies_to(None, Some("+919811111111")));
+        assert!(!config.applies_to(None, Some("+919800000000")));
+        assert!(!ConfirmIntentConfig::default().applies_to(Some("1234"), None));
+    }
+
+    #[test]
+    fn test_llm_models_default_to_current_ones() {
+        let config = Config::from_json(&minimal_config().to_string()).unwrap();
+        assert_eq!(config.claude.model, "claude-sonnet-4-20250514");
+        assert_eq!(config.claude.max_tokens, 10240);
+        assert_eq!(config.claude.overload_retries, 3);
+        assert_eq!(config.groq, GroqConfig::default());
+
+        let mut config = minimal_config();
+        config["claude"]["model"] = serde_json::json!("claude-sonnet-4-5");
+        config["groq"] = serde_json::json!({"model": "openai/gpt-oss-120b"});
+        let config = Config::from_json(&config.to_string()).unwrap();
+        assert_eq!(config.claude.model, "claude-sonnet-4-5");
+        assert_eq!(config.groq.model, "openai/gpt-oss-120b");
+        assert_eq!(config.groq.max_tokens, 8192);
+    }
+
+    #[test]
+    fn test_features_default_to_on() {
+        let features: FeaturesConfig =
+            serde_json::from_str(r#"{"conversation_classifier": false}"#).unwrap();
+        assert!(features.conversation_continuation);
+        assert!(!features.conversation_classifier);
+        assert!(features.cost_notifications);
+        assert_eq!(
+            serde_json::from_str::<FeaturesConfig>("{}").unwrap(),
+            FeaturesConfig::default()
+        );
+    }
+
+    #[test]
+    fn test_metal_price_sources_start_with_the_main_url() {
+        let config = Config::from_json(&minimal_config().to_string()).unwrap();
+        assert_eq!(
+            config.metal_pricing.aluminium_sources(),
+            vec![MetalPriceSource::new("http://al")]
+        );
+
+        let mut config = minimal_config();
+        config["metal_pricing"]["cu_fallbacks"] = serde_json::json!([
+            {"url": "http://cu2", "selector": "span.price"},
+            {"url": "http://cu3"}
+        ]);
+        let config = Config::from_json(&config.to_string()).unwrap();
+        let sources = config.metal_pricing.copper_sources();
+        assert_eq!(sources.len(), 3);
+        assert_eq!(sources[0].url, "http://cu");
+        assert_eq!(sources[1].selector, "span.price");
+        assert_eq!(sources[2], MetalPriceSource::new("http://cu3"));
+        assert_eq!(config.metal_pricing.metal_sources().len(), 2);
+
+        let mut config = minimal_config();
+        config["metal_pricing"]["metals"] = serde_json::json!({"Lead": [{"url": "http://pb"}]});
+        let config = Config::from_json(&config.to_string()).unwrap();
+        let sources = config.metal_pricing.metal_sources();
+        assert_eq!(sources.len(), 3);
+        assert_eq!(sources["lead"], vec![MetalPriceSource::new("http://pb")]);
+    }
+
+    #[test]
+    fn test_alert_times_default_to_both_sessions() {
+        let config = Config::from_json(&minimal_config().to_string()).unwrap();
+        assert_eq!(config.metal_pricing.alert_times, default_alert_times());
+
+        let mut config = minimal_config();
+        config["metal_pricing"]["alert_times"] = serde_json::json!([{"hour": 9, "minute": 15}]);
+        let config = Config::from_json(&config.to_string()).unwrap();
+        assert_eq!(
+            config.metal_pricing.alert_times,
+            vec![AlertTime {
+                hour: 9,
+                minute: 15
+            }]
+        );
+    }
+
+    fn minimal_config() -> Value {
+        serde_json::json!({
+            "log_level": "info",
+            "pricelists": [{"pricelist": "kei.json", "brand": "kei"}],
+            "pdf_pricelists": [],
+            "metal_pricing": {"al_url": "http://al", "cu_url": "http://cu"},
+            "claude": {"system_prompt": "prompt"},
+            "telegram": {
+                "price_alert_subscribers": [],
+                "error_channel_id": 1,
+                "admin_telegram_id": "1"
+            },
+            "whatsapp": {
+                "webhook_port": 3000,
+                "file_base_url": "http://files",
+                "price_alert_subscribers": [],
+                "twilio_from_number": "+10000000000",
+                "template_sid": "sid"
+            }
+        })
+    }
+
+    fn load_error(config: &Value) -> String {
+        Config::from_json(&config.to_string()).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_config_errors_name_the_section() {
+        assert!(Config::from_json(&minimal_config().to_string()).is_ok());
+
+        let mut config = minimal_config();
+        config["telegram"]
+            .as_object_mut()
+            .unwrap()
+            .remove("admin_telegram_id");
+        assert_eq!(
+            load_error(&config),
+            "Invalid \"telegram\" section: missing field `admin_telegram_id` - add the required \"admin_telegram_id\" field"
+        );
+
+        let mut config = minimal_config();
+        config["whatsapp"]["webhook_port"] = serde_json::json!("3000");
+        assert_eq!(
+            load_error(&config),
+            "Invalid \"whatsapp\" section: invalid type: string \"3000\", expected u16 - the value should be u16"
+        );
+
+        let mut config = minimal_config();
+        config.as_object_mut().unwrap().remove("metal_pricing");
+        assert!(load_error(&config)
+            .starts_with("Invalid \"metal_pricing\" section: section is missing"));
+
+        let mut config = minimal_config();
+        config["quotation"] = serde_json::json!({"number_locale": "american"});
+        assert!(load_error(&config)
+            .starts_with("Invalid \"quotation\" section: unknown variant"));
+    }
+
+    #[test]
+    fn test_retention_days_must_be_positive() {
+        let mut config = minimal_config();
+        config["retention"] = serde_json::json!({"conversation_retention_days": 90});
+        let loaded = Config::from_json(&config.to_string()).unwrap();
+        assert_eq!(loaded.retention.unwrap().conversation_retention_days.get(), 90);
+
+        for days in [0, -30] {
+            config["retention"] = serde_json::json!({"conversation_retention_days": days});
+            assert!(load_error(&config)
+                .starts_with("Invalid \"retention\" section: invalid value"));
+        }
+    }
+
+    #[test]
+    fn test_config_syntax_error_is_reported() {
+        let error = Config::from_json(r#"{"log_level": "info" "pricelists": []}"#).unwrap_err();
+        assert!(matches!(error, ConfigError::DeserializationError(_)));
+        assert!(error.to_string().contains("not valid JSON"));
     }
 }
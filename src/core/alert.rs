@@ -0,0 +1,127 @@
+use std::fmt;
+
+// Ordered so that `severity >= Severity::Error` picks out the alerts someone must act on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warn,
+    Error,
+    Critical,
+}
+
+impl Severity {
+    // Info alerts carry their own emoji (eg. cost notifications) so get no prefix
+    pub fn icon(&self) -> &'static str {
+        match self {
+            Severity::Info => "",
+            Severity::Warn => "⚠️",
+            Severity::Error => "❌",
+            Severity::Critical => "🚨",
+        }
+    }
+}
+
+// Routine usage reports (query costs, timings) go to their own channel so failures stand out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertCategory {
+    Error,
+    Usage,
+}
+
+#[derive(Debug, Clone)]
+pub struct Alert {
+    pub severity: Severity,
+    pub category: AlertCategory,
+    pub message: String,
+}
+
+impl Alert {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            category: AlertCategory::Error,
+            message: message.into(),
+        }
+    }
+
+    pub fn usage(message: impl Into<String>) -> Self {
+        Self {
+            category: AlertCategory::Usage,
+            ..Self::info(message)
+        }
+    }
+
+    pub fn info(message: impl Into<String>) -> Self {
+        Self::new(Severity::Info, message)
+    }
+
+    pub fn warn(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warn, message)
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    pub fn critical(message: impl Into<String>) -> Self {
+        Self::new(Severity::Critical, message)
+    }
+
+    pub fn needs_attention(&self) -> bool {
+        self.severity >= Severity::Error
+    }
+}
+
+// Untyped messages predate severities and are treated as errors
+impl From<String> for Alert {
+    fn from(message: String) -> Self {
+        Self::error(message)
+    }
+}
+
+impl From<&str> for Alert {
+    fn from(message: &str) -> Self {
+        Self::error(message)
+    }
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.severity.icon() {
+            icon if icon.is_empty() || self.message.starts_with(icon) => {
+                write!(f, "{}", self.message)
+            }
+            icon => write!(f, "{} {}", icon, self.message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_strings_become_errors() {
+        let alert: Alert = "Failed to create session".to_string().into();
+        assert_eq!(alert.severity, Severity::Error);
+        assert!(alert.needs_attention());
+    }
+
+    #[test]
+    fn test_severity_ordering() {
+        assert!(!Alert::info("cost").needs_attention());
+        assert!(!Alert::warn("slow").needs_attention());
+        assert!(Alert::critical("down").needs_attention());
+        assert_eq!(Alert::warn("slow").to_string(), "⚠️ slow");
+        assert_eq!(Alert::info("💰 cost").to_string(), "💰 cost");
+        assert_eq!(Alert::error("❌ failed").to_string(), "❌ failed");
+    }
+
+    #[test]
+    fn test_usage_alerts_are_informational() {
+        let alert = Alert::usage("💰 Query Cost Alert");
+        assert_eq!(alert.category, AlertCategory::Usage);
+        assert_eq!(alert.severity, Severity::Info);
+        assert_eq!(Alert::error("down").category, AlertCategory::Error);
+    }
+}
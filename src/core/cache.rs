@@ -1,9 +1,16 @@
+use moka::policy::EvictionPolicy;
 use moka::sync::Cache;
+use std::collections::HashMap;
+use std::future::Future;
 use std::hash::Hash;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 pub struct ExpirableCache<K, V> {
     cache: Cache<K, V>,
+    capacity: u64,
+    // One lock per key being loaded, so concurrent misses wait on a single load
+    loading: Mutex<HashMap<K, Arc<tokio::sync::Mutex<()>>>>,
 }
 
 impl<K, V> ExpirableCache<K, V>
@@ -12,15 +19,24 @@ where
     V: Clone + Send + Sync + 'static,
 {
     pub fn new(max_capacity: u64, ttl: Duration) -> Self {
+        // Plain LRU - moka's default TinyLFU may turn a new key away instead of evicting
         let cache = Cache::builder()
             .max_capacity(max_capacity)
+            .eviction_policy(EvictionPolicy::lru())
             .time_to_live(ttl)
             .build();
-        ExpirableCache { cache }
+        ExpirableCache {
+            cache,
+            capacity: max_capacity,
+            loading: Mutex::new(HashMap::new()),
+        }
     }
 
     pub fn insert(&self, key: K, value: V) {
         self.cache.insert(key, value);
+        // moka evicts on its next housekeeping pass - run it now so the bound holds once
+        // insert returns
+        self.cache.run_pending_tasks();
     }
 
     pub fn get(&self, key: &K) -> Option<V> {
@@ -30,4 +46,132 @@ where
     pub fn remove(&self, key: &K) {
         self.cache.invalidate(key);
     }
+
+    // Live entries, leaving out any that have expired
+    pub fn len(&self) -> usize {
+        self.cache.run_pending_tasks();
+        self.cache.entry_count() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    // The cached value, or the one `load` produces on a miss. Lookups of a key that is already
+    // loading wait for that load instead of starting their own. Failures aren't cached, so the
+    // next waiter in line tries again.
+    pub async fn get_or_insert_with<F, Fut, E>(&self, key: K, load: F) -> Result<V, E>
+    where
+        K: Clone,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V, E>>,
+    {
+        if let Some(value) = self.get(&key) {
+            return Ok(value);
+        }
+
+        let lock = self
+            .loading
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_default()
+            .clone();
+        let loading_guard = lock.lock().await;
+
+        let result = match self.get(&key) {
+            Some(value) => Ok(value),
+            None => {
+                let result = load().await;
+                if let Ok(value) = &result {
+                    self.insert(key.clone(), value.clone());
+                }
+                result
+            }
+        };
+
+        // Let go of the key's lock first, then drop it from the map if nobody else holds it.
+        // Waiters clone the lock under the map's mutex, so the count can't rise behind our back.
+        drop(loading_guard);
+        drop(lock);
+        let mut loading = self.loading.lock().unwrap();
+        if loading
+            .get(&key)
+            .is_some_and(|lock| Arc::strong_count(lock) == 1)
+        {
+            loading.remove(&key);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_least_recently_used_is_evicted_past_capacity() {
+        let cache = ExpirableCache::new(3, Duration::from_secs(60));
+        for (key, value) in [("a", 1), ("b", 2), ("c", 3)] {
+            cache.insert(key.to_string(), value);
+        }
+        assert_eq!(cache.len(), 3);
+
+        // Reading "a" leaves "b" as the least recently used
+        assert_eq!(cache.get(&"a".to_string()), Some(1));
+        cache.insert("d".to_string(), 4);
+
+        assert_eq!(cache.len(), 3);
+        assert_eq!(cache.capacity(), 3);
+        assert_eq!(cache.get(&"b".to_string()), None);
+        assert_eq!(cache.get(&"a".to_string()), Some(1));
+        assert_eq!(cache.get(&"c".to_string()), Some(3));
+        assert_eq!(cache.get(&"d".to_string()), Some(4));
+    }
+
+    #[test]
+    fn test_expired_entries_are_not_counted() {
+        let cache = ExpirableCache::new(3, Duration::from_millis(50));
+        cache.insert("a".to_string(), 1);
+        assert!(!cache.is_empty());
+
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(cache.get(&"a".to_string()), None);
+        assert!(cache.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_misses_load_once() {
+        let cache = Arc::new(ExpirableCache::new(2, Duration::from_millis(300)));
+        let loads = Arc::new(AtomicUsize::new(0));
+
+        let lookup = |cache: Arc<ExpirableCache<String, f64>>, loads: Arc<AtomicUsize>| async move {
+            cache
+                .get_or_insert_with("copper".to_string(), || async {
+                    loads.fetch_add(1, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    Ok::<_, String>(850.5)
+                })
+                .await
+        };
+
+        let lookups: Vec<_> = (0..8)
+            .map(|_| tokio::spawn(lookup(cache.clone(), loads.clone())))
+            .collect();
+        for lookup in lookups {
+            assert_eq!(lookup.await.unwrap(), Ok(850.5));
+        }
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+        assert!(cache.loading.lock().unwrap().is_empty());
+
+        // Once the value expires the next lookup loads it again
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert_eq!(lookup(cache.clone(), loads.clone()).await, Ok(850.5));
+        assert_eq!(loads.load(Ordering::SeqCst), 2);
+    }
 }
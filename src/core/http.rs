@@ -1,8 +1,11 @@
 use reqwest::{Client, RequestBuilder, Response};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
 use std::ops::Deref;
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use thiserror::Error;
-use tracing::{error, warn};
+use tracing::{error, info, warn};
 
 #[derive(Debug, Error)]
 pub enum RetryError {
@@ -10,39 +13,286 @@ pub enum RetryError {
     AllRetriesFailed(String),
     #[error("Non-retryable error: {0}")]
     NonRetryable(String),
+    #[error("Circuit open for {host} - failing fast for another {}s", .retry_in.as_secs())]
+    CircuitOpen { host: String, retry_in: Duration },
+}
+
+// Retry counts for one upstream host, since startup
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct HostRetryStats {
+    pub retries: u64,
+    pub exhausted: u64,
+}
+
+// Shared by every RetryableClient, so /status sees all upstreams wherever the client was built
+static RETRY_STATS: Mutex<BTreeMap<String, HostRetryStats>> = Mutex::new(BTreeMap::new());
+
+pub fn retry_stats() -> BTreeMap<String, HostRetryStats> {
+    RETRY_STATS.lock().unwrap().clone()
+}
+
+fn record_retry_stats(host: &str, update: impl FnOnce(&mut HostRetryStats)) {
+    update(
+        RETRY_STATS
+            .lock()
+            .unwrap()
+            .entry(host.to_string())
+            .or_default(),
+    );
+}
+
+// Per call override of the client's retry policy - unset fields keep the client's defaults.
+// A quick scrape can fail fast while a slow LLM call keeps its patience.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RetryPolicy {
+    pub timeout: Option<Duration>,
+    pub max_attempts: Option<u32>,
+    pub passthrough_status: Option<u16>,
+}
+
+impl RetryPolicy {
+    pub const fn new() -> Self {
+        Self {
+            timeout: None,
+            max_attempts: None,
+            passthrough_status: None,
+        }
+    }
+
+    // Timeout for each attempt, replacing any set on the request
+    pub const fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub const fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+
+    // A status handed straight back to the caller, who retries it in its own way
+    pub const fn with_passthrough_status(mut self, status: u16) -> Self {
+        self.passthrough_status = Some(status);
+        self
+    }
+}
+
+// How a client retries by default - each service tunes it to its upstream
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryConfig {
+    // Attempts in all, including the first
+    pub max_retries: u32,
+    // Wait before the first retry, doubling for each one after up to max_delay
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    // Statuses worth another attempt - any 5xx or 429 when empty
+    pub retry_on_status: Vec<u16>,
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+}
+
+// Once `failure_threshold` requests in a row to a host have failed, requests to it fail straight
+// away for `cooldown`. A single probe then goes through and its outcome closes or reopens the
+// circuit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CircuitBreakerConfig {
+    pub failure_threshold: u32,
+    pub cooldown: Duration,
+}
+
+#[derive(Debug, Default)]
+struct CircuitState {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+    probing: bool,
+}
+
+// Frees the host for the next probe however the current one ends
+struct ProbeGuard<'a> {
+    circuits: &'a Mutex<HashMap<String, CircuitState>>,
+    host: &'a str,
+}
+
+impl Drop for ProbeGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(circuit) = self.circuits.lock().unwrap().get_mut(self.host) {
+            circuit.probing = false;
+        }
+    }
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_secs(2),
+            max_delay: Duration::from_secs(30),
+            retry_on_status: Vec::new(),
+            circuit_breaker: None,
+        }
+    }
+}
+
+impl RetryConfig {
+    fn delay(&self, attempt: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(2_u32.saturating_pow(attempt))
+            .min(self.max_delay)
+    }
+
+    fn should_retry_status(&self, status: reqwest::StatusCode) -> bool {
+        if self.retry_on_status.is_empty() {
+            should_retry_status(status)
+        } else {
+            self.retry_on_status.contains(&status.as_u16())
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct RetryableClient {
     client: Client,
-    max_retries: u32,
+    config: RetryConfig,
+    // Circuit breaker state per host, shared between clones of the client
+    circuits: Arc<Mutex<HashMap<String, CircuitState>>>,
 }
 
 impl RetryableClient {
     pub fn new() -> Self {
+        Self::with_config(RetryConfig::default())
+    }
+
+    pub fn with_config(config: RetryConfig) -> Self {
         Self {
             client: Client::builder()
                 .timeout(Duration::from_secs(45))
                 .build()
                 .unwrap(),
-            max_retries: 3,
+            config,
+            circuits: Arc::default(),
         }
     }
 
     pub fn with_retries(client: Client, max_retries: u32) -> Self {
         Self {
             client,
-            max_retries,
+            config: RetryConfig {
+                max_retries,
+                ..RetryConfig::default()
+            },
+            circuits: Arc::default(),
         }
     }
 
+    // Whether requests to the host are currently being failed fast, host as in "host:port"
+    pub fn is_tripped(&self, host: &str) -> bool {
+        self.circuits
+            .lock()
+            .unwrap()
+            .get(host)
+            .and_then(|circuit| Some((circuit.open_until?, circuit.probing)))
+            .is_some_and(|(open_until, probing)| probing || Instant::now() < open_until)
+    }
+
     pub async fn execute_with_retry(
         &self,
         request_builder: RequestBuilder,
     ) -> Result<Response, RetryError> {
+        self.execute_with_policy(request_builder, RetryPolicy::default())
+            .await
+    }
+
+    pub async fn execute_with_policy(
+        &self,
+        request_builder: RequestBuilder,
+        policy: RetryPolicy,
+    ) -> Result<Response, RetryError> {
+        let host = request_host(&request_builder);
+        let Some(breaker) = self.config.circuit_breaker else {
+            return self.send_with_retries(request_builder, policy, &host).await;
+        };
+
+        let probe = self.admit(&host)?;
+        // A probe dropped mid-flight, eg. by a caller's timeout, never gets to record_outcome
+        let _probe_guard = probe.then(|| ProbeGuard {
+            circuits: &self.circuits,
+            host: &host,
+        });
+        // The probe gets one attempt - a host that is still down shouldn't cost a full retry run
+        let policy = if probe {
+            policy.with_max_attempts(1)
+        } else {
+            policy
+        };
+        let result = self.send_with_retries(request_builder, policy, &host).await;
+        self.record_outcome(&host, breaker, &result);
+        result
+    }
+
+    // Err while the host's circuit is open, Ok(true) for the probe once its cooldown is over
+    fn admit(&self, host: &str) -> Result<bool, RetryError> {
+        let mut circuits = self.circuits.lock().unwrap();
+        let Some(circuit) = circuits.get_mut(host) else {
+            return Ok(false);
+        };
+        let Some(open_until) = circuit.open_until else {
+            return Ok(false);
+        };
+        let now = Instant::now();
+        if circuit.probing || now < open_until {
+            return Err(RetryError::CircuitOpen {
+                host: host.to_string(),
+                retry_in: open_until.saturating_duration_since(now),
+            });
+        }
+        circuit.probing = true;
+        Ok(true)
+    }
+
+    fn record_outcome(
+        &self,
+        host: &str,
+        breaker: CircuitBreakerConfig,
+        result: &Result<Response, RetryError>,
+    ) {
+        let mut circuits = self.circuits.lock().unwrap();
+        match result {
+            // A status handed back without retrying, eg. Claude's 529, is still the host failing
+            Ok(response) if !host_answered(response.status()) => {
+                let circuit = circuits.entry(host.to_string()).or_default();
+                open_if_failing(circuit, host, breaker);
+            }
+            Ok(_) => {
+                let closed = circuits.remove(host);
+                if closed.is_some_and(|circuit| circuit.open_until.is_some()) {
+                    info!(host = %host, "Circuit closed, host is answering again");
+                }
+            }
+            Err(RetryError::AllRetriesFailed(_)) => {
+                let circuit = circuits.entry(host.to_string()).or_default();
+                open_if_failing(circuit, host, breaker);
+            }
+            // Not the host's doing, but a probe that ends this way leaves room for another
+            Err(_) => {
+                if let Some(circuit) = circuits.get_mut(host) {
+                    circuit.probing = false;
+                }
+            }
+        }
+    }
+
+    async fn send_with_retries(
+        &self,
+        request_builder: RequestBuilder,
+        policy: RetryPolicy,
+        host: &str,
+    ) -> Result<Response, RetryError> {
+        let max_retries = policy
+            .max_attempts
+            .unwrap_or(self.config.max_retries)
+            .max(1);
         let mut last_error = None;
 
-        for attempt in 0..self.max_retries {
+        for attempt in 0..max_retries {
             // Clone the request for retry
             let request = match request_builder.try_clone() {
                 Some(req) => req,
@@ -52,10 +302,17 @@ impl RetryableClient {
                     ))
                 }
             };
+            let request = match policy.timeout {
+                Some(timeout) => request.timeout(timeout),
+                None => request,
+            };
 
             match request.send().await {
                 Ok(response) => {
-                    if response.status().is_success() || !should_retry_status(response.status()) {
+                    if response.status().is_success()
+                        || !self.config.should_retry_status(response.status())
+                        || policy.passthrough_status == Some(response.status().as_u16())
+                    {
                         return Ok(response);
                     }
                     warn!(response = ?response, "Error response received on attempt {}; ", (attempt+1));
@@ -70,17 +327,21 @@ impl RetryableClient {
                 }
             }
 
-            if attempt < self.max_retries - 1 {
-                let delay = Duration::from_millis(1000 * (2_u64.pow(attempt + 1)));
+            if attempt < max_retries - 1 {
+                let delay = self.config.delay(attempt);
                 warn!(
-                    "Request attempt {} failed, retrying in {:?}",
-                    attempt + 1,
-                    delay
+                    host = %host,
+                    attempt = attempt + 1,
+                    max_retries,
+                    delay_ms = delay.as_millis() as u64,
+                    "Retrying failed request"
                 );
+                record_retry_stats(host, |stats| stats.retries += 1);
                 tokio::time::sleep(delay).await;
             }
         }
-        error!("All retires failed - check server");
+        record_retry_stats(host, |stats| stats.exhausted += 1);
+        error!(host = %host, "All retries failed - check server");
         Err(RetryError::AllRetriesFailed(
             last_error.unwrap_or_else(|| "Unknown error".to_string()),
         ))
@@ -95,6 +356,42 @@ impl Deref for RetryableClient {
     }
 }
 
+// Host the request goes to, with the port when it isn't the scheme's default
+fn request_host(request_builder: &RequestBuilder) -> String {
+    request_builder
+        .try_clone()
+        .and_then(|builder| builder.build().ok())
+        .and_then(|request| {
+            let url = request.url();
+            let host = url.host_str()?;
+            Some(match url.port() {
+                Some(port) => format!("{}:{}", host, port),
+                None => host.to_string(),
+            })
+        })
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+// Client errors are the request's fault, so only server errors and rate limiting count against
+// the host
+fn host_answered(status: reqwest::StatusCode) -> bool {
+    !status.is_server_error() && status != reqwest::StatusCode::TOO_MANY_REQUESTS
+}
+
+fn open_if_failing(circuit: &mut CircuitState, host: &str, breaker: CircuitBreakerConfig) {
+    circuit.consecutive_failures += 1;
+    if circuit.probing || circuit.consecutive_failures >= breaker.failure_threshold {
+        circuit.open_until = Some(Instant::now() + breaker.cooldown);
+        circuit.probing = false;
+        warn!(
+            host = %host,
+            failures = circuit.consecutive_failures,
+            cooldown_secs = breaker.cooldown.as_secs(),
+            "Circuit opened, failing requests fast"
+        );
+    }
+}
+
 fn should_retry_status(status: reqwest::StatusCode) -> bool {
     status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
 }
@@ -102,3 +399,290 @@ fn should_retry_status(status: reqwest::StatusCode) -> bool {
 fn should_retry_error(error: &reqwest::Error) -> bool {
     error.is_timeout() || error.is_connect() || error.is_request()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::Server;
+
+    #[tokio::test]
+    async fn test_retries_are_counted_per_host() {
+        let mut server = Server::new_async().await;
+        let failing = server
+            .mock("GET", "/flaky")
+            .with_status(503)
+            .expect(2)
+            .create_async()
+            .await;
+        let host = server.host_with_port();
+        // The stats are global and mockito reuses ports, so compare against what's already there
+        let before = retry_stats().get(&host).cloned().unwrap_or_default();
+
+        let client = RetryableClient::with_retries(Client::new(), 2);
+        let result = client
+            .execute_with_retry(client.get(format!("{}/flaky", server.url())))
+            .await;
+
+        assert!(matches!(result, Err(RetryError::AllRetriesFailed(_))));
+        failing.assert_async().await;
+        let after = retry_stats().get(&host).cloned().unwrap_or_default();
+        assert_eq!(after.retries - before.retries, 1);
+        assert_eq!(after.exhausted - before.exhausted, 1);
+    }
+
+    #[tokio::test]
+    async fn test_policy_overrides_attempts_and_timeout() {
+        let mut server = Server::new_async().await;
+        let failing = server
+            .mock("GET", "/flaky")
+            .with_status(503)
+            .expect(1)
+            .create_async()
+            .await;
+
+        // The client would try three times, the policy allows one
+        let client = RetryableClient::with_retries(Client::new(), 3);
+        let result = client
+            .execute_with_policy(
+                client.get(format!("{}/flaky", server.url())),
+                RetryPolicy::new().with_max_attempts(1),
+            )
+            .await;
+        assert!(matches!(result, Err(RetryError::AllRetriesFailed(_))));
+        failing.assert_async().await;
+
+        // A server that accepts the connection but never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let _silent = tokio::spawn(async move {
+            let (_socket, _) = listener.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(30)).await;
+        });
+
+        let started = std::time::Instant::now();
+        let result = client
+            .execute_with_policy(
+                client.get(format!("http://{}/slow", address)),
+                RetryPolicy::new()
+                    .with_timeout(Duration::from_millis(200))
+                    .with_max_attempts(1),
+            )
+            .await;
+        assert!(matches!(result, Err(RetryError::AllRetriesFailed(_))));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    fn quick_retries(max_retries: u32, retry_on_status: Vec<u16>) -> RetryableClient {
+        RetryableClient::with_config(RetryConfig {
+            max_retries,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(20),
+            retry_on_status,
+            circuit_breaker: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_configured_retries_ride_out_unavailable_server() {
+        for (unavailable, max_retries, succeeds) in [(2, 3, true), (2, 2, false), (4, 5, true)] {
+            let mut server = Server::new_async().await;
+            let failing = server
+                .mock("GET", "/media")
+                .with_status(503)
+                .expect(unavailable)
+                .create_async()
+                .await;
+            let ok = server
+                .mock("GET", "/media")
+                .with_status(200)
+                .expect(usize::from(succeeds))
+                .create_async()
+                .await;
+
+            let client = quick_retries(max_retries, Vec::new());
+            let result = client
+                .execute_with_retry(client.get(format!("{}/media", server.url())))
+                .await;
+
+            assert_eq!(result.is_ok(), succeeds);
+            failing.assert_async().await;
+            ok.assert_async().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_only_configured_statuses_are_retried() {
+        let mut server = Server::new_async().await;
+        let failing = server
+            .mock("GET", "/upload")
+            .with_status(500)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let client = quick_retries(3, vec![503]);
+        let response = client
+            .execute_with_retry(client.get(format!("{}/upload", server.url())))
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 500);
+        failing.assert_async().await;
+    }
+
+    #[test]
+    fn test_retry_delay_doubles_up_to_max() {
+        let config = RetryConfig::default();
+        assert_eq!(config.delay(0), Duration::from_secs(2));
+        assert_eq!(config.delay(1), Duration::from_secs(4));
+        assert_eq!(config.delay(3), Duration::from_secs(16));
+        assert_eq!(config.delay(4), Duration::from_secs(30));
+        assert_eq!(config.delay(40), Duration::from_secs(30));
+    }
+
+    #[tokio::test]
+    async fn test_circuit_fails_fast_once_tripped() {
+        let mut server = Server::new_async().await;
+        let failing = server
+            .mock("GET", "/messages")
+            .with_status(503)
+            .expect(3)
+            .create_async()
+            .await;
+        let host = server.host_with_port();
+        let url = format!("{}/messages", server.url());
+
+        let client = RetryableClient::with_config(RetryConfig {
+            max_retries: 2,
+            base_delay: Duration::from_millis(10),
+            circuit_breaker: Some(CircuitBreakerConfig {
+                failure_threshold: 1,
+                cooldown: Duration::from_millis(300),
+            }),
+            ..RetryConfig::default()
+        });
+        let result = client.execute_with_retry(client.get(&url)).await;
+        assert!(matches!(result, Err(RetryError::AllRetriesFailed(_))));
+        assert!(client.is_tripped(&host));
+
+        // Within the cooldown nothing reaches the server
+        let started = Instant::now();
+        let result = client.execute_with_retry(client.get(&url)).await;
+        assert!(matches!(result, Err(RetryError::CircuitOpen { .. })));
+        assert!(started.elapsed() < Duration::from_millis(50));
+
+        // After it a single probe goes out, and failing it opens the circuit again
+        tokio::time::sleep(Duration::from_millis(350)).await;
+        assert!(!client.is_tripped(&host));
+        let result = client.execute_with_retry(client.get(&url)).await;
+        assert!(matches!(result, Err(RetryError::AllRetriesFailed(_))));
+        let result = client.execute_with_retry(client.get(&url)).await;
+        assert!(matches!(result, Err(RetryError::CircuitOpen { .. })));
+        failing.assert_async().await;
+
+        server.reset();
+        server
+            .mock("GET", "/messages")
+            .with_status(200)
+            .create_async()
+            .await;
+        tokio::time::sleep(Duration::from_millis(350)).await;
+        let response = client.execute_with_retry(client.get(&url)).await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert!(!client.is_tripped(&host));
+    }
+
+    #[tokio::test]
+    async fn test_dropped_probe_frees_the_circuit() {
+        // Accepts connections but never answers, so requests hang until they're dropped
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let host = listener.local_addr().unwrap().to_string();
+        let url = format!("http://{}/messages", host);
+
+        let client = RetryableClient::with_config(RetryConfig {
+            max_retries: 1,
+            circuit_breaker: Some(CircuitBreakerConfig {
+                failure_threshold: 1,
+                cooldown: Duration::from_secs(60),
+            }),
+            ..RetryConfig::default()
+        });
+        // Tripped, with the cooldown just over
+        client.circuits.lock().unwrap().insert(
+            host.clone(),
+            CircuitState {
+                consecutive_failures: 1,
+                open_until: Some(Instant::now()),
+                probing: false,
+            },
+        );
+
+        let probe = client.execute_with_retry(client.get(&url));
+        assert!(tokio::time::timeout(Duration::from_millis(100), probe)
+            .await
+            .is_err());
+        assert!(!client.circuits.lock().unwrap()[&host].probing);
+
+        // So the next request goes out as a probe instead of failing fast
+        let probe = client.execute_with_retry(client.get(&url));
+        assert!(tokio::time::timeout(Duration::from_millis(100), probe)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_passthrough_status_is_not_retried() {
+        let mut server = Server::new_async().await;
+        let overloaded = server
+            .mock("GET", "/overloaded")
+            .with_status(529)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let client = RetryableClient::with_retries(Client::new(), 3);
+        let response = client
+            .execute_with_policy(
+                client.get(format!("{}/overloaded", server.url())),
+                RetryPolicy::new().with_passthrough_status(529),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 529);
+        overloaded.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_passthrough_server_errors_trip_the_circuit() {
+        let mut server = Server::new_async().await;
+        let overloaded = server
+            .mock("GET", "/overloaded")
+            .with_status(529)
+            .expect(2)
+            .create_async()
+            .await;
+        let host = server.host_with_port();
+        let url = format!("{}/overloaded", server.url());
+
+        let client = RetryableClient::with_config(RetryConfig {
+            circuit_breaker: Some(CircuitBreakerConfig {
+                failure_threshold: 2,
+                cooldown: Duration::from_secs(60),
+            }),
+            ..RetryConfig::default()
+        });
+        let overloaded_policy = RetryPolicy::new().with_passthrough_status(529);
+        for _ in 0..2 {
+            let response = client
+                .execute_with_policy(client.get(&url), overloaded_policy)
+                .await
+                .unwrap();
+            assert_eq!(response.status().as_u16(), 529);
+        }
+        assert!(client.is_tripped(&host));
+        let result = client
+            .execute_with_policy(client.get(&url), overloaded_policy)
+            .await;
+        assert!(matches!(result, Err(RetryError::CircuitOpen { .. })));
+        overloaded.assert_async().await;
+    }
+}
@@ -1,4 +1,6 @@
+pub mod alert;
 pub mod cache;
 pub mod http;
 pub mod service_manager;
+pub use alert::{Alert, AlertCategory, Severity};
 pub use service_manager::{Service, ServiceManager};
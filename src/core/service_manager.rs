@@ -1,3 +1,4 @@
+use crate::core::alert::Alert;
 use async_trait::async_trait;
 use std::sync::Arc;
 use thiserror::Error;
@@ -36,16 +37,17 @@ pub trait ServiceWithSender {
 #[async_trait]
 pub trait ServiceWithErrorSender {
     type Context: Clone + Send;
-    async fn new(context: Self::Context, sender: mpsc::Sender<String>) -> Self;
+    async fn new(context: Self::Context, sender: mpsc::Sender<Alert>) -> Self;
     async fn run(self) -> Result<(), Error>;
 }
 
 #[async_trait]
 pub trait ServiceWithReceiver {
     type Context: Clone + Send;
+    type Message: Send + 'static;
     async fn new(
         context: Self::Context,
-        receiver: Option<Arc<Mutex<mpsc::Receiver<String>>>>,
+        receiver: Option<Arc<Mutex<mpsc::Receiver<Self::Message>>>>,
     ) -> Self;
     async fn run(self) -> Result<(), Error>;
 }
@@ -93,7 +95,7 @@ where
         });
     }
 
-    pub fn spawn_with_price_receiver<T: ServiceWithReceiver<Context = C>>(
+    pub fn spawn_with_price_receiver<T: ServiceWithReceiver<Context = C, Message = String>>(
         &mut self,
         receiver: Arc<Mutex<mpsc::Receiver<String>>>,
     ) {
@@ -118,7 +120,7 @@ where
 
     pub fn spawn_with_error_sender<T: ServiceWithErrorSender<Context = C>>(
         &mut self,
-        error_sender: mpsc::Sender<String>,
+        error_sender: mpsc::Sender<Alert>,
     ) {
         let context = self.context.clone();
         self.services.spawn(async move {
@@ -131,9 +133,9 @@ where
         });
     }
 
-    pub fn spawn_with_error_receiver<T: ServiceWithReceiver<Context = C>>(
+    pub fn spawn_with_error_receiver<T: ServiceWithReceiver<Context = C, Message = Alert>>(
         &mut self,
-        receiver: Arc<Mutex<mpsc::Receiver<String>>>,
+        receiver: Arc<Mutex<mpsc::Receiver<Alert>>>,
     ) {
         let context = self.context.clone();
         self.services.spawn(async move {
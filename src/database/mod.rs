@@ -1,7 +1,9 @@
 mod errors;
+mod retention;
 mod services;
 mod types;
 pub use errors::DatabaseError;
+pub use retention::RetentionService;
 pub use services::DatabaseService;
 pub use types::*;
 
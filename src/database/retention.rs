@@ -0,0 +1,62 @@
+use crate::configuration::{Context, RetentionConfig};
+use crate::core::service_manager::{Error as ServiceManagerError, ServiceWithErrorSender};
+use crate::core::Alert;
+use crate::database::DatabaseService;
+use async_trait::async_trait;
+use chrono::Utc;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{error, info};
+
+// Periodically purges conversations older than the configured retention period
+pub struct RetentionService {
+    database: Arc<DatabaseService>,
+    config: Option<RetentionConfig>,
+    error_sender: mpsc::Sender<Alert>,
+}
+
+#[async_trait]
+impl ServiceWithErrorSender for RetentionService {
+    type Context = Context;
+
+    async fn new(context: Context, error_sender: mpsc::Sender<Alert>) -> Self {
+        Self {
+            database: context.database.clone(),
+            config: context.config.retention.clone(),
+            error_sender,
+        }
+    }
+
+    async fn run(self) -> Result<(), ServiceManagerError> {
+        let Some(config) = self.config else {
+            info!("No conversation retention configured, purge task disabled");
+            std::future::pending::<()>().await;
+            return Ok(());
+        };
+
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(config.purge_interval_hours.max(1) * 3600));
+        loop {
+            interval.tick().await;
+
+            let cutoff = Utc::now()
+                - chrono::Duration::days(config.conversation_retention_days.get().into());
+            match self.database.purge_conversations_before(cutoff).await {
+                Ok(report) => {
+                    info!("Conversation purge before {} completed: {:?}", cutoff, report);
+                }
+                Err(e) => {
+                    error!("Conversation purge failed: {}", e);
+                    let _ = self
+                        .error_sender
+                        .send(Alert::error(format!(
+                            "Scheduled conversation purge failed: {}",
+                            e
+                        )))
+                        .await;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use super::super::types::{ApprovalStatus, QuotationApproval};
+use super::DatabaseError;
+use super::DatabaseService;
+use uuid::Uuid;
+
+impl DatabaseService {
+    pub async fn create_quotation_approval(
+        &self,
+        approval: &QuotationApproval,
+    ) -> Result<(), DatabaseError> {
+        let row = serde_json::to_string(approval)
+            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+        let response = self
+            .client
+            .from("quotation_approvals")
+            .insert(row)
+            .execute()
+            .await
+            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(DatabaseError::QueryError(format!(
+                "Saving quotation approval failed with status: {}",
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+
+    // Only a pending approval can be decided, so of two admins pressing a button at once just the
+    // first gets the approval back
+    pub async fn decide_quotation_approval(
+        &self,
+        id: Uuid,
+        status: ApprovalStatus,
+        decided_by: &str,
+    ) -> Result<Option<QuotationApproval>, DatabaseError> {
+        let update = serde_json::json!({
+            "status": status,
+            "decided_by": decided_by,
+            "decided_at": chrono::Utc::now(),
+        });
+        let response = self
+            .client
+            .from("quotation_approvals")
+            .update(update.to_string())
+            .eq("id", id.to_string())
+            .eq("status", "pending")
+            .select("*")
+            .execute()
+            .await
+            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(DatabaseError::QueryError(format!(
+                "Deciding quotation approval failed with status: {}",
+                response.status()
+            )));
+        }
+
+        let approvals: Vec<QuotationApproval> = response
+            .json()
+            .await
+            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+        Ok(approvals.into_iter().next())
+    }
+
+    pub async fn list_pending_quotation_approvals(
+        &self,
+    ) -> Result<Vec<QuotationApproval>, DatabaseError> {
+        let response = self
+            .client
+            .from("quotation_approvals")
+            .select("*")
+            .eq("status", "pending")
+            .order("created_at.asc")
+            .execute()
+            .await
+            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(DatabaseError::QueryError(format!(
+                "Pending approval lookup failed with status: {}",
+                response.status()
+            )));
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|e| DatabaseError::QueryError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::Matcher;
+
+    #[tokio::test]
+    async fn test_only_pending_approvals_are_decided() {
+        let id = Uuid::new_v4();
+        let mut server = mockito::Server::new_async().await;
+        let decided = server
+            .mock("PATCH", "/quotation_approvals")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("id".into(), format!("eq.{}", id)),
+                Matcher::UrlEncoded("status".into(), "eq.pending".into()),
+            ]))
+            .match_body(Matcher::PartialJsonString(
+                r#"{"status": "approved", "decided_by": "42"}"#.into(),
+            ))
+            .with_status(200)
+            .with_body(format!(
+                r#"[{{
+                    "id": "{}",
+                    "user_id": "{}",
+                    "platform": "telegram",
+                    "recipient": "12345",
+                    "document_reference": "Q-20250821-0001",
+                    "grand_total": 250000.0,
+                    "text": "Quotation created for given enquiry",
+                    "files": ["artifacts/quotation.pdf"],
+                    "status": "approved",
+                    "decided_by": "42"
+                }}]"#,
+                id,
+                Uuid::new_v4()
+            ))
+            .create_async()
+            .await;
+
+        let db = DatabaseService::with_url(&server.url());
+        let approval = db
+            .decide_quotation_approval(id, ApprovalStatus::Approved, "42")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(approval.id, id);
+        assert_eq!(approval.status, ApprovalStatus::Approved);
+        assert_eq!(approval.files, vec!["artifacts/quotation.pdf"]);
+        decided.assert_async().await;
+
+        // Already decided - nothing matches the pending filter
+        server.reset();
+        server
+            .mock("PATCH", "/quotation_approvals")
+            .match_query(Matcher::Any)
+            .with_status(200)
+            .with_body("[]")
+            .create_async()
+            .await;
+        let approval = db
+            .decide_quotation_approval(id, ApprovalStatus::Rejected, "42")
+            .await
+            .unwrap();
+        assert_eq!(approval, None);
+    }
+}
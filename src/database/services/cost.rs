@@ -1,7 +1,7 @@
 use super::super::types::{ClaudeRates, CostEvent, CostEventBuilder, GroqRates, SessionContext};
 use super::DatabaseError;
 use super::DatabaseService;
-use tracing::error;
+use tracing::{error, info};
 use uuid::Uuid;
 
 impl DatabaseService {
@@ -130,6 +130,15 @@ impl DatabaseService {
         let cache_read_cost = (cache_read_tokens as f64 * rates.cache_hit_refresh) / 1_000_000.0;
         let output_cost = (output_tokens as f64 * rates.output_token) / 1_000_000.0;
         let cache_write_cost = (cache_write_tokens as f64 * rates.one_h_cache_writes) / 1_000_000.0;
+        // What the cached prefix would have cost as plain input tokens
+        let cache_savings = (cache_read_tokens as f64 * (rates.input_token - rates.cache_hit_refresh))
+            / 1_000_000.0;
+        if cache_read_tokens > 0 {
+            info!(
+                "Claude cache hit on {} tokens saved ${:.4}",
+                cache_read_tokens, cache_savings
+            );
+        }
 
         let metadata = serde_json::json!({
             "model": model,
@@ -140,7 +149,8 @@ impl DatabaseService {
             "input_cost": input_cost,
             "cache_read_cost": cache_read_cost,
             "output_cost": output_cost,
-            "cache_write_cost": cache_write_cost
+            "cache_write_cost": cache_write_cost,
+            "cache_savings": cache_savings
         });
 
         let total_cost = input_cost + cache_read_cost + cache_write_cost + output_cost;
@@ -160,12 +170,29 @@ impl DatabaseService {
         context: &SessionContext,
         image_size_bytes: usize,
     ) -> Result<(), DatabaseError> {
+        self.log_ocr_usage(context, "textract", image_size_bytes).await
+    }
+
+    // Log an OCR call against the provider that actually served it
+    pub async fn log_ocr_usage(
+        &self,
+        context: &SessionContext,
+        provider: &str,
+        image_size_bytes: usize,
+    ) -> Result<(), DatabaseError> {
+        let (event_type, unit_cost) = match provider {
+            "textract" => ("textract_api", 0.0015),
+            // Runs locally, so there is no per page charge
+            "tesseract" => ("tesseract_ocr", 0.0),
+            _ => ("ocr_api", 0.0),
+        };
         let metadata = serde_json::json!({
-            "image_size_bytes": image_size_bytes
+            "image_size_bytes": image_size_bytes,
+            "provider": provider
         });
 
-        CostEventBuilder::new(context.clone(), "textract_api")
-            .with_cost(0.0015, "per_page", 1)
+        CostEventBuilder::new(context.clone(), event_type)
+            .with_cost(unit_cost, "per_page", 1)
             .with_metadata(metadata)
             .log(self)
             .await
@@ -314,14 +341,7 @@ mod tests {
     }
 
     fn create_mock_database_service(server: &ServerGuard) -> DatabaseService {
-        let client = postgrest::Postgrest::new(&server.url())
-            .insert_header("apikey", "test_key")
-            .insert_header("Authorization", "Bearer test_key");
-
-        DatabaseService {
-            client,
-            admin_telegram_id: "test_admin".to_string(),
-        }
+        DatabaseService::with_url(&server.url())
     }
     
     #[tokio::test]
@@ -611,6 +631,29 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[tokio::test]
+    #[serial]
+    async fn test_log_ocr_usage_attributes_fallback_provider() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/cost_events")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "event_type": "tesseract_ocr",
+                "cost_amount": 0.0,
+                "metadata": {"provider": "tesseract"}
+            })))
+            .with_status(201)
+            .create_async()
+            .await;
+
+        let db = create_mock_database_service(&server);
+        let context = create_test_session_context();
+
+        let result = db.log_ocr_usage(&context, "tesseract", 1024).await;
+        assert!(result.is_ok());
+        mock.assert_async().await;
+    }
+
     #[tokio::test]
     #[serial]
     async fn test_create_cost_notification_long_query_truncation() {
@@ -0,0 +1,184 @@
+use super::super::types::{customer_key, CustomerProfile};
+use super::DatabaseError;
+use super::DatabaseService;
+
+impl DatabaseService {
+    // Keys are tried in the order given, so pass the most specific first
+    pub async fn find_customer_profile(
+        &self,
+        keys: &[String],
+    ) -> Result<Option<CustomerProfile>, DatabaseError> {
+        let keys: Vec<String> = keys.iter().map(|key| customer_key(key)).collect();
+        if keys.is_empty() {
+            return Ok(None);
+        }
+        let profiles = self.fetch_customer_profiles(Some(&keys)).await?;
+
+        Ok(keys
+            .iter()
+            .find_map(|key| profiles.iter().find(|profile| &profile.customer_key == key))
+            .cloned())
+    }
+
+    pub async fn list_customer_profiles(&self) -> Result<Vec<CustomerProfile>, DatabaseError> {
+        self.fetch_customer_profiles(None).await
+    }
+
+    // Replaces any existing profile under the same key
+    pub async fn save_customer_profile(
+        &self,
+        profile: &CustomerProfile,
+    ) -> Result<CustomerProfile, DatabaseError> {
+        let profile = CustomerProfile {
+            customer_key: customer_key(&profile.customer_key),
+            ..profile.clone()
+        };
+        if profile.customer_key.is_empty() {
+            return Err(DatabaseError::QueryError(
+                "Customer key cannot be empty".to_string(),
+            ));
+        }
+        let mut row =
+            serde_json::to_value(&profile).map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+        row["updated_at"] = serde_json::json!(chrono::Utc::now());
+
+        let response = self
+            .client
+            .from("customer_profiles")
+            .upsert(row.to_string())
+            .on_conflict("customer_key")
+            .execute()
+            .await
+            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(DatabaseError::QueryError(format!(
+                "Saving customer profile failed with status: {}",
+                response.status()
+            )));
+        }
+
+        Ok(profile)
+    }
+
+    // Returns whether there was a profile to delete
+    pub async fn delete_customer_profile(&self, key: &str) -> Result<bool, DatabaseError> {
+        let response = self
+            .client
+            .from("customer_profiles")
+            .delete()
+            .eq("customer_key", customer_key(key))
+            .select("customer_key")
+            .execute()
+            .await
+            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(DatabaseError::QueryError(format!(
+                "Deleting customer profile failed with status: {}",
+                response.status()
+            )));
+        }
+
+        let rows: Vec<serde_json::Value> = response
+            .json()
+            .await
+            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+        Ok(!rows.is_empty())
+    }
+
+    async fn fetch_customer_profiles(
+        &self,
+        keys: Option<&[String]>,
+    ) -> Result<Vec<CustomerProfile>, DatabaseError> {
+        let mut query = self
+            .client
+            .from("customer_profiles")
+            .select("*")
+            .order("customer_key.asc");
+        if let Some(keys) = keys {
+            // Keys are free text (names with commas, brackets...), which postgrest only reads as
+            // one value each when quoted
+            let quoted = keys
+                .iter()
+                .map(|key| format!("\"{}\"", key.replace('\\', "\\\\").replace('"', "\\\"")));
+            query = query.in_("customer_key", quoted);
+        }
+
+        let response = query
+            .execute()
+            .await
+            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(DatabaseError::QueryError(format!(
+                "Customer profile lookup failed with status: {}",
+                response.status()
+            )));
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|e| DatabaseError::QueryError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::Matcher;
+
+    #[tokio::test]
+    async fn test_find_customer_profile_prefers_earlier_keys() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/customer_profiles")
+            .match_query(Matcher::Regex("customer_key=in".into()))
+            .with_status(200)
+            .with_body(
+                r#"[
+                    {"customer_key": "btl epc ltd", "discount": 0.05, "delivery_charges": null},
+                    {"customer_key": "telegram:12345", "discount": 0.02}
+                ]"#,
+            )
+            .create_async()
+            .await;
+
+        let db = DatabaseService::with_url(&server.url());
+        let keys = vec!["telegram:12345".to_string(), "BTL  EPC Ltd".to_string()];
+        let profile = db.find_customer_profile(&keys).await.unwrap().unwrap();
+        assert_eq!(profile.customer_key, "telegram:12345");
+        assert_eq!(profile.discount, Some(0.02));
+
+        let keys = vec!["BTL EPC Ltd".to_string(), "telegram:12345".to_string()];
+        let profile = db.find_customer_profile(&keys).await.unwrap().unwrap();
+        assert_eq!(profile.customer_key, "btl epc ltd");
+        assert_eq!(profile.discount, Some(0.05));
+
+        assert_eq!(db.find_customer_profile(&[]).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_customer_keys_are_quoted_in_lookups() {
+        let mut server = mockito::Server::new_async().await;
+        let lookup = server
+            .mock("GET", "/customer_profiles")
+            .match_query(Matcher::UrlEncoded(
+                "customer_key".into(),
+                r#"in.("sharma, sons (pune)","the \"best\" cables")"#.into(),
+            ))
+            .with_status(200)
+            .with_body("[]")
+            .create_async()
+            .await;
+
+        let db = DatabaseService::with_url(&server.url());
+        let keys = vec![
+            "Sharma, Sons (Pune)".to_string(),
+            r#"The "Best" Cables"#.to_string(),
+        ];
+        assert_eq!(db.find_customer_profile(&keys).await.unwrap(), None);
+        lookup.assert_async().await;
+    }
+}
@@ -0,0 +1,111 @@
+use super::super::types::MetalPrice;
+use super::DatabaseError;
+use super::DatabaseService;
+use chrono::{DateTime, Utc};
+use chrono_tz::Asia::Kolkata;
+
+impl DatabaseService {
+    pub async fn log_metal_price(
+        &self,
+        metal: &str,
+        price: f64,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), DatabaseError> {
+        let row = MetalPrice {
+            metal: metal.to_string(),
+            price,
+            fetched_at: timestamp,
+        };
+        let response = self
+            .client
+            .from("metal_prices")
+            .insert(serde_json::to_string(&row).unwrap())
+            .execute()
+            .await
+            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(DatabaseError::QueryError(format!(
+                "Saving metal price failed with status: {}",
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+
+    // The last price logged for the metal before today (IST), None until there is any history
+    pub async fn get_previous_close(&self, metal: &str) -> Result<Option<f64>, DatabaseError> {
+        let start_of_day = Utc::now()
+            .with_timezone(&Kolkata)
+            .date_naive()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+            .and_local_timezone(Kolkata)
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let response = self
+            .client
+            .from("metal_prices")
+            .select("*")
+            .eq("metal", metal)
+            .lt("fetched_at", start_of_day.to_rfc3339())
+            .order("fetched_at.desc")
+            .limit(1)
+            .execute()
+            .await
+            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(DatabaseError::QueryError(format!(
+                "Previous close lookup failed with status: {}",
+                response.status()
+            )));
+        }
+
+        let prices: Vec<MetalPrice> = response
+            .json()
+            .await
+            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+        Ok(prices.first().map(|close| close.price))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::Matcher;
+
+    #[tokio::test]
+    async fn test_previous_close_is_latest_price_before_today() {
+        let mut server = mockito::Server::new_async().await;
+        let lookup = server
+            .mock("GET", "/metal_prices")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("metal".into(), "eq.copper".into()),
+                Matcher::UrlEncoded("order".into(), "fetched_at.desc".into()),
+                Matcher::Regex("fetched_at=lt.".into()),
+            ]))
+            .match_header("range", "0-0")
+            .with_status(200)
+            .with_body(
+                r#"[{"metal": "copper", "price": 840.0, "fetched_at": "2025-06-02T09:39:00Z"}]"#,
+            )
+            .create_async()
+            .await;
+
+        let db = DatabaseService::with_url(&server.url());
+        assert_eq!(db.get_previous_close("copper").await.unwrap(), Some(840.0));
+        lookup.assert_async().await;
+
+        server.reset();
+        server
+            .mock("GET", "/metal_prices")
+            .match_query(Matcher::Any)
+            .with_status(200)
+            .with_body("[]")
+            .create_async()
+            .await;
+        assert_eq!(db.get_previous_close("lead").await.unwrap(), None);
+    }
+}
@@ -2,12 +2,17 @@ use super::errors::DatabaseError;
 use postgrest::Postgrest;
 use std::env;
 
+mod approval;
 mod cost;
+mod customer;
+mod metal_price;
+mod privacy;
 mod session;
 mod user;
 pub struct DatabaseService {
     pub client: Postgrest,
     admin_telegram_id: String,
+    cost_notifications: bool,
 }
 
 impl DatabaseService {
@@ -25,6 +30,29 @@ impl DatabaseService {
         Ok(Self {
             client,
             admin_telegram_id,
+            cost_notifications: true,
         })
     }
+
+    // Whether completing a successful session sends its cost report
+    pub fn with_cost_notifications(mut self, enabled: bool) -> Self {
+        self.cost_notifications = enabled;
+        self
+    }
+}
+
+#[cfg(test)]
+impl DatabaseService {
+    // Client pointed at a mock PostgREST server, e.g. a mockito instance
+    pub(crate) fn with_url(url: &str) -> Self {
+        let client = Postgrest::new(url)
+            .insert_header("apikey", "test_key")
+            .insert_header("Authorization", "Bearer test_key");
+
+        Self {
+            client,
+            admin_telegram_id: "test_admin".to_string(),
+            cost_notifications: true,
+        }
+    }
 }
@@ -0,0 +1,363 @@
+use super::super::types::{PurgeReport, User, UserDataExport};
+use super::DatabaseError;
+use super::DatabaseService;
+use chrono::{DateTime, Utc};
+use postgrest::Builder;
+use uuid::Uuid;
+
+// Keeps the in.(...) filters well within URL length limits
+const PURGE_BATCH_SIZE: usize = 100;
+
+impl DatabaseService {
+    // Gather everything held about a user - used for compliance / customer data requests
+    pub async fn export_user_data(&self, user_id: Uuid) -> Result<UserDataExport, DatabaseError> {
+        let user = self.get_user_by_id(user_id).await?.ok_or(DatabaseError::UserNotFound)?;
+
+        let user_id_str = user_id.to_string();
+        let sessions = self.fetch_rows("query_sessions", "user_id", &user_id_str).await?;
+        let conversations = self.fetch_rows("conversations", "user_id", &user_id_str).await?;
+        let cost_events = self.fetch_rows("cost_events", "user_id", &user_id_str).await?;
+
+        let conversation_ids: Vec<String> = conversations
+            .iter()
+            .filter_map(|c| c["id"].as_str().map(|id| id.to_string()))
+            .collect();
+
+        let conversation_messages = if conversation_ids.is_empty() {
+            Vec::new()
+        } else {
+            let response = self
+                .client
+                .from("conversation_messages")
+                .select("*")
+                .in_("conversation_id", &conversation_ids)
+                .order("created_at.asc")
+                .execute()
+                .await
+                .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+
+            response
+                .json()
+                .await
+                .map_err(|e| DatabaseError::QueryError(e.to_string()))?
+        };
+
+        Ok(UserDataExport {
+            user,
+            sessions,
+            conversations,
+            conversation_messages,
+            cost_events,
+            exported_at: Utc::now(),
+        })
+    }
+
+    pub async fn get_user_by_id(&self, user_id: Uuid) -> Result<Option<User>, DatabaseError> {
+        let response = self
+            .client
+            .from("users")
+            .select("*")
+            .eq("id", user_id.to_string())
+            .single()
+            .execute()
+            .await
+            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+
+        if response.status() == 406 {
+            // No rows found
+            return Ok(None);
+        }
+
+        let user: User = response
+            .json()
+            .await
+            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+
+        Ok(Some(user))
+    }
+
+    // Remove conversations (and their messages) idle since before `cutoff`. Sessions and
+    // cost events are kept for accounting - sessions only lose their conversation link.
+    pub async fn purge_conversations_before(
+        &self,
+        cutoff: DateTime<Utc>,
+    ) -> Result<PurgeReport, DatabaseError> {
+        let response = self
+            .client
+            .from("conversations")
+            .select("id")
+            .lt("last_activity_at", cutoff.to_rfc3339())
+            .execute()
+            .await
+            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+
+        let conversations: Vec<serde_json::Value> = response
+            .json()
+            .await
+            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+
+        let conversation_ids: Vec<String> = conversations
+            .iter()
+            .filter_map(|c| c["id"].as_str().map(|id| id.to_string()))
+            .collect();
+
+        let mut report = PurgeReport::default();
+        for batch in conversation_ids.chunks(PURGE_BATCH_SIZE) {
+            // Messages and session links reference conversations, so they go first
+            report.messages_deleted += self
+                .delete_rows_in("conversation_messages", "conversation_id", batch)
+                .await?;
+
+            let unlink = self
+                .client
+                .from("query_sessions")
+                .update(serde_json::json!({ "conversation_id": null }).to_string())
+                .in_("conversation_id", batch)
+                .select("id");
+            report.sessions_unlinked += Self::count_returned_rows(unlink, "query_sessions").await?;
+
+            report.conversations_deleted +=
+                self.delete_rows_in("conversations", "id", batch).await?;
+        }
+
+        Ok(report)
+    }
+
+    async fn delete_rows_in(
+        &self,
+        table: &str,
+        column: &str,
+        values: &[String],
+    ) -> Result<usize, DatabaseError> {
+        let delete = self
+            .client
+            .from(table)
+            .delete()
+            .in_(column, values)
+            .select("id");
+
+        Self::count_returned_rows(delete, table).await
+    }
+
+    // Mutations return the affected rows (return=representation), so counting them gives
+    // the number of rows removed / updated
+    async fn count_returned_rows(builder: Builder, table: &str) -> Result<usize, DatabaseError> {
+        let response = builder
+            .execute()
+            .await
+            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(DatabaseError::QueryError(format!(
+                "Purging {} failed with status: {}",
+                table,
+                response.status()
+            )));
+        }
+
+        let rows: Vec<serde_json::Value> = response
+            .json()
+            .await
+            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+        Ok(rows.len())
+    }
+
+    // Rows are kept as raw json so the export carries every column, including ones
+    // (like session metadata) that the typed structs don't model
+    async fn fetch_rows(
+        &self,
+        table: &str,
+        column: &str,
+        value: &str,
+    ) -> Result<Vec<serde_json::Value>, DatabaseError> {
+        let response = self
+            .client
+            .from(table)
+            .select("*")
+            .eq(column, value)
+            .order("created_at.asc")
+            .execute()
+            .await
+            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(DatabaseError::QueryError(format!(
+                "Fetching {} failed with status: {}",
+                table,
+                response.status()
+            )));
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|e| DatabaseError::QueryError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{Matcher, ServerGuard};
+
+    fn create_mock_database_service(server: &ServerGuard) -> DatabaseService {
+        let client = postgrest::Postgrest::new(server.url())
+            .insert_header("apikey", "test_key")
+            .insert_header("Authorization", "Bearer test_key");
+
+        DatabaseService {
+            client,
+            admin_telegram_id: "test_admin".to_string(),
+            cost_notifications: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_export_user_data_collects_all_tables() {
+        let mut server = mockito::Server::new_async().await;
+        let user_id = Uuid::new_v4();
+        let conversation_id = Uuid::new_v4();
+
+        let user_data = format!(
+            r#"{{"id": "{}", "phone_number": null, "telegram_id": "12345", "status": "active", "platform": "telegram", "created_at": "2025-01-01T10:00:00Z"}}"#,
+            user_id
+        );
+        let _user_mock = server
+            .mock("GET", "/users")
+            .match_query(Matcher::UrlEncoded("id".into(), format!("eq.{}", user_id)))
+            .with_status(200)
+            .with_body(&user_data)
+            .create_async()
+            .await;
+
+        let _sessions_mock = server
+            .mock("GET", "/query_sessions")
+            .match_query(Matcher::UrlEncoded("user_id".into(), format!("eq.{}", user_id)))
+            .with_status(200)
+            .with_body(r#"[{"id": "s1", "query_text": "copper price"}, {"id": "s2", "query_text": "quote"}]"#)
+            .create_async()
+            .await;
+
+        let _conversations_mock = server
+            .mock("GET", "/conversations")
+            .match_query(Matcher::UrlEncoded("user_id".into(), format!("eq.{}", user_id)))
+            .with_status(200)
+            .with_body(format!(r#"[{{"id": "{}"}}]"#, conversation_id))
+            .create_async()
+            .await;
+
+        let _messages_mock = server
+            .mock("GET", "/conversation_messages")
+            .match_query(Matcher::UrlEncoded(
+                "conversation_id".into(),
+                format!("in.({})", conversation_id),
+            ))
+            .with_status(200)
+            .with_body(r#"[{"user_query": "copper price"}]"#)
+            .create_async()
+            .await;
+
+        let _costs_mock = server
+            .mock("GET", "/cost_events")
+            .match_query(Matcher::UrlEncoded("user_id".into(), format!("eq.{}", user_id)))
+            .with_status(200)
+            .with_body(r#"[{"event_type": "groq_api", "cost_amount": 0.001}]"#)
+            .create_async()
+            .await;
+
+        let db = create_mock_database_service(&server);
+        let export = db.export_user_data(user_id).await.unwrap();
+
+        assert_eq!(export.user.id, user_id);
+        assert_eq!(export.sessions.len(), 2);
+        assert_eq!(export.conversations.len(), 1);
+        assert_eq!(export.conversation_messages.len(), 1);
+        assert_eq!(export.cost_events.len(), 1);
+        assert!(serde_json::to_string(&export).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_export_user_data_unknown_user() {
+        let mut server = mockito::Server::new_async().await;
+        let _user_mock = server
+            .mock("GET", "/users")
+            .match_query(Matcher::Any)
+            .with_status(406)
+            .create_async()
+            .await;
+
+        let db = create_mock_database_service(&server);
+        let result = db.export_user_data(Uuid::new_v4()).await;
+
+        assert!(matches!(result, Err(DatabaseError::UserNotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_purge_conversations_deletes_messages_then_conversations() {
+        let mut server = mockito::Server::new_async().await;
+        let old_conversation = Uuid::new_v4();
+        let ids_filter = format!("in.({})", old_conversation);
+
+        let _select_mock = server
+            .mock("GET", "/conversations")
+            .match_query(Matcher::Regex(r".*last_activity_at=lt\..*".to_string()))
+            .with_status(200)
+            .with_body(format!(r#"[{{"id": "{}"}}]"#, old_conversation))
+            .create_async()
+            .await;
+
+        let messages_mock = server
+            .mock("DELETE", "/conversation_messages")
+            .match_query(Matcher::UrlEncoded("conversation_id".into(), ids_filter.clone()))
+            .with_status(200)
+            .with_body(r#"[{"id": "m1"}, {"id": "m2"}, {"id": "m3"}]"#)
+            .create_async()
+            .await;
+
+        let sessions_mock = server
+            .mock("PATCH", "/query_sessions")
+            .match_query(Matcher::UrlEncoded("conversation_id".into(), ids_filter.clone()))
+            .with_status(200)
+            .with_body(r#"[{"id": "s1"}, {"id": "s2"}]"#)
+            .create_async()
+            .await;
+
+        let conversations_mock = server
+            .mock("DELETE", "/conversations")
+            .match_query(Matcher::UrlEncoded("id".into(), ids_filter))
+            .with_status(200)
+            .with_body(format!(r#"[{{"id": "{}"}}]"#, old_conversation))
+            .create_async()
+            .await;
+
+        let db = create_mock_database_service(&server);
+        let report = db
+            .purge_conversations_before(Utc::now() - chrono::Duration::days(30))
+            .await
+            .unwrap();
+
+        messages_mock.assert_async().await;
+        sessions_mock.assert_async().await;
+        conversations_mock.assert_async().await;
+        assert_eq!(report.messages_deleted, 3);
+        assert_eq!(report.sessions_unlinked, 2);
+        assert_eq!(report.conversations_deleted, 1);
+    }
+
+    #[tokio::test]
+    async fn test_purge_conversations_nothing_to_purge() {
+        let mut server = mockito::Server::new_async().await;
+        let _select_mock = server
+            .mock("GET", "/conversations")
+            .match_query(Matcher::Any)
+            .with_status(200)
+            .with_body("[]")
+            .create_async()
+            .await;
+
+        let db = create_mock_database_service(&server);
+        let report = db.purge_conversations_before(Utc::now()).await.unwrap();
+
+        assert_eq!(report, PurgeReport::default());
+    }
+}
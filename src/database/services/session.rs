@@ -1,10 +1,11 @@
 use super::super::types::{
-    ConversationContext, ConversationMessage, QuerySession, SessionContext, SessionResult,
-    StructuredResponse,
+    ConversationContext, ConversationMessage, ModelUsage, QuerySession, ReferencedSession,
+    SessionContext, SessionResult, StructuredResponse, DOCUMENT_REFERENCE_KEY,
 };
 use super::DatabaseError;
 use super::DatabaseService;
-use chrono::Utc;
+use crate::core::Alert;
+use chrono::{DateTime, Utc};
 use tokio::sync::mpsc;
 use tracing::error;
 use uuid::Uuid;
@@ -52,13 +53,19 @@ impl DatabaseService {
         processing_time: i32,
         query_metadata: Option<serde_json::Value>,
     ) -> Result<(), DatabaseError> {
+        let document_reference = query_metadata
+            .as_ref()
+            .and_then(|metadata| metadata[DOCUMENT_REFERENCE_KEY].as_str())
+            .map(str::to_string);
+
         let update_data = if let Some(err_msg) = error_message {
             serde_json::json!({
                 "response_type": response_type,
                 "error_message": err_msg,
                 "total_cost": total_cost,
                 "processing_time_ms": processing_time,
-                "metadata": query_metadata
+                "metadata": query_metadata,
+                "document_reference": document_reference
             })
         } else {
             serde_json::json!({
@@ -66,7 +73,8 @@ impl DatabaseService {
                 "error_message": null,
                 "total_cost": total_cost,
                 "processing_time_ms": processing_time,
-                "metadata": query_metadata
+                "metadata": query_metadata,
+                "document_reference": document_reference
             })
         };
 
@@ -115,13 +123,48 @@ impl DatabaseService {
         Ok(total)
     }
 
+    // Spend on every query of a conversation, joined through the sessions its messages were saved
+    // with so the request stays the same size however long the conversation gets
+    pub async fn get_conversation_total_cost(
+        &self,
+        conversation_id: Uuid,
+    ) -> Result<f64, DatabaseError> {
+        let response = self
+            .client
+            .from("cost_events")
+            .select("cost_amount,query_sessions!inner(conversation_messages!inner(conversation_id))")
+            .eq(
+                "query_sessions.conversation_messages.conversation_id",
+                conversation_id.to_string(),
+            )
+            .execute()
+            .await
+            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(DatabaseError::QueryError(format!(
+                "Conversation cost lookup failed with status: {}",
+                response.status()
+            )));
+        }
+
+        let costs: Vec<serde_json::Value> = response
+            .json()
+            .await
+            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+        Ok(costs.iter().filter_map(|c| c["cost_amount"].as_f64()).sum())
+    }
+
+    // model_used is the LLM whose parse was finally used, after any fallback
     pub async fn update_session_query_type(
         &self,
         session_id: Uuid,
         query_type: &str,
+        model_used: Option<&str>,
     ) -> Result<(), DatabaseError> {
         let update_data = serde_json::json!({
-            "query_type": query_type
+            "query_type": query_type,
+            "model_used": model_used
         });
 
         let response = self
@@ -193,7 +236,7 @@ impl DatabaseService {
         context: &SessionContext,
         result: SessionResult,
         query_text: &str,
-        error_sender: &mpsc::Sender<String>,
+        error_sender: &mpsc::Sender<Alert>,
     ) -> Result<(), DatabaseError> {
         let total_cost = self
             .get_session_total_cost(context.session_id)
@@ -213,7 +256,7 @@ impl DatabaseService {
             )
             .await;
 
-        if update_result.is_ok() && result.success {
+        if update_result.is_ok() && result.success && self.cost_notifications {
             let cost_message = self
                 .create_cost_notification(
                     context,
@@ -222,12 +265,79 @@ impl DatabaseService {
                     result.processing_time_ms,
                 )
                 .await;
-            let _ = error_sender.send(cost_message).await;
+            let _ = error_sender.send(Alert::usage(cost_message)).await;
         }
 
         update_result
     }
 
+    // Sessions served by each LLM since the given time. Sessions that never got a parse
+    // (eg. failed before reaching the LLM) are left out.
+    pub async fn get_model_usage(&self, since: DateTime<Utc>) -> Result<ModelUsage, DatabaseError> {
+        let response = self
+            .client
+            .from("query_sessions")
+            .select("model_used")
+            .gte("created_at", since.to_rfc3339())
+            .execute()
+            .await
+            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+
+        let sessions: Vec<serde_json::Value> = response
+            .json()
+            .await
+            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+
+        let mut usage = ModelUsage::default();
+        for model in sessions.iter().filter_map(|s| s["model_used"].as_str()) {
+            *usage.sessions_by_model.entry(model.to_string()).or_insert(0) += 1;
+        }
+        Ok(usage)
+    }
+
+    // The user's latest session whose document carries this reference. Only the user's own
+    // sessions are searched, so a guessed number doesn't give away someone else's quotation.
+    pub async fn get_session_by_reference(
+        &self,
+        user_id: Uuid,
+        reference: &str,
+    ) -> Result<Option<ReferencedSession>, DatabaseError> {
+        // Anything but letters, digits and dashes would be read as filter syntax
+        let reference: String = reference
+            .trim()
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+            .collect();
+        if reference.is_empty() {
+            return Ok(None);
+        }
+
+        let response = self
+            .client
+            .from("query_sessions")
+            .select("id,document_reference,metadata,created_at")
+            .eq("user_id", user_id.to_string())
+            .ilike("document_reference", format!("*{}*", reference))
+            .order("created_at.desc")
+            .limit(1)
+            .execute()
+            .await
+            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(DatabaseError::QueryError(format!(
+                "Session lookup by reference failed with status: {}",
+                response.status()
+            )));
+        }
+
+        let sessions: Vec<ReferencedSession> = response
+            .json()
+            .await
+            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+        Ok(sessions.into_iter().next())
+    }
+
     // Conversation management methods
     pub async fn get_recent_conversation(
         &self,
@@ -412,6 +522,7 @@ mod tests {
         DatabaseService {
             client,
             admin_telegram_id: "test_admin".to_string(),
+            cost_notifications: true,
         }
     }
 
@@ -483,6 +594,74 @@ mod tests {
         assert_eq!(total_cost, 0.11); // 0.05 + 0.03 + 0.02 + 0.01
     }
 
+    #[tokio::test]
+    async fn test_complete_session_without_cost_notifications() {
+        let mut server = mockito::Server::new_async().await;
+        let _costs = server
+            .mock("GET", "/cost_events")
+            .match_query(mockito::Matcher::Any)
+            .with_status(200)
+            .with_body(r#"[{"cost_amount": 0.05}]"#)
+            .create_async()
+            .await;
+        let _update = server
+            .mock("PATCH", "/query_sessions")
+            .match_query(mockito::Matcher::Any)
+            .with_status(204)
+            .create_async()
+            .await;
+
+        let db = create_mock_database_service(&server).with_cost_notifications(false);
+        let (sender, mut receiver) = mpsc::channel(10);
+        let result = SessionResult {
+            success: true,
+            error_message: None,
+            processing_time_ms: 120,
+            query_metadata: None,
+        };
+
+        db.complete_session_with_notification(
+            &create_test_session_context(),
+            result,
+            "copper rate",
+            &sender,
+        )
+        .await
+        .unwrap();
+
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_update_session_result_stores_document_reference() {
+        let mut server = mockito::Server::new_async().await;
+        let session_id = create_test_session_context().session_id;
+
+        let mock = server
+            .mock("PATCH", "/query_sessions")
+            .match_query(mockito::Matcher::UrlEncoded("id".into(), format!("eq.{}", session_id)))
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "document_reference": "Q-20250821-1234"
+            })))
+            .with_status(204)
+            .create_async()
+            .await;
+
+        let db = create_mock_database_service(&server);
+        db.update_session_result(
+            session_id,
+            "success",
+            None,
+            0.1,
+            1000,
+            Some(serde_json::json!({"document_reference": "Q-20250821-1234"})),
+        )
+        .await
+        .unwrap();
+
+        mock.assert_async().await;
+    }
+
     #[tokio::test]
     async fn test_update_session_result_error() {
         let mut server = mockito::Server::new_async().await;
@@ -712,4 +891,117 @@ mod tests {
             panic!("Expected DatabaseError::QueryError for missing conversation ID");
         }
     }
+
+    #[tokio::test]
+    async fn test_get_model_usage_counts_sessions_per_model() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/query_sessions")
+            .match_query(mockito::Matcher::UrlEncoded("select".into(), "model_used".into()))
+            .with_status(200)
+            .with_body(
+                r#"[{"model_used": "groq"}, {"model_used": "groq"}, {"model_used": "claude"}, {"model_used": null}]"#,
+            )
+            .create_async()
+            .await;
+
+        let db = create_mock_database_service(&server);
+        let usage = db
+            .get_model_usage(Utc::now() - chrono::Duration::days(7))
+            .await
+            .unwrap();
+
+        assert_eq!(usage.sessions_by_model.get("groq"), Some(&2));
+        assert_eq!(usage.sessions_by_model.get("claude"), Some(&1));
+        assert_eq!(usage.total(), 3);
+        assert!((usage.share("groq") - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(usage.share("unknown"), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_get_session_by_reference_searches_users_documents() {
+        let mut server = mockito::Server::new_async().await;
+        let user_id = Uuid::new_v4();
+        let session_id = Uuid::new_v4();
+        let _mock = server
+            .mock("GET", "/query_sessions")
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("user_id".into(), format!("eq.{}", user_id)),
+                mockito::Matcher::UrlEncoded(
+                    "document_reference".into(),
+                    "ilike.*Q-20250821-1234*".into(),
+                ),
+            ]))
+            // postgrest sends limit as a Range header rather than a query param
+            .match_header("range", "0-0")
+            .with_status(200)
+            .with_body(format!(
+                r#"[{{"id": "{}", "document_reference": "Q-20250821-1234, PI-20250821-1234", "metadata": {{"quotation_totals": {{"grand_total": 1180.0}}}}, "created_at": "2025-08-21T06:30:00Z"}}]"#,
+                session_id
+            ))
+            .create_async()
+            .await;
+
+        let db = create_mock_database_service(&server);
+        let session = db
+            .get_session_by_reference(user_id, " Q-20250821-1234 ")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(session.id, session_id);
+        assert_eq!(
+            session.document_reference,
+            "Q-20250821-1234, PI-20250821-1234"
+        );
+        assert_eq!(
+            session.metadata.unwrap()["quotation_totals"]["grand_total"],
+            1180.0
+        );
+
+        // Nothing that could be filter syntax reaches the query
+        assert_eq!(
+            db.get_session_by_reference(user_id, "*,()").await.unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_conversation_total_cost_sums_its_sessions() {
+        let mut server = mockito::Server::new_async().await;
+        let conversation_id = Uuid::new_v4();
+        let _costs = server
+            .mock("GET", "/cost_events")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "query_sessions.conversation_messages.conversation_id".into(),
+                format!("eq.{}", conversation_id),
+            ))
+            .with_status(200)
+            .with_body(r#"[{"cost_amount": 0.25}, {"cost_amount": 0.5}, {"cost_amount": 0.125}]"#)
+            .create_async()
+            .await;
+
+        let db = create_mock_database_service(&server);
+        let cost = db
+            .get_conversation_total_cost(conversation_id)
+            .await
+            .unwrap();
+        assert!((cost - 0.875).abs() < 1e-9);
+
+        // An error body is reported as a failed lookup, not read as costs
+        let _failed = server
+            .mock("GET", "/cost_events")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "query_sessions.conversation_messages.conversation_id".into(),
+                format!("eq.{}", Uuid::nil()),
+            ))
+            .with_status(400)
+            .with_body(r#"{"message": "Could not find a relationship"}"#)
+            .create_async()
+            .await;
+        let error = db
+            .get_conversation_total_cost(Uuid::nil())
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("failed with status: 400"));
+    }
 }
@@ -1,15 +1,20 @@
-use super::super::types::User;
+use super::super::types::{
+    normalize_phone, stored_whatsapp_number, stored_whatsapp_number_forms, User,
+};
 use super::DatabaseError;
 use super::DatabaseService;
 
 impl DatabaseService {
-    // Find user based on whatsapp phone number
+    // Find user based on whatsapp phone number, in any format the number was written in
     pub async fn get_user_by_phone(&self, phone: &str) -> Result<Option<User>, DatabaseError> {
+        let Some(phone) = normalize_phone(phone) else {
+            return Ok(None);
+        };
         let response = self
             .client
             .from("users")
             .select("*")
-            .eq("phone_number", format!("whatsapp:{}", phone))
+            .in_("phone_number", stored_whatsapp_number_forms(&phone))
             .single()
             .execute()
             .await
@@ -86,6 +91,10 @@ impl DatabaseService {
         telegram_id == self.admin_telegram_id
     }
 
+    pub fn admin_telegram_id(&self) -> &str {
+        &self.admin_telegram_id
+    }
+
     // Approve pending telegram user
     pub async fn approve_telegram_user(&self, telegram_id: &str) -> Result<bool, DatabaseError> {
         let response = self
@@ -109,8 +118,10 @@ impl DatabaseService {
 
     // approva whatsapp user - no pending step for whatsapp users like it is for telegram users
     pub async fn approve_whatsapp_user(&self, phone: &str) -> Result<(), DatabaseError> {
+        let phone = normalize_phone(phone)
+            .ok_or_else(|| DatabaseError::QueryError(format!("Invalid phone number: {}", phone)))?;
         let new_user = serde_json::json!({
-            "phone_number": format!("whatsapp:{}",phone),
+            "phone_number": stored_whatsapp_number(&phone),
             "status": "active",
             "platform": "whatsapp",
             "approved_at": chrono::Utc::now()
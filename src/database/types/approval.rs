@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+// A high value quotation / proforma held back until an admin decides on it. Its documents wait
+// in held/, out of the publicly served artifacts/, until it is approved.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuotationApproval {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    // Where the request came in and who to send the documents to there - a Telegram chat id or
+    // a "whatsapp:+91..." number
+    pub platform: String,
+    pub recipient: String,
+    pub document_reference: String,
+    pub grand_total: f32,
+    // Reply sent along with the documents once approved
+    pub text: String,
+    pub files: Vec<String>,
+    pub status: ApprovalStatus,
+    #[serde(default)]
+    pub decided_by: Option<String>,
+}
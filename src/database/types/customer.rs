@@ -0,0 +1,62 @@
+use super::normalize_phone;
+use serde::{Deserialize, Serialize};
+
+const TELEGRAM_KEY_PREFIX: &str = "telegram:";
+
+// Standing terms for a regular customer, filled into their quotations / proformas wherever the
+// request leaves them out
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CustomerProfile {
+    #[serde(default)]
+    pub customer_key: String,
+    // "To:" lines printed when a request names the customer on its own
+    #[serde(default)]
+    pub address: Option<Vec<String>>,
+    #[serde(default)]
+    pub terms_and_conditions: Option<Vec<String>>,
+    // eg. 0.05 means 5% - for items that don't give their own discount
+    #[serde(default)]
+    pub discount: Option<f32>,
+    #[serde(default)]
+    pub delivery_charges: Option<f32>,
+    #[serde(default)]
+    pub estimated_delivery: Option<String>,
+}
+
+// Profiles are keyed by a customer name, a phone number or a "telegram:<id>". Names match
+// regardless of case and spacing, and phone numbers in any format.
+pub fn customer_key(raw: &str) -> String {
+    let raw = raw.trim();
+    if let Some(telegram_id) = raw.strip_prefix(TELEGRAM_KEY_PREFIX) {
+        return telegram_customer_key(telegram_id.trim());
+    }
+    if raw.starts_with('+') || raw.to_lowercase().starts_with("whatsapp:") {
+        if let Some(phone) = normalize_phone(raw) {
+            return phone;
+        }
+    }
+    raw.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+pub fn telegram_customer_key(telegram_id: &str) -> String {
+    format!("{}{}", TELEGRAM_KEY_PREFIX, telegram_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_customer_key_forms() {
+        assert_eq!(customer_key("  BTL  EPC Ltd. "), "btl epc ltd.");
+        assert_eq!(customer_key("+91 98765-43210"), "+919876543210");
+        assert_eq!(customer_key("whatsapp:+919876543210"), "+919876543210");
+        assert_eq!(customer_key("telegram: 12345"), "telegram:12345");
+        assert_eq!(telegram_customer_key("12345"), "telegram:12345");
+        // Bare digits could be a phone number or a telegram id, so they are left alone
+        assert_eq!(customer_key("12345"), "12345");
+    }
+}
@@ -0,0 +1,9 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetalPrice {
+    pub metal: String,
+    pub price: f64,
+    pub fetched_at: DateTime<Utc>,
+}
@@ -1,7 +1,15 @@
+mod approval;
 mod cost;
+mod customer;
+mod metal_price;
+mod phone;
 mod session;
 mod user;
 
+pub use approval::*;
 pub use cost::*;
+pub use customer::*;
+pub use metal_price::*;
+pub use phone::*;
 pub use session::*;
 pub use user::*;
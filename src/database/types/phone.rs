@@ -0,0 +1,122 @@
+// Numbers given without a country code are Indian mobiles
+const DEFAULT_COUNTRY_CODE: &str = "91";
+const WHATSAPP_PREFIX: &str = "whatsapp:";
+
+// Brings a phone number to E.164 (+919876543210), whether it comes from the Twilio webhook
+// ("whatsapp:+91..."), an admin command or config. Spaces, dashes, dots and brackets are
+// ignored; "00" international and "0" trunk prefixes are dropped and bare 10 digit numbers get
+// the default country code. Returns None for anything that isn't a plausible number.
+pub fn normalize_phone(raw: &str) -> Option<String> {
+    let raw = raw.trim();
+    let number = match raw.get(..WHATSAPP_PREFIX.len()) {
+        Some(prefix) if prefix.eq_ignore_ascii_case(WHATSAPP_PREFIX) => {
+            raw[WHATSAPP_PREFIX.len()..].trim()
+        }
+        _ => raw,
+    };
+
+    let (international, body) = match number.strip_prefix('+') {
+        Some(body) => (true, body),
+        None => (false, number),
+    };
+    if !body
+        .chars()
+        .all(|c| c.is_ascii_digit() || matches!(c, ' ' | '-' | '.' | '(' | ')'))
+    {
+        return None;
+    }
+    let digits: String = body.chars().filter(|c| c.is_ascii_digit()).collect();
+
+    let digits = if international {
+        digits
+    } else if let Some(rest) = digits.strip_prefix("00") {
+        rest.to_string()
+    } else if digits.len() == 11 && digits.starts_with('0') {
+        format!("{}{}", DEFAULT_COUNTRY_CODE, &digits[1..])
+    } else if digits.len() == 10 {
+        format!("{}{}", DEFAULT_COUNTRY_CODE, digits)
+    } else {
+        digits
+    };
+
+    // E.164 allows at most 15 digits and country codes never start with 0
+    if !(8..=15).contains(&digits.len()) || digits.starts_with('0') {
+        return None;
+    }
+    Some(format!("+{}", digits))
+}
+
+// How a WhatsApp user's number is kept in users.phone_number
+pub fn stored_whatsapp_number(e164: &str) -> String {
+    format!("{}{}", WHATSAPP_PREFIX, e164)
+}
+
+// Every form a number may have been stored in before normalization, so older rows still match
+pub fn stored_whatsapp_number_forms(e164: &str) -> Vec<String> {
+    let digits = e164.trim_start_matches('+');
+    vec![
+        stored_whatsapp_number(e164),
+        format!("{}{}", WHATSAPP_PREFIX, digits),
+        e164.to_string(),
+        digits.to_string(),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_phone_variants() {
+        let expected = Some("+919876543210".to_string());
+        for raw in [
+            "+919876543210",
+            "919876543210",
+            "whatsapp:+919876543210",
+            "WhatsApp:919876543210",
+            "9876543210",
+            "09876543210",
+            "00919876543210",
+            " +91 98765-43210 ",
+            "+91 (98765) 43210",
+            "98765.43210",
+        ] {
+            assert_eq!(normalize_phone(raw), expected, "{}", raw);
+        }
+        assert_eq!(
+            normalize_phone("whatsapp:+14155238886"),
+            Some("+14155238886".to_string())
+        );
+    }
+
+    #[test]
+    fn test_normalize_phone_rejects_invalid() {
+        for raw in [
+            "",
+            "whatsapp:",
+            "12345",
+            "+1234567890123456",
+            "98765abc10",
+            "+0919876543210",
+        ] {
+            assert_eq!(normalize_phone(raw), None, "{}", raw);
+        }
+    }
+
+    #[test]
+    fn test_stored_whatsapp_number_forms() {
+        assert_eq!(
+            stored_whatsapp_number("+919876543210"),
+            "whatsapp:+919876543210"
+        );
+        assert_eq!(
+            stored_whatsapp_number_forms("+919876543210"),
+            vec![
+                "whatsapp:+919876543210",
+                "whatsapp:919876543210",
+                "+919876543210",
+                "919876543210"
+            ]
+        );
+    }
+}
@@ -1,5 +1,7 @@
+use super::normalize_phone;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use uuid::Uuid;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -24,6 +26,21 @@ pub struct SessionResult {
     pub query_metadata: Option<serde_json::Value>,
 }
 
+// Query metadata key holding the number printed on a generated quotation / proforma. It is also
+// written to its own query_sessions column so support can look a session up by it.
+pub const DOCUMENT_REFERENCE_KEY: &str = "document_reference";
+
+// The session a quotation / proforma was generated in, found by the number printed on it
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ReferencedSession {
+    pub id: Uuid,
+    // "Q-20250821-1234", or "Q-..., PI-..." when both documents came from one request
+    pub document_reference: String,
+    #[serde(default)]
+    pub metadata: Option<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
+}
+
 // Holds session related information
 #[derive(Debug, Clone)]
 pub struct SessionContext {
@@ -50,7 +67,7 @@ impl SessionContext {
     }
 
     pub fn with_phone(mut self, phone: String) -> Self {
-        self.user_phone = Some(phone);
+        self.user_phone = Some(normalize_phone(&phone).unwrap_or(phone));
         self
     }
 
@@ -85,3 +102,31 @@ pub struct ConversationMessage {
     pub user_query: String,
     pub structured_response: Option<StructuredResponse>,
 }
+
+// Row counts removed by a conversation retention purge
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PurgeReport {
+    pub messages_deleted: usize,
+    pub conversations_deleted: usize,
+    pub sessions_unlinked: usize,
+}
+
+// Number of sessions each LLM produced the final parse for
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ModelUsage {
+    pub sessions_by_model: BTreeMap<String, usize>,
+}
+
+impl ModelUsage {
+    pub fn total(&self) -> usize {
+        self.sessions_by_model.values().sum()
+    }
+
+    pub fn share(&self, model: &str) -> f64 {
+        let total = self.total();
+        if total == 0 {
+            return 0.0;
+        }
+        self.sessions_by_model.get(model).copied().unwrap_or(0) as f64 / total as f64
+    }
+}
@@ -11,3 +11,14 @@ pub struct User {
     pub platform: String,
     pub created_at: DateTime<Utc>,
 }
+
+// Everything held about a single user - returned for data export requests
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserDataExport {
+    pub user: User,
+    pub sessions: Vec<serde_json::Value>,
+    pub conversations: Vec<serde_json::Value>,
+    pub conversation_messages: Vec<serde_json::Value>,
+    pub cost_events: Vec<serde_json::Value>,
+    pub exported_at: DateTime<Utc>,
+}
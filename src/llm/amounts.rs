@@ -0,0 +1,156 @@
+use crate::configuration::NumberLocale;
+use serde_json::{Number, Value};
+
+// Cleans up the money fields of a quotation / proforma tool input. The LLM sometimes passes
+// amounts through as text ("Rs. 1,234.56") or drops a separator it didn't recognise, turning
+// "1.234,56" into 1.23456 - both are corrected here before they reach the pricing math.
+pub(crate) fn normalize_amounts(input: &mut Value, locale: NumberLocale) {
+    if let Some(delivery_charges) = input.get_mut("delivery_charges") {
+        normalize_amount(delivery_charges, locale);
+    }
+    if let Some(packing_forwarding) = input.pointer_mut("/packing_forwarding/Flat") {
+        normalize_amount(packing_forwarding, locale);
+    }
+    if let Some(items) = input
+        .get_mut("items")
+        .and_then(|items| items.as_array_mut())
+    {
+        for item in items {
+            if let Some(user_base_price) = item.get_mut("user_base_price") {
+                normalize_amount(user_base_price, locale);
+            }
+        }
+    }
+}
+
+fn normalize_amount(value: &mut Value, locale: NumberLocale) {
+    let amount = match value {
+        Value::String(text) => parse_amount(text, locale),
+        Value::Number(number) if locale == NumberLocale::European => {
+            regroup_dropped_separator(&number.to_string())
+        }
+        _ => None,
+    };
+    if let Some(amount) = amount.and_then(Number::from_f64) {
+        *value = Value::Number(amount);
+    }
+}
+
+// Reads a user written amount eg. "1,23,456.50", "1.234,56" or "Rs. 1,500/-". The last
+// separator is the decimal point when both kinds appear, a repeated one is always grouping, and
+// a lone one followed by exactly three digits is read the locale's way.
+fn parse_amount(text: &str, locale: NumberLocale) -> Option<f64> {
+    let cleaned: String = text
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == '.' || *c == ',')
+        .collect();
+    // "Rs. 1,500/-" leaves the abbreviation's dot in front
+    let cleaned = cleaned.trim_matches(|c| c == '.' || c == ',');
+
+    let decimal_separator = match (cleaned.rfind('.'), cleaned.rfind(',')) {
+        (Some(dot), Some(comma)) => Some(if dot > comma { '.' } else { ',' }),
+        (Some(_), None) => lone_separator_as_decimal(cleaned, '.', locale),
+        (None, Some(_)) => lone_separator_as_decimal(cleaned, ',', locale),
+        (None, None) => None,
+    };
+
+    let normalized: String = cleaned
+        .chars()
+        .filter_map(|c| match c {
+            '.' | ',' if Some(c) == decimal_separator => Some('.'),
+            '.' | ',' => None,
+            digit => Some(digit),
+        })
+        .collect();
+    normalized.parse().ok()
+}
+
+fn lone_separator_as_decimal(text: &str, separator: char, locale: NumberLocale) -> Option<char> {
+    if text.matches(separator).count() > 1 {
+        return None;
+    }
+    let trailing_digits = text.len() - text.rfind(separator)? - 1;
+    if trailing_digits == 3 && separator == locale.grouping_separator() {
+        None
+    } else {
+        Some(separator)
+    }
+}
+
+// A money value never has more than two decimals, so extra ones are grouped thousands that
+// were read as a decimal point: 1.234 -> 1234, 1.23456 -> 1234.56
+fn regroup_dropped_separator(number: &str) -> Option<f64> {
+    let (whole, fraction) = number.split_once('.')?;
+    if whole.is_empty() || whole.starts_with('0') || whole.starts_with('-') {
+        return None;
+    }
+    let (grouped, decimals) = match fraction.len() {
+        n if n > 2 && n % 3 == 0 => (fraction, ""),
+        n if n > 2 && (n - 2) % 3 == 0 => fraction.split_at(n - 2),
+        _ => return None,
+    };
+    format!("{}{}.{}", whole, grouped, decimals).parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_amount_formats() {
+        let indian = NumberLocale::Indian;
+        let european = NumberLocale::European;
+
+        assert_eq!(parse_amount("1,234.56", indian), Some(1234.56));
+        assert_eq!(parse_amount("1,23,456.78", indian), Some(123456.78));
+        assert_eq!(parse_amount("1.234,56", indian), Some(1234.56));
+        assert_eq!(parse_amount("Rs. 1,500/-", indian), Some(1500.0));
+        assert_eq!(parse_amount("₹ 250.5", indian), Some(250.5));
+        assert_eq!(parse_amount("1.234.567", european), Some(1234567.0));
+        assert_eq!(parse_amount("12,5", european), Some(12.5));
+        assert_eq!(parse_amount("abc", indian), None);
+    }
+
+    #[test]
+    fn test_lone_separator_with_three_digits_follows_locale() {
+        assert_eq!(parse_amount("1,234", NumberLocale::Indian), Some(1234.0));
+        assert_eq!(parse_amount("1.234", NumberLocale::Indian), Some(1.234));
+        assert_eq!(parse_amount("1.234", NumberLocale::European), Some(1234.0));
+        assert_eq!(parse_amount("1,234", NumberLocale::European), Some(1.234));
+    }
+
+    #[test]
+    fn test_regroup_dropped_separator() {
+        assert_eq!(regroup_dropped_separator("1.23456"), Some(1234.56));
+        assert_eq!(regroup_dropped_separator("1.234"), Some(1234.0));
+        assert_eq!(regroup_dropped_separator("1.23456789"), Some(1234567.89));
+        assert_eq!(regroup_dropped_separator("250.5"), None);
+        assert_eq!(regroup_dropped_separator("0.125"), None);
+        assert_eq!(regroup_dropped_separator("1500"), None);
+    }
+
+    #[test]
+    fn test_normalize_amounts_in_tool_input() {
+        let mut input = json!({
+            "items": [
+                {"user_base_price": "1.234,56"},
+                {"user_base_price": 1.23456},
+                {"user_base_price": null}
+            ],
+            "delivery_charges": "Rs. 1.500",
+            "packing_forwarding": {"Flat": "750,00"}
+        });
+        normalize_amounts(&mut input, NumberLocale::European);
+        assert_eq!(input["items"][0]["user_base_price"], json!(1234.56));
+        assert_eq!(input["items"][1]["user_base_price"], json!(1234.56));
+        assert_eq!(input["items"][2]["user_base_price"], Value::Null);
+        assert_eq!(input["delivery_charges"], json!(1500.0));
+        assert_eq!(input["packing_forwarding"]["Flat"], json!(750.0));
+
+        // Numbers are only regrouped where '.' groups thousands
+        let mut input = json!({"items": [{"user_base_price": 1.234}], "delivery_charges": 0.0});
+        normalize_amounts(&mut input, NumberLocale::Indian);
+        assert_eq!(input["items"][0]["user_base_price"], json!(1.234));
+    }
+}
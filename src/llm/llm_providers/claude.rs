@@ -1,18 +1,45 @@
-use crate::core::http::RetryableClient;
+use crate::configuration::{
+    default_claude_max_tokens, default_claude_model, default_claude_overload_retries, ClaudeConfig,
+};
+use crate::core::http::{
+    CircuitBreakerConfig, RetryConfig, RetryError, RetryPolicy, RetryableClient,
+};
 use crate::database::DatabaseService;
 use crate::database::SessionContext;
+use crate::llm::build_tool_definitions;
 use crate::llm::LLMOrchestrator;
 use crate::llm::LLMProvider;
 use crate::llm::{LLMError, Query};
 use async_trait::async_trait;
+use rand::Rng;
 use serde_json::json;
 use std::sync::Arc;
 use std::time::Duration;
-use tracing::{error, info};
+use tracing::{error, info, warn};
+
+// Anthropic's overloaded status - retried with a longer backoff here, not by the http client
+const OVERLOADED_STATUS: u16 = 529;
+const CLAUDE_RETRY_POLICY: RetryPolicy =
+    RetryPolicy::new().with_passthrough_status(OVERLOADED_STATUS);
+// When the API is down, queries go straight to the Groq fallback instead of waiting out
+// every retry and timeout first
+const CLAUDE_CIRCUIT_BREAKER: CircuitBreakerConfig = CircuitBreakerConfig {
+    failure_threshold: 3,
+    cooldown: Duration::from_secs(60),
+};
 
 pub struct Claude {
     system_prompt: String,
     api_key: String,
+    api_url: String,
+    model: String,
+    max_tokens: u32,
+    overload_retries: u32,
+    // First wait after an overloaded response, doubled on each retry
+    overload_backoff: Duration,
+    // The tool definitions with the prompt cache breakpoint on the last one, sent with every
+    // request
+    tools: serde_json::Value,
     client: RetryableClient,
     pub database: Arc<DatabaseService>,
 }
@@ -35,7 +62,7 @@ impl LLMProvider for Claude {
                 query.to_string()
             };
 
-            match self.make_api_request(&query_text, context, llm_orchestrator).await {
+            match self.make_api_request(&query_text, context).await {
                 Ok(response) => match llm_orchestrator
                     .parse_response_with_multistep(&response, query, context)
                     .await
@@ -63,50 +90,110 @@ impl LLMProvider for Claude {
 
 impl Claude {
     pub fn new(system_prompt: &str, api_key: &str, database: Arc<DatabaseService>) -> Self {
-        let client = RetryableClient::new();
+        let client = RetryableClient::with_config(RetryConfig {
+            circuit_breaker: Some(CLAUDE_CIRCUIT_BREAKER),
+            ..RetryConfig::default()
+        });
         Self {
             system_prompt: system_prompt.to_string(),
             api_key: api_key.to_string(),
+            api_url: "https://api.anthropic.com/v1/messages".to_string(),
+            model: default_claude_model(),
+            max_tokens: default_claude_max_tokens(),
+            overload_retries: default_claude_overload_retries(),
+            overload_backoff: Duration::from_secs(1),
+            tools: with_cache_breakpoint(build_tool_definitions()),
             database,
             client,
         }
     }
 
+    pub fn with_config(mut self, config: &ClaudeConfig) -> Self {
+        self.model = config.model.clone();
+        self.max_tokens = config.max_tokens;
+        self.overload_retries = config.overload_retries;
+        self
+    }
+
+    pub fn system_prompt(&self) -> &str {
+        &self.system_prompt
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn request_body(&self, query: &str) -> serde_json::Value {
+        json!({
+            "model": self.model,
+            "temperature": 0.0,
+            "system": [
+                {
+                    "type" : "text",
+                    "text" : self.system_prompt.as_str(),
+                    "cache_control": cache_control(),
+                }
+            ],
+            "max_tokens": self.max_tokens,
+            "tool_choice": {"type": "any"},
+            "tools": &self.tools,
+            "messages": [{
+                "role": "user",
+                "content": query
+            }]
+        })
+    }
+
+    // An overloaded Claude usually recovers within seconds, and Groq handles complex quotations
+    // worse - so overloads are waited out a few times before the caller falls back
     async fn make_api_request(
         &self,
         query: &str,
         context: &SessionContext,
-        llm_orchestrator: &LLMOrchestrator,
+    ) -> Result<serde_json::Value, LLMError> {
+        let mut attempt = 0;
+        loop {
+            match self.send_request(query, context).await {
+                Err(LLMError::OverloadedError) if attempt < self.overload_retries => {
+                    let delay = overload_backoff(self.overload_backoff, attempt);
+                    attempt += 1;
+                    warn!(
+                        attempt,
+                        max_retries = self.overload_retries,
+                        delay_ms = delay.as_millis() as u64,
+                        "Claude is overloaded, retrying"
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn send_request(
+        &self,
+        query: &str,
+        context: &SessionContext,
     ) -> Result<serde_json::Value, LLMError> {
         info!("About to make HTTP request to Claude API");
         let response = self
             .client
-            .execute_with_retry(
+            .execute_with_policy(
                 self.client
-                    .post("https://api.anthropic.com/v1/messages")
+                    .post(&self.api_url)
                     .timeout(Duration::from_secs(45))
                     .header("x-api-key", &self.api_key)
                     .header("anthropic-version", "2023-06-01")
-                    .json(&json!({
-                        "model": "claude-sonnet-4-20250514",
-                        "temperature": 0.0,
-                        "system": [
-                            {
-                                "type" : "text",
-                                "text" : self.system_prompt.as_str(),
-                            }
-                        ],
-                        "max_tokens": 10240,
-                        "tool_choice": {"type": "any"},
-                        "tools": llm_orchestrator.get_tool_definitions(),
-                        "messages": [{
-                            "role": "user",
-                            "content": query
-                        }]
-                    })),
+                    .json(&self.request_body(query)),
+                CLAUDE_RETRY_POLICY,
             )
             .await
-            .map_err(|e| LLMError::ClientError(e.to_string()))?;
+            .map_err(|e| {
+                if let RetryError::CircuitOpen { .. } = e {
+                    warn!(error = %e, "Claude API circuit open, skipping request");
+                }
+                LLMError::ClientError(e.to_string())
+            })?;
 
         info!("Received HTTP response, parsing JSON...");
         let json_response: serde_json::Value = response
@@ -168,6 +255,13 @@ impl Claude {
             .and_then(|t| t.as_i64())
             .unwrap_or(0) as i32;
 
+        if cache_read_tokens > 0 || cache_write_tokens > 0 {
+            info!(
+                "Claude prompt cache: {} tokens read, {} tokens written",
+                cache_read_tokens, cache_write_tokens
+            );
+        }
+
         // Get rates from database
 
         let _ = self
@@ -178,10 +272,130 @@ impl Claude {
                 cache_read_tokens,
                 cache_write_tokens,
                 output_tokens,
-                "claude-sonnet-4-20250514",
+                &self.model,
             )
             .await;
 
         Ok(json_response)
     }
 }
+
+// Doubles from `base` on each attempt, plus up to a quarter of `base` at random so that queries
+// caught in the same overload don't all come back at once
+fn overload_backoff(base: Duration, attempt: u32) -> Duration {
+    let jitter_ms = rand::rng().random_range(0..=base.as_millis() as u64 / 4);
+    base.saturating_mul(2_u32.saturating_pow(attempt)) + Duration::from_millis(jitter_ms)
+}
+
+// The tools are sent ahead of the system prompt, so the system breakpoint caches both. The
+// breakpoint on the last tool keeps the (larger) tool block cached even if the prompt changes.
+fn cache_control() -> serde_json::Value {
+    json!({"type": "ephemeral", "ttl": "1h"})
+}
+
+fn with_cache_breakpoint(mut tools: serde_json::Value) -> serde_json::Value {
+    if let Some(last_tool) = tools
+        .as_array_mut()
+        .and_then(|tools| tools.last_mut())
+        .and_then(|tool| tool.as_object_mut())
+    {
+        last_tool.insert("cache_control".to_string(), cache_control());
+    }
+    tools
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_breakpoint_marks_only_the_last_tool() {
+        let tools = with_cache_breakpoint(json!([{"name": "a"}, {"name": "b"}]));
+        assert!(tools[0].get("cache_control").is_none());
+        assert_eq!(tools[1]["cache_control"], cache_control());
+        assert_eq!(with_cache_breakpoint(json!([])), json!([]));
+    }
+
+    #[test]
+    fn test_request_uses_configured_model() {
+        let database = Arc::new(DatabaseService::with_url("http://localhost"));
+        let claude = Claude::new("prompt", "key", database.clone());
+        let body = claude.request_body("quote");
+        assert_eq!(body["model"], "claude-sonnet-4-20250514");
+        assert_eq!(body["max_tokens"], 10240);
+
+        let claude = Claude::new("prompt", "key", database).with_config(&ClaudeConfig {
+            system_prompt: "prompt.txt".to_string(),
+            model: "claude-sonnet-4-5".to_string(),
+            max_tokens: 4096,
+            overload_retries: 3,
+        });
+        let body = claude.request_body("quote");
+        assert_eq!(body["model"], "claude-sonnet-4-5");
+        assert_eq!(body["max_tokens"], 4096);
+        assert_eq!(body["messages"][0]["content"], "quote");
+        let tools = body["tools"].as_array().unwrap();
+        assert_eq!(
+            tools.len(),
+            build_tool_definitions().as_array().unwrap().len()
+        );
+        assert_eq!(tools.last().unwrap()["cache_control"], cache_control());
+    }
+
+    #[test]
+    fn test_overload_backoff_doubles_with_jitter() {
+        let base = Duration::from_secs(1);
+        for (attempt, expected) in [(0, 1000), (1, 2000), (2, 4000)] {
+            let delay = overload_backoff(base, attempt).as_millis();
+            assert!((expected..=expected + 250).contains(&delay), "{}", delay);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_overloaded_claude_is_retried() {
+        let mut server = mockito::Server::new_async().await;
+        let overloaded = server
+            .mock("POST", "/v1/messages")
+            .with_status(529)
+            .with_body(r#"{"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}}"#)
+            .expect(2)
+            .create_async()
+            .await;
+        let answered = server
+            .mock("POST", "/v1/messages")
+            .with_status(200)
+            .with_body(
+                r#"{"content": [{"type": "tool_use", "name": "get_metal_prices", "input": {}}]}"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+
+        let database = Arc::new(DatabaseService::with_url(&server.url()));
+        let context = SessionContext::new(uuid::Uuid::new_v4(), "telegram");
+        let mut claude = Claude::new("prompt", "key", database);
+        claude.api_url = format!("{}/v1/messages", server.url());
+        claude.overload_backoff = Duration::from_millis(10);
+
+        let response = claude
+            .make_api_request("copper price", &context)
+            .await
+            .unwrap();
+        assert_eq!(response["content"][0]["name"], "get_metal_prices");
+        overloaded.assert_async().await;
+        answered.assert_async().await;
+
+        // Once the retries run out the overload goes back to the caller, to fall back on Groq
+        let overloaded = server
+            .mock("POST", "/v1/messages")
+            .with_status(529)
+            .with_body(r#"{"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}}"#)
+            .expect(2)
+            .create_async()
+            .await;
+        claude.overload_retries = 1;
+        let result = claude.make_api_request("copper price", &context).await;
+        assert!(matches!(result, Err(LLMError::OverloadedError)));
+        overloaded.assert_async().await;
+    }
+}
@@ -1,4 +1,5 @@
-use crate::core::http::RetryableClient;
+use crate::configuration::GroqConfig;
+use crate::core::http::{RetryPolicy, RetryableClient};
 use crate::database::CostEventBuilder;
 use crate::database::DatabaseService;
 use crate::database::SessionContext;
@@ -8,11 +9,33 @@ use crate::llm::{LLMError, Query};
 use async_trait::async_trait;
 use serde_json::json;
 use std::sync::Arc;
-use tracing::{error, info};
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+// Query variants the Groq system prompt describes, with the tool each corresponds to
+const QUERY_VARIANT_TOOLS: [(&str, &str); 10] = [
+    ("MetalPricing", "get_metal_prices"),
+    ("GetStock", "get_stock_info"),
+    ("GetQuotation", "generate_quotation"),
+    ("GetProformaInvoice", "generate_proforma"),
+    ("GetQuotationAndProforma", "generate_quotation_and_proforma"),
+    ("GetPricesOnly", "get_prices_only"),
+    ("DescribeProduct", "describe_product"),
+    ("GetPriceList", "find_price_list"),
+    ("ListAvailablePricelists", "list_available_pricelists"),
+    ("GetOrderStatus", "get_order_status"),
+];
+
+// The continuation decision is a ten token answer on the hot path of every follow-up query
+const DECISION_RETRY_POLICY: RetryPolicy = RetryPolicy::new()
+    .with_timeout(Duration::from_secs(10))
+    .with_max_attempts(2);
 
 pub struct Groq {
     system_prompt: String,
     api_key: String,
+    model: String,
+    max_tokens: u32,
     client: RetryableClient,
     database: Arc<DatabaseService>,
 }
@@ -64,14 +87,31 @@ impl LLMProvider for Groq {
 impl Groq {
     pub fn new(system_prompt: &str, api_key: &str, database: Arc<DatabaseService>) -> Self {
         let client = RetryableClient::new();
+        let config = GroqConfig::default();
         Self {
             system_prompt: system_prompt.to_string(),
             api_key: api_key.to_string(),
+            model: config.model,
+            max_tokens: config.max_tokens,
             database,
             client,
         }
     }
 
+    pub fn with_config(mut self, config: &GroqConfig) -> Self {
+        self.model = config.model.clone();
+        self.max_tokens = config.max_tokens;
+        self
+    }
+
+    pub fn system_prompt(&self) -> &str {
+        &self.system_prompt
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
     // Decision call with custom system prompt for conversation continuation
     pub async fn make_decision_call(
         &self,
@@ -83,13 +123,13 @@ impl Groq {
 
         let response = self
             .client
-            .execute_with_retry(
+            .execute_with_policy(
                 self.client
                     .post("https://api.groq.com/openai/v1/chat/completions")
                     .header("Authorization", format!("Bearer {}", self.api_key))
                     .header("Content-Type", "application/json")
                     .json(&serde_json::json!({
-                        "model": "moonshotai/kimi-k2-instruct-0905",
+                        "model": self.model,
                         "messages": [
                             {
                                 "role": "system",
@@ -103,6 +143,7 @@ impl Groq {
                         "temperature": 0.0,
                         "max_completion_tokens": 10
                     })),
+                DECISION_RETRY_POLICY,
             )
             .await
             .map_err(|e| LLMError::GroqError(e.to_string()))?;
@@ -132,7 +173,7 @@ impl Groq {
         let total_cost = input_cost + output_cost;
 
         let metadata = serde_json::json!({
-            "model": "moonshotai/kimi-k2-instruct-0905",
+            "model": self.model,
             "prompt_tokens": prompt_tokens,
             "completion_tokens": completion_tokens,
             "input_cost": input_cost,
@@ -169,25 +210,6 @@ impl Groq {
         ))
     }
 
-    fn get_groq_tool_definitions(&self, llm_orchestrator: &LLMOrchestrator) -> serde_json::Value {
-        let claude_tools = llm_orchestrator.get_tool_definitions();
-        let mut groq_tools = Vec::new();
-
-        for tool in claude_tools.as_array().unwrap() {
-            let groq_tool = json!({
-                "type": "function",
-                "function": {
-                    "name": tool["name"],
-                    "description": tool["description"],
-                    "parameters": tool["input_schema"]
-                }
-            });
-            groq_tools.push(groq_tool);
-        }
-
-        json!(groq_tools)
-    }
-
     async fn make_api_request(
         &self,
         query: &str,
@@ -196,7 +218,7 @@ impl Groq {
     ) -> Result<serde_json::Value, LLMError> {
         info!("Attempting Groq API call");
 
-        let tools = self.get_groq_tool_definitions(llm_orchestrator);
+        let tools = llm_orchestrator.get_groq_tool_definitions();
         //info!(tools = ?tools, "Groq tool definitions");
 
         let response = self
@@ -207,7 +229,7 @@ impl Groq {
                     .header("Authorization", format!("Bearer {}", self.api_key))
                     .header("Content-Type", "application/json")
                     .json(&json!({
-                        "model": "moonshotai/kimi-k2-instruct-0905",
+                        "model": self.model,
                         "messages": [
                             {
                                 "role": "system",
@@ -221,7 +243,7 @@ impl Groq {
                         "tools": tools,
                         "tool_choice": "required",
                         "temperature": 0.0,
-                        "max_completion_tokens": 8192
+                        "max_completion_tokens": self.max_tokens
                     })),
             )
             .await
@@ -267,7 +289,7 @@ impl Groq {
         let total_cost = input_cost + output_cost;
 
         let metadata = serde_json::json!({
-            "model": "moonshotai/kimi-k2-instruct-0905",
+            "model": self.model,
             "prompt_tokens": prompt_tokens,
             "completion_tokens": completion_tokens,
             "input_cost": input_cost,
@@ -292,22 +314,26 @@ impl Groq {
                     // Check for tool calls first
                     if let Some(tool_calls) = message.get("tool_calls").and_then(|tc| tc.as_array())
                     {
-                        if let Some(tool_call) = tool_calls.first() {
-                            // Return tool call in Claude-compatible format
-                            return Ok(json!({
-                                "content": [{
-                                    "type": "tool_use",
-                                    "name": tool_call["function"]["name"],
-                                    "input": serde_json::from_str::<serde_json::Value>(
-                                        tool_call["function"]["arguments"].as_str().unwrap_or("{}")
-                                    ).unwrap_or(json!({}))
-                                }]
-                            }));
+                        if !tool_calls.is_empty() {
+                            // Return every tool call in Claude-compatible format
+                            let content: Vec<serde_json::Value> = tool_calls
+                                .iter()
+                                .map(|tool_call| {
+                                    json!({
+                                        "type": "tool_use",
+                                        "name": tool_call["function"]["name"],
+                                        "input": serde_json::from_str::<serde_json::Value>(
+                                            tool_call["function"]["arguments"].as_str().unwrap_or("{}")
+                                        ).unwrap_or(json!({}))
+                                    })
+                                })
+                                .collect();
+                            return Ok(json!({ "content": content }));
                         }
                     }
                     // Fallback to text content if no tool calls
                     if let Some(content) = message.get("content").and_then(|c| c.as_str()) {
-                        return Ok(json!({ "content": [{ "text": content }] }));
+                        return Ok(text_response(content, &tool_names(tools)));
                     }
                 }
             }
@@ -318,3 +344,162 @@ impl Groq {
         ))
     }
 }
+
+// The orchestrator's tool definitions in the OpenAI function format Groq takes
+pub(crate) fn groq_tool_definitions(claude_tools: &serde_json::Value) -> serde_json::Value {
+    let mut groq_tools = Vec::new();
+
+    for tool in claude_tools.as_array().unwrap() {
+        let groq_tool = json!({
+            "type": "function",
+            "function": {
+                "name": tool["name"],
+                "description": tool["description"],
+                "parameters": tool["input_schema"]
+            }
+        });
+        groq_tools.push(groq_tool);
+    }
+
+    json!(groq_tools)
+}
+
+fn tool_names(groq_tools: &serde_json::Value) -> Vec<&str> {
+    groq_tools
+        .as_array()
+        .map(|tools| {
+            tools
+                .iter()
+                .filter_map(|tool| tool["function"]["name"].as_str())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// A reply without tool_calls, in Claude's format. The tool call is recovered when the model wrote
+// it into the text instead; otherwise the text is kept (and ends up as an unsupported query).
+fn text_response(content: &str, tool_names: &[&str]) -> serde_json::Value {
+    match recover_tool_call(content, tool_names) {
+        Some(tool_use) => {
+            warn!(tool = %tool_use["name"], "Recovered a tool call from Groq's text reply");
+            json!({ "content": [tool_use] })
+        }
+        None => {
+            warn!(text = %content, "Groq replied with text instead of a tool call");
+            json!({ "content": [{ "text": content }] })
+        }
+    }
+}
+
+// Finds a JSON tool call in reply text, possibly inside a code block, written as
+// {"name": <tool>, "arguments": {...}}, {<tool>: {...}} or the Query JSON of the system prompt
+fn recover_tool_call(content: &str, tool_names: &[&str]) -> Option<serde_json::Value> {
+    let start = content.find('{')?;
+    let end = content.rfind('}')?;
+    let value: serde_json::Value = serde_json::from_str(content.get(start..=end)?).ok()?;
+    let object = value.as_object()?;
+
+    let (name, input) = match object.get("name").and_then(|name| name.as_str()) {
+        Some(name) => {
+            let input = ["arguments", "parameters", "input"]
+                .iter()
+                .find_map(|key| object.get(*key))
+                .cloned()
+                .unwrap_or(json!({}));
+            (name, input)
+        }
+        None if object.len() == 1 => {
+            let (key, input) = object.iter().next()?;
+            let name = QUERY_VARIANT_TOOLS
+                .iter()
+                .find(|(variant, _)| *variant == key.as_str())
+                .map_or(key.as_str(), |(_, tool)| *tool);
+            (name, input.clone())
+        }
+        None => return None,
+    };
+    if !tool_names.contains(&name) {
+        return None;
+    }
+
+    // OpenAI style arguments are a JSON string
+    let input = match input {
+        serde_json::Value::String(arguments) => serde_json::from_str(&arguments).ok()?,
+        serde_json::Value::Object(_) => input,
+        _ => json!({}),
+    };
+    Some(json!({ "type": "tool_use", "name": name, "input": input }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::SharedRuntimeConfig;
+    use crate::llm::llm_providers::claude::Claude;
+    use crate::llm::llm_providers::LLM;
+
+    const TOOLS: [&str; 3] = ["get_metal_prices", "generate_quotation", "get_stock_info"];
+
+    #[test]
+    fn test_recover_tool_call_forms() {
+        let stock = Some(json!({
+            "type": "tool_use",
+            "name": "get_stock_info",
+            "input": {"query": "4 C x 2.5 2XWYL"}
+        }));
+        for content in [
+            r#"{"name": "get_stock_info", "arguments": {"query": "4 C x 2.5 2XWYL"}}"#,
+            r#"{"name": "get_stock_info", "arguments": "{\"query\": \"4 C x 2.5 2XWYL\"}"}"#,
+            r#"{"get_stock_info": {"query": "4 C x 2.5 2XWYL"}}"#,
+            "Checking stock:\n```json\n{\"GetStock\": {\"query\": \"4 C x 2.5 2XWYL\"}}\n```",
+        ] {
+            assert_eq!(recover_tool_call(content, &TOOLS), stock, "{}", content);
+        }
+
+        for content in [
+            "I can only help with electrical items.",
+            r#"{"name": "delete_everything", "arguments": {}}"#,
+            r#"{"GetProformaInvoice": {"items": []}}"#,
+            r#"{"query": "stock", "warehouse": "main"}"#,
+            "{ not json }",
+        ] {
+            assert_eq!(recover_tool_call(content, &TOOLS), None, "{}", content);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_text_reply_falls_back_to_recovered_tool_call() {
+        let database = Arc::new(DatabaseService::with_url("http://localhost"));
+        let orchestrator = LLMOrchestrator::with_providers(
+            LLM::Claude(Claude::new("claude prompt", "key", database.clone())),
+            LLM::Groq(Groq::new("groq prompt", "key", database.clone())),
+            database,
+            SharedRuntimeConfig::default(),
+        );
+        let tools = orchestrator.get_tool_definitions();
+        let tool_names: Vec<&str> = tools
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|tool| tool["name"].as_str())
+            .collect();
+        let context = SessionContext::new(uuid::Uuid::new_v4(), "telegram");
+
+        let response = text_response(
+            r#"{"GetQuotation": {"items": [], "delivery_charges": 0.0}}"#,
+            &tool_names,
+        );
+        let query = orchestrator
+            .parse_response_with_multistep(&response, "quote", &context)
+            .await
+            .unwrap();
+        assert!(matches!(query, Query::GetQuotation(_)));
+
+        let response = text_response("Sorry, I can't help with that.", &tool_names);
+        let query = orchestrator
+            .parse_response_with_multistep(&response, "tell me a joke", &context)
+            .await
+            .unwrap();
+        assert!(matches!(query, Query::UnsupportedQuery));
+    }
+}
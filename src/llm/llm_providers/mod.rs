@@ -11,6 +11,27 @@ use crate::llm::LLMProvider;
 pub enum LLM {
     Claude(Claude),
     Groq(Groq),
+    // Any other provider, e.g. one returning canned queries in tests
+    Custom(Box<dyn LLMProvider + Send + Sync>),
+}
+
+impl LLM {
+    // None for custom providers, which bring their own prompting
+    pub fn system_prompt(&self) -> Option<&str> {
+        match self {
+            LLM::Claude(claude) => Some(claude.system_prompt()),
+            LLM::Groq(groq) => Some(groq.system_prompt()),
+            LLM::Custom(_) => None,
+        }
+    }
+
+    pub fn model(&self) -> Option<&str> {
+        match self {
+            LLM::Claude(claude) => Some(claude.model()),
+            LLM::Groq(groq) => Some(groq.model()),
+            LLM::Custom(_) => None,
+        }
+    }
 }
 
 #[async_trait]
@@ -24,6 +45,7 @@ impl LLMProvider for LLM {
         match self {
             LLM::Claude(claude) => claude.try_parse(query, context, llm_orchestrator).await,
             LLM::Groq(groq) => groq.try_parse(query, context, llm_orchestrator).await,
+            LLM::Custom(provider) => provider.try_parse(query, context, llm_orchestrator).await,
         }
     }
 }
@@ -1,18 +1,21 @@
+use crate::configuration::{ClaudeConfig, GroqConfig, NumberLocale};
+use crate::core::Alert;
 use crate::database::{DatabaseService, SessionContext, StructuredResponse};
 use crate::prices::price_list::{AvailablePricelists, PriceListService};
-use crate::query::RuntimeConfig;
-use crate::quotation::{PriceOnlyRequest, QuotationRequest};
+use crate::query::SharedRuntimeConfig;
+use crate::quotation::{DescribeProductRequest, PriceOnlyRequest, QuotationRequest};
 use async_trait::async_trait;
 use schemars::schema_for;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::env;
 use std::fs;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 use thiserror::Error;
 use tokio::sync::mpsc::Sender;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
+mod amounts;
 pub mod llm_providers;
 use llm_providers::claude::Claude;
 use llm_providers::groq::Groq;
@@ -28,7 +31,10 @@ pub enum Query {
     },
     GetQuotation(QuotationRequest),
     GetProformaInvoice(QuotationRequest),
+    // Both documents from a single parse, so their line items are identical
+    GetQuotationAndProforma(QuotationRequest),
     GetPricesOnly(PriceOnlyRequest),
+    DescribeProduct(DescribeProductRequest),
     UnsupportedQuery,
     GetStock {
         query: String,
@@ -37,6 +43,17 @@ pub enum Query {
         #[serde(default)]
         brand: Option<String>,
     },
+    // Reference printed on an earlier document eg. "Q-20250821-1234"
+    GetOrderStatus {
+        reference: String,
+    },
+    // Several action tool calls in one response eg. "copper price and stock for 4C x 2.5"
+    Batch(Vec<Query>),
+    // The conversation has cost more than the configured limit - nothing was sent to an LLM
+    SessionLimitReached {
+        spent_usd: f64,
+        limit_usd: f64,
+    },
 }
 
 #[async_trait]
@@ -80,120 +97,260 @@ trait ToolExecutor {
     fn execute_tool(&self, tool_name: &str, input: &serde_json::Value) -> Option<ToolResult>;
 }
 
+// How long after a reply a query still counts as a follow-up when the classifier is off
+const FOLLOW_UP_WINDOW_MINUTES: i64 = 5;
+
+/// Outcome of asking the classifier whether a query follows up on the previous conversation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConversationDecision {
+    Continue,
+    NewConversation,
+    /// The classifier answered, but not with a recognisable yes/no
+    Uncertain,
+}
+
+impl ConversationDecision {
+    // Models don't always stick to a bare YES/NO - accept "YES, it's a follow-up", "**No.**" etc.
+    pub fn from_classifier_response(text: &str) -> Self {
+        let words: Vec<String> = text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .map(|w| w.to_uppercase())
+            .collect();
+
+        let Some(first) = words.first() else {
+            return ConversationDecision::Uncertain;
+        };
+
+        let is_hedge = |w: &str| {
+            matches!(
+                w,
+                "MAYBE" | "UNSURE" | "UNCERTAIN" | "UNCLEAR" | "POSSIBLY" | "PERHAPS" | "PROBABLY"
+            )
+        };
+        let hedged = words.iter().any(|w| is_hedge(w.as_str()))
+            || words.windows(2).any(|pair| pair[0] == "NOT" && pair[1] == "SURE");
+        if hedged {
+            return ConversationDecision::Uncertain;
+        }
+
+        let has_yes = words.iter().any(|w| w == "YES");
+        let has_no = words.iter().any(|w| w == "NO");
+        if has_yes && has_no {
+            return ConversationDecision::Uncertain;
+        }
+
+        let is_yes = |w: &str| matches!(w, "YES" | "Y" | "YEP" | "YEAH" | "TRUE" | "CONTINUE");
+        let is_no = |w: &str| matches!(w, "NO" | "N" | "NOPE" | "FALSE" | "NEW");
+
+        if is_yes(first.as_str()) {
+            return ConversationDecision::Continue;
+        }
+        if is_no(first.as_str()) {
+            return ConversationDecision::NewConversation;
+        }
+
+        // Answer buried in a sentence ("Answer: YES")
+        match (has_yes, has_no) {
+            (true, false) => ConversationDecision::Continue,
+            (false, true) => ConversationDecision::NewConversation,
+            _ => ConversationDecision::Uncertain,
+        }
+    }
+}
+
+// Every tool offered to the LLMs, built once per orchestrator - the request schemas alone are
+// sizeable, and the definitions go out with every API call
+fn build_tool_definitions() -> Value {
+    let quotation_schema = serde_json::to_value(schema_for!(QuotationRequest))
+        .expect("Error creating quotation schema");
+    let price_only_schema = serde_json::to_value(schema_for!(PriceOnlyRequest))
+        .expect("Error creating price only schema");
+    let describe_product_schema = serde_json::to_value(schema_for!(DescribeProductRequest))
+        .expect("Error creating describe product schema");
+    json!([
+        {
+            "name": "get_metal_prices",
+            "description": "Get current metal prices from MCX/online for copper and aluminum",
+            "input_schema": {
+                "type": "object",
+                "properties": {},
+                "required": []
+            }
+        },
+        {
+            "name": "get_stock_info",
+            "description": "Check stock availability for electrical items using Tally ERP",
+            "input_schema": {
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "Stock query string (e.g., '4 C x 2.5 2XWYL')"
+                    }
+                },
+                "required": ["query"]
+            }
+        },
+        {
+            "name": "generate_quotation",
+            "description": "Generate a PDF quotation for electrical items",
+            "input_schema": quotation_schema
+        },
+        {
+            "name": "generate_proforma",
+            "description": "Generate a PDF proforma invoice for electrical items",
+            "input_schema": quotation_schema
+        },
+        {
+            "name": "generate_quotation_and_proforma",
+            "description": "Generate both a PDF quotation and a PDF proforma invoice for the same electrical items, when the user asks for both",
+            "input_schema": quotation_schema
+        },
+        {
+            "name": "get_prices_only",
+            "description": "Get prices for electrical items without generating quotation PDF",
+            "input_schema": price_only_schema
+        },
+        {
+            "name": "describe_product",
+            "description": "Preview the exact item description that would appear on a quotation/proforma PDF for a product spec",
+            "input_schema": describe_product_schema
+        },
+        {
+            "name": "find_price_list",
+            "description": "Find and return PDF pricelists for specific brands and categories",
+            "input_schema": {
+                "type": "object",
+                "properties": {
+                    "brand": {
+                        "type": "string",
+                        "default": "kei",
+                        "description": "Brand name (kei or polycab)"
+                    },
+                    "keywords": {
+                        "type": "array",
+                        "items": {"type": "string"},
+                        "description": "Keywords to match pricelists (e.g., ['latest armoured', 'current cable'])"
+                    }
+                },
+                "required": ["keywords"]
+            }
+        },
+        {
+            "name": "list_available_pricelists",
+            "description": "List all available PDF pricelists with their keywords and metadata. Use this before find_price_list to see what's available.",
+            "input_schema": {
+                "type": "object",
+                "properties": {
+                    "brand": {
+                        "type": "string",
+                        "description": "Optional brand filter (kei, polycab). If not specified, shows all brands."
+                    }
+                },
+                "required": []
+            }
+        },
+        {
+            "name": "get_order_status",
+            "description": "Look up an earlier quotation or proforma invoice by the reference number printed on it",
+            "input_schema": {
+                "type": "object",
+                "properties": {
+                    "reference": {
+                        "type": "string",
+                        "description": "Document reference (e.g., 'Q-20250821-1234' or 'PI-20250821-1234')"
+                    }
+                },
+                "required": ["reference"]
+            }
+        }
+    ])
+}
+
 pub struct LLMOrchestrator {
     claude: LLM,
     groq: LLM,
-    runtime_config: Arc<Mutex<RuntimeConfig>>,
+    database: Arc<DatabaseService>,
+    runtime_config: SharedRuntimeConfig,
     pricelist_service: Option<Arc<PriceListService>>,
-    quotation_schema: Value,
-    price_only_schema: Value
+    number_locale: NumberLocale,
+    max_session_cost_usd: Option<f64>,
+    tool_definitions: Value,
+    groq_tool_definitions: Value,
 }
 
 impl LLMOrchestrator {
-    pub fn get_tool_definitions(&self) -> serde_json::Value {
+    pub fn get_tool_definitions(&self) -> &Value {
+        &self.tool_definitions
+    }
 
-        json!([
-            {
-                "name": "get_metal_prices",
-                "description": "Get current metal prices from MCX/online for copper and aluminum",
-                "input_schema": {
-                    "type": "object",
-                    "properties": {},
-                    "required": []
-                }
-            },
-            {
-                "name": "get_stock_info",
-                "description": "Check stock availability for electrical items using Tally ERP",
-                "input_schema": {
-                    "type": "object",
-                    "properties": {
-                        "query": {
-                            "type": "string",
-                            "description": "Stock query string (e.g., '4 C x 2.5 2XWYL')"
-                        }
-                    },
-                    "required": ["query"]
-                }
-            },
-            {
-                "name": "generate_quotation",
-                "description": "Generate a PDF quotation for electrical items",
-                "input_schema": self.quotation_schema
-            },
-            {
-                "name": "generate_proforma",
-                "description": "Generate a PDF proforma invoice for electrical items",
-                "input_schema": self.quotation_schema
-            },
-            {
-                "name": "get_prices_only",
-                "description": "Get prices for electrical items without generating quotation PDF",
-                "input_schema": self.price_only_schema
-            },
-            {
-                "name": "find_price_list",
-                "description": "Find and return PDF pricelists for specific brands and categories",
-                "input_schema": {
-                    "type": "object",
-                    "properties": {
-                        "brand": {
-                            "type": "string",
-                            "default": "kei",
-                            "description": "Brand name (kei or polycab)"
-                        },
-                        "keywords": {
-                            "type": "array",
-                            "items": {"type": "string"},
-                            "description": "Keywords to match pricelists (e.g., ['latest armoured', 'current cable'])"
-                        }
-                    },
-                    "required": ["keywords"]
-                }
-            },
-            {
-                "name": "list_available_pricelists",
-                "description": "List all available PDF pricelists with their keywords and metadata. Use this before find_price_list to see what's available.",
-                "input_schema": {
-                    "type": "object",
-                    "properties": {
-                        "brand": {
-                            "type": "string",
-                            "description": "Optional brand filter (kei, polycab). If not specified, shows all brands."
-                        }
-                    },
-                    "required": []
-                }
-            }
-        ])
+    pub fn get_groq_tool_definitions(&self) -> &Value {
+        &self.groq_tool_definitions
+    }
+
+    // What the assistant can do, one line per tool - shown to users whose query matched none
+    pub fn capabilities(&self) -> Vec<String> {
+        tool_capabilities(self.get_tool_definitions())
     }
 
     pub fn new(
-        system_prompt_file: &str,
+        claude_config: &ClaudeConfig,
+        groq_config: &GroqConfig,
         database: Arc<DatabaseService>,
-        runtime_config: Arc<Mutex<RuntimeConfig>>,
+        runtime_config: SharedRuntimeConfig,
     ) -> Result<Self, LLMError> {
-        let prompt = fs::read_to_string(system_prompt_file)
+        let prompt = fs::read_to_string(&claude_config.system_prompt)
             .map_err(|e| LLMError::SystemPromptError(e.to_string()))?;
 
         let api_key = env::var("ANTHROPIC_API_KEY").map_err(|_| LLMError::EnvError)?;
         let groq_api_key = env::var("GROQ_API_KEY").map_err(|_| LLMError::EnvError)?;
-        let claude = Claude::new(prompt.as_str(), api_key.as_str(), Arc::clone(&database));
+        let claude = Claude::new(prompt.as_str(), api_key.as_str(), Arc::clone(&database))
+            .with_config(claude_config);
         let groq = Groq::new(
             prompt.as_str(),
             groq_api_key.as_str(),
             Arc::clone(&database),
-        );
-        let quotation_schema = serde_json::to_value(schema_for!(QuotationRequest)).expect("Error creating quotation schema");
-        let price_only_schema = serde_json::to_value(schema_for!(PriceOnlyRequest)).expect("Error creating price only schema");
-        Ok(Self {
-            claude: LLM::Claude(claude),
-            groq: LLM::Groq(groq),
+        )
+        .with_config(groq_config);
+        Ok(Self::with_providers(
+            LLM::Claude(claude),
+            LLM::Groq(groq),
+            database,
+            runtime_config,
+        ))
+    }
+
+    // Lets the providers be swapped out, e.g. for canned ones in tests
+    pub fn with_providers(
+        claude: LLM,
+        groq: LLM,
+        database: Arc<DatabaseService>,
+        runtime_config: SharedRuntimeConfig,
+    ) -> Self {
+        let tool_definitions = build_tool_definitions();
+        let groq_tool_definitions = llm_providers::groq::groq_tool_definitions(&tool_definitions);
+        Self {
+            claude,
+            groq,
+            database,
             runtime_config,
             pricelist_service: None,
-            quotation_schema,
-            price_only_schema
-        })
+            number_locale: NumberLocale::default(),
+            max_session_cost_usd: None,
+            tool_definitions,
+            groq_tool_definitions,
+        }
+    }
+
+    pub fn with_number_locale(mut self, number_locale: NumberLocale) -> Self {
+        self.number_locale = number_locale;
+        self
+    }
+
+    pub fn with_session_cost_limit(mut self, max_session_cost_usd: Option<f64>) -> Self {
+        self.max_session_cost_usd = max_session_cost_usd;
+        self
     }
 
     pub fn set_pricelist_service(&mut self, pricelist_service: Arc<PriceListService>) {
@@ -234,7 +391,7 @@ impl LLMOrchestrator {
         &self,
         query: &str,
         context: &mut SessionContext,
-        error_sender: &Sender<String>,
+        error_sender: &Sender<Alert>,
     ) -> Result<Query, LLMError> {
         // Handle conversation context first - this is LLM's responsibility
         let conversation_context = match self.handle_conversation_context(query, context).await {
@@ -242,12 +399,18 @@ impl LLMOrchestrator {
             Err(e) => {
                 tracing::error!("Failed to handle conversation context: {}", e);
                 let _ = error_sender
-                    .send(format!("Conversation context error: {}", e))
+                    .send(Alert::warn(format!("Conversation context error: {}", e)))
                     .await;
                 None // Continue with fresh query
             }
         };
 
+        if let Some(conv_context) = &conversation_context {
+            if let Some(query) = self.session_limit_reached(conv_context, context).await {
+                return Ok(query);
+            }
+        }
+
         // Build query with conversation history if continuing conversation
         let query_with_context = if let Some(conv_context) = conversation_context {
             self.build_query_with_conversation_history(query, &conv_context)
@@ -255,10 +418,7 @@ impl LLMOrchestrator {
             query.to_string()
         };
 
-        let primary_model = {
-            let config = self.runtime_config.lock().unwrap();
-            config.primary_llm.clone()
-        };
+        let primary_model = self.runtime_config.snapshot().await.primary_llm;
         context.last_model_used = Some(primary_model.clone());
         match primary_model.as_str() {
             "claude" => match self
@@ -297,6 +457,27 @@ impl LLMOrchestrator {
         }
     }
 
+    // Debugging aid for admins - the primary model's system prompt, and how a follow-up query is
+    // wrapped with conversation history before it is sent
+    pub async fn preview_prompt(&self, sample_query: &str) -> String {
+        let primary_model = self.runtime_config.snapshot().await.primary_llm;
+        // Same choice as parse_query - anything but groq goes to claude
+        let provider = match primary_model.as_str() {
+            "groq" => &self.groq,
+            _ => &self.claude,
+        };
+        let system_prompt = provider
+            .system_prompt()
+            .unwrap_or("(custom provider - no system prompt)");
+        format!(
+            "=== System prompt ({}, {} chars) ===\n{}\n\n=== Follow-up query as sent (sample history) ===\n{}",
+            primary_model,
+            system_prompt.chars().count(),
+            system_prompt,
+            self.build_query_with_conversation_history(sample_query, &sample_conversation())
+        )
+    }
+
     async fn parse_response_with_multistep(
         &self,
         response: &serde_json::Value,
@@ -310,6 +491,8 @@ impl LLMOrchestrator {
             .ok_or(LLMError::ParseError("No content returned".into()))?;
 
         // Look for tool_use in content blocks
+        let mut queries = Vec::new();
+        let mut info_result = None;
         for content_block in content_array {
             if let Some(content_type) = content_block.get("type").and_then(|t| t.as_str()) {
                 if content_type == "tool_use" {
@@ -320,24 +503,36 @@ impl LLMOrchestrator {
 
                     // Check if this is an information tool that requires multi-step handling
                     if let Some(tool_result) = self.execute_tool(tool_name, input) {
-                        // This is an information tool - continue conversation with result
-                        return self
-                            .continue_conversation_with_tool_result(
-                                original_query,
-                                tool_result,
-                                context,
-                            )
-                            .await;
+                        info_result.get_or_insert(tool_result);
                     } else {
                         // This is an action tool - handle normally
-                        return self.handle_tool_call(content_block);
+                        queries.push(self.handle_tool_call(content_block)?);
                     }
                 }
             }
         }
 
-        // If no tool use found, it's an unsupported query
-        Ok(Query::UnsupportedQuery)
+        // Tools we don't know of don't take a place in a batch
+        queries.retain(|query| !matches!(query, Query::UnsupportedQuery));
+        // An information tool alone means the model wants its result before answering. Next to
+        // actions it was asked for on the side - the actions are complete, so they go ahead.
+        if let Some(tool_result) = info_result {
+            if queries.is_empty() {
+                return self
+                    .continue_conversation_with_tool_result(original_query, tool_result, context)
+                    .await;
+            }
+            warn!(
+                "Ignoring an information tool call made alongside {} action tool call(s)",
+                queries.len()
+            );
+        }
+        match queries.len() {
+            // If no tool use found, it's an unsupported query
+            0 => Ok(Query::UnsupportedQuery),
+            1 => Ok(queries.remove(0)),
+            _ => Ok(Query::Batch(queries)),
+        }
     }
 
     fn handle_tool_call(&self, tool_content: &serde_json::Value) -> Result<Query, LLMError> {
@@ -358,15 +553,28 @@ impl LLMOrchestrator {
                 Ok(Query::GetStock { query })
             }
             "generate_quotation" => {
-                let quotation_request: QuotationRequest = serde_json::from_value(input.clone())
+                let mut input = input.clone();
+                amounts::normalize_amounts(&mut input, self.number_locale);
+                let quotation_request: QuotationRequest = serde_json::from_value(input)
                     .map_err(|_| LLMError::ParseError("Error parsing quotation request".into()))?;
                 Ok(Query::GetQuotation(quotation_request))
             }
             "generate_proforma" => {
-                let quotation_request: QuotationRequest = serde_json::from_value(input.clone())
+                let mut input = input.clone();
+                amounts::normalize_amounts(&mut input, self.number_locale);
+                let quotation_request: QuotationRequest = serde_json::from_value(input)
                     .map_err(|_| LLMError::ParseError("Error parsing proforma request".into()))?;
                 Ok(Query::GetProformaInvoice(quotation_request))
             }
+            "generate_quotation_and_proforma" => {
+                let mut input = input.clone();
+                amounts::normalize_amounts(&mut input, self.number_locale);
+                let quotation_request: QuotationRequest =
+                    serde_json::from_value(input).map_err(|_| {
+                        LLMError::ParseError("Error parsing quotation and proforma request".into())
+                    })?;
+                Ok(Query::GetQuotationAndProforma(quotation_request))
+            }
             "get_prices_only" => {
                 let price_request: PriceOnlyRequest = serde_json::from_value(input.clone())
                     .map_err(|_| {
@@ -374,6 +582,13 @@ impl LLMOrchestrator {
                     })?;
                 Ok(Query::GetPricesOnly(price_request))
             }
+            "describe_product" => {
+                let describe_request: DescribeProductRequest = serde_json::from_value(input.clone())
+                    .map_err(|_| {
+                        LLMError::ParseError("Describe product request cannot be parsed".into())
+                    })?;
+                Ok(Query::DescribeProduct(describe_request))
+            }
             "find_price_list" => {
                 let brand = input["brand"].as_str().unwrap_or("kei").to_string();
                 let keywords: Vec<String> = input["keywords"]
@@ -388,6 +603,15 @@ impl LLMOrchestrator {
                 let brand = input["brand"].as_str().map(|s| s.to_string());
                 Ok(Query::ListAvailablePricelists { brand })
             }
+            "get_order_status" => {
+                let reference = input["reference"]
+                    .as_str()
+                    .ok_or(LLMError::ParseError(
+                        "Reference not found for get_order_status".into(),
+                    ))?
+                    .to_string();
+                Ok(Query::GetOrderStatus { reference })
+            }
             _ => Ok(Query::UnsupportedQuery),
         }
     }
@@ -398,27 +622,38 @@ impl LLMOrchestrator {
         query: &str,
         context: &mut SessionContext,
     ) -> Result<Option<crate::database::ConversationContext>, LLMError> {
-        // Get database service from claude provider
-        let database = match &self.claude {
-            LLM::Claude(claude_provider) => &claude_provider.database,
-            _ => return Err(LLMError::ClientError("Database not available".to_string())),
+        let database = &self.database;
+        let features = self.runtime_config.snapshot().await.features;
+
+        // Check for existing conversation - with continuation off every query starts afresh
+        let recent_conversation = if features.conversation_continuation {
+            database
+                .get_recent_conversation(context.user_id)
+                .await
+                .map_err(|e| LLMError::ClientError(e.to_string()))?
+        } else {
+            None
         };
 
-        // Check for existing conversation
-        let recent_conversation = database
-            .get_recent_conversation(context.user_id)
-            .await
-            .map_err(|e| LLMError::ClientError(e.to_string()))?;
+        let decision = if let Some(conv_context) = &recent_conversation {
+            self.should_continue_conversation(query, &conv_context.messages, context)
+                .await?
+        } else {
+            ConversationDecision::NewConversation
+        };
 
-        let should_continue = if let Some(conv_context) = &recent_conversation {
-            if !conv_context.messages.is_empty() {
-                self.should_continue_conversation(query, &conv_context.messages, context)
-                    .await?
-            } else {
+        let should_continue = match decision {
+            ConversationDecision::Continue => true,
+            ConversationDecision::NewConversation => false,
+            ConversationDecision::Uncertain => {
+                // Carrying stale context into an unrelated query does more harm than
+                // losing it on a genuine follow-up, so err on the side of a fresh start
+                warn!(
+                    "Conversation classifier gave no clear answer for user {}, starting new conversation",
+                    context.user_id
+                );
                 false
             }
-        } else {
-            false
         };
 
         // Set or create conversation ID and return context if continuing
@@ -440,6 +675,45 @@ impl LLMOrchestrator {
         }
     }
 
+    // Every follow-up resends the whole conversation, so a long one gets steadily more
+    // expensive. Past the limit the user is refused, and a new conversation is opened so that
+    // their next query goes out without the history.
+    async fn session_limit_reached(
+        &self,
+        conversation: &crate::database::ConversationContext,
+        context: &mut SessionContext,
+    ) -> Option<Query> {
+        let limit_usd = self.max_session_cost_usd?;
+        let spent_usd = match self
+            .database
+            .get_conversation_total_cost(conversation.conversation_id)
+            .await
+        {
+            Ok(spent_usd) => spent_usd,
+            Err(e) => {
+                // Not knowing the spend is no reason to turn the user away
+                warn!("Could not get the cost of the conversation: {}", e);
+                return None;
+            }
+        };
+        if spent_usd < limit_usd {
+            return None;
+        }
+
+        warn!(
+            "Conversation {} has cost ${:.4}, over the ${:.2} limit",
+            conversation.conversation_id, spent_usd, limit_usd
+        );
+        match self.database.create_conversation(context.user_id).await {
+            Ok(conversation_id) => context.conversation_id = Some(conversation_id),
+            Err(e) => warn!("Could not start a new conversation: {}", e),
+        }
+        Some(Query::SessionLimitReached {
+            spent_usd,
+            limit_usd,
+        })
+    }
+
     // Build query with conversation history for LLM context
     fn build_query_with_conversation_history(
         &self,
@@ -476,9 +750,12 @@ impl LLMOrchestrator {
         current_query: &str,
         conversation_messages: &[crate::database::ConversationMessage],
         context: &SessionContext,
-    ) -> Result<bool, LLMError> {
+    ) -> Result<ConversationDecision, LLMError> {
         if conversation_messages.is_empty() {
-            return Ok(false);
+            return Ok(ConversationDecision::NewConversation);
+        }
+        if !self.runtime_config.snapshot().await.features.conversation_classifier {
+            return Ok(decide_by_recency(conversation_messages, chrono::Utc::now()));
         }
 
         // Build conversation history with exact stored responses
@@ -507,7 +784,7 @@ impl LLMOrchestrator {
         // Use Groq decision call with same session context for accurate cost tracking
         let groq_provider = match &self.groq {
             LLM::Groq(groq) => groq,
-            _ => return Ok(false),
+            _ => return Ok(ConversationDecision::NewConversation),
         };
 
         let response = groq_provider
@@ -520,9 +797,11 @@ impl LLMOrchestrator {
             .and_then(|arr| arr.first())
             .and_then(|item| item.get("text"))
             .and_then(|text| text.as_str())
-            .unwrap_or("NO");
+            .unwrap_or("");
 
-        Ok(decision_text.trim().to_uppercase() == "YES")
+        let decision = ConversationDecision::from_classifier_response(decision_text);
+        info!("Conversation decision {:?} from response {:?}", decision, decision_text);
+        Ok(decision)
     }
 
     // Create structured response for storage - no serde_json::Value
@@ -557,3 +836,440 @@ impl ToolExecutor for LLMOrchestrator {
         }
     }
 }
+
+// Without the classifier, a query continues the conversation only if it comes in shortly after
+// the previous reply
+fn decide_by_recency(
+    conversation_messages: &[crate::database::ConversationMessage],
+    now: chrono::DateTime<chrono::Utc>,
+) -> ConversationDecision {
+    let last_reply = conversation_messages
+        .last()
+        .and_then(|msg| msg.structured_response.as_ref())
+        .and_then(|response| chrono::DateTime::parse_from_rfc3339(&response.timestamp).ok());
+    let follow_up_window = chrono::Duration::minutes(FOLLOW_UP_WINDOW_MINUTES);
+    match last_reply {
+        Some(replied_at) if now.signed_duration_since(replied_at) <= follow_up_window => {
+            ConversationDecision::Continue
+        }
+        _ => ConversationDecision::NewConversation,
+    }
+}
+
+// One earlier quotation, standing in for real history in prompt previews
+fn sample_conversation() -> crate::database::ConversationContext {
+    crate::database::ConversationContext {
+        conversation_id: uuid::Uuid::nil(),
+        messages: vec![crate::database::ConversationMessage {
+            user_query: "quote for 4C x 2.5 cu flex 100 M discount 58%".to_string(),
+            structured_response: Some(StructuredResponse {
+                response_text: "Quotation created for given enquiry".to_string(),
+                response_metadata: Some(
+                    r#"{"GetQuotation":{"items":[{"quantity":100.0}]}}"#.to_string(),
+                ),
+                timestamp: "2025-08-21T10:00:00+00:00".to_string(),
+            }),
+        }],
+    }
+}
+
+// The first sentence of each tool description - the rest is guidance meant for the LLM
+fn tool_capabilities(tools: &Value) -> Vec<String> {
+    tools
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|tool| tool["description"].as_str())
+        .map(|description| {
+            let sentence = description.split(". ").next().unwrap_or(description);
+            sentence.trim_end_matches('.').to_string()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_preview_prompt_shows_primary_prompt_and_history() {
+        let database = Arc::new(DatabaseService::with_url("http://localhost"));
+        let runtime_config = SharedRuntimeConfig::default();
+        let orchestrator = LLMOrchestrator::with_providers(
+            LLM::Claude(Claude::new("claude prompt", "key", database.clone())),
+            LLM::Groq(Groq::new("groq prompt", "key", database.clone())),
+            database,
+            runtime_config.clone(),
+        );
+
+        let preview = orchestrator.preview_prompt("and 50 M of 4C x 4").await;
+        assert!(preview.starts_with("=== System prompt (groq, 11 chars) ===\ngroq prompt\n"));
+        assert!(preview.contains("User query: quote for 4C x 2.5 cu flex 100 M discount 58%"));
+        assert!(preview.contains("Current User Query: and 50 M of 4C x 4"));
+
+        runtime_config
+            .update(|config| config.primary_llm = "claude".to_string())
+            .await;
+        let preview = orchestrator.preview_prompt("and 50 M of 4C x 4").await;
+        assert!(preview.contains("\nclaude prompt\n"));
+    }
+
+    #[test]
+    fn test_orchestrator_with_configured_models() {
+        let database = Arc::new(DatabaseService::with_url("http://localhost"));
+        let claude_config = ClaudeConfig {
+            system_prompt: "prompt.txt".to_string(),
+            model: "claude-sonnet-4-5".to_string(),
+            max_tokens: 4096,
+            overload_retries: 1,
+        };
+        let groq_config = GroqConfig {
+            model: "openai/gpt-oss-120b".to_string(),
+            ..GroqConfig::default()
+        };
+        let orchestrator = LLMOrchestrator::with_providers(
+            LLM::Claude(
+                Claude::new("claude prompt", "key", database.clone()).with_config(&claude_config),
+            ),
+            LLM::Groq(Groq::new("groq prompt", "key", database.clone()).with_config(&groq_config)),
+            database.clone(),
+            SharedRuntimeConfig::default(),
+        );
+        assert_eq!(orchestrator.claude.model(), Some("claude-sonnet-4-5"));
+        assert_eq!(orchestrator.groq.model(), Some("openai/gpt-oss-120b"));
+
+        // Without a config the providers keep the models they always used
+        let groq = Groq::new("groq prompt", "key", database);
+        assert_eq!(groq.model(), "moonshotai/kimi-k2-instruct-0905");
+    }
+
+    struct UnreachableLLM;
+
+    #[async_trait]
+    impl LLMProvider for UnreachableLLM {
+        async fn try_parse(
+            &self,
+            _query: &str,
+            _context: &SessionContext,
+            _llm_orchestrator: &LLMOrchestrator,
+        ) -> Result<Query, LLMError> {
+            Err(LLMError::ClientError("No LLM in tests".into()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_conversation_over_cost_limit_is_refused() {
+        let mut server = mockito::Server::new_async().await;
+        let conversation_id = uuid::Uuid::new_v4();
+        let new_conversation_id = uuid::Uuid::new_v4();
+        let session_id = uuid::Uuid::new_v4();
+        let _conversation = server
+            .mock("GET", "/conversations")
+            .match_query(mockito::Matcher::Any)
+            .with_status(200)
+            .with_body(format!(r#"[{{"id": "{}"}}]"#, conversation_id))
+            .create_async()
+            .await;
+        let _history = server
+            .mock("GET", "/conversation_messages")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "select".into(),
+                "user_query,structured_response".into(),
+            ))
+            .with_status(200)
+            .with_body(
+                json!([{
+                    "user_query": "quote for 4C x 2.5 cu flex 100 M",
+                    "structured_response": {
+                        "response_text": "Quotation created for given enquiry",
+                        "response_metadata": null,
+                        "timestamp": chrono::Utc::now().to_rfc3339()
+                    }
+                }])
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        let _sessions = server
+            .mock("GET", "/conversation_messages")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "select".into(),
+                "session_id".into(),
+            ))
+            .with_status(200)
+            .with_body(format!(r#"[{{"session_id": "{}"}}]"#, session_id))
+            .create_async()
+            .await;
+        let _costs = server
+            .mock("GET", "/cost_events")
+            .match_query(mockito::Matcher::Any)
+            .with_status(200)
+            .with_body(r#"[{"cost_amount": 0.4}, {"cost_amount": 0.2}]"#)
+            .create_async()
+            .await;
+        let new_conversation = server
+            .mock("POST", "/conversations")
+            .match_query(mockito::Matcher::Any)
+            .with_status(201)
+            .with_body(format!(r#"[{{"id": "{}"}}]"#, new_conversation_id))
+            .expect(1)
+            .create_async()
+            .await;
+
+        let database = Arc::new(DatabaseService::with_url(&server.url()));
+        let runtime_config = SharedRuntimeConfig::default();
+        runtime_config
+            .update(|config| config.features.conversation_classifier = false)
+            .await;
+        // Neither provider answers, so a query that got past the limit would fail
+        let orchestrator = LLMOrchestrator::with_providers(
+            LLM::Custom(Box::new(UnreachableLLM)),
+            LLM::Custom(Box::new(UnreachableLLM)),
+            database,
+            runtime_config,
+        )
+        .with_session_cost_limit(Some(0.5));
+        let mut context = SessionContext::new(uuid::Uuid::new_v4(), "telegram");
+        let (error_sender, _error_receiver) = tokio::sync::mpsc::channel(1);
+
+        let query = orchestrator
+            .parse_query("and 50 M of 4C x 4", &mut context, &error_sender)
+            .await
+            .unwrap();
+        let Query::SessionLimitReached {
+            spent_usd,
+            limit_usd,
+        } = query
+        else {
+            panic!("Expected the session limit, got {:?}", query);
+        };
+        assert!((spent_usd - 0.6).abs() < 1e-9);
+        assert_eq!(limit_usd, 0.5);
+        // The next query starts a conversation of its own
+        assert_eq!(context.conversation_id, Some(new_conversation_id));
+        new_conversation.assert_async().await;
+
+        // Under the limit nothing is refused
+        let orchestrator = orchestrator.with_session_cost_limit(Some(1.0));
+        let conversation = crate::database::ConversationContext {
+            conversation_id,
+            messages: vec![],
+        };
+        assert!(orchestrator
+            .session_limit_reached(&conversation, &mut context)
+            .await
+            .is_none());
+    }
+
+    #[test]
+    fn test_tool_definitions_are_built_once() {
+        let database = Arc::new(DatabaseService::with_url("http://localhost"));
+        let orchestrator = LLMOrchestrator::with_providers(
+            LLM::Claude(Claude::new("claude prompt", "key", database.clone())),
+            LLM::Groq(Groq::new("groq prompt", "key", database.clone())),
+            database,
+            SharedRuntimeConfig::default(),
+        );
+
+        let fresh = build_tool_definitions();
+        assert_eq!(orchestrator.get_tool_definitions(), &fresh);
+        assert_eq!(
+            orchestrator.get_groq_tool_definitions(),
+            &llm_providers::groq::groq_tool_definitions(&fresh)
+        );
+    }
+
+    #[test]
+    fn test_tool_capabilities() {
+        let tools = json!([
+            {"name": "get_metal_prices", "description": "Get current metal prices"},
+            {"name": "list", "description": "List all pricelists. Use this before find_price_list."},
+            {"name": "no_description"}
+        ]);
+        assert_eq!(
+            tool_capabilities(&tools),
+            vec!["Get current metal prices", "List all pricelists"]
+        );
+        assert!(tool_capabilities(&json!({})).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_parse_response_collects_every_action_tool_call() {
+        let database = Arc::new(DatabaseService::with_url("http://localhost"));
+        let orchestrator = LLMOrchestrator::with_providers(
+            LLM::Claude(Claude::new("claude prompt", "key", database.clone())),
+            LLM::Groq(Groq::new("groq prompt", "key", database.clone())),
+            database,
+            SharedRuntimeConfig::default(),
+        );
+        let context = SessionContext::new(uuid::Uuid::new_v4(), "telegram");
+
+        let response = json!({"content": [
+            {"type": "text", "text": "Fetching both"},
+            {"type": "tool_use", "name": "get_metal_prices", "input": {}},
+            {"type": "tool_use", "name": "get_stock_info", "input": {"query": "4C 2.5"}}
+        ]});
+        let query = orchestrator
+            .parse_response_with_multistep(&response, "copper price and stock for 4C 2.5", &context)
+            .await
+            .unwrap();
+        let Query::Batch(queries) = query else {
+            panic!("Expected a batch, got {:?}", query);
+        };
+        assert_eq!(queries.len(), 2);
+        assert!(matches!(queries[0], Query::MetalPricing));
+        assert!(matches!(&queries[1], Query::GetStock { query } if query == "4C 2.5"));
+
+        // A single tool call, or one alongside an unknown tool, is not batched
+        let response = json!({"content": [
+            {"type": "tool_use", "name": "get_metal_prices", "input": {}},
+            {"type": "tool_use", "name": "no_such_tool", "input": {}}
+        ]});
+        let query = orchestrator
+            .parse_response_with_multistep(&response, "copper price", &context)
+            .await
+            .unwrap();
+        assert!(matches!(query, Query::MetalPricing));
+    }
+
+    #[tokio::test]
+    async fn test_parse_response_keeps_actions_next_to_an_info_tool_call() {
+        let database = Arc::new(DatabaseService::with_url("http://localhost"));
+        let mut orchestrator = LLMOrchestrator::with_providers(
+            LLM::Claude(Claude::new("claude prompt", "key", database.clone())),
+            LLM::Groq(Groq::new("groq prompt", "key", database.clone())),
+            database,
+            SharedRuntimeConfig::default(),
+        );
+        orchestrator.set_pricelist_service(Arc::new(PriceListService::new(vec![]).unwrap()));
+        let context = SessionContext::new(uuid::Uuid::new_v4(), "telegram");
+
+        // Continuing the conversation would need an LLM, so only the action can come back
+        let response = json!({"content": [
+            {"type": "tool_use", "name": "get_metal_prices", "input": {}},
+            {"type": "tool_use", "name": "list_available_pricelists", "input": {}}
+        ]});
+        let query = orchestrator
+            .parse_response_with_multistep(&response, "copper price and the price lists", &context)
+            .await
+            .unwrap();
+        assert!(matches!(query, Query::MetalPricing));
+    }
+
+    #[test]
+    fn test_get_order_status_tool_call() {
+        let database = Arc::new(DatabaseService::with_url("http://localhost"));
+        let orchestrator = LLMOrchestrator::with_providers(
+            LLM::Claude(Claude::new("claude prompt", "key", database.clone())),
+            LLM::Groq(Groq::new("groq prompt", "key", database.clone())),
+            database,
+            SharedRuntimeConfig::default(),
+        );
+
+        let query = orchestrator
+            .handle_tool_call(&json!({
+                "name": "get_order_status",
+                "input": {"reference": "Q-20250821-1234"}
+            }))
+            .unwrap();
+        assert!(matches!(
+            query,
+            Query::GetOrderStatus { reference } if reference == "Q-20250821-1234"
+        ));
+
+        let missing = orchestrator.handle_tool_call(&json!({
+            "name": "get_order_status",
+            "input": {}
+        }));
+        assert!(matches!(missing, Err(LLMError::ParseError(_))));
+        assert!(orchestrator
+            .get_tool_definitions()
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|tool| tool["name"] == "get_order_status"));
+    }
+
+    #[test]
+    fn test_conversation_decision_plain_answers() {
+        assert_eq!(
+            ConversationDecision::from_classifier_response("YES"),
+            ConversationDecision::Continue
+        );
+        assert_eq!(
+            ConversationDecision::from_classifier_response("NO"),
+            ConversationDecision::NewConversation
+        );
+    }
+
+    #[test]
+    fn test_conversation_decision_messy_affirmatives() {
+        for text in [
+            "YES, it's a follow-up",
+            "yes.",
+            "  Yes\n",
+            "**YES**",
+            "\"YES\"",
+            "Answer: YES",
+        ] {
+            assert_eq!(
+                ConversationDecision::from_classifier_response(text),
+                ConversationDecision::Continue,
+                "{:?} should continue the conversation",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn test_conversation_decision_messy_negatives() {
+        for text in [" NO ", "No.", "no - this is a new topic", "NO, unrelated query"] {
+            assert_eq!(
+                ConversationDecision::from_classifier_response(text),
+                ConversationDecision::NewConversation,
+                "{:?} should start a new conversation",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn test_conversation_decision_uncertain() {
+        for text in ["", "Maybe", "Not sure", "YES or NO", "I cannot determine that"] {
+            assert_eq!(
+                ConversationDecision::from_classifier_response(text),
+                ConversationDecision::Uncertain,
+                "{:?} should be uncertain",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn test_decide_by_recency() {
+        let message = |timestamp: &str| crate::database::ConversationMessage {
+            user_query: "quote 4c x 2.5".to_string(),
+            structured_response: Some(StructuredResponse {
+                response_text: "Quotation created for given enquiry".to_string(),
+                response_metadata: None,
+                timestamp: timestamp.to_string(),
+            }),
+        };
+        let now = chrono::DateTime::parse_from_rfc3339("2025-08-01T10:10:00+00:00")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+
+        assert_eq!(
+            decide_by_recency(&[message("2025-08-01T10:07:00+00:00")], now),
+            ConversationDecision::Continue
+        );
+        assert_eq!(
+            decide_by_recency(&[message("2025-08-01T09:30:00+00:00")], now),
+            ConversationDecision::NewConversation
+        );
+        assert_eq!(
+            decide_by_recency(&[message("not a timestamp")], now),
+            ConversationDecision::NewConversation
+        );
+    }
+}
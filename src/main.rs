@@ -2,7 +2,8 @@ use assistant::communication::price_alert::PriceAlertService;
 use assistant::communication::telegram::TelegramService;
 use assistant::communication::whatsapp::WhatsAppService;
 use assistant::configuration::Context;
-use assistant::core::ServiceManager;
+use assistant::core::{Alert, ServiceManager};
+use assistant::database::RetentionService;
 use assistant::prices::PriceService;
 use assistant::AppError;
 use assistant::{communication::error_alert::ErrorAlertService, stock::StockService};
@@ -30,13 +31,14 @@ async fn main() -> Result<(), AppError> {
 
     let mut service_manager = ServiceManager::new(context);
     let (sender, receiver) = mpsc::channel::<String>(100);
-    let (error_sender, error_receiver) = mpsc::channel::<String>(100);
+    let (error_sender, error_receiver) = mpsc::channel::<Alert>(100);
     let shared_receiver = Arc::new(Mutex::new(receiver));
     let shared_error_receiver = Arc::new(Mutex::new(error_receiver));
 
     service_manager.spawn_with_error_receiver::<ErrorAlertService>(shared_error_receiver);
     service_manager.spawn_with_error_sender::<WhatsAppService>(error_sender.clone());
-    service_manager.spawn_with_error_sender::<TelegramService>(error_sender);
+    service_manager.spawn_with_error_sender::<TelegramService>(error_sender.clone());
+    service_manager.spawn_with_error_sender::<RetentionService>(error_sender);
     service_manager.spawn_with_price_receiver::<PriceAlertService>(shared_receiver);
     service_manager.spawn_with_price_sender::<PriceService>(sender.clone());
 
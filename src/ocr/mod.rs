@@ -1,27 +1,65 @@
+use crate::configuration::{OcrConfig, OcrProviderKind};
 use crate::database::SessionContext;
-use aws_config::BehaviorVersion;
-use aws_sdk_textract::{types::Document, Client as AWSClient};
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, ImageOutputFormat};
+use std::io::Cursor;
 use std::sync::Arc;
 use thiserror::Error;
+use tracing::{info, warn};
 
 use crate::database::DatabaseService;
 
+pub mod providers;
+use providers::{OcrProvider, Tesseract, Textract};
+
 #[derive(Debug, Error)]
 pub enum OcrError {
     #[error("Image processing error: {0}")]
     ProcessingError(String),
+
+    #[error("No OCR providers configured")]
+    NoProviders,
 }
 
 pub struct OcrService {
-    client: AWSClient,
+    providers: Vec<Box<dyn OcrProvider>>,
     database: Arc<DatabaseService>,
+    max_image_pixels: u64,
 }
 
 impl OcrService {
-    pub async fn new(database: Arc<DatabaseService>) -> Result<Self, OcrError> {
-        let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
-        let client = AWSClient::new(&config);
-        Ok(Self { client, database })
+    pub async fn new(database: Arc<DatabaseService>, config: OcrConfig) -> Result<Self, OcrError> {
+        let mut providers: Vec<Box<dyn OcrProvider>> = Vec::new();
+        for kind in &config.providers {
+            match kind {
+                OcrProviderKind::Textract => providers.push(Box::new(Textract::new().await)),
+                OcrProviderKind::Tesseract => {
+                    providers.push(Box::new(Tesseract::new(&config.tesseract_command)))
+                }
+            }
+        }
+        Self::with_providers(providers, database, config.max_image_pixels())
+    }
+
+    // Providers are tried in the given order
+    pub fn with_providers(
+        providers: Vec<Box<dyn OcrProvider>>,
+        database: Arc<DatabaseService>,
+        max_image_pixels: u64,
+    ) -> Result<Self, OcrError> {
+        if providers.is_empty() {
+            return Err(OcrError::NoProviders);
+        }
+
+        Ok(Self {
+            providers,
+            database,
+            max_image_pixels,
+        })
+    }
+
+    pub fn max_image_pixels(&self) -> u64 {
+        self.max_image_pixels
     }
 
     pub async fn extract_text_from_image(
@@ -29,34 +67,13 @@ impl OcrService {
         image_data: Vec<u8>,
         context: &SessionContext,
     ) -> Result<String, OcrError> {
-        let image_data_len = image_data.len();
-        let document = Document::builder()
-            .bytes(aws_sdk_textract::primitives::Blob::new(image_data))
-            .build();
-
-        let response = self
-            .client
-            .detect_document_text()
-            .document(document)
-            .send()
-            .await
-            .map_err(|e| OcrError::ProcessingError(e.to_string()))?;
-
-        let mut extracted_text = String::new();
-        if let Some(blocks) = response.blocks {
-            for block in blocks {
-                if block.block_type() == Some(&aws_sdk_textract::types::BlockType::Line) {
-                    if let Some(text) = block.text() {
-                        extracted_text.push_str(text);
-                        extracted_text.push('\n');
-                    }
-                }
-            }
-        }
+        let image_data = downscale_image(image_data, self.max_image_pixels)?;
+        let (extracted_text, provider) =
+            extract_with_failover(&self.providers, &image_data).await?;
 
         let _ = self
             .database
-            .log_textract_usage(context, image_data_len)
+            .log_ocr_usage(context, provider, image_data.len())
             .await;
         if extracted_text.trim().is_empty() {
             Ok("No readable text found".to_string())
@@ -65,3 +82,150 @@ impl OcrService {
         }
     }
 }
+
+// Tries providers in configured order, returning the text along with the provider that served it
+async fn extract_with_failover(
+    providers: &[Box<dyn OcrProvider>],
+    image_data: &[u8],
+) -> Result<(String, &'static str), OcrError> {
+    let mut last_error = OcrError::NoProviders;
+    for provider in providers {
+        match provider.extract_text(image_data).await {
+            Ok(text) => return Ok((text, provider.name())),
+            Err(e) => {
+                warn!(
+                    "OCR provider {} failed, trying next: {}",
+                    provider.name(),
+                    e
+                );
+                last_error = e;
+            }
+        }
+    }
+    Err(last_error)
+}
+
+// Shrinks the image (keeping aspect ratio) when it is above the pixel cap. Images within the
+// cap, or ones we cannot decode, are passed through untouched for Textract to handle.
+fn downscale_image(image_data: Vec<u8>, max_pixels: u64) -> Result<Vec<u8>, OcrError> {
+    let image = match image::load_from_memory(&image_data) {
+        Ok(image) => image,
+        Err(e) => {
+            warn!("Could not decode image for resizing, sending as is: {}", e);
+            return Ok(image_data);
+        }
+    };
+
+    let (width, height) = image.dimensions();
+    let pixels = width as u64 * height as u64;
+    if pixels <= max_pixels {
+        info!("Using image resolution {}x{} for OCR", width, height);
+        return Ok(image_data);
+    }
+
+    let scale = (max_pixels as f64 / pixels as f64).sqrt();
+    let target_width = ((width as f64 * scale).floor() as u32).max(1);
+    let target_height = ((height as f64 * scale).floor() as u32).max(1);
+    let resized = image.resize(target_width, target_height, FilterType::Triangle);
+    info!(
+        "Downscaled image from {}x{} to {}x{} for OCR",
+        width,
+        height,
+        resized.width(),
+        resized.height()
+    );
+
+    // Jpeg encoder only takes rgb data
+    let mut output = Cursor::new(Vec::new());
+    DynamicImage::ImageRgb8(resized.to_rgb8())
+        .write_to(&mut output, ImageOutputFormat::Jpeg(85))
+        .map_err(|e| OcrError::ProcessingError(format!("Failed to encode resized image: {}", e)))?;
+    Ok(output.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use image::RgbImage;
+
+    struct FakeProvider {
+        name: &'static str,
+        result: Result<&'static str, &'static str>,
+    }
+
+    #[async_trait]
+    impl OcrProvider for FakeProvider {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        async fn extract_text(&self, _image_data: &[u8]) -> Result<String, OcrError> {
+            self.result
+                .map(|text| text.to_string())
+                .map_err(|e| OcrError::ProcessingError(e.to_string()))
+        }
+    }
+
+    fn encoded_image(width: u32, height: u32) -> Vec<u8> {
+        let mut output = Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(RgbImage::new(width, height))
+            .write_to(&mut output, ImageOutputFormat::Png)
+            .unwrap();
+        output.into_inner()
+    }
+
+    #[test]
+    fn test_downscale_image_respects_pixel_cap() {
+        let resized = downscale_image(encoded_image(400, 300), 30_000).unwrap();
+        let (width, height) = image::load_from_memory(&resized).unwrap().dimensions();
+
+        assert!(width as u64 * height as u64 <= 30_000);
+        assert_eq!((width, height), (200, 150));
+    }
+
+    #[test]
+    fn test_downscale_image_keeps_small_images_untouched() {
+        let original = encoded_image(100, 50);
+        let result = downscale_image(original.clone(), 30_000).unwrap();
+
+        assert_eq!(result, original);
+    }
+
+    #[tokio::test]
+    async fn test_extract_with_failover_uses_next_provider_on_error() {
+        let providers: Vec<Box<dyn OcrProvider>> = vec![
+            Box::new(FakeProvider {
+                name: "textract",
+                result: Err("throttled"),
+            }),
+            Box::new(FakeProvider {
+                name: "tesseract",
+                result: Ok("4C x 2.5 sqmm"),
+            }),
+        ];
+
+        let (text, provider) = extract_with_failover(&providers, &[]).await.unwrap();
+
+        assert_eq!(text, "4C x 2.5 sqmm");
+        assert_eq!(provider, "tesseract");
+    }
+
+    #[tokio::test]
+    async fn test_extract_with_failover_all_providers_fail() {
+        let providers: Vec<Box<dyn OcrProvider>> = vec![
+            Box::new(FakeProvider {
+                name: "textract",
+                result: Err("down"),
+            }),
+            Box::new(FakeProvider {
+                name: "tesseract",
+                result: Err("not installed"),
+            }),
+        ];
+
+        let result = extract_with_failover(&providers, &[]).await;
+
+        assert!(matches!(result, Err(OcrError::ProcessingError(e)) if e == "not installed"));
+    }
+}
@@ -0,0 +1,14 @@
+pub mod tesseract;
+pub mod textract;
+use super::OcrError;
+use async_trait::async_trait;
+pub use tesseract::Tesseract;
+pub use textract::Textract;
+
+#[async_trait]
+pub trait OcrProvider: Send + Sync {
+    // Used to attribute cost events to the provider that served the request
+    fn name(&self) -> &'static str;
+
+    async fn extract_text(&self, image_data: &[u8]) -> Result<String, OcrError>;
+}
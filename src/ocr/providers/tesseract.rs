@@ -0,0 +1,59 @@
+use super::OcrProvider;
+use crate::ocr::OcrError;
+use async_trait::async_trait;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+// Local fallback - shells out to the tesseract binary, which must be installed on the host
+pub struct Tesseract {
+    command: String,
+}
+
+impl Tesseract {
+    pub fn new(command: &str) -> Self {
+        Self {
+            command: command.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl OcrProvider for Tesseract {
+    fn name(&self) -> &'static str {
+        "tesseract"
+    }
+
+    async fn extract_text(&self, image_data: &[u8]) -> Result<String, OcrError> {
+        let mut child = Command::new(&self.command)
+            .args(["stdin", "stdout"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| OcrError::ProcessingError(format!("Failed to start tesseract: {}", e)))?;
+
+        let mut stdin = child.stdin.take().ok_or(OcrError::ProcessingError(
+            "Tesseract stdin unavailable".into(),
+        ))?;
+        stdin
+            .write_all(image_data)
+            .await
+            .map_err(|e| OcrError::ProcessingError(e.to_string()))?;
+        // Close stdin so tesseract knows the image is complete
+        drop(stdin);
+
+        let output = child
+            .wait_with_output()
+            .await
+            .map_err(|e| OcrError::ProcessingError(e.to_string()))?;
+
+        if !output.status.success() {
+            return Err(OcrError::ProcessingError(format!(
+                "Tesseract failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+}
@@ -0,0 +1,52 @@
+use super::OcrProvider;
+use crate::ocr::OcrError;
+use async_trait::async_trait;
+use aws_config::BehaviorVersion;
+use aws_sdk_textract::{types::Document, Client as AWSClient};
+
+pub struct Textract {
+    client: AWSClient,
+}
+
+impl Textract {
+    pub async fn new() -> Self {
+        let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
+        Self {
+            client: AWSClient::new(&config),
+        }
+    }
+}
+
+#[async_trait]
+impl OcrProvider for Textract {
+    fn name(&self) -> &'static str {
+        "textract"
+    }
+
+    async fn extract_text(&self, image_data: &[u8]) -> Result<String, OcrError> {
+        let document = Document::builder()
+            .bytes(aws_sdk_textract::primitives::Blob::new(image_data))
+            .build();
+
+        let response = self
+            .client
+            .detect_document_text()
+            .document(document)
+            .send()
+            .await
+            .map_err(|e| OcrError::ProcessingError(e.to_string()))?;
+
+        let mut extracted_text = String::new();
+        if let Some(blocks) = response.blocks {
+            for block in blocks {
+                if block.block_type() == Some(&aws_sdk_textract::types::BlockType::Line) {
+                    if let Some(text) = block.text() {
+                        extracted_text.push_str(text);
+                        extracted_text.push('\n');
+                    }
+                }
+            }
+        }
+        Ok(extracted_text)
+    }
+}
@@ -0,0 +1,147 @@
+use ::image::{GenericImageView, ImageFormat};
+use chrono::Local;
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+// Default letterhead printed on every quotation / proforma, unless branding points elsewhere. It
+// is read from disk for each document, so a replaced header is used from the next PDF onwards.
+pub const HEADER_IMAGE_PATH: &str = "assets/header.jpg";
+const HEADER_BACKUP_DIR: &str = "assets/header_backups";
+
+// Letterheads span the page width - anything narrower prints blurry
+const MIN_HEADER_WIDTH_PX: u32 = 600;
+const MAX_HEADER_WIDTH_PX: u32 = 5000;
+
+#[derive(Debug, Error)]
+pub enum HeaderImageError {
+    #[error("Header image must be a JPEG")]
+    NotJpeg,
+
+    #[error("Could not decode header image: {0}")]
+    DecodeError(String),
+
+    #[error(
+        "Header image is {0}x{1}px - it should be a landscape image {min}-{max}px wide",
+        min = MIN_HEADER_WIDTH_PX,
+        max = MAX_HEADER_WIDTH_PX
+    )]
+    InvalidDimensions(u32, u32),
+
+    #[error("Could not save header image: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+// Returns the (width, height) of a usable header image
+pub fn validate_header_image(data: &[u8]) -> Result<(u32, u32), HeaderImageError> {
+    if ::image::guess_format(data).ok() != Some(ImageFormat::Jpeg) {
+        return Err(HeaderImageError::NotJpeg);
+    }
+    // Full decode, so a truncated upload can't break every PDF afterwards
+    let (width, height) = ::image::load_from_memory_with_format(data, ImageFormat::Jpeg)
+        .map_err(|e| HeaderImageError::DecodeError(e.to_string()))?
+        .dimensions();
+
+    if !(MIN_HEADER_WIDTH_PX..=MAX_HEADER_WIDTH_PX).contains(&width) || height >= width {
+        return Err(HeaderImageError::InvalidDimensions(width, height));
+    }
+    Ok((width, height))
+}
+
+// Validates and swaps in a new header, returning where the previous one was backed up
+pub fn replace_header_image(
+    header_path: &str,
+    data: &[u8],
+) -> Result<Option<PathBuf>, HeaderImageError> {
+    replace_header_image_at(Path::new(header_path), Path::new(HEADER_BACKUP_DIR), data)
+}
+
+fn replace_header_image_at(
+    header_path: &Path,
+    backup_dir: &Path,
+    data: &[u8],
+) -> Result<Option<PathBuf>, HeaderImageError> {
+    validate_header_image(data)?;
+
+    let backup = if header_path.exists() {
+        fs::create_dir_all(backup_dir)?;
+        let backup_path = backup_dir.join(format!(
+            "header-{}.jpg",
+            Local::now().format("%Y%m%d-%H%M%S")
+        ));
+        fs::copy(header_path, &backup_path)?;
+        Some(backup_path)
+    } else {
+        None
+    };
+
+    // Written alongside and renamed, so a PDF being generated never reads a partial file
+    let staging_path = header_path.with_extension("jpg.upload");
+    fs::write(&staging_path, data)?;
+    fs::rename(&staging_path, header_path)?;
+    Ok(backup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::image::{DynamicImage, ImageOutputFormat, RgbImage};
+    use std::io::Cursor;
+
+    fn encoded(width: u32, height: u32, format: ImageOutputFormat) -> Vec<u8> {
+        let mut output = Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(RgbImage::new(width, height))
+            .write_to(&mut output, format)
+            .unwrap();
+        output.into_inner()
+    }
+
+    #[test]
+    fn test_validate_header_image() {
+        assert_eq!(
+            validate_header_image(&encoded(1200, 300, ImageOutputFormat::Jpeg(90))).unwrap(),
+            (1200, 300)
+        );
+        assert!(matches!(
+            validate_header_image(&encoded(1200, 300, ImageOutputFormat::Png)),
+            Err(HeaderImageError::NotJpeg)
+        ));
+        assert!(matches!(
+            validate_header_image(&encoded(300, 100, ImageOutputFormat::Jpeg(90))),
+            Err(HeaderImageError::InvalidDimensions(300, 100))
+        ));
+        assert!(matches!(
+            validate_header_image(&encoded(800, 1200, ImageOutputFormat::Jpeg(90))),
+            Err(HeaderImageError::InvalidDimensions(800, 1200))
+        ));
+
+        let mut truncated = encoded(1200, 300, ImageOutputFormat::Jpeg(90));
+        truncated.truncate(truncated.len() / 2);
+        assert!(validate_header_image(&truncated).is_err());
+    }
+
+    #[test]
+    fn test_replace_header_image_keeps_backup() {
+        let dir = std::env::temp_dir().join(format!("header-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let header_path = dir.join("header.jpg");
+        let backup_dir = dir.join("backups");
+        let old_header = encoded(1000, 200, ImageOutputFormat::Jpeg(90));
+        fs::write(&header_path, &old_header).unwrap();
+
+        let new_header = encoded(1200, 300, ImageOutputFormat::Jpeg(90));
+        let backup = replace_header_image_at(&header_path, &backup_dir, &new_header)
+            .unwrap()
+            .expect("previous header should be backed up");
+
+        assert_eq!(fs::read(&header_path).unwrap(), new_header);
+        assert_eq!(fs::read(&backup).unwrap(), old_header);
+
+        // A rejected upload leaves the current header alone
+        let result = replace_header_image_at(&header_path, &backup_dir, b"not an image");
+        assert!(matches!(result, Err(HeaderImageError::NotJpeg)));
+        assert_eq!(fs::read(&header_path).unwrap(), new_header);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
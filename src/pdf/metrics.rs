@@ -0,0 +1,59 @@
+// Advance widths of the printable ASCII characters (space to '~') in the builtin Helvetica
+// fonts, in 1/1000 em, from the Adobe core font AFM files. printpdf doesn't carry metrics for
+// builtin fonts, so right aligned text is measured from these.
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+    611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+    222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+const HELVETICA_BOLD_WIDTHS: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611, 975, 722, 722, 722, 722, 667,
+    611, 778, 722, 278, 556, 722, 611, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 333, 278, 333, 584, 556, 333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556,
+    278, 889, 611, 611, 611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584,
+];
+
+// Anything outside printable ASCII is measured as a digit
+const DEFAULT_WIDTH: u16 = 556;
+const MM_PER_PT: f64 = 25.4 / 72.0;
+
+// Width in mm of `text` set in Helvetica (or Helvetica-Bold) at `font_size_pt`
+pub(crate) fn get_text_width(text: &str, font_size_pt: f64, bold: bool) -> f64 {
+    let widths = if bold {
+        &HELVETICA_BOLD_WIDTHS
+    } else {
+        &HELVETICA_WIDTHS
+    };
+    let units: u32 = text
+        .chars()
+        .map(|c| match c {
+            ' '..='~' => u32::from(widths[c as usize - ' ' as usize]),
+            _ => u32::from(DEFAULT_WIDTH),
+        })
+        .sum();
+    units as f64 / 1000.0 * font_size_pt * MM_PER_PT
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_helvetica_text_width() {
+        assert_eq!(HELVETICA_WIDTHS[('0' as usize) - 32], 556);
+        assert_eq!(HELVETICA_WIDTHS[('~' as usize) - 32], 584);
+        assert_eq!(HELVETICA_BOLD_WIDTHS[('R' as usize) - 32], 722);
+
+        // R + s + . + 5 digits + . at 10pt
+        let width = get_text_width("Rs.100.00", 10.0, false);
+        assert!((width - 4.558 * 10.0 * MM_PER_PT).abs() < 1e-9);
+        // Bold only differs in the letters here
+        let bold = get_text_width("Rs.100.00", 10.0, true);
+        assert!((bold - width - 0.056 * 10.0 * MM_PER_PT).abs() < 1e-9);
+        assert_eq!(get_text_width("", 10.0, false), 0.0);
+    }
+}
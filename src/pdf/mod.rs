@@ -1,25 +1,91 @@
-use crate::prices::item_prices::Description;
+use crate::configuration::{BankDetails, BrandingConfig};
+use crate::prices::item_prices::{Description, QuantityUnit};
 use crate::quotation::{QuotationResponse, QuotedItem};
 use ::image::codecs::jpeg::JpegDecoder;
 use ::image::io::Reader as ImageReader;
+use ::image::{DynamicImage, Rgb as RgbPixel, RgbImage};
 use printpdf::*;
+use qrcode::QrCode;
 use std::fs;
-use std::fs::File;
-use std::io::BufWriter;
 use std::path::Path;
 
+mod header;
+mod metrics;
+mod optimize;
+use metrics::get_text_width;
+pub use header::{replace_header_image, HeaderImageError, HEADER_IMAGE_PATH};
+
 const PAGE_WIDTH_MM: f64 = 210.0;
 const PAGE_HEIGHT_MM: f64 = 297.0;
 const MARGIN_MM: f64 = 10.0;
 const BASE_TABLE_START_Y: f64 = 200.0;
 const ROW_HEIGHT_MM: f64 = 10.0;
 const MIN_ROW_HEIGHT_MM: f64 = 10.0;
+// Description wrap width for MAX_CHARS_WIDTH_MM of description text
 const MAX_CHARS_PER_LINE: usize = 60;
+const MAX_CHARS_WIDTH_MM: f64 = 110.0;
+const TABLE_RIGHT_MM: f64 = 200.0;
+const DEFAULT_DESCRIPTION_WIDTH_MM: f64 = 90.0;
+const MIN_DESCRIPTION_WIDTH_MM: f64 = 60.0;
+// The five number columns need 95mm between them for the "Amount Rs." and "Disc %" headers
+const MAX_DESCRIPTION_WIDTH_MM: f64 = 95.0;
+// Carved out of the right of the description column, wide enough for an 8 digit code
+const HSN_COLUMN_WIDTH_MM: f64 = 16.0;
 const TO_SECTION_LINE_SPACING: f64 = 5.0;
+const DELIVERY_LINE_HEIGHT: f64 = 7.0;
 const SECOND_PAGE_START_Y: f64 = 230.0;
 const TC_SECTION_LINE_SPACING: f64 = 5.0;
-const MAX_TOTALS_SECTION_HEIGHT: f64 = 28.0;
+const BANK_DETAILS_LINE_SPACING: f64 = 5.0;
+const BANK_DETAILS_WIDTH_MM: f64 = 110.0;
+// Keeps the block clear of the page number and footer
+const BANK_DETAILS_BOTTOM_MM: f64 = 15.0;
+const UPI_QR_SIZE_MM: f64 = 30.0;
+const UPI_QR_PIXELS_PER_MODULE: u32 = 8;
+// Scanners need a blank margin of 4 modules around the code
+const UPI_QR_QUIET_ZONE_MODULES: u32 = 4;
+const MAX_TOTALS_SECTION_HEIGHT: f64 = 56.0;
+const TOTALS_ROW_SEPARATION: f64 = 7.0;
+// The amount in words runs across the page under the totals
+const AMOUNT_IN_WORDS_CHARS_PER_LINE: usize = 110;
+const ONES: [&str; 20] = [
+    "",
+    "One",
+    "Two",
+    "Three",
+    "Four",
+    "Five",
+    "Six",
+    "Seven",
+    "Eight",
+    "Nine",
+    "Ten",
+    "Eleven",
+    "Twelve",
+    "Thirteen",
+    "Fourteen",
+    "Fifteen",
+    "Sixteen",
+    "Seventeen",
+    "Eighteen",
+    "Nineteen",
+];
+const TENS: [&str; 10] = [
+    "", "", "Twenty", "Thirty", "Forty", "Fifty", "Sixty", "Seventy", "Eighty", "Ninety",
+];
 const FOOTER_Y_MM: f64 = 5.0;
+const FOOTER_FONT_SIZE_PT: f64 = 8.0;
+// "Page X of Y" sits just above the marketing footer
+const PAGE_NUMBER_Y_MM: f64 = 9.0;
+const DRAFT_WATERMARK_FONT_SIZE_PT: f64 = 120.0;
+const DRAFT_WATERMARK_GREY: f64 = 0.9;
+const ENQUIRY_LINE_SPACING: f64 = 5.0;
+const ENQUIRY_CHARS_PER_LINE: usize = 100;
+// Keeps the enquiry text clear of the footer
+const ENQUIRY_BOTTOM_MM: f64 = 20.0;
+// The fixed positions above are laid out for a letterhead up to this tall. Taller headers push
+// the content down by the difference, and beyond the cap they are shrunk to fit.
+const DESIGNED_HEADER_HEIGHT_MM: f64 = 52.0;
+const MAX_HEADER_HEIGHT_MM: f64 = 80.0;
 
 #[derive(Debug, Clone, Copy)]
 pub enum DocumentType {
@@ -43,14 +109,150 @@ impl DocumentType {
     }
 }
 
+// Column positions of the item table. Everything that draws the table takes them from here, so
+// the text and the borders always line up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TableLayout {
+    item: f64,
+    hsn: f64,
+    qty: f64,
+    list_rate: f64,
+    discount: f64,
+    rate: f64,
+    amount: f64,
+    right: f64,
+    description_chars: usize,
+}
+
+impl TableLayout {
+    // The number columns split whatever the description leaves in a 4:4:3:4:5 ratio
+    pub fn new(description_width_mm: f64) -> Self {
+        let description_width_mm = Self::description_width(description_width_mm);
+        let qty = MARGIN_MM + description_width_mm;
+        let numbers_width = TABLE_RIGHT_MM - qty;
+        Self {
+            item: MARGIN_MM,
+            hsn: qty - HSN_COLUMN_WIDTH_MM,
+            qty,
+            list_rate: qty + numbers_width * 0.2,
+            discount: qty + numbers_width * 0.4,
+            rate: qty + numbers_width * 0.55,
+            amount: qty + numbers_width * 0.75,
+            right: TABLE_RIGHT_MM,
+            description_chars: (MAX_CHARS_PER_LINE as f64
+                * (description_width_mm - HSN_COLUMN_WIDTH_MM)
+                / MAX_CHARS_WIDTH_MM) as usize,
+        }
+    }
+
+    // The width a configured description column is actually drawn at
+    pub fn description_width(configured_width_mm: f64) -> f64 {
+        configured_width_mm.clamp(MIN_DESCRIPTION_WIDTH_MM, MAX_DESCRIPTION_WIDTH_MM)
+    }
+
+    fn width(&self) -> f64 {
+        self.right - self.item
+    }
+
+    fn column_lines(&self) -> [f64; 8] {
+        [
+            self.item,
+            self.hsn,
+            self.qty,
+            self.list_rate,
+            self.discount,
+            self.rate,
+            self.amount,
+            self.right,
+        ]
+    }
+}
+
+impl Default for TableLayout {
+    fn default() -> Self {
+        Self::new(DEFAULT_DESCRIPTION_WIDTH_MM)
+    }
+}
+
+// Writes the document to artifacts/{filename}
 pub fn create_quotation_pdf(
     quotation_number: &str,
     date: &str,
     quotation: &QuotationResponse,
     filename: &str,
     document_type: DocumentType,
+    layout: &TableLayout,
+    branding: &BrandingConfig,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    let pdf = create_quotation_pdf_bytes(
+        quotation_number,
+        date,
+        quotation,
+        document_type,
+        layout,
+        branding,
+    )?;
     fs::create_dir_all("artifacts")?;
+    fs::write(format!("artifacts/{}", filename), pdf)?;
+    Ok(())
+}
+
+// Documents held for approval wait here, outside the publicly served artifacts/
+pub const HELD_DOCUMENTS_DIR: &str = "held";
+
+// Moves a document out of artifacts/ until it is approved, returning its new path
+pub fn hold_document(path: &str) -> std::io::Result<String> {
+    move_document(path, "artifacts", HELD_DOCUMENTS_DIR)
+}
+
+// Puts an approved document back in artifacts/ so it can be sent, returning its path there
+pub fn release_document(path: &str) -> std::io::Result<String> {
+    move_document(path, HELD_DOCUMENTS_DIR, "artifacts")
+}
+
+// Paths outside `from` are left where they are
+fn move_document(path: &str, from: &str, to: &str) -> std::io::Result<String> {
+    let Some(name) = path
+        .strip_prefix(from)
+        .and_then(|rest| rest.strip_prefix('/'))
+    else {
+        return Ok(path.to_string());
+    };
+    fs::create_dir_all(to)?;
+    let moved = format!("{}/{}", to, name);
+    fs::rename(path, &moved)?;
+    Ok(moved)
+}
+
+// The finished document in memory, for senders that don't need it on disk
+pub fn create_quotation_pdf_bytes(
+    quotation_number: &str,
+    date: &str,
+    quotation: &QuotationResponse,
+    document_type: DocumentType,
+    layout: &TableLayout,
+    branding: &BrandingConfig,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let pdf = render_quotation_pdf(
+        quotation_number,
+        date,
+        quotation,
+        document_type,
+        layout,
+        branding,
+    )?;
+    Ok(optimize::optimize_pdf(&pdf)?)
+}
+
+// Lays out the document as printpdf produces it, before any size optimization
+fn render_quotation_pdf(
+    quotation_number: &str,
+    date: &str,
+    quotation: &QuotationResponse,
+    document_type: DocumentType,
+    layout: &TableLayout,
+    branding: &BrandingConfig,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
     let (doc, page1, layer1) = PdfDocument::new(
         "Quotation",
         Mm(PAGE_WIDTH_MM),
@@ -67,38 +269,44 @@ pub fn create_quotation_pdf(
         .map(|lines| (lines.len() + 1) as f64 * TO_SECTION_LINE_SPACING + 5.0) // line height + spacing
         .unwrap_or(0.0);
 
-    let table_start_y = BASE_TABLE_START_Y - to_section_height;
-    let mut current_page = page1;
-    let mut current_layer = doc.get_page(current_page).get_layer(layer1);
-    let mut current_y = table_start_y;
+    let delivery_line_height = if quotation.estimated_delivery.is_some() {
+        DELIVERY_LINE_HEIGHT
+    } else {
+        0.0
+    };
+
+    let mut current_layer = doc.get_page(page1).get_layer(layer1);
+    // Every page's layer, numbered once the page count is known
+    let mut page_layers = vec![current_layer.clone()];
+    if quotation.draft {
+        add_draft_watermark(&current_layer, &font_bold);
+    }
 
     // Add header to first page
-    add_header_to_page(
+    let header_offset = add_header_to_page(
         &current_layer,
         quotation_number,
         date,
         &quotation.to,
+        &quotation.estimated_delivery,
         &font,
         document_type,
+        branding,
     )?;
 
-    // Table column positions
-    let col_item = MARGIN_MM;
-    let col_qty = 120.0;
-    let col_rate = 140.0;
-    let col_amount = 170.0;
-    let table_width = col_amount + 30.0 - col_item;
+    let table_start_y =
+        BASE_TABLE_START_Y - header_offset - to_section_height - delivery_line_height;
+    let mut current_y = table_start_y;
+
+    let common_unit = common_quantity_unit(&quotation.items);
 
     // Add table headers
     add_table_headers(
         &current_layer,
         &font_bold,
+        common_unit,
         current_y,
-        col_item,
-        col_qty,
-        col_rate,
-        col_amount,
-        table_width,
+        layout,
     );
     current_y -= ROW_HEIGHT_MM;
 
@@ -116,41 +324,33 @@ pub fn create_quotation_pdf(
             "{}",
             item.product.get_description(extras)
         );
-        let lines = wrap_text(&description, MAX_CHARS_PER_LINE);
+        let lines = wrap_text(&description, layout.description_chars);
         let row_height = (lines.len() as f64 * 8.0).max(MIN_ROW_HEIGHT_MM);
 
         // Check if we need a new page
-        if current_y - row_height < MAX_TOTALS_SECTION_HEIGHT + 10.0 {
+        if current_y - row_height < totals_section_height(quotation) + 10.0 {
             // Leave space for totals
             // Add current page table border
             //draw_table_border(&current_layer, col_item, TABLE_START_Y, table_width, TABLE_START_Y - current_y - ROW_HEIGHT_MM);
 
-            // Create new page
-            let (new_page, new_layer) =
-                doc.add_page(Mm(PAGE_WIDTH_MM), Mm(PAGE_HEIGHT_MM), "Layer");
-            current_page = new_page;
-            current_layer = doc.get_page(current_page).get_layer(new_layer);
-            current_y = SECOND_PAGE_START_Y;
-
-            // Add header to new page
-            add_image_only_to_page(&current_layer, &font)?;
+            // Create new page, with the header
+            (current_layer, current_y) =
+                add_continuation_page(&doc, quotation.draft, branding, &font, &font_bold)?;
+            page_layers.push(current_layer.clone());
 
             // Add table headers on new page
             add_table_headers(
                 &current_layer,
                 &font_bold,
+                common_unit,
                 current_y,
-                col_item,
-                col_qty,
-                col_rate,
-                col_amount,
-                table_width,
+                layout,
             );
             current_y -= ROW_HEIGHT_MM;
         }
 
         // Draw row border
-        draw_row_border(&current_layer, col_item, current_y, table_width, row_height);
+        draw_row_border(&current_layer, layout, current_y, row_height);
 
         // Add item data
         add_item_row(
@@ -158,11 +358,9 @@ pub fn create_quotation_pdf(
             &font,
             &lines,
             item,
+            common_unit,
             current_y,
-            col_item,
-            col_qty,
-            col_rate,
-            col_amount,
+            layout,
         );
 
         current_y -= row_height;
@@ -177,15 +375,9 @@ pub fn create_quotation_pdf(
         &font_bold,
         quotation,
         current_y,
-        col_amount + 40.0,
+        layout.right + 10.0,
     );
-
-    let totals_height = if quotation.delivery_charges > 0.0 {
-        MAX_TOTALS_SECTION_HEIGHT
-    } else {
-        MAX_TOTALS_SECTION_HEIGHT - 7.0
-    };
-    current_y = totals_start_y - totals_height;
+    current_y = totals_start_y - totals_section_height(quotation);
 
     let terms_section_height = quotation
         .terms_and_conditions
@@ -198,51 +390,148 @@ pub fn create_quotation_pdf(
         // Check if terms fit on current page
         if current_y - terms_section_height < 10.0 {
             // 20mm bottom margin
-            // Create new page for terms
-            let (new_page, new_layer) =
-                doc.add_page(Mm(PAGE_WIDTH_MM), Mm(PAGE_HEIGHT_MM), "Layer");
-            current_layer = doc.get_page(new_page).get_layer(new_layer);
-            add_image_only_to_page(&current_layer, &font)?;
-            current_y = SECOND_PAGE_START_Y; // Start high on new page
+            // Create new page for terms, starting high on it
+            (current_layer, current_y) =
+                add_continuation_page(&doc, quotation.draft, branding, &font, &font_bold)?;
+            page_layers.push(current_layer.clone());
         } else {
             current_y -= 5.0; // Space after totals on same page
         }
 
-        add_terms_and_conditions(&current_layer, &font, &font_bold, terms, current_y);
+        current_y = add_terms_and_conditions(&current_layer, &font, &font_bold, terms, current_y);
     }
 
-    // Save PDF
-    let full_filename = format!("artifacts/{}", filename);
-    doc.save(&mut BufWriter::new(File::create(full_filename)?))?;
-    Ok(())
+    // Payment details, so the customer can pay against a proforma
+    if let (DocumentType::ProformaInvoice, Some(bank)) = (document_type, &quotation.bank_details) {
+        let lines = bank_detail_lines(bank);
+        let upi_qr = bank
+            .upi_id
+            .as_ref()
+            .map(|upi_id| upi_qr_image(&upi_payment_uri(bank, upi_id, quotation.grand_total)))
+            .transpose()?;
+        if current_y - 5.0 - bank_details_height(lines.len()) < BANK_DETAILS_BOTTOM_MM {
+            (current_layer, current_y) =
+                add_continuation_page(&doc, quotation.draft, branding, &font, &font_bold)?;
+            page_layers.push(current_layer.clone());
+        } else {
+            current_y -= 5.0;
+        }
+
+        add_bank_details(
+            &current_layer,
+            &font,
+            &font_bold,
+            &lines,
+            upi_qr.as_ref(),
+            current_y,
+        );
+    }
+
+    if let Some(enquiry) = &quotation.enquiry {
+        page_layers.extend(add_enquiry_reference(
+            &doc,
+            enquiry,
+            quotation.draft,
+            branding,
+            &font,
+            &font_bold,
+        )?);
+    }
+    add_page_numbers(&page_layers, &font);
+
+    Ok(doc.save_to_bytes()?)
 }
 
-fn add_image_only_to_page(layer: &PdfLayerReference, font: &IndirectFontRef) -> Result<(), Box<dyn std::error::Error>> {
+// A page after the first, with the letterhead, footer and any draft watermark. Returns its layer
+// and where its content starts.
+fn add_continuation_page(
+    doc: &PdfDocumentReference,
+    draft: bool,
+    branding: &BrandingConfig,
+    font: &IndirectFontRef,
+    font_bold: &IndirectFontRef,
+) -> Result<(PdfLayerReference, f64), Box<dyn std::error::Error>> {
+    let (page, layer) = doc.add_page(Mm(PAGE_WIDTH_MM), Mm(PAGE_HEIGHT_MM), "Layer");
+    let layer = doc.get_page(page).get_layer(layer);
+    if draft {
+        add_draft_watermark(&layer, font_bold);
+    }
+    let offset = add_image_only_to_page(&layer, font, branding)?;
+    Ok((layer, SECOND_PAGE_START_Y - offset))
+}
+
+// Returns how far the page content has to move down to clear the header
+fn add_image_only_to_page(
+    layer: &PdfLayerReference,
+    font: &IndirectFontRef,
+    branding: &BrandingConfig,
+) -> Result<f64, Box<dyn std::error::Error>> {
     // Load and add header image only
-    let img_info = ImageReader::open("assets/header.jpg")?.decode()?.to_rgb8();
-    let (width_px, height_px) = (img_info.width() as f32, img_info.height() as f32);
+    let fit = add_header_image(layer, &branding.header_image_path)?;
+
+    // Add marketing footer
+    add_marketing_footer(layer, font, branding);
+
+    Ok(fit.content_offset())
+}
+
+// Where the letterhead goes on the page. It normally spans the full width; one too tall for
+// that is capped at MAX_HEADER_HEIGHT_MM and centred instead.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct HeaderFit {
+    scale: f64,
+    width_mm: f64,
+    height_mm: f64,
+    capped: bool,
+}
+
+impl HeaderFit {
+    fn new(width_px: f32, height_px: f32) -> Self {
+        let natural_width_mm = (width_px * 25.4 / 96.0) as f64;
+        let natural_height_mm = (height_px * 25.4 / 96.0) as f64;
+
+        let scale = PAGE_WIDTH_MM / natural_width_mm;
+        let capped = natural_height_mm * scale > MAX_HEADER_HEIGHT_MM;
+        let scale = if capped {
+            MAX_HEADER_HEIGHT_MM / natural_height_mm
+        } else {
+            scale
+        };
+        Self {
+            scale,
+            width_mm: natural_width_mm * scale,
+            height_mm: natural_height_mm * scale,
+            capped,
+        }
+    }
+
+    fn content_offset(&self) -> f64 {
+        (self.height_mm - DESIGNED_HEADER_HEIGHT_MM).max(0.0)
+    }
+}
 
-    let mut image_file = std::fs::File::open(Path::new("assets/header.jpg"))?;
-    let img = Image::try_from(JpegDecoder::new(&mut image_file).unwrap()).unwrap();
+fn add_header_image(
+    layer: &PdfLayerReference,
+    header_image_path: &str,
+) -> Result<HeaderFit, Box<dyn std::error::Error>> {
+    let img_info = ImageReader::open(header_image_path)?.decode()?.to_rgb8();
+    let fit = HeaderFit::new(img_info.width() as f32, img_info.height() as f32);
 
-    let scale = PAGE_WIDTH_MM / (width_px * 25.4 / 96.0) as f64;
-    let scaled_height_mm = (height_px * scale as f32 * 25.4 / 96.0) as f64;
+    let mut image_file = std::fs::File::open(Path::new(header_image_path))?;
+    // Only JPEG letterheads can be embedded - anything else fails the document, not the process
+    let img = Image::try_from(JpegDecoder::new(&mut image_file)?)?;
 
     let transform = ImageTransform {
-        translate_x: Some(Mm(0.0)),
-        translate_y: Some(Mm(PAGE_HEIGHT_MM - scaled_height_mm)),
+        translate_x: Some(Mm((PAGE_WIDTH_MM - fit.width_mm) / 2.0)),
+        translate_y: Some(Mm(PAGE_HEIGHT_MM - fit.height_mm)),
         rotate: None,
-        scale_x: Some(scale),
-        scale_y: Some(scale),
+        scale_x: Some(fit.scale),
+        scale_y: Some(fit.scale),
         dpi: Some(96.0),
     };
 
     img.add_to_layer(layer.clone(), transform);
-
-    // Add marketing footer
-    add_marketing_footer(layer, font);
-
-    Ok(())
+    Ok(fit)
 }
 
 fn add_header_to_page(
@@ -250,44 +539,41 @@ fn add_header_to_page(
     quotation_number: &str,
     date: &str,
     to: &Option<Vec<String>>,
+    estimated_delivery: &Option<String>,
     font: &IndirectFontRef,
     document_type: DocumentType,
-) -> Result<(), Box<dyn std::error::Error>> {
+    branding: &BrandingConfig,
+) -> Result<f64, Box<dyn std::error::Error>> {
     // Load and add header image
-    let img_info = ImageReader::open("assets/header.jpg")?.decode()?.to_rgb8();
-    let (width_px, height_px) = (img_info.width() as f32, img_info.height() as f32);
-
-    let mut image_file = std::fs::File::open(Path::new("assets/header.jpg"))?;
-    let img = Image::try_from(JpegDecoder::new(&mut image_file).unwrap()).unwrap();
-
-    let scale = PAGE_WIDTH_MM / (width_px * 25.4 / 96.0) as f64;
-    let scaled_height_mm = (height_px * scale as f32 * 25.4 / 96.0) as f64;
-
-    let transform = ImageTransform {
-        translate_x: Some(Mm(0.0)),
-        translate_y: Some(Mm(PAGE_HEIGHT_MM - scaled_height_mm)),
-        rotate: None,
-        scale_x: Some(scale),
-        scale_y: Some(scale),
-        dpi: Some(96.0),
-    };
-
-    img.add_to_layer(layer.clone(), transform);
+    let fit = add_header_image(layer, &branding.header_image_path)?;
+    if fit.capped {
+        tracing::warn!(
+            "Header image is too tall for the page - shrunk to {:.0}mm high",
+            fit.height_mm
+        );
+    }
+    let offset = fit.content_offset();
 
     let header_text = document_type.get_header_text();
     let page_center_x = PAGE_WIDTH_MM / 2.0;
 
     let header_x = page_center_x - (header_text.len() as f64 * 2.0);
-    layer.use_text(header_text, 12.0, Mm(header_x), Mm(240.0), font);
+    layer.use_text(header_text, 12.0, Mm(header_x), Mm(240.0 - offset), font);
     let text_width = header_text.len() as f64 * 2.8;
-    draw_horizontal_line(layer, header_x, 238.0, text_width);
+    draw_horizontal_line(layer, header_x, 238.0 - offset, text_width);
 
     // Add quotation details
+    let mut current_y = 220.0 - offset;
     let quotation_reference = format!("Ref: {}", quotation_number);
-    layer.use_text(quotation_reference, 10.0, Mm(MARGIN_MM), Mm(220.0), font);
-    layer.use_text(date, 10.0, Mm(157.0), Mm(220.0), font);
+    layer.use_text(
+        quotation_reference,
+        10.0,
+        Mm(MARGIN_MM),
+        Mm(current_y),
+        font,
+    );
+    layer.use_text(date, 10.0, Mm(157.0), Mm(current_y), font);
 
-    let mut current_y = 220.0;
     if let Some(to_lines) = to {
         current_y -= 7.0; // Space after date
         layer.use_text("To:", 10.0, Mm(MARGIN_MM), Mm(current_y), font);
@@ -302,6 +588,13 @@ fn add_header_to_page(
         current_y -= 10.0; // Standard spacing when no "to" section
     }
 
+    // Kept out of the terms block since it's the first thing customers look for
+    if let Some(delivery) = estimated_delivery {
+        let delivery_text = format!("Estimated Delivery: {}", delivery);
+        layer.use_text(delivery_text, 10.0, Mm(MARGIN_MM), Mm(current_y), font);
+        current_y -= DELIVERY_LINE_HEIGHT;
+    }
+
     let mut introduction_text =
         "Thank you for enquiry. Please find the quotation below for your consideration:-";
     match document_type {
@@ -311,58 +604,70 @@ fn add_header_to_page(
     layer.use_text(introduction_text, 10.0, Mm(MARGIN_MM), Mm(current_y), font);
 
     // Add marketing footer
-    add_marketing_footer(layer, font);
+    add_marketing_footer(layer, font, branding);
 
-    Ok(())
+    Ok(offset)
 }
 
 fn add_table_headers(
     layer: &PdfLayerReference,
     font_bold: &IndirectFontRef,
+    unit: Option<QuantityUnit>,
     y_pos: f64,
-    col_item: f64,
-    col_qty: f64,
-    col_rate: f64,
-    col_amount: f64,
-    table_width: f64,
+    layout: &TableLayout,
 ) {
     // Add header text with proper padding from lines
-    layer.use_text("Item", 10.0, Mm(col_item + 2.0), Mm(y_pos - 4.0), font_bold); // Changed from -2.0 to -4.0
+    layer.use_text("Item", 10.0, Mm(layout.item + 2.0), Mm(y_pos - 4.0), font_bold); // Changed from -2.0 to -4.0
+    layer.use_text("HSN", 10.0, Mm(layout.hsn + 2.0), Mm(y_pos - 4.0), font_bold);
+    let (qty_header, rate_header) = match unit {
+        Some(unit) => (
+            format!("Qty ({})", unit.label()),
+            format!("Rate/{}.", unit.rate_suffix()),
+        ),
+        None => ("Qty".to_string(), "Rate".to_string()),
+    };
     layer.use_text(
-        "Qty (Mtr)",
+        &qty_header,
         10.0,
-        Mm(col_qty + 2.0),
+        Mm(layout.qty + 2.0),
         Mm(y_pos - 4.0),
         font_bold,
     );
     layer.use_text(
-        "Rate/mtr.",
+        "List Rate",
         10.0,
-        Mm(col_rate + 2.0),
+        Mm(layout.list_rate + 2.0),
+        Mm(y_pos - 4.0),
+        font_bold,
+    );
+    layer.use_text(
+        "Disc %",
+        10.0,
+        Mm(layout.discount + 2.0),
+        Mm(y_pos - 4.0),
+        font_bold,
+    );
+    layer.use_text(
+        &rate_header,
+        10.0,
+        Mm(layout.rate + 2.0),
         Mm(y_pos - 4.0),
         font_bold,
     );
     layer.use_text(
         "Amount Rs.",
         10.0,
-        Mm(col_amount + 2.0),
+        Mm(layout.amount + 2.0),
         Mm(y_pos - 4.0),
         font_bold,
     );
 
     // Draw header border
-    draw_horizontal_line(layer, col_item, y_pos + 5.0, table_width);
-    draw_horizontal_line(layer, col_item, y_pos - ROW_HEIGHT_MM, table_width);
-    draw_vertical_line(layer, col_item, y_pos + 5.0, ROW_HEIGHT_MM + 10.0);
-    draw_vertical_line(layer, col_qty, y_pos + 5.0, ROW_HEIGHT_MM + 10.0);
-    draw_vertical_line(layer, col_rate, y_pos + 5.0, ROW_HEIGHT_MM + 10.0);
-    draw_vertical_line(layer, col_amount, y_pos + 5.0, ROW_HEIGHT_MM + 10.0);
-    draw_vertical_line(
-        layer,
-        col_item + table_width,
-        y_pos + 5.0,
-        ROW_HEIGHT_MM + 10.0,
-    );
+    draw_horizontal_line(layer, layout.item, y_pos + 5.0, layout.width());
+    draw_horizontal_line(layer, layout.item, y_pos - ROW_HEIGHT_MM, layout.width());
+    for x in layout.column_lines() {
+        draw_vertical_line(layer, x, y_pos + 5.0, ROW_HEIGHT_MM + 10.0);
+    }
 }
 
 fn add_item_row(
@@ -370,42 +675,71 @@ fn add_item_row(
     font: &IndirectFontRef,
     description_lines: &[String],
     item: &QuotedItem,
+    common_unit: Option<QuantityUnit>,
     y_pos: f64,
-    col_item: f64,
-    col_qty: f64,
-    col_rate: f64,
-    col_amount: f64,
+    layout: &TableLayout,
 ) {
     // Add description (multi-line) - start from top of row with proper padding
     let mut row_y_pos = y_pos;
     for (i, line) in description_lines.iter().enumerate() {
         row_y_pos = y_pos - 4.0 - (i as f64 * 8.0);
-        layer.use_text(line, 9.0, Mm(col_item + 2.0), Mm(row_y_pos), font);
+        layer.use_text(line, 9.0, Mm(layout.item + 2.0), Mm(row_y_pos), font);
     }
 
     // Center other values vertically in the row with proper padding
     // let text_y = y_pos - (row_height / 2.0) - 2.0; // Changed from -1.0 to -2.0
     let text_y = row_y_pos;
+    // Left blank when the item has no HSN code
+    if let Some(hsn_code) = &item.hsn_code {
+        layer.use_text(hsn_code, 9.0, Mm(layout.hsn + 2.0), Mm(text_y), font);
+    }
+    // With mixed units the header can't carry the unit, so each row shows its own
+    let quantity = match common_unit {
+        Some(_) => format_quantity(item.quantity_mtrs),
+        None => format!(
+            "{} {}",
+            format_quantity(item.quantity_mtrs),
+            item.unit.label()
+        ),
+    };
+    layer.use_text(
+        &quantity,
+        9.0,
+        Mm(layout.qty + 2.0),
+        Mm(text_y),
+        font,
+    );
+
+    layer.use_text(
+        format_amount(item.list_price),
+        9.0,
+        Mm(layout.list_rate + 2.0),
+        Mm(text_y),
+        font,
+    );
     layer.use_text(
-        &format!("{:.0}", item.quantity_mtrs),
+        format_discount(item.discount),
         9.0,
-        Mm(col_qty + 2.0),
+        Mm(layout.discount + 2.0),
         Mm(text_y),
         font,
     );
 
+    // Net of the discount
     layer.use_text(
-        &format!("{:.2}", item.price),
+        format_amount(item.price),
         9.0,
-        Mm(col_rate + 2.0),
+        Mm(layout.rate + 2.0),
         Mm(text_y),
         font,
     );
 
+    // Right aligned, so the digits line up down the column
+    let amount = format_amount(item.amount);
     layer.use_text(
-        &format!("{:.2}", item.amount),
+        &amount,
         9.0,
-        Mm(col_amount + 2.0),
+        Mm(layout.right - 2.0 - get_text_width(&amount, 9.0, false)),
         Mm(text_y),
         font,
     );
@@ -419,53 +753,190 @@ fn add_totals_section(
     mut y_pos: f64,
     right_align_x: f64,
 ) {
-    let label_x = right_align_x - 60.0;
+    // Far enough left for "Packing & Forwarding:" beside a lakh amount
+    let label_x = right_align_x - 75.0;
     let value_x = right_align_x - 5.0;
-    let row_separation = 7.0;
+    let row_separation = TOTALS_ROW_SEPARATION;
     // Sub Total
+    let value = format!("Rs.{}", format_amount(quotation.basic_total));
     layer.use_text("Sub Total:", 10.0, Mm(label_x), Mm(y_pos), font_bold);
     layer.use_text(
-        &format!("Rs.{:.2}", quotation.basic_total),
+        &value,
         10.0,
-        Mm(value_x - get_text_width(&format!("Rs.{:.2}", quotation.basic_total))),
+        Mm(value_x - get_text_width(&value, 10.0, true)),
         Mm(y_pos),
         font_bold,
     );
 
+    // Volume Discount (if applicable)
+    if let Some(discount) = &quotation.volume_discount {
+        y_pos -= row_separation;
+        let value = format!("-Rs.{}", format_amount(discount.amount));
+        layer.use_text("Volume Discount:", 10.0, Mm(label_x), Mm(y_pos), font);
+        layer.use_text(
+            &value,
+            10.0,
+            Mm(value_x - get_text_width(&value, 10.0, false)),
+            Mm(y_pos),
+            font,
+        );
+    }
+
     // Delivery Charges (if applicable)
     if quotation.delivery_charges > 0.0 {
         y_pos -= row_separation;
+        let value = format!("Rs.{}", format_amount(quotation.delivery_charges));
         layer.use_text("Delivery Charges:", 10.0, Mm(label_x), Mm(y_pos), font);
         layer.use_text(
-            &format!("Rs.{:.2}", quotation.delivery_charges),
+            &value,
             10.0,
-            Mm(value_x - get_text_width(&format!("Rs.{:.2}", quotation.delivery_charges))),
+            Mm(value_x - get_text_width(&value, 10.0, false)),
             Mm(y_pos),
             font,
         );
     }
 
-    // GST
-    y_pos -= row_separation;
-    layer.use_text("GST @ 18%:", 10.0, Mm(label_x), Mm(y_pos), font);
-    layer.use_text(
-        &format!("Rs.{:.2}", quotation.taxes),
-        10.0,
-        Mm(value_x - get_text_width(&format!("Rs.{:.2}", quotation.taxes))),
-        Mm(y_pos),
-        font,
-    );
+    // Packing & Forwarding (if applicable)
+    if quotation.packing_forwarding > 0.0 {
+        y_pos -= row_separation;
+        let value = format!("Rs.{}", format_amount(quotation.packing_forwarding));
+        layer.use_text("Packing & Forwarding:", 10.0, Mm(label_x), Mm(y_pos), font);
+        layer.use_text(
+            &value,
+            10.0,
+            Mm(value_x - get_text_width(&value, 10.0, false)),
+            Mm(y_pos),
+            font,
+        );
+    }
+
+    // GST, or CGST + SGST / IGST when the buyer's state is known
+    for (label, amount) in quotation.tax_lines() {
+        y_pos -= row_separation;
+        let value = format!("Rs.{}", format_amount(amount));
+        layer.use_text(format!("{}:", label), 10.0, Mm(label_x), Mm(y_pos), font);
+        layer.use_text(
+            &value,
+            10.0,
+            Mm(value_x - get_text_width(&value, 10.0, false)),
+            Mm(y_pos),
+            font,
+        );
+    }
+
+    // Round Off (if applicable)
+    if quotation.round_off != 0.0 {
+        y_pos -= row_separation;
+        let value = format_round_off(quotation.round_off);
+        layer.use_text("Round Off:", 10.0, Mm(label_x), Mm(y_pos), font);
+        layer.use_text(
+            &value,
+            10.0,
+            Mm(value_x - get_text_width(&value, 10.0, false)),
+            Mm(y_pos),
+            font,
+        );
+    }
 
     // Total
     y_pos -= row_separation;
+    let value = format!("Rs.{}", format_amount(quotation.grand_total));
     layer.use_text("Total:", 10.0, Mm(label_x), Mm(y_pos), font_bold);
     layer.use_text(
-        &format!("Rs.{:.2}", quotation.grand_total),
+        &value,
         10.0,
-        Mm(value_x - get_text_width(&format!("Rs.{:.2}", quotation.grand_total))),
+        Mm(value_x - get_text_width(&value, 10.0, true)),
         Mm(y_pos),
         font_bold,
     );
+
+    // Amount in words, across the page
+    for line in amount_in_words_lines(quotation) {
+        y_pos -= row_separation;
+        layer.use_text(line, 9.0, Mm(MARGIN_MM), Mm(y_pos), font);
+    }
+}
+
+// Volume discount, delivery and the second (SGST) tax rows are only printed when they apply
+fn totals_section_height(quotation: &QuotationResponse) -> f64 {
+    let mut totals_height = MAX_TOTALS_SECTION_HEIGHT;
+    if quotation.tax_lines().len() == 1 {
+        totals_height -= TOTALS_ROW_SEPARATION;
+    }
+    if quotation.volume_discount.is_none() {
+        totals_height -= TOTALS_ROW_SEPARATION;
+    }
+    if quotation.delivery_charges <= 0.0 {
+        totals_height -= TOTALS_ROW_SEPARATION;
+    }
+    if quotation.packing_forwarding <= 0.0 {
+        totals_height -= TOTALS_ROW_SEPARATION;
+    }
+    if quotation.round_off == 0.0 {
+        totals_height -= TOTALS_ROW_SEPARATION;
+    }
+    totals_height + amount_in_words_lines(quotation).len() as f64 * TOTALS_ROW_SEPARATION
+}
+
+fn amount_in_words_lines(quotation: &QuotationResponse) -> Vec<String> {
+    let words = amount_to_indian_words(quotation.grand_total as f64);
+    wrap_text(
+        &format!("Amount in words: {}", words),
+        AMOUNT_IN_WORDS_CHARS_PER_LINE,
+    )
+}
+
+// Spelled out the Indian way, in thousands, lakhs and crores eg. 40810.30 is
+// "Rupees Forty Thousand Eight Hundred Ten and Thirty Paise Only"
+fn amount_to_indian_words(amount: f64) -> String {
+    let total_paise = (amount.abs() * 100.0).round() as u64;
+    let (rupees, paise) = (total_paise / 100, total_paise % 100);
+    let rupees = match rupees {
+        0 => "Zero".to_string(),
+        rupees => number_to_indian_words(rupees),
+    };
+    match paise {
+        0 => format!("Rupees {} Only", rupees),
+        paise => format!(
+            "Rupees {} and {} Paise Only",
+            rupees,
+            below_hundred_words(paise)
+        ),
+    }
+}
+
+fn number_to_indian_words(number: u64) -> String {
+    let mut words = Vec::new();
+    // Anything past 99 crores is still counted in crores
+    let crores = number / 10_000_000;
+    if crores > 0 {
+        words.push(format!("{} Crore", number_to_indian_words(crores)));
+    }
+    for (count, name) in [
+        (number / 100_000 % 100, "Lakh"),
+        (number / 1000 % 100, "Thousand"),
+    ] {
+        if count > 0 {
+            words.push(format!("{} {}", below_hundred_words(count), name));
+        }
+    }
+    let hundreds = number / 100 % 10;
+    if hundreds > 0 {
+        words.push(format!("{} Hundred", ONES[hundreds as usize]));
+    }
+    if !number.is_multiple_of(100) {
+        words.push(below_hundred_words(number % 100));
+    }
+    words.join(" ")
+}
+
+fn below_hundred_words(number: u64) -> String {
+    let number = number as usize;
+    match number {
+        0..=19 => ONES[number].to_string(),
+        _ if number.is_multiple_of(10) => TENS[number / 10].to_string(),
+        _ => format!("{} {}", TENS[number / 10], ONES[number % 10]),
+    }
 }
 
 fn add_terms_and_conditions(
@@ -474,7 +945,7 @@ fn add_terms_and_conditions(
     font_bold: &IndirectFontRef,
     terms: &[String],
     mut y_pos: f64,
-) {
+) -> f64 {
     layer.use_text(
         "Terms & Conditions:",
         10.0,
@@ -488,6 +959,178 @@ fn add_terms_and_conditions(
         layer.use_text(term, 9.0, Mm(MARGIN_MM), Mm(y_pos), font);
         y_pos -= TC_SECTION_LINE_SPACING;
     }
+    y_pos
+}
+
+fn bank_detail_lines(bank: &BankDetails) -> Vec<String> {
+    let mut lines = vec![
+        format!("Account Name: {}", bank.account_name),
+        format!("Account Number: {}", bank.account_number),
+        format!("IFSC: {}", bank.ifsc),
+        match &bank.branch {
+            Some(branch) => format!("Bank: {}, {}", bank.bank, branch),
+            None => format!("Bank: {}", bank.bank),
+        },
+    ];
+    if let Some(upi_id) = &bank.upi_id {
+        lines.push(format!("UPI: {}", upi_id));
+    }
+    lines
+}
+
+// The heading, the detail lines and padding below them
+fn bank_details_height(line_count: usize) -> f64 {
+    (line_count + 2) as f64 * BANK_DETAILS_LINE_SPACING
+}
+
+// A bordered box whose top edge is at y_top, with the UPI QR code on its right when there is one
+fn add_bank_details(
+    layer: &PdfLayerReference,
+    font: &IndirectFontRef,
+    font_bold: &IndirectFontRef,
+    lines: &[String],
+    upi_qr: Option<&DynamicImage>,
+    y_top: f64,
+) {
+    let height = bank_details_height(lines.len());
+    draw_horizontal_line(layer, MARGIN_MM, y_top, BANK_DETAILS_WIDTH_MM);
+    draw_horizontal_line(layer, MARGIN_MM, y_top - height, BANK_DETAILS_WIDTH_MM);
+    draw_vertical_line(layer, MARGIN_MM, y_top, height);
+    draw_vertical_line(layer, MARGIN_MM + BANK_DETAILS_WIDTH_MM, y_top, height);
+
+    let text_x = MARGIN_MM + 3.0;
+    let mut y_pos = y_top - BANK_DETAILS_LINE_SPACING - 1.0;
+    layer.use_text(
+        "Bank Details for Payment:",
+        10.0,
+        Mm(text_x),
+        Mm(y_pos),
+        font_bold,
+    );
+    for line in lines {
+        y_pos -= BANK_DETAILS_LINE_SPACING;
+        layer.use_text(line, 9.0, Mm(text_x), Mm(y_pos), font);
+    }
+
+    if let Some(qr) = upi_qr {
+        let transform = ImageTransform {
+            translate_x: Some(Mm(MARGIN_MM + BANK_DETAILS_WIDTH_MM - UPI_QR_SIZE_MM - 3.0)),
+            translate_y: Some(Mm(y_top - (height + UPI_QR_SIZE_MM) / 2.0)),
+            rotate: None,
+            scale_x: None,
+            scale_y: None,
+            // Whatever the code's pixel size, it prints UPI_QR_SIZE_MM wide
+            dpi: Some(qr.width() as f64 * 25.4 / UPI_QR_SIZE_MM),
+        };
+        Image::from_dynamic_image(qr).add_to_layer(layer.clone(), transform);
+    }
+}
+
+// Opens the payer's UPI app with our account and the proforma's grand total filled in
+fn upi_payment_uri(bank: &BankDetails, upi_id: &str, amount: f32) -> String {
+    format!(
+        "upi://pay?pa={}&pn={}&am={}&cu=INR",
+        upi_id,
+        urlencoding::encode(&bank.account_name),
+        format_amount(amount)
+    )
+}
+
+fn upi_qr_image(uri: &str) -> Result<DynamicImage, qrcode::types::QrError> {
+    let code = QrCode::new(uri.as_bytes())?;
+    let modules = code.width() as u32;
+    let colors = code.to_colors();
+    let quiet_zone = UPI_QR_QUIET_ZONE_MODULES;
+    let size = (modules + 2 * quiet_zone) * UPI_QR_PIXELS_PER_MODULE;
+
+    let image = RgbImage::from_fn(size, size, |x, y| {
+        let (column, row) = (x / UPI_QR_PIXELS_PER_MODULE, y / UPI_QR_PIXELS_PER_MODULE);
+        let in_code = (quiet_zone..quiet_zone + modules).contains(&column)
+            && (quiet_zone..quiet_zone + modules).contains(&row);
+        let dark = in_code
+            && colors[((row - quiet_zone) * modules + column - quiet_zone) as usize]
+                == qrcode::Color::Dark;
+        if dark {
+            RgbPixel([0, 0, 0])
+        } else {
+            RgbPixel([255, 255, 255])
+        }
+    });
+    Ok(DynamicImage::ImageRgb8(image))
+}
+
+// The customer's original request, on its own page(s) after the quotation
+fn add_enquiry_reference(
+    doc: &PdfDocumentReference,
+    enquiry: &str,
+    draft: bool,
+    branding: &BrandingConfig,
+    font: &IndirectFontRef,
+    font_bold: &IndirectFontRef,
+) -> Result<Vec<PdfLayerReference>, Box<dyn std::error::Error>> {
+    let new_page = || add_continuation_page(doc, draft, branding, font, font_bold);
+
+    let (mut layer, mut y_pos) = new_page()?;
+    let mut pages = vec![layer.clone()];
+    layer.use_text("Enquiry Reference", 12.0, Mm(MARGIN_MM), Mm(y_pos), font_bold);
+    y_pos -= 2.0 * ENQUIRY_LINE_SPACING;
+
+    // Line breaks in the enquiry are kept, long lines are wrapped
+    let lines = enquiry.trim().lines().flat_map(|line| {
+        let wrapped = wrap_text(line, ENQUIRY_CHARS_PER_LINE);
+        if wrapped.is_empty() {
+            vec![String::new()]
+        } else {
+            wrapped
+        }
+    });
+    for line in lines {
+        if y_pos < ENQUIRY_BOTTOM_MM {
+            (layer, y_pos) = new_page()?;
+            pages.push(layer.clone());
+        }
+        layer.use_text(line, 9.0, Mm(MARGIN_MM), Mm(y_pos), font);
+        y_pos -= ENQUIRY_LINE_SPACING;
+    }
+    Ok(pages)
+}
+
+// A large light grey "DRAFT" across the middle of the page. It has to be the first thing drawn
+// on the page, so the table and totals are printed over it.
+fn add_draft_watermark(layer: &PdfLayerReference, font_bold: &IndirectFontRef) {
+    let text = "DRAFT";
+    // Running up at 45 degrees, the text's middle lands on the page centre
+    let half_width = get_text_width(text, DRAFT_WATERMARK_FONT_SIZE_PT, true) / 2.0;
+    let offset = half_width * std::f64::consts::FRAC_1_SQRT_2;
+
+    layer.set_fill_color(Color::Rgb(Rgb::new(
+        DRAFT_WATERMARK_GREY,
+        DRAFT_WATERMARK_GREY,
+        DRAFT_WATERMARK_GREY,
+        None,
+    )));
+    layer.begin_text_section();
+    layer.set_font(font_bold, DRAFT_WATERMARK_FONT_SIZE_PT);
+    layer.set_text_matrix(TextMatrix::TranslateRotate(
+        Mm(PAGE_WIDTH_MM / 2.0 - offset).into(),
+        Mm(PAGE_HEIGHT_MM / 2.0 - offset).into(),
+        45.0,
+    ));
+    layer.write_text(text, font_bold);
+    layer.end_text_section();
+    layer.set_fill_color(Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)));
+}
+
+// Single page documents are left unnumbered
+fn add_page_numbers(page_layers: &[PdfLayerReference], font: &IndirectFontRef) {
+    if page_layers.len() < 2 {
+        return;
+    }
+    for (index, layer) in page_layers.iter().enumerate() {
+        let text = format!("Page {} of {}", index + 1, page_layers.len());
+        let x = (PAGE_WIDTH_MM - get_text_width(&text, FOOTER_FONT_SIZE_PT, false)) / 2.0;
+        layer.use_text(text, FOOTER_FONT_SIZE_PT, Mm(x), Mm(PAGE_NUMBER_Y_MM), font);
+    }
 }
 
 fn wrap_text(text: &str, max_chars_per_line: usize) -> Vec<String> {
@@ -495,7 +1138,7 @@ fn wrap_text(text: &str, max_chars_per_line: usize) -> Vec<String> {
     let mut current_line = String::new();
 
     for word in text.split_whitespace() {
-        if current_line.len() + word.len() + 1 <= max_chars_per_line {
+        if current_line.len() + word.len() < max_chars_per_line {
             if !current_line.is_empty() {
                 current_line.push(' ');
             }
@@ -527,20 +1170,14 @@ fn wrap_text(text: &str, max_chars_per_line: usize) -> Vec<String> {
     lines
 }
 
-fn draw_row_border(layer: &PdfLayerReference, x: f64, y: f64, width: f64, height: f64) {
-    let col_qty = 120.0;
-    let col_rate = 140.0;
-    let col_amount = 170.0;
-
+fn draw_row_border(layer: &PdfLayerReference, layout: &TableLayout, y: f64, height: f64) {
     // Horizontal lines
-    draw_horizontal_line(layer, x, y - height, width);
+    draw_horizontal_line(layer, layout.item, y - height, layout.width());
 
     // Vertical lines for columns
-    draw_vertical_line(layer, x, y, height);
-    draw_vertical_line(layer, col_qty, y, height);
-    draw_vertical_line(layer, col_rate, y, height);
-    draw_vertical_line(layer, col_amount, y, height);
-    draw_vertical_line(layer, x + width, y, height);
+    for x in layout.column_lines() {
+        draw_vertical_line(layer, x, y, height);
+    }
 }
 
 fn draw_horizontal_line(layer: &PdfLayerReference, x: f64, y: f64, width: f64) {
@@ -573,34 +1210,76 @@ fn draw_vertical_line(layer: &PdfLayerReference, x: f64, y: f64, height: f64) {
     layer.add_shape(line);
 }
 
-fn get_text_width(text: &str) -> f64 {
-    // Approximate text width calculation (you might want to use a more accurate method)
-    // This is a rough estimation based on character count
-    text.len() as f64 * 2.5 // Adjust multiplier based on your font size
+// Money values are printed exactly as stored - the quotation service has already rounded
+// them to paise so that the printed line amounts add up to the printed totals
+pub(crate) fn format_amount(value: f32) -> String {
+    format!("{:.2}", value)
+}
+
+// Whole quantities print without decimals, fractional lengths keep up to two places ("12.5")
+pub(crate) fn format_quantity(quantity: f32) -> String {
+    let rounded = (quantity * 100.0).round() / 100.0;
+    if rounded.fract() == 0.0 {
+        format!("{:.0}", rounded)
+    } else {
+        format!("{:.2}", rounded).trim_end_matches('0').to_string()
+    }
+}
+
+// Signed either way eg. "Rs.-0.04", "Rs.+0.40"
+fn format_round_off(round_off: f32) -> String {
+    let sign = if round_off < 0.0 { "-" } else { "+" };
+    format!("Rs.{}{}", sign, format_amount(round_off.abs()))
+}
+
+// Discount fraction as a percentage, "0%" for undiscounted items and "12.5%" for fractional ones
+fn format_discount(discount: f32) -> String {
+    format!("{}%", format_quantity(discount * 100.0))
+}
+
+fn common_quantity_unit(items: &[QuotedItem]) -> Option<QuantityUnit> {
+    let first = items.first()?.unit;
+    items
+        .iter()
+        .all(|item| item.unit == first)
+        .then_some(first)
 }
 
-fn add_marketing_footer(layer: &PdfLayerReference, font: &IndirectFontRef) {
-    let grey_color = Color::Rgb(Rgb::new(0.5, 0.5, 0.5, None)); // 50% grey
-    let blue_color = Color::Rgb(Rgb::new(0.27, 0.51, 0.71, None)); // Steel blue (70, 130, 180)
 
-    let prefix_text = "Prepared using ";
-    let emphasis_text = "AGL Intelligent Commercial Automation";
+// Start x of the prefix and the emphasis text of a two coloured line, so that together they
+// sit centred on the page
+fn centered_text_positions(
+    page_width_mm: f64,
+    prefix: &str,
+    emphasis: &str,
+    font_size_pt: f64,
+) -> (f64, f64) {
+    let prefix_width = get_text_width(prefix, font_size_pt, false);
+    let emphasis_width = get_text_width(emphasis, font_size_pt, false);
+    let start_x = (page_width_mm - prefix_width - emphasis_width) / 2.0;
+    (start_x, start_x + prefix_width)
+}
 
-    // Calculate text widths more accurately for 8pt font
-    let prefix_width = prefix_text.len() as f64 * 1.4; // Better estimate for 8pt font
-    let emphasis_width = emphasis_text.len() as f64 * 1.4;
-    let total_width = prefix_width + emphasis_width;
+fn add_marketing_footer(
+    layer: &PdfLayerReference,
+    font: &IndirectFontRef,
+    branding: &BrandingConfig,
+) {
+    let grey_color = Color::Rgb(Rgb::new(0.5, 0.5, 0.5, None)); // 50% grey
+    let blue_color = Color::Rgb(Rgb::new(0.27, 0.51, 0.71, None)); // Steel blue (70, 130, 180)
 
-    // Center the entire text block
-    let start_x = (PAGE_WIDTH_MM - total_width) / 2.0;
+    let prefix_text = branding.footer_prefix.as_str();
+    let emphasis_text = branding.footer_emphasis.as_str();
+    let (prefix_x, emphasis_x) =
+        centered_text_positions(PAGE_WIDTH_MM, prefix_text, emphasis_text, FOOTER_FONT_SIZE_PT);
 
     // Add grey prefix text
     layer.set_fill_color(grey_color);
-    layer.use_text(prefix_text, 8.0, Mm(start_x), Mm(FOOTER_Y_MM), font);
+    layer.use_text(prefix_text, FOOTER_FONT_SIZE_PT, Mm(prefix_x), Mm(FOOTER_Y_MM), font);
 
     // Add blue emphasis text (positioned after prefix text)
     layer.set_fill_color(blue_color);
-    layer.use_text(emphasis_text, 8.0, Mm(start_x + prefix_width), Mm(FOOTER_Y_MM), font);
+    layer.use_text(emphasis_text, FOOTER_FONT_SIZE_PT, Mm(emphasis_x), Mm(FOOTER_Y_MM), font);
 
     // Reset to black color for any subsequent text
     layer.set_fill_color(Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)));
@@ -612,99 +1291,54 @@ mod pdf_tests {
     use crate::prices::item_prices::*;
     use crate::quotation::*;
 
-    #[test]
-    fn test_pdf_generation() {
-        let test_quotation = QuotationResponse {
+    fn flexible_item() -> QuotedItem {
+        let flexible = Flexible {
+            core_size: "4".to_string(),
+            sqmm: "2.5".to_string(),
+            flexible_type: FlexibleType::FR,
+        };
+        QuotedItem {
+            list_price: 278.44,
+            discount: 0.1,
+            loading_frls: 0.05,
+            loading_pvc: 0.03,
+            hsn_code: Some("854449".to_string()),
+            ..QuotedItem::new(
+                Product::Cable(Cable::PowerControl(PowerControl::Flexible(flexible))),
+                "polycab",
+                100.0,
+                250.60,
+            )
+        }
+    }
+
+    fn armoured_item() -> QuotedItem {
+        let lt = LT {
+            conductor: Conductor::Copper,
+            core_size: "3".to_string(),
+            sqmm: "1.5".to_string(),
+            armoured: true,
+        };
+        QuotedItem {
+            hsn_code: Some("854449".to_string()),
+            ..QuotedItem::new(
+                Product::Cable(Cable::PowerControl(PowerControl::LT(lt))),
+                "kei",
+                50.0,
+                180.50,
+            )
+        }
+    }
+
+    fn sample_quotation() -> QuotationResponse {
+        QuotationResponse {
             items: vec![
-                QuotedItem {
-                    product: Product::Cable(Cable::PowerControl(PowerControl::Flexible(
-                        Flexible {
-                            core_size: "4".to_string(),
-                            sqmm: "2.5".to_string(),
-                            flexible_type: FlexibleType::FR,
-                        },
-                    ))),
-                    brand: "polycab".to_string(),
-                    quantity_mtrs: 100.0,
-                    price: 250.60,
-                    amount: 25060.00,
-                    loading_frls: 0.05,
-                    loading_pvc: 0.03,
-                },
-                QuotedItem {
-                    product: Product::Cable(Cable::PowerControl(PowerControl::Flexible(
-                        Flexible {
-                            core_size: "4".to_string(),
-                            sqmm: "2.5".to_string(),
-                            flexible_type: FlexibleType::FR,
-                        },
-                    ))),
-                    brand: "polycab".to_string(),
-                    quantity_mtrs: 100.0,
-                    price: 250.60,
-                    amount: 25060.00,
-                    loading_frls: 0.05,
-                    loading_pvc: 0.03,
-                },
-                QuotedItem {
-                    product: Product::Cable(Cable::PowerControl(PowerControl::Flexible(
-                        Flexible {
-                            core_size: "4".to_string(),
-                            sqmm: "2.5".to_string(),
-                            flexible_type: FlexibleType::FR,
-                        },
-                    ))),
-                    brand: "polycab".to_string(),
-                    quantity_mtrs: 100.0,
-                    price: 250.60,
-                    amount: 25060.00,
-                    loading_frls: 0.05,
-                    loading_pvc: 0.03,
-                },
-                QuotedItem {
-                    product: Product::Cable(Cable::PowerControl(PowerControl::Flexible(
-                        Flexible {
-                            core_size: "4".to_string(),
-                            sqmm: "2.5".to_string(),
-                            flexible_type: FlexibleType::FR,
-                        },
-                    ))),
-                    brand: "polycab".to_string(),
-                    quantity_mtrs: 100.0,
-                    price: 250.60,
-                    amount: 25060.00,
-                    loading_frls: 0.05,
-                    loading_pvc: 0.03,
-                },
-                QuotedItem {
-                    product: Product::Cable(Cable::PowerControl(PowerControl::Flexible(
-                        Flexible {
-                            core_size: "4".to_string(),
-                            sqmm: "2.5".to_string(),
-                            flexible_type: FlexibleType::FR,
-                        },
-                    ))),
-                    brand: "polycab".to_string(),
-                    quantity_mtrs: 100.0,
-                    price: 250.60,
-                    amount: 25060.00,
-                    loading_frls: 0.05,
-                    loading_pvc: 0.03,
-                },
-                QuotedItem {
-                    product: Product::Cable(Cable::PowerControl(PowerControl::LT(LT {
-                        conductor: Conductor::Copper,
-                        core_size: "3".to_string(),
-                        sqmm: "1.5".to_string(),
-                        armoured: true,
-                    }))),
-                    brand: "kei".to_string(),
-                    quantity_mtrs: 50.0,
-                    price: 180.50,
-                    amount: 9025.00,
-                    loading_frls: 0.0,
-                    loading_pvc: 0.0,
-                },
+                flexible_item(),
+                flexible_item(),
+                flexible_item(),
+                flexible_item(),
+                flexible_item(),
+                armoured_item(),
             ],
             basic_total: 34085.00,
             delivery_charges: 500.00,
@@ -729,7 +1363,13 @@ mod pdf_tests {
                 .map(|x| x.to_string())
                 .collect(),
             ),
-        };
+            ..QuotationResponse::default()
+        }
+    }
+
+    #[test]
+    fn test_pdf_generation() {
+        let test_quotation = sample_quotation();
 
         let result = create_quotation_pdf(
             "Q-20250821-TEST",
@@ -737,9 +1377,759 @@ mod pdf_tests {
             &test_quotation,
             "test_quotation.pdf",
             DocumentType::Quotation,
+            &TableLayout::default(),
+            &BrandingConfig::default(),
         );
 
         assert!(result.is_ok(), "PDF generation failed: {:?}", result.err());
         assert!(std::path::Path::new("artifacts/test_quotation.pdf").exists());
     }
+
+    #[test]
+    fn test_held_documents_are_moved_out_of_artifacts() {
+        let result = create_quotation_pdf(
+            "Q-20250821-HELD",
+            "21st August, 2025",
+            &sample_quotation(),
+            "test_held_quotation.pdf",
+            DocumentType::Quotation,
+            &TableLayout::default(),
+            &BrandingConfig::default(),
+        );
+        assert!(result.is_ok(), "PDF generation failed: {:?}", result.err());
+
+        let held = hold_document("artifacts/test_held_quotation.pdf").unwrap();
+        assert_eq!(held, "held/test_held_quotation.pdf");
+        assert!(Path::new(&held).exists());
+        assert!(!Path::new("artifacts/test_held_quotation.pdf").exists());
+
+        let released = release_document(&held).unwrap();
+        assert_eq!(released, "artifacts/test_held_quotation.pdf");
+        assert!(Path::new(&released).exists());
+        assert!(!Path::new(&held).exists());
+
+        // Only artifacts/ is ever served, so anything else stays put
+        assert_eq!(
+            hold_document("pricelists/cables.pdf").unwrap(),
+            "pricelists/cables.pdf"
+        );
+    }
+
+    #[test]
+    fn test_pdf_bytes_are_the_written_document() {
+        use printpdf::lopdf::Document;
+
+        let test_quotation = sample_quotation();
+        let bytes = create_quotation_pdf_bytes(
+            "Q-20250821-BYTES",
+            "21st August, 2025",
+            &test_quotation,
+            DocumentType::Quotation,
+            &TableLayout::default(),
+            &BrandingConfig::default(),
+        )
+        .unwrap();
+        create_quotation_pdf(
+            "Q-20250821-BYTES",
+            "21st August, 2025",
+            &test_quotation,
+            "test_quotation_bytes.pdf",
+            DocumentType::Quotation,
+            &TableLayout::default(),
+            &BrandingConfig::default(),
+        )
+        .unwrap();
+        let written = fs::read("artifacts/test_quotation_bytes.pdf").unwrap();
+
+        assert!(bytes.starts_with(b"%PDF"));
+        // printpdf stamps each document with a fresh id, so compare the structure
+        let page_count = |pdf: &[u8]| Document::load_mem(pdf).unwrap().get_pages().len();
+        assert_eq!(page_count(&bytes), page_count(&written));
+    }
+
+    #[test]
+    fn test_proforma_pdf_generation_with_hsn_codes() {
+        let test_quotation = sample_quotation();
+
+        let result = create_quotation_pdf(
+            "PI-20250821-TEST",
+            "21st August, 2025",
+            &test_quotation,
+            "test_proforma_hsn.pdf",
+            DocumentType::ProformaInvoice,
+            &TableLayout::default(),
+            &BrandingConfig::default(),
+        );
+
+        assert!(result.is_ok(), "PDF generation failed: {:?}", result.err());
+        assert!(std::path::Path::new("artifacts/test_proforma_hsn.pdf").exists());
+    }
+
+    #[test]
+    fn test_proforma_pdf_carries_bank_details() {
+        use printpdf::lopdf::Document;
+
+        let mut test_quotation = sample_quotation();
+        test_quotation.bank_details = Some(BankDetails {
+            account_name: "Skipper Cables Pvt Ltd".to_string(),
+            account_number: "50200012345678".to_string(),
+            ifsc: "HDFC0000123".to_string(),
+            bank: "HDFC Bank".to_string(),
+            branch: Some("Fort, Mumbai".to_string()),
+            upi_id: Some("skippercables@hdfcbank".to_string()),
+        });
+        let text = |quotation: &QuotationResponse, document_type: DocumentType| {
+            let pdf = create_quotation_pdf_bytes(
+                "PI-20250821-BANK",
+                "21st August, 2025",
+                quotation,
+                document_type,
+                &TableLayout::default(),
+                &BrandingConfig::default(),
+            )
+            .unwrap();
+            let doc = Document::load_mem(&pdf).unwrap();
+            let pages: Vec<u32> = doc.get_pages().keys().copied().collect();
+            doc.extract_text(&pages).unwrap()
+        };
+
+        let proforma = text(&test_quotation, DocumentType::ProformaInvoice);
+        assert!(proforma.contains("Bank Details for Payment:"));
+        assert!(proforma.contains("IFSC: HDFC0000123"));
+        assert!(proforma.contains("Bank: HDFC Bank, Fort, Mumbai"));
+        assert!(proforma.contains("UPI: skippercables@hdfcbank"));
+        // Quotations aren't paid against
+        assert!(!text(&test_quotation, DocumentType::Quotation).contains("Bank Details"));
+
+        // Pushed down by a long list of terms, the block breaks onto the next page if it must
+        test_quotation.terms_and_conditions = Some(vec!["Payment: 100% advance".to_string(); 25]);
+        assert!(text(&test_quotation, DocumentType::ProformaInvoice).contains("IFSC: HDFC0000123"));
+        let result = create_quotation_pdf(
+            "PI-20250821-BANK",
+            "21st August, 2025",
+            &test_quotation,
+            "test_proforma_bank_details.pdf",
+            DocumentType::ProformaInvoice,
+            &TableLayout::default(),
+            &BrandingConfig::default(),
+        );
+        assert!(result.is_ok(), "PDF generation failed: {:?}", result.err());
+    }
+
+    #[test]
+    fn test_proforma_pdf_with_upi_qr_code() {
+        use printpdf::lopdf::{Document, Object};
+
+        let bank = BankDetails {
+            account_name: "Skipper Cables Pvt Ltd".to_string(),
+            account_number: "50200012345678".to_string(),
+            ifsc: "HDFC0000123".to_string(),
+            bank: "HDFC Bank".to_string(),
+            branch: None,
+            upi_id: Some("skippercables@hdfcbank".to_string()),
+        };
+        let test_quotation = sample_quotation();
+        assert_eq!(
+            upi_payment_uri(&bank, "skippercables@hdfcbank", test_quotation.grand_total),
+            format!(
+                "upi://pay?pa=skippercables@hdfcbank&pn=Skipper%20Cables%20Pvt%20Ltd&am={}&cu=INR",
+                format_amount(test_quotation.grand_total)
+            )
+        );
+        let qr = upi_qr_image("upi://pay?pa=skippercables@hdfcbank&am=100.00").unwrap();
+        assert_eq!(qr.width(), qr.height());
+        assert_eq!(qr.width() % UPI_QR_PIXELS_PER_MODULE, 0);
+
+        // The shared header image, plus the QR code when there is a UPI id to pay
+        let image_count = |bank: BankDetails| {
+            let mut quotation = sample_quotation();
+            quotation.bank_details = Some(bank);
+            let pdf = create_quotation_pdf_bytes(
+                "PI-20250821-UPI",
+                "21st August, 2025",
+                &quotation,
+                DocumentType::ProformaInvoice,
+                &TableLayout::default(),
+                &BrandingConfig::default(),
+            )
+            .unwrap();
+            Document::load_mem(&pdf)
+                .unwrap()
+                .objects
+                .values()
+                .filter(|object| match object {
+                    Object::Stream(stream) => stream
+                        .dict
+                        .get(b"Subtype")
+                        .and_then(|subtype| subtype.as_name())
+                        .is_ok_and(|subtype| subtype == b"Image"),
+                    _ => false,
+                })
+                .count()
+        };
+        assert_eq!(image_count(bank.clone()), 2);
+        assert_eq!(
+            image_count(BankDetails {
+                upi_id: None,
+                ..bank
+            }),
+            1
+        );
+    }
+
+    #[test]
+    fn test_pdf_generation_with_mixed_hsn_codes() {
+        let mut test_quotation = sample_quotation();
+        // Every other row without a code, so blank HSN cells sit between filled ones
+        for item in test_quotation.items.iter_mut().step_by(2) {
+            item.hsn_code = None;
+        }
+        test_quotation.items[1].hsn_code = Some("85444920".to_string());
+
+        let result = create_quotation_pdf(
+            "Q-20250821-HSN",
+            "21st August, 2025",
+            &test_quotation,
+            "test_mixed_hsn.pdf",
+            DocumentType::Quotation,
+            &TableLayout::default(),
+            &BrandingConfig::default(),
+        );
+
+        assert!(result.is_ok(), "PDF generation failed: {:?}", result.err());
+        assert!(std::path::Path::new("artifacts/test_mixed_hsn.pdf").exists());
+    }
+
+    #[test]
+    fn test_item_rows_show_list_rate_and_discount() {
+        use printpdf::lopdf::Document;
+
+        let pdf = create_quotation_pdf_bytes(
+            "Q-20250821-DISC",
+            "21st August, 2025",
+            &sample_quotation(),
+            DocumentType::Quotation,
+            &TableLayout::default(),
+            &BrandingConfig::default(),
+        )
+        .unwrap();
+        let doc = Document::load_mem(&pdf).unwrap();
+        let text = doc.extract_text(&[1]).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert!(lines.contains(&"List Rate"));
+        assert!(lines.contains(&"Disc %"));
+        // 278.44 less 10% is the 250.60 net rate
+        let flexible = lines.iter().position(|line| *line == "278.44").unwrap();
+        assert_eq!(lines[flexible + 1..flexible + 3], ["10%", "250.60"]);
+        // Undiscounted items still say so
+        let armoured = lines.iter().position(|line| *line == "180.50").unwrap();
+        assert_eq!(lines[armoured + 1..armoured + 3], ["0%", "180.50"]);
+
+        assert_eq!(format_discount(0.125), "12.5%");
+    }
+
+    #[test]
+    fn test_right_aligned_amounts_line_up() {
+        let value_x = TABLE_RIGHT_MM + 5.0;
+        let short = format!("Rs.{}", format_amount(100.0));
+        let long = format!("Rs.{}", format_amount(12345678.0));
+        assert_eq!(
+            (short.as_str(), long.as_str()),
+            ("Rs.100.00", "Rs.12345678.00")
+        );
+
+        // Right edges from the Helvetica widths summed by hand, 10pt = 3.5278mm per em
+        let short_right = value_x - get_text_width(&short, 10.0, false) + 4.558 * 3.5278;
+        let long_right = value_x - get_text_width(&long, 10.0, false) + 7.338 * 3.5278;
+        assert!((short_right - long_right).abs() < 0.5);
+        assert!((short_right - value_x).abs() < 0.5);
+    }
+
+    #[test]
+    fn test_amount_to_indian_words() {
+        assert_eq!(
+            amount_to_indian_words(40810.30),
+            "Rupees Forty Thousand Eight Hundred Ten and Thirty Paise Only"
+        );
+        assert_eq!(amount_to_indian_words(100000.0), "Rupees One Lakh Only");
+        assert_eq!(amount_to_indian_words(1.0), "Rupees One Only");
+        assert_eq!(
+            amount_to_indian_words(12345678.0),
+            "Rupees One Crore Twenty Three Lakh Forty Five Thousand Six Hundred Seventy Eight Only"
+        );
+        assert_eq!(amount_to_indian_words(0.0), "Rupees Zero Only");
+        assert_eq!(
+            amount_to_indian_words(0.5),
+            "Rupees Zero and Fifty Paise Only"
+        );
+        assert_eq!(
+            amount_to_indian_words(2_150_000_000.07),
+            "Rupees Two Hundred Fifteen Crore and Seven Paise Only"
+        );
+        // Grand totals are f32, which must not lose the paise on the way
+        assert_eq!(
+            amount_to_indian_words(f64::from(40810.3_f32)),
+            "Rupees Forty Thousand Eight Hundred Ten and Thirty Paise Only"
+        );
+    }
+
+    #[test]
+    fn test_format_quantity() {
+        assert_eq!(format_quantity(100.0), "100");
+        assert_eq!(format_quantity(12.5), "12.5");
+        assert_eq!(format_quantity(12.25), "12.25");
+        assert_eq!(format_quantity(0.999), "1");
+    }
+
+    #[test]
+    fn test_footer_block_is_centred() {
+        let prefix = "Prepared using ";
+        let emphasis = "AGL Intelligent Commercial Automation";
+        // A4, Letter and A4 landscape
+        for page_width in [210.0, 215.9, 297.0] {
+            for font_size in [8.0, 10.0] {
+                let (prefix_x, emphasis_x) =
+                    centered_text_positions(page_width, prefix, emphasis, font_size);
+                let block_end = emphasis_x + get_text_width(emphasis, font_size, false);
+
+                assert!((prefix_x - (page_width - block_end)).abs() < 0.01);
+                assert!(
+                    (emphasis_x - prefix_x - get_text_width(prefix, font_size, false)).abs() < 0.01
+                );
+            }
+        }
+        // A larger font takes up more of the page
+        assert!(
+            centered_text_positions(210.0, prefix, emphasis, 10.0).0
+                < centered_text_positions(210.0, prefix, emphasis, 8.0).0
+        );
+    }
+
+    #[test]
+    fn test_pdf_generation_with_fractional_quantity() {
+        let mut test_quotation = sample_quotation();
+        test_quotation.items[0].quantity_mtrs = 12.5;
+        assert_eq!(
+            common_quantity_unit(&test_quotation.items),
+            Some(QuantityUnit::Metre)
+        );
+
+        let result = create_quotation_pdf(
+            "Q-20250821-FRAC",
+            "21st August, 2025",
+            &test_quotation,
+            "test_quotation_fractional.pdf",
+            DocumentType::Quotation,
+            &TableLayout::default(),
+            &BrandingConfig::default(),
+        );
+
+        assert!(result.is_ok(), "PDF generation failed: {:?}", result.err());
+        assert!(std::path::Path::new("artifacts/test_quotation_fractional.pdf").exists());
+    }
+
+    #[test]
+    fn test_pdf_generation_with_mixed_units() {
+        let mut test_quotation = sample_quotation();
+        test_quotation.items[1].unit = QuantityUnit::Piece;
+        test_quotation.items[1].quantity_mtrs = 20.0;
+        assert_eq!(common_quantity_unit(&test_quotation.items), None);
+
+        let result = create_quotation_pdf(
+            "Q-20250821-UNITS",
+            "21st August, 2025",
+            &test_quotation,
+            "test_quotation_mixed_units.pdf",
+            DocumentType::Quotation,
+            &TableLayout::default(),
+            &BrandingConfig::default(),
+        );
+
+        assert!(result.is_ok(), "PDF generation failed: {:?}", result.err());
+        assert!(std::path::Path::new("artifacts/test_quotation_mixed_units.pdf").exists());
+    }
+
+    #[test]
+    fn test_optimized_pdf_shares_one_header_image() {
+        use printpdf::lopdf::{Document, Object};
+
+        // Enough rows to spill over several pages, each with the header
+        let mut test_quotation = sample_quotation();
+        test_quotation.items = (0..40).map(|_| armoured_item()).collect();
+
+        let raw = render_quotation_pdf(
+            "Q-20250821-PAGES",
+            "21st August, 2025",
+            &test_quotation,
+            DocumentType::Quotation,
+            &TableLayout::default(),
+            &BrandingConfig::default(),
+        )
+        .unwrap();
+        let optimized = optimize::optimize_pdf(&raw).unwrap();
+
+        let raw_doc = Document::load_mem(&raw).unwrap();
+        let optimized_doc = Document::load_mem(&optimized).unwrap();
+        let image_count = |doc: &Document| {
+            doc.objects
+                .values()
+                .filter(|object| match object {
+                    Object::Stream(stream) => stream
+                        .dict
+                        .get(b"Subtype")
+                        .and_then(|subtype| subtype.as_name())
+                        .is_ok_and(|subtype| subtype == b"Image"),
+                    _ => false,
+                })
+                .count()
+        };
+
+        let pages = raw_doc.get_pages();
+        assert!(pages.len() > 1);
+        assert_eq!(image_count(&raw_doc), pages.len());
+        assert_eq!(image_count(&optimized_doc), 1);
+        assert!(optimized.len() * 2 < raw.len());
+
+        // Every page draws exactly what it did before
+        let optimized_pages = optimized_doc.get_pages();
+        assert_eq!(optimized_pages.len(), pages.len());
+        for (page_number, page_id) in &pages {
+            assert_eq!(
+                optimized_doc
+                    .get_page_content(optimized_pages[page_number])
+                    .unwrap(),
+                raw_doc.get_page_content(*page_id).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_pdf_generation_with_estimated_delivery() {
+        let mut test_quotation = sample_quotation();
+        test_quotation.estimated_delivery = Some("2 weeks".to_string());
+
+        let result = create_quotation_pdf(
+            "Q-20250821-ETA",
+            "21st August, 2025",
+            &test_quotation,
+            "test_quotation_delivery.pdf",
+            DocumentType::Quotation,
+            &TableLayout::default(),
+            &BrandingConfig::default(),
+        );
+
+        assert!(result.is_ok(), "PDF generation failed: {:?}", result.err());
+        assert!(std::path::Path::new("artifacts/test_quotation_delivery.pdf").exists());
+    }
+
+    #[test]
+    fn test_pdf_generation_with_volume_discount() {
+        let mut test_quotation = sample_quotation();
+        test_quotation.volume_discount = Some(VolumeDiscount {
+            rate: 0.03,
+            amount: 1022.55,
+        });
+
+        let result = create_quotation_pdf(
+            "Q-20250821-VOL",
+            "21st August, 2025",
+            &test_quotation,
+            "test_quotation_volume_discount.pdf",
+            DocumentType::Quotation,
+            &TableLayout::default(),
+            &BrandingConfig::default(),
+        );
+
+        assert!(result.is_ok(), "PDF generation failed: {:?}", result.err());
+        assert!(std::path::Path::new("artifacts/test_quotation_volume_discount.pdf").exists());
+    }
+
+    #[test]
+    fn test_pdf_generation_with_cgst_and_sgst() {
+        let mut test_quotation = sample_quotation();
+        test_quotation.buyer_state = Some("Maharashtra".to_string());
+        test_quotation.seller_state = Some("Maharashtra".to_string());
+        test_quotation.delivery_charges = 500.0;
+        assert_eq!(test_quotation.tax_lines().len(), 2);
+
+        let result = create_quotation_pdf(
+            "PI-20250821-CGST",
+            "21st August, 2025",
+            &test_quotation,
+            "test_proforma_cgst_sgst.pdf",
+            DocumentType::ProformaInvoice,
+            &TableLayout::default(),
+            &BrandingConfig::default(),
+        );
+
+        assert!(result.is_ok(), "PDF generation failed: {:?}", result.err());
+        assert!(std::path::Path::new("artifacts/test_proforma_cgst_sgst.pdf").exists());
+    }
+
+    #[test]
+    fn test_round_off_line_is_printed_when_needed() {
+        use printpdf::lopdf::Document;
+
+        let text = |quotation: &QuotationResponse| {
+            let pdf = create_quotation_pdf_bytes(
+                "Q-20250821-ROUND",
+                "21st August, 2025",
+                quotation,
+                DocumentType::Quotation,
+                &TableLayout::default(),
+                &BrandingConfig::default(),
+            )
+            .unwrap();
+            let doc = Document::load_mem(&pdf).unwrap();
+            let pages: Vec<u32> = doc.get_pages().keys().copied().collect();
+            doc.extract_text(&pages).unwrap()
+        };
+
+        let mut test_quotation = sample_quotation();
+        assert!(!text(&test_quotation).contains("Round Off:"));
+
+        test_quotation.round_off = -0.04;
+        let rounded = text(&test_quotation);
+        assert!(rounded.contains("Round Off:"));
+        assert!(rounded.lines().any(|line| line == "Rs.-0.04"));
+        assert_eq!(format_round_off(0.19), "Rs.+0.19");
+    }
+
+    #[test]
+    fn test_packing_forwarding_line_is_printed_when_charged() {
+        use printpdf::lopdf::Document;
+
+        let mut test_quotation = sample_quotation();
+        let text = |quotation: &QuotationResponse| {
+            let pdf = create_quotation_pdf_bytes(
+                "Q-20250821-PF",
+                "21st August, 2025",
+                quotation,
+                DocumentType::Quotation,
+                &TableLayout::default(),
+                &BrandingConfig::default(),
+            )
+            .unwrap();
+            let doc = Document::load_mem(&pdf).unwrap();
+            let pages: Vec<u32> = doc.get_pages().keys().copied().collect();
+            doc.extract_text(&pages).unwrap()
+        };
+        assert!(!text(&test_quotation).contains("Packing & Forwarding:"));
+
+        test_quotation.packing_forwarding = 12345.0;
+        let charged = text(&test_quotation);
+        assert!(charged.contains("Packing & Forwarding:"));
+        assert!(charged.lines().any(|line| line == "Rs.12345.00"));
+    }
+
+    #[test]
+    fn test_header_fit_keeps_content_clear_of_tall_headers() {
+        // The bundled letterhead spans the page without moving anything
+        let fit = HeaderFit::new(1039.0, 253.0);
+        assert!(!fit.capped);
+        assert!((fit.width_mm - PAGE_WIDTH_MM).abs() < 1e-6);
+        assert_eq!(fit.content_offset(), 0.0);
+
+        // A taller one pushes the content down by the extra height
+        let fit = HeaderFit::new(1000.0, 330.0);
+        assert!(!fit.capped);
+        assert!((fit.height_mm - 69.3).abs() < 1e-3);
+        assert!((fit.content_offset() - (69.3 - DESIGNED_HEADER_HEIGHT_MM)).abs() < 1e-3);
+
+        // A portrait image is shrunk to the cap, keeping its proportions
+        let fit = HeaderFit::new(500.0, 1000.0);
+        assert!(fit.capped);
+        assert!((fit.height_mm - MAX_HEADER_HEIGHT_MM).abs() < 1e-6);
+        assert!((fit.width_mm - MAX_HEADER_HEIGHT_MM / 2.0).abs() < 1e-6);
+        assert_eq!(
+            fit.content_offset(),
+            MAX_HEADER_HEIGHT_MM - DESIGNED_HEADER_HEIGHT_MM
+        );
+        // The first page still has room for the To section and several rows
+        assert!(BASE_TABLE_START_Y - fit.content_offset() > 150.0);
+    }
+
+    #[test]
+    fn test_table_layout_columns() {
+        let layout = TableLayout::default();
+        assert_eq!(
+            layout.column_lines(),
+            [MARGIN_MM, 84.0, 100.0, 120.0, 140.0, 155.0, 175.0, TABLE_RIGHT_MM]
+        );
+        // The HSN column comes out of the description, which wraps sooner for it
+        assert_eq!(layout.description_chars, 40);
+
+        // A wider description column wraps less and squeezes the number columns
+        let wide = TableLayout::new(95.0);
+        assert_eq!(
+            wide.column_lines(),
+            [MARGIN_MM, 89.0, 105.0, 124.0, 143.0, 157.25, 176.25, TABLE_RIGHT_MM]
+        );
+        assert!(wide.description_chars > layout.description_chars);
+        assert_eq!(wide.width(), layout.width());
+        // Out of range widths are clamped rather than pushing columns off the page
+        assert_eq!(TableLayout::new(500.0), wide);
+        assert_eq!(
+            TableLayout::new(0.0).qty,
+            MARGIN_MM + MIN_DESCRIPTION_WIDTH_MM
+        );
+
+        let result = create_quotation_pdf(
+            "Q-20250821-WIDE",
+            "21st August, 2025",
+            &sample_quotation(),
+            "test_quotation_wide_description.pdf",
+            DocumentType::Quotation,
+            &wide,
+            &BrandingConfig::default(),
+        );
+        assert!(result.is_ok(), "PDF generation failed: {:?}", result.err());
+    }
+
+    #[test]
+    fn test_enquiry_reference_is_appended_as_a_page() {
+        use printpdf::lopdf::Document;
+
+        let page_count = |quotation: &QuotationResponse| {
+            let pdf = render_quotation_pdf(
+                "Q-20250821-ENQ",
+                "21st August, 2025",
+                quotation,
+                DocumentType::Quotation,
+                &TableLayout::default(),
+                &BrandingConfig::default(),
+            )
+            .unwrap();
+            Document::load_mem(&pdf).unwrap().get_pages().len()
+        };
+
+        let mut test_quotation = sample_quotation();
+        let without_enquiry = page_count(&test_quotation);
+
+        test_quotation.enquiry =
+            Some("Please quote for\n\n100m 4C x 2.5 sqmm armoured copper cable".to_string());
+        assert_eq!(page_count(&test_quotation), without_enquiry + 1);
+
+        // A long enquiry carries on over further pages
+        test_quotation.enquiry = Some(vec!["4C x 16 sqmm armoured, 500m"; 80].join("\n"));
+        assert_eq!(page_count(&test_quotation), without_enquiry + 2);
+    }
+
+    #[test]
+    fn test_draft_pdf_generation() {
+        let mut test_quotation = sample_quotation();
+        test_quotation.draft = true;
+        test_quotation.enquiry = Some("Please quote for 100m 4C x 2.5 sqmm".to_string());
+
+        let result = create_quotation_pdf(
+            "Q-20250821-DRAFT",
+            "21st August, 2025",
+            &test_quotation,
+            "test_quotation_draft.pdf",
+            DocumentType::Quotation,
+            &TableLayout::default(),
+            &BrandingConfig::default(),
+        );
+
+        assert!(result.is_ok(), "PDF generation failed: {:?}", result.err());
+        let doc = printpdf::lopdf::Document::load("artifacts/test_quotation_draft.pdf").unwrap();
+        for page in 1..=doc.get_pages().len() as u32 {
+            assert!(doc.extract_text(&[page]).unwrap().starts_with("DRAFT"));
+        }
+    }
+
+    #[test]
+    fn test_pdf_generation_with_custom_branding() {
+        use ::image::{DynamicImage, ImageFormat, RgbImage};
+        use printpdf::lopdf::Document;
+
+        let dir = std::env::temp_dir().join(format!("branding-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let header_path = dir.join("letterhead.jpg");
+        DynamicImage::ImageRgb8(RgbImage::new(1200, 300))
+            .save_with_format(&header_path, ImageFormat::Jpeg)
+            .unwrap();
+        let branding = BrandingConfig {
+            header_image_path: header_path.to_string_lossy().to_string(),
+            footer_prefix: "Quoted by ".to_string(),
+            footer_emphasis: "Acme Cables".to_string(),
+        };
+        let render = |branding: &BrandingConfig| {
+            create_quotation_pdf_bytes(
+                "Q-20250821-BRAND",
+                "21st August, 2025",
+                &sample_quotation(),
+                DocumentType::Quotation,
+                &TableLayout::default(),
+                branding,
+            )
+        };
+
+        let pdf = render(&branding).unwrap();
+        let text = Document::load_mem(&pdf)
+            .unwrap()
+            .extract_text(&[1])
+            .unwrap();
+        // The footer is printed as two runs - the prefix, then the emphasis in blue
+        assert!(text.contains("Quoted by"));
+        assert!(text.contains("Acme Cables"));
+        assert!(!text.contains("AGL Intelligent Commercial Automation"));
+
+        // A letterhead that isn't there fails the document rather than printing it bare
+        let missing = BrandingConfig {
+            header_image_path: dir.join("missing.jpg").to_string_lossy().to_string(),
+            ..BrandingConfig::default()
+        };
+        assert!(render(&missing).is_err());
+        let png_path = dir.join("letterhead.png");
+        DynamicImage::ImageRgb8(RgbImage::new(1200, 300))
+            .save_with_format(&png_path, ImageFormat::Png)
+            .unwrap();
+        let png = BrandingConfig {
+            header_image_path: png_path.to_string_lossy().to_string(),
+            ..BrandingConfig::default()
+        };
+        assert!(render(&png).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_multi_page_documents_are_numbered() {
+        use printpdf::lopdf::Document;
+
+        let render = |quotation: &QuotationResponse| {
+            let pdf = render_quotation_pdf(
+                "Q-20250821-PAGES",
+                "21st August, 2025",
+                quotation,
+                DocumentType::Quotation,
+                &TableLayout::default(),
+                &BrandingConfig::default(),
+            )
+            .unwrap();
+            Document::load_mem(&pdf).unwrap()
+        };
+
+        let mut test_quotation = sample_quotation();
+        test_quotation.items.truncate(1);
+        let single = render(&test_quotation);
+        assert_eq!(single.get_pages().len(), 1);
+        assert!(!single.extract_text(&[1]).unwrap().contains("Page 1 of 1"));
+
+        // One page of quotation, then two of enquiry reference
+        test_quotation.enquiry = Some(vec!["4C x 16 sqmm armoured, 500m"; 80].join("\n"));
+        let doc = render(&test_quotation);
+        assert_eq!(doc.get_pages().len(), 3);
+        for page in 1..=3 {
+            let text = doc.extract_text(&[page]).unwrap();
+            assert!(
+                text.contains(&format!("Page {} of 3", page)),
+                "page {} is missing its number: {}",
+                page,
+                text
+            );
+        }
+    }
 }
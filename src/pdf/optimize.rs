@@ -0,0 +1,68 @@
+use printpdf::lopdf::{Document, Object, ObjectId};
+use std::collections::HashMap;
+
+// printpdf embeds its own copy of the header image on every page. Identical streams are
+// collapsed into a single object that every page refers to, then everything left is flate
+// compressed. Page content streams are untouched, so the document renders the same.
+pub(crate) fn optimize_pdf(pdf: &[u8]) -> Result<Vec<u8>, printpdf::lopdf::Error> {
+    let mut doc = Document::load_mem(pdf)?;
+
+    let duplicates = find_duplicate_streams(&doc);
+    if !duplicates.is_empty() {
+        for object in doc.objects.values_mut() {
+            replace_references(object, &duplicates);
+        }
+        doc.prune_objects();
+    }
+    doc.compress();
+
+    let mut optimized = Vec::new();
+    doc.save_to(&mut optimized)?;
+    Ok(optimized)
+}
+
+// Maps every repeated stream to the first identical one
+fn find_duplicate_streams(doc: &Document) -> HashMap<ObjectId, ObjectId> {
+    let mut first_seen: HashMap<(String, &[u8]), ObjectId> = HashMap::new();
+    let mut duplicates = HashMap::new();
+    for (id, object) in &doc.objects {
+        if let Object::Stream(stream) = object {
+            let key = (format!("{:?}", stream.dict), stream.content.as_slice());
+            match first_seen.get(&key) {
+                Some(original) => {
+                    duplicates.insert(*id, *original);
+                }
+                None => {
+                    first_seen.insert(key, *id);
+                }
+            }
+        }
+    }
+    duplicates
+}
+
+fn replace_references(object: &mut Object, replacements: &HashMap<ObjectId, ObjectId>) {
+    match object {
+        Object::Reference(id) => {
+            if let Some(replacement) = replacements.get(id) {
+                *id = *replacement;
+            }
+        }
+        Object::Array(items) => {
+            for item in items {
+                replace_references(item, replacements);
+            }
+        }
+        Object::Dictionary(dict) => {
+            for (_, value) in dict.iter_mut() {
+                replace_references(value, replacements);
+            }
+        }
+        Object::Stream(stream) => {
+            for (_, value) in stream.dict.iter_mut() {
+                replace_references(value, replacements);
+            }
+        }
+        _ => {}
+    }
+}
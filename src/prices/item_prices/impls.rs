@@ -3,6 +3,37 @@ use super::Description;
 use crate::prices::utils::normalize_decimal;
 use std::collections::HashMap;
 
+impl QuantityUnit {
+    // Quantity label eg. "Qty (Mtr)"
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Metre => "Mtr",
+            Self::Piece => "Nos",
+            Self::Kilometre => "Km",
+        }
+    }
+
+    // Per unit suffix for rates eg. "Rate/mtr."
+    pub fn rate_suffix(&self) -> &'static str {
+        match self {
+            Self::Metre => "mtr",
+            Self::Piece => "no",
+            Self::Kilometre => "km",
+        }
+    }
+
+    // How many `other` make one of this unit eg. 1000 metres to the kilometre. None when a
+    // length can't be counted in pieces or the other way round.
+    pub fn in_units_of(&self, other: QuantityUnit) -> Option<f32> {
+        match (self, other) {
+            (a, b) if *a == b => Some(1.0),
+            (Self::Kilometre, Self::Metre) => Some(1000.0),
+            (Self::Metre, Self::Kilometre) => Some(0.001),
+            _ => None,
+        }
+    }
+}
+
 impl Description for Product {
     fn get_description(&self, extras: Vec<String>) -> String {
         match self {
@@ -283,6 +314,30 @@ impl Product {
             Product::Cable(cable) => Product::Cable(cable.normalize()),
         }
     }
+
+    // HSN (GST tariff) code by product category - required on proforma invoices
+    pub fn hsn_code(&self) -> Option<&'static str> {
+        match self {
+            Product::Cable(cable) => Some(match cable {
+                // Insulated conductors above 1000V
+                Cable::PowerControl(PowerControl::HT(_)) => "854460",
+                Cable::Coaxial(_) => "854420",
+                // Insulated conductors up to 1000V, not fitted with connectors
+                Cable::PowerControl(PowerControl::LT(_))
+                | Cable::PowerControl(PowerControl::Flexible(_))
+                | Cable::Telephone { .. }
+                | Cable::Submersible { .. }
+                | Cable::Solar { .. } => "854449",
+            }),
+        }
+    }
+
+    // Unit used when the pricelist doesn't give one - cables are sold by length
+    pub fn unit(&self) -> QuantityUnit {
+        match self {
+            Self::Cable(_) => QuantityUnit::Metre,
+        }
+    }
 }
 
 impl Cable {
@@ -357,9 +412,14 @@ impl Flexible {
 impl PricingSystem {
     pub fn from_price_list(price_list: PriceList) -> Self {
         let mut prices = HashMap::new();
+        let mut units = HashMap::new();
 
         for price_entry in price_list.prices {
-            prices.insert(price_entry.product.normalize(), price_entry.price);
+            let product = price_entry.product.normalize();
+            if let Some(unit) = price_entry.unit.or(price_list.unit) {
+                units.insert(product.clone(), unit);
+            }
+            prices.insert(product, price_entry.price);
         }
 
         PricingSystem {
@@ -369,6 +429,7 @@ impl PricingSystem {
                 .map(|tag| tag.trim().to_lowercase())
                 .collect(),
             prices,
+            units,
         }
     }
 
@@ -379,6 +440,14 @@ impl PricingSystem {
             None
         }
     }
+
+    pub fn get_unit(&self, product: &Product, tag: &str) -> Option<QuantityUnit> {
+        if self.tags.contains(&tag.to_string().trim().to_lowercase()) {
+            self.units.get(&product.normalize()).copied()
+        } else {
+            None
+        }
+    }
 }
 
 #[cfg(test)]
@@ -418,4 +487,28 @@ mod pricelist_tests {
             assert!(!pricelist.prices.is_empty(), "Prices should not be empty");
         }
     }
+
+    #[test]
+    fn test_entry_unit_overrides_pricelist_unit() {
+        use crate::prices::item_prices::{Cable, CoaxialType, PricingSystem, Product, QuantityUnit};
+
+        let pricelist: PriceList = serde_json::from_str(
+            r#"{
+                "tags": ["latest"],
+                "unit": "Nos",
+                "prices": [
+                    {"product": {"Cable": {"Coaxial": "RG6"}}, "price": 12.3},
+                    {"product": {"Cable": {"Coaxial": "RG11"}}, "price": 20.0, "unit": "Metre"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let pricing_system = PricingSystem::from_price_list(pricelist);
+        let rg6 = Product::Cable(Cable::Coaxial(CoaxialType::RG6));
+        let rg11 = Product::Cable(Cable::Coaxial(CoaxialType::RG11));
+
+        assert_eq!(pricing_system.get_unit(&rg6, "latest"), Some(QuantityUnit::Piece));
+        assert_eq!(pricing_system.get_unit(&rg11, "latest"), Some(QuantityUnit::Metre));
+        assert_eq!(pricing_system.get_unit(&rg6, "july 2025"), None);
+    }
 }
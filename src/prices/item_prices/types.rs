@@ -94,9 +94,27 @@ pub enum Conductor {
     Aluminium,
 }
 
+/// Unit an item is sold and quoted in
+#[derive(PartialEq, Eq, Hash, Deserialize, Clone, Copy, Debug, Default, Serialize, JsonSchema)]
+pub enum QuantityUnit {
+    /// Sold by length eg. cables
+    #[default]
+    #[serde(alias = "Mtr", alias = "mtr", alias = "Meter")]
+    Metre,
+    /// Sold per piece eg. lugs, glands, switchgear
+    #[serde(alias = "Nos", alias = "nos", alias = "Pcs")]
+    Piece,
+    /// Bulk cable quoted by the kilometre
+    #[serde(alias = "Km", alias = "km", alias = "Kilometer")]
+    Kilometre,
+}
+
 #[derive(Deserialize, Clone, Debug)]
 pub struct PriceList {
     pub tags: Vec<String>,
+    // Unit for every entry that doesn't set its own
+    #[serde(default)]
+    pub unit: Option<QuantityUnit>,
     pub prices: Vec<Prices>,
 }
 
@@ -104,9 +122,13 @@ pub struct PriceList {
 pub struct Prices {
     pub product: Product,
     pub price: f32,
+    #[serde(default)]
+    pub unit: Option<QuantityUnit>,
 }
 
 pub struct PricingSystem {
     pub tags: Vec<String>,
     pub prices: HashMap<Product, f32>,
+    // Only entries whose pricelist gives a unit - the rest use the product's default
+    pub units: HashMap<Product, QuantityUnit>,
 }
@@ -1,18 +1,26 @@
 use crate::communication::price_alert::PriceAlert;
-use crate::configuration::Context;
+use crate::configuration::{AlertTime, Context, MetalPriceSource};
 use crate::core::cache::ExpirableCache;
-use crate::core::http::RetryableClient;
+use crate::core::http::{RetryPolicy, RetryableClient};
 use crate::core::service_manager::Error as ServiceManagerError;
 use crate::core::{service_manager::ServiceWithSender, Service};
+use crate::database::DatabaseService;
 use async_trait::async_trait;
-use chrono::{DateTime, Timelike, Utc};
+use chrono::{DateTime, NaiveDate, Timelike, Utc};
 use chrono_tz::Asia::Kolkata;
 use reqwest;
 use scraper::{Html, Selector};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use std::time::Duration;
 use thiserror::Error;
 use tokio::sync::mpsc;
-use tracing::{error, info};
+use tracing::{error, info, warn};
+
+// The scrape is a single small page - a stalled site should fail the query quickly
+const METAL_PRICE_RETRY_POLICY: RetryPolicy = RetryPolicy::new()
+    .with_timeout(Duration::from_secs(10))
+    .with_max_attempts(2);
 
 pub mod item_prices;
 pub mod price_list;
@@ -40,11 +48,17 @@ pub enum PriceError {
 }
 
 pub struct PriceService {
-    pub url_al: String,
-    pub url_cu: String,
+    pub metal_sources: HashMap<String, Vec<MetalPriceSource>>,
+    pub database: Arc<DatabaseService>,
     pub price_channel: Option<mpsc::Sender<String>>,
     pub price_cache: ExpirableCache<String, f64>,
-    pub last_alert_hour: Option<u32>,
+    pub alert_times: Vec<AlertTime>,
+    // Windows already alerted on `alerted_date`, so each sends at most once a day
+    pub alerted_windows: HashSet<AlertTime>,
+    pub alerted_date: Option<NaiveDate>,
+    pub alert_threshold_percent: Option<f64>,
+    // (copper, aluminium) as of the last alert sent
+    pub last_alerted_prices: Option<(f64, f64)>,
     pub client: RetryableClient,
 }
 
@@ -56,42 +70,31 @@ impl Service for PriceService {
             .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36")
             .build()
             .unwrap();
+        let metal_sources = context.config.metal_pricing.metal_sources();
         Self {
-            url_al: context.config.metal_pricing.al_url.to_string(),
-            url_cu: context.config.metal_pricing.cu_url.to_string(),
+            // One cached price per metal
+            price_cache: ExpirableCache::new(metal_sources.len() as u64, Duration::from_secs(300)),
+            metal_sources,
+            database: context.database.clone(),
             price_channel: None,
-            price_cache: ExpirableCache::new(2, Duration::from_secs(300)),
-            last_alert_hour: None,
+            alert_times: context.config.metal_pricing.alert_times.clone(),
+            alerted_windows: HashSet::new(),
+            alerted_date: None,
+            alert_threshold_percent: context.config.metal_pricing.alert_threshold_percent,
+            last_alerted_prices: None,
             client: RetryableClient::with_retries(client, 2),
         }
     }
 
     async fn run(mut self) -> Result<(), ServiceManagerError> {
-        let hour1 = 10_u32;
-        let minute1 = 28;
-        let hour2 = 15_u32;
-        let minute2 = 9;
         loop {
             let now_ist = Utc::now().with_timezone(&Kolkata);
-            let hour = now_ist.hour();
-            let minute = now_ist.minute();
 
-            // Check if we're in a valid time window and haven't sent alert this hour
-            let should_send_alert = match hour {
-                _ if hour == hour1 && minute >= minute1 && minute <= minute1 + 2 => {
-                    self.last_alert_hour != Some(hour1)
-                }
-                _ if hour == hour2 && minute >= minute2 && minute <= minute2 + 2 => {
-                    self.last_alert_hour != Some(hour2)
-                }
-                _ => false,
-            };
-
-            if should_send_alert {
+            if let Some(window) = self.due_alert(now_ist) {
                 match self.send_price_alert(now_ist).await {
                     Ok(_) => {
-                        self.last_alert_hour = Some(hour);
-                        info!(hour = %hour, minute = %minute, "Price alert sent successfully");
+                        self.alerted_windows.insert(window);
+                        info!(hour = %window.hour, minute = %window.minute, "Price alert sent successfully");
                     }
                     Err(e) => {
                         error!(error = %e, "Failed to send price alert");
@@ -114,12 +117,17 @@ impl ServiceWithSender for PriceService {
             .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36")
             .build()
             .unwrap();
+        let metal_sources = context.config.metal_pricing.metal_sources();
         Self {
-            url_al: context.config.metal_pricing.al_url.to_string(),
-            url_cu: context.config.metal_pricing.cu_url.to_string(),
+            price_cache: ExpirableCache::new(metal_sources.len() as u64, Duration::from_secs(300)),
+            metal_sources,
+            database: context.database.clone(),
             price_channel,
-            price_cache: ExpirableCache::new(2, Duration::from_secs(300)),
-            last_alert_hour: None,
+            alert_times: context.config.metal_pricing.alert_times.clone(),
+            alerted_windows: HashSet::new(),
+            alerted_date: None,
+            alert_threshold_percent: context.config.metal_pricing.alert_threshold_percent,
+            last_alerted_prices: None,
             client: RetryableClient::with_retries(client, 3),
         }
     }
@@ -131,7 +139,7 @@ impl ServiceWithSender for PriceService {
 
 impl PriceService {
     async fn send_price_alert(
-        &self,
+        &mut self,
         now_ist: DateTime<chrono_tz::Tz>,
     ) -> Result<(), ServiceManagerError> {
         let price_al = self
@@ -146,13 +154,17 @@ impl PriceService {
             .await
             .map_err(|e| ServiceManagerError::from(e))?;
 
-        if let Some(sender) = &self.price_channel {
-            let alert = PriceAlert {
-                timestamp: now_ist.format("%d/%m/%Y %I:%M %p").to_string(),
-                copper_price: price_cu,
-                aluminum_price: price_al,
-            };
+        let timestamp = now_ist.format("%d/%m/%Y %I:%M %p").to_string();
+        let Some(alert) = self.next_alert(&timestamp, price_cu, price_al) else {
+            info!(
+                copper = %price_cu,
+                aluminium = %price_al,
+                "Metal prices within alert threshold, skipping alert"
+            );
+            return Ok(());
+        };
 
+        if let Some(sender) = &self.price_channel {
             let alert_json = serde_json::to_string(&alert)
                 .map_err(|e| ServiceManagerError::new(&format!("Serialization error: {}", e)))?;
 
@@ -163,24 +175,105 @@ impl PriceService {
         Ok(())
     }
 
+    // The configured window `now_ist` falls in, unless it has already been alerted today.
+    // A window stays open for three minutes so a slow loop iteration can't miss it.
+    fn due_alert(&mut self, now_ist: DateTime<chrono_tz::Tz>) -> Option<AlertTime> {
+        let today = now_ist.date_naive();
+        if self.alerted_date != Some(today) {
+            self.alerted_windows.clear();
+            self.alerted_date = Some(today);
+        }
+
+        let now_minutes = now_ist.hour() * 60 + now_ist.minute();
+        self.alert_times.iter().copied().find(|window| {
+            let start = window.hour * 60 + window.minute;
+            (start..=start + 2).contains(&now_minutes) && !self.alerted_windows.contains(window)
+        })
+    }
+
+    // The alert to send for these prices, or None when neither moved past the threshold
+    fn next_alert(&mut self, timestamp: &str, price_cu: f64, price_al: f64) -> Option<PriceAlert> {
+        let changes = self.last_alerted_prices.map(|(last_cu, last_al)| {
+            (
+                percent_change(last_cu, price_cu),
+                percent_change(last_al, price_al),
+            )
+        });
+
+        if let (Some(threshold), Some((change_cu, change_al))) =
+            (self.alert_threshold_percent, changes)
+        {
+            if change_cu.abs() <= threshold && change_al.abs() <= threshold {
+                return None;
+            }
+        }
+
+        self.last_alerted_prices = Some((price_cu, price_al));
+        Some(PriceAlert {
+            timestamp: timestamp.to_string(),
+            copper_price: price_cu,
+            aluminum_price: price_al,
+            copper_change_percent: changes.map(|(change_cu, _)| change_cu),
+            aluminum_change_percent: changes.map(|(_, change_al)| change_al),
+        })
+    }
+
     pub async fn fetch_price(&self, metal: &str) -> Result<f64, PriceError> {
-        let price = self.price_cache.get(&metal.to_string());
-        if price.is_some() {
-            return Ok(price.unwrap());
+        let metal = metal.to_lowercase();
+        let sources = self
+            .metal_sources
+            .get(&metal)
+            .ok_or(PriceError::InvalidMetalType)?;
+
+        // Queries arriving together on a cold cache share one scrape
+        self.price_cache
+            .get_or_insert_with(metal.clone(), || self.scrape_price(&metal, sources))
+            .await
+    }
+
+    async fn scrape_price(
+        &self,
+        metal: &str,
+        sources: &[MetalPriceSource],
+    ) -> Result<f64, PriceError> {
+        // A source that is down or has changed its layout falls through to the next one
+        let mut last_error = PriceError::PriceNotFoundError;
+        for source in sources {
+            match self.fetch_price_from(source).await {
+                Ok(price) => {
+                    info!(metal = %metal, price = %price, source = %source.url, "Fetched metal price");
+                    self.log_price(metal.to_string(), price);
+                    return Ok(price);
+                }
+                Err(e) => {
+                    warn!(metal = %metal, source = %source.url, error = %e, "Metal price source failed");
+                    last_error = e;
+                }
+            }
         }
+        Err(last_error)
+    }
 
-        let url = match metal.to_lowercase().as_str() {
-            "aluminium" => &self.url_al,
-            "copper" => &self.url_cu,
-            _ => return Err(PriceError::InvalidMetalType),
-        };
+    // Kept for the day-over-day change. Saving happens in the background so a slow or failing
+    // database never holds up the price itself.
+    fn log_price(&self, metal: String, price: f64) {
+        let database = self.database.clone();
+        tokio::spawn(async move {
+            if let Err(e) = database.log_metal_price(&metal, price, Utc::now()).await {
+                warn!(metal = %metal, error = %e, "Failed to log metal price");
+            }
+        });
+    }
+
+    async fn fetch_price_from(&self, source: &MetalPriceSource) -> Result<f64, PriceError> {
         let response = self
             .client
-            .execute_with_retry(
+            .execute_with_policy(
                 self.client
-                    .get(url)
+                    .get(&source.url)
                     .header("Accept", "text/html")
                     .header("Accept-Language", "en-US,en;q=0.9"),
+                METAL_PRICE_RETRY_POLICY,
             )
             .await
             .map_err(|e| PriceError::GetUrlError(e.to_string()))?
@@ -189,8 +282,7 @@ impl PriceService {
             .map_err(|e| PriceError::GetUrlError(e.to_string()))?;
 
         let document = Html::parse_document(&response);
-        // Updated selectors to match the actual HTML structure
-        let value_selector = Selector::parse("div.commodity-page__value")
+        let value_selector = Selector::parse(&source.selector)
             .map_err(|e| PriceError::HTMLParseError(e.to_string()))?;
 
         // Extract the main price value
@@ -204,32 +296,366 @@ impl PriceService {
         let main_price_text = value_element
             .text()
             .collect::<String>()
-            .replace("₹", "")
+            .replace(['₹', ','], "")
             .trim()
             .to_string();
 
         // Parse the combined price string
-        let price = main_price_text
+        main_price_text
             .as_str()
             .parse::<f64>()
-            .map_err(|_| PriceError::PriceParseError)?;
-
-        info!(metal = %metal, price = %price, "Fetched metal price");
-        self.price_cache.insert(metal.to_string(), price);
-        Ok(price)
+            .ok()
+            .filter(|price| price.is_finite() && *price > 0.0)
+            .ok_or(PriceError::PriceParseError)
     }
 
     pub async fn fetch_formatted_prices(&self) -> Result<String, PriceError> {
-        let price_cu = self.fetch_price("copper").await?;
-        tokio::time::sleep(Duration::from_secs(2)).await;
-        let price_al = self.fetch_price("aluminium").await?;
+        let mut metals: Vec<&String> = self.metal_sources.keys().collect();
+        metals.sort_by_key(|metal| (metal_rank(metal), metal.as_str()));
+
+        let mut lines = Vec::new();
+        let mut priced_any = false;
+        let mut last_error = None;
+        for (index, metal) in metals.into_iter().enumerate() {
+            if index > 0 {
+                tokio::time::sleep(Duration::from_secs(2)).await;
+            }
+            // One flaky source shouldn't hold back the prices of the other metals
+            let price = match self.fetch_price(metal).await {
+                Ok(price) => price,
+                Err(e) => {
+                    warn!(metal = %metal, error = %e, "Failed to fetch price, leaving it out");
+                    lines.push(format!("{}: unavailable", metal_label(metal)));
+                    last_error = Some(e);
+                    continue;
+                }
+            };
+            priced_any = true;
+            let change = match self.database.get_previous_close(metal).await {
+                Ok(close) => close.map(|close| percent_change(close, price)),
+                Err(e) => {
+                    warn!(metal = %metal, error = %e, "Failed to get previous close");
+                    None
+                }
+            };
+            lines.push(price_line(metal, price, change));
+        }
+        if !priced_any {
+            return Err(last_error.unwrap_or(PriceError::PriceNotFoundError));
+        }
 
         let now_ist = Utc::now().with_timezone(&Kolkata);
         let timestamp = now_ist.format("%d/%m/%Y %I:%M %p IST");
         let message = format!(
-            "🔔 Metal Price Update\n {}\n\n🟤 Copper: Rs. {:.2}\n⚪ Aluminium: Rs. {:.2}",
-            timestamp, price_cu, price_al
+            "🔔 Metal Price Update\n {}\n\n{}",
+            timestamp,
+            lines.join("\n")
         );
         Ok(message)
     }
 }
+
+fn percent_change(from: f64, to: f64) -> f64 {
+    (to - from) / from * 100.0
+}
+
+// Copper and aluminium lead the update, any other metals follow by name
+fn metal_rank(metal: &str) -> u8 {
+    match metal {
+        "copper" => 0,
+        "aluminium" => 1,
+        _ => 2,
+    }
+}
+
+// The metal's icon and capitalised name, eg. "🟤 Copper"
+fn metal_label(metal: &str) -> String {
+    let icon = match metal {
+        "copper" => "🟤",
+        "aluminium" => "⚪",
+        _ => "🔘",
+    };
+    let mut chars = metal.chars();
+    let name = chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+        .unwrap_or_default();
+    format!("{} {}", icon, name)
+}
+
+// `change` is the percentage move since yesterday's close, when there is one
+fn price_line(metal: &str, price: f64, change: Option<f64>) -> String {
+    let line = format!("{}: Rs. {:.2}", metal_label(metal), price);
+    match change {
+        Some(change) => {
+            let arrow = if change < 0.0 { "▼" } else { "▲" };
+            format!("{} {} {:+.1}% vs yesterday", line, arrow, change)
+        }
+        None => line,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price_service(metal_sources: Vec<(&str, Vec<MetalPriceSource>)>) -> PriceService {
+        PriceService {
+            metal_sources: metal_sources
+                .into_iter()
+                .map(|(metal, sources)| (metal.to_string(), sources))
+                .collect(),
+            // Nothing listens here, so prices aren't saved unless a test points this at a mock
+            database: Arc::new(DatabaseService::with_url("http://127.0.0.1:9")),
+            price_channel: None,
+            price_cache: ExpirableCache::new(2, Duration::from_secs(300)),
+            alert_times: vec![],
+            alerted_windows: HashSet::new(),
+            alerted_date: None,
+            alert_threshold_percent: None,
+            last_alerted_prices: None,
+            client: RetryableClient::with_retries(reqwest::Client::new(), 1),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_price_falls_back_to_next_source() {
+        let mut server = mockito::Server::new_async().await;
+        let primary = server
+            .mock("GET", "/mcx")
+            .with_body("<html><body><div class=\"new-layout\">1012.35</div></body></html>")
+            .create_async()
+            .await;
+        let secondary = server
+            .mock("GET", "/copper")
+            .with_body("<html><body><span class=\"price\">₹ 1,012.35</span></body></html>")
+            .create_async()
+            .await;
+
+        let service = price_service(vec![(
+            "copper",
+            vec![
+                MetalPriceSource::new(&format!("{}/mcx", server.url())),
+                MetalPriceSource {
+                    url: format!("{}/copper", server.url()),
+                    selector: "span.price".to_string(),
+                },
+            ],
+        )]);
+        assert_eq!(service.fetch_price("copper").await.unwrap(), 1012.35);
+        primary.assert_async().await;
+        secondary.assert_async().await;
+
+        // The cached price is served without asking either source again
+        assert_eq!(service.fetch_price("copper").await.unwrap(), 1012.35);
+        primary.assert_async().await;
+        secondary.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_fetch_price_fails_when_no_source_has_a_price() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/mcx")
+            .with_body("<div class=\"commodity-page__value\">n/a</div>")
+            .create_async()
+            .await;
+
+        let service = price_service(vec![
+            (
+                "copper",
+                vec![MetalPriceSource::new(&format!("{}/mcx", server.url()))],
+            ),
+            ("aluminium", vec![]),
+        ]);
+        assert!(matches!(
+            service.fetch_price("copper").await,
+            Err(PriceError::PriceParseError)
+        ));
+        assert!(matches!(
+            service.fetch_price("aluminium").await,
+            Err(PriceError::PriceNotFoundError)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_price_for_configured_lead() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/lead")
+            .with_body("<div class=\"commodity-page__value\">₹182.40</div>")
+            .create_async()
+            .await;
+
+        let service = price_service(vec![(
+            "lead",
+            vec![MetalPriceSource::new(&format!("{}/lead", server.url()))],
+        )]);
+        assert_eq!(service.fetch_price("Lead").await.unwrap(), 182.40);
+        assert!(matches!(
+            service.fetch_price("tin").await,
+            Err(PriceError::InvalidMetalType)
+        ));
+
+        let message = service.fetch_formatted_prices().await.unwrap();
+        assert!(message.ends_with("\n\n🔘 Lead: Rs. 182.40"));
+    }
+
+    #[tokio::test]
+    async fn test_formatted_prices_skip_a_failing_metal() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/copper")
+            .with_body("<div class=\"commodity-page__value\">₹850.00</div>")
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/lead")
+            .with_status(404)
+            .create_async()
+            .await;
+
+        let service = price_service(vec![
+            (
+                "copper",
+                vec![MetalPriceSource::new(&format!("{}/copper", server.url()))],
+            ),
+            (
+                "lead",
+                vec![MetalPriceSource::new(&format!("{}/lead", server.url()))],
+            ),
+        ]);
+        let message = service.fetch_formatted_prices().await.unwrap();
+        assert!(message.ends_with("\n\n🟤 Copper: Rs. 850.00\n🔘 Lead: unavailable"));
+
+        // With nothing priced there is no update to send
+        let service = price_service(vec![(
+            "lead",
+            vec![MetalPriceSource::new(&format!("{}/lead", server.url()))],
+        )]);
+        assert!(service.fetch_formatted_prices().await.is_err());
+    }
+
+    #[test]
+    fn test_alert_due_once_per_window_each_day() {
+        use chrono::TimeZone;
+
+        let mut service = price_service(vec![]);
+        service.alert_times = vec![
+            AlertTime {
+                hour: 9,
+                minute: 59,
+            },
+            AlertTime {
+                hour: 15,
+                minute: 0,
+            },
+        ];
+        let at = |day, hour, minute| {
+            Kolkata
+                .with_ymd_and_hms(2025, 6, day, hour, minute, 0)
+                .unwrap()
+        };
+        let morning = service.alert_times[0];
+
+        assert_eq!(service.due_alert(at(2, 9, 58)), None);
+        assert_eq!(service.due_alert(at(2, 9, 59)), Some(morning));
+        // The window carries over into the next hour
+        assert_eq!(service.due_alert(at(2, 10, 1)), Some(morning));
+        assert_eq!(service.due_alert(at(2, 10, 2)), None);
+
+        service.alerted_windows.insert(morning);
+        assert_eq!(service.due_alert(at(2, 10, 0)), None);
+        assert_eq!(
+            service.due_alert(at(2, 15, 2)),
+            Some(service.alert_times[1])
+        );
+
+        // A new day in IST starts with no windows alerted
+        assert_eq!(service.due_alert(at(3, 10, 0)), Some(morning));
+        assert!(service.alerted_windows.is_empty());
+    }
+
+    #[test]
+    fn test_alert_only_sent_past_threshold() {
+        let mut service = price_service(vec![]);
+        service.alert_threshold_percent = Some(1.0);
+
+        let first = service.next_alert("10:28", 800.0, 250.0).unwrap();
+        assert_eq!(first.copper_change_percent, None);
+        assert_eq!(first.copper_text(), "Rs. 800.00");
+
+        // Copper up 0.5% and aluminium down 0.8% - not worth a message
+        assert!(service.next_alert("15:09", 804.0, 248.0).is_none());
+
+        // Compared with the last alert sent, so small moves still add up
+        let alert = service.next_alert("10:28", 810.0, 248.0).unwrap();
+        assert_eq!(alert.copper_text(), "Rs. 810.00 (+1.25%)");
+        assert_eq!(alert.aluminum_text(), "Rs. 248.00 (-0.80%)");
+        assert_eq!(service.last_alerted_prices, Some((810.0, 248.0)));
+
+        service.alert_threshold_percent = None;
+        assert!(service.next_alert("15:09", 810.0, 248.0).is_some());
+    }
+
+    #[test]
+    fn test_price_lines_keep_copper_and_aluminium_first() {
+        let mut metals = vec!["zinc", "aluminium", "lead", "copper"];
+        metals.sort_by_key(|metal| (metal_rank(metal), *metal));
+        assert_eq!(metals, vec!["copper", "aluminium", "lead", "zinc"]);
+        assert_eq!(price_line("copper", 850.5, None), "🟤 Copper: Rs. 850.50");
+        assert_eq!(price_line("zinc", 265.0, None), "🔘 Zinc: Rs. 265.00");
+        assert_eq!(
+            price_line("aluminium", 248.0, Some(-0.8)),
+            "⚪ Aluminium: Rs. 248.00 ▼ -0.8% vs yesterday"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_formatted_prices_show_change_since_previous_close() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/copper")
+            .with_body("<div class=\"commodity-page__value\">₹850.00</div>")
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/metal_prices")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "metal".into(),
+                "eq.copper".into(),
+            ))
+            .with_body(
+                r#"[{"metal": "copper", "price": 840.0, "fetched_at": "2025-06-02T09:39:00Z"}]"#,
+            )
+            .create_async()
+            .await;
+        let logged = server
+            .mock("POST", "/metal_prices")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"metal": "copper", "price": 850.0}"#.into(),
+            ))
+            .with_status(201)
+            .create_async()
+            .await;
+
+        let mut service = price_service(vec![(
+            "copper",
+            vec![MetalPriceSource::new(&format!("{}/copper", server.url()))],
+        )]);
+        service.database = Arc::new(DatabaseService::with_url(&server.url()));
+
+        let message = service.fetch_formatted_prices().await.unwrap();
+        assert!(message.ends_with("\n\n🟤 Copper: Rs. 850.00 ▲ +1.2% vs yesterday"));
+
+        // Logging runs in the background, wait for it to land
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !logged.matched_async().await {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("price was never logged");
+        logged.assert_async().await;
+    }
+}
@@ -0,0 +1,743 @@
+// Drives `fulfil_query` end to end with every external (LLM, scrape, OCR, transcription,
+// database) replaced by a canned provider or a mockito endpoint
+use super::*;
+use crate::configuration::{
+    BrandingConfig, ConfirmIntentConfig, MetalPriceSource, PriceListConfig, QuotationConfig,
+    TranscriptionConfig,
+};
+use crate::core::cache::ExpirableCache;
+use crate::core::http::RetryableClient;
+use crate::llm::llm_providers::LLM;
+use crate::llm::{LLMError, LLMProvider};
+use crate::ocr::providers::OcrProvider;
+use crate::ocr::OcrError;
+use async_trait::async_trait;
+use mockito::{Matcher, Server, ServerGuard};
+use serial_test::serial;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+// Answers every parse with the same query and records what it was asked
+#[derive(Clone)]
+struct ScriptedLLM {
+    query: serde_json::Value,
+    seen: Arc<Mutex<Vec<String>>>,
+}
+
+impl ScriptedLLM {
+    fn new(query: serde_json::Value) -> Self {
+        Self {
+            query,
+            seen: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    fn seen(&self) -> Vec<String> {
+        self.seen.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl LLMProvider for ScriptedLLM {
+    async fn try_parse(
+        &self,
+        query: &str,
+        _context: &SessionContext,
+        _llm_orchestrator: &LLMOrchestrator,
+    ) -> Result<Query, LLMError> {
+        self.seen.lock().unwrap().push(query.to_string());
+        serde_json::from_value(self.query.clone()).map_err(|e| LLMError::ParseError(e.to_string()))
+    }
+}
+
+struct FixedOcr(&'static str);
+
+#[async_trait]
+impl OcrProvider for FixedOcr {
+    fn name(&self) -> &'static str {
+        "tesseract"
+    }
+
+    async fn extract_text(&self, _image_data: &[u8]) -> Result<String, OcrError> {
+        Ok(self.0.to_string())
+    }
+}
+
+async fn mock_database(server: &mut ServerGuard) {
+    mock_database_with_last_response(server, None).await;
+}
+
+// `last_metadata` is the stored metadata of the previous reply in a recent conversation
+async fn mock_database_with_last_response(
+    server: &mut ServerGuard,
+    last_metadata: Option<serde_json::Value>,
+) {
+    match last_metadata {
+        Some(metadata) => {
+            server
+                .mock("GET", "/conversations")
+                .match_query(Matcher::Any)
+                .with_status(200)
+                .with_body(format!(r#"[{{"id": "{}"}}]"#, Uuid::new_v4()))
+                .create_async()
+                .await;
+            let messages = serde_json::json!([{
+                "user_query": "quote 100m 3c x 2.5 armoured at 50%",
+                "structured_response": {
+                    "response_text": "I'll generate a QUOTATION for 1 item, reply 'go' to proceed",
+                    "response_metadata": metadata.to_string(),
+                    "timestamp": "2025-08-01T10:00:00+00:00"
+                }
+            }]);
+            server
+                .mock("GET", "/conversation_messages")
+                .match_query(Matcher::Any)
+                .with_status(200)
+                .with_body(messages.to_string())
+                .create_async()
+                .await;
+        }
+        None => {
+            server
+                .mock("GET", "/conversations")
+                .match_query(Matcher::Any)
+                .with_status(200)
+                .with_body("[]")
+                .create_async()
+                .await;
+        }
+    }
+    server
+        .mock("POST", "/conversations")
+        .match_query(Matcher::Any)
+        .with_status(201)
+        .with_body(format!(r#"[{{"id": "{}"}}]"#, Uuid::new_v4()))
+        .create_async()
+        .await;
+    server
+        .mock("PATCH", "/conversations")
+        .match_query(Matcher::Any)
+        .with_status(204)
+        .create_async()
+        .await;
+    server
+        .mock("POST", "/conversation_messages")
+        .with_status(201)
+        .create_async()
+        .await;
+    server
+        .mock("PATCH", "/query_sessions")
+        .match_query(Matcher::Any)
+        .with_status(204)
+        .create_async()
+        .await;
+    server
+        .mock("POST", "/cost_events")
+        .with_status(201)
+        .create_async()
+        .await;
+}
+
+fn build_fulfilment(server: &ServerGuard, llm: &ScriptedLLM) -> QueryFulfilment {
+    let database = Arc::new(DatabaseService::with_url(&server.url()));
+    let runtime_config = SharedRuntimeConfig::default();
+
+    let llm_service = LLMOrchestrator::with_providers(
+        LLM::Custom(Box::new(llm.clone())),
+        LLM::Custom(Box::new(llm.clone())),
+        database.clone(),
+        runtime_config.clone(),
+    );
+    let price_service = PriceService {
+        metal_sources: HashMap::from([
+            (
+                "aluminium".to_string(),
+                vec![MetalPriceSource::new(&format!(
+                    "{}/aluminium",
+                    server.url()
+                ))],
+            ),
+            (
+                "copper".to_string(),
+                vec![MetalPriceSource::new(&format!("{}/copper", server.url()))],
+            ),
+        ]),
+        database: database.clone(),
+        price_channel: None,
+        price_cache: ExpirableCache::new(2, Duration::from_secs(300)),
+        alert_times: vec![],
+        alerted_windows: HashSet::new(),
+        alerted_date: None,
+        alert_threshold_percent: None,
+        last_alerted_prices: None,
+        client: RetryableClient::with_retries(reqwest::Client::new(), 1),
+    };
+    let quotation_service = QuotationService::new(vec![PriceListConfig {
+        pricelist: "tests/test_files/pricelist.json".to_string(),
+        brand: "kei".to_string(),
+        default_discount: None,
+        current_tags: Vec::new(),
+        markup: None,
+    }])
+    .expect("test pricelist should load");
+    let ocr_service = OcrService::with_providers(
+        vec![Box::new(FixedOcr("4C x 2.5 sqmm armoured"))],
+        database.clone(),
+        u64::MAX,
+    )
+    .unwrap();
+    let transcription_service = TranscriptionService::new(
+        "test_key".to_string(),
+        database.clone(),
+        TranscriptionConfig {
+            api_url: format!("{}/audio/transcriptions", server.url()),
+            ..TranscriptionConfig::default()
+        },
+    );
+
+    QueryFulfilment {
+        price_service,
+        llm_service,
+        quotation_service,
+        pricelist_service: Arc::new(PriceListService::new(Vec::new()).unwrap()),
+        ocr_service,
+        stock_service: Arc::new(StockService::new()),
+        database,
+        transcription_service,
+        runtime_config,
+        confirm_intent: ConfirmIntentConfig::default(),
+        query_timeout: Duration::from_secs(30),
+        media_limits: MediaConfig::default(),
+        approvals: broadcast::channel(8).0,
+        branding: BrandingConfig::default(),
+    }
+}
+
+fn session_context() -> SessionContext {
+    SessionContext {
+        user_id: Uuid::new_v4(),
+        session_id: Uuid::new_v4(),
+        platform: "telegram".to_string(),
+        user_phone: None,
+        telegram_id: Some("test_user".to_string()),
+        last_model_used: None,
+        conversation_id: None,
+    }
+}
+
+fn quote_query(variant: &str) -> serde_json::Value {
+    serde_json::json!({
+        variant: {
+            "items": [{
+                "product": {"Cable": {"PowerControl": {"LT": {
+                    "conductor": "Copper",
+                    "core_size": "3",
+                    "sqmm": "2.5",
+                    "armoured": true
+                }}}},
+                "brand": "kei",
+                "tag": "latest",
+                "discount": 0.5,
+                "loading_frls": 0.0,
+                "loading_pvc": 0.0,
+                "quantity": 100.0,
+                "user_base_price": null,
+                "markup": null
+            }],
+            "delivery_charges": 0.0,
+            "to": null,
+            "terms_and_conditions": null,
+            "estimated_delivery": null,
+            "internal_note": null
+        }
+    })
+}
+
+#[tokio::test]
+#[serial]
+async fn test_metal_pricing_uses_scraped_prices() {
+    let mut server = Server::new_async().await;
+    mock_database(&mut server).await;
+    server
+        .mock("GET", "/copper")
+        .with_status(200)
+        .with_body(r#"<div class="commodity-page__value">₹850.50</div>"#)
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/aluminium")
+        .with_status(200)
+        .with_body(r#"<div class="commodity-page__value">₹245.10</div>"#)
+        .create_async()
+        .await;
+
+    let llm = ScriptedLLM::new(serde_json::json!("MetalPricing"));
+    let fulfilment = build_fulfilment(&server, &llm);
+    let (error_sender, _error_receiver) = mpsc::channel::<Alert>(10);
+
+    let response = fulfilment
+        .fulfil_query("copper rate", &mut session_context(), &error_sender)
+        .await
+        .unwrap();
+
+    assert!(response.text.contains("Copper: Rs. 850.50"));
+    assert!(response.text.contains("Aluminium: Rs. 245.10"));
+    assert!(response.file.is_none());
+    assert_eq!(
+        response.query_metadata,
+        Some(serde_json::json!("MetalPricing"))
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_quotation_renders_pdf() {
+    let mut server = Server::new_async().await;
+    mock_database(&mut server).await;
+
+    let llm = ScriptedLLM::new(quote_query("GetQuotation"));
+    let fulfilment = build_fulfilment(&server, &llm);
+    let (error_sender, _error_receiver) = mpsc::channel::<Alert>(10);
+    let mut context = session_context();
+
+    let response = fulfilment
+        .fulfil_query(
+            "quote 100m 3c x 2.5 armoured at 50%",
+            &mut context,
+            &error_sender,
+        )
+        .await
+        .unwrap();
+
+    assert!(response
+        .text
+        .starts_with("Quotation created for given enquiry"));
+    let file = response.file.expect("quotation should come with a pdf");
+    assert!(file.starts_with("artifacts/"));
+    assert!(std::path::Path::new(&file).exists());
+    let metadata = response.query_metadata.unwrap();
+    assert!(metadata.get("GetQuotation").is_some());
+    // A fresh conversation is opened for the message to be saved against
+    assert!(context.conversation_id.is_some());
+
+    let _ = std::fs::remove_file(file);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_quotation_and_proforma_share_one_parse() {
+    let mut server = Server::new_async().await;
+    mock_database(&mut server).await;
+
+    let llm = ScriptedLLM::new(quote_query("GetQuotationAndProforma"));
+    let fulfilment = build_fulfilment(&server, &llm);
+    let (error_sender, _error_receiver) = mpsc::channel::<Alert>(10);
+
+    let response = fulfilment
+        .fulfil_query(
+            "quotation and PI for 100m 3c x 2.5 armoured at 50%",
+            &mut session_context(),
+            &error_sender,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(llm.seen().len(), 1);
+    assert!(response
+        .text
+        .starts_with("Quotation and Proforma Invoice created"));
+    let files: Vec<String> = response.files().map(str::to_string).collect();
+    assert_eq!(files.len(), 2);
+    assert!(files[0].starts_with("artifacts/Q-"));
+    assert!(files[1].starts_with("artifacts/PI-"));
+    for file in &files {
+        assert!(std::path::Path::new(file).exists());
+    }
+    let metadata = response.query_metadata.unwrap();
+    assert!(metadata.get("GetQuotationAndProforma").is_some());
+    assert!(metadata["document_reference"]
+        .as_str()
+        .is_some_and(|reference| reference.starts_with("Q-") && reference.contains(", PI-")));
+
+    for file in files {
+        let _ = std::fs::remove_file(file);
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_high_value_quotation_waits_for_approval() {
+    let mut server = Server::new_async().await;
+    mock_database(&mut server).await;
+    let saved = server
+        .mock("POST", "/quotation_approvals")
+        .match_body(Matcher::PartialJsonString(
+            r#"{"platform": "telegram", "recipient": "test_user", "status": "pending"}"#.into(),
+        ))
+        .with_status(201)
+        .create_async()
+        .await;
+
+    let llm = ScriptedLLM::new(quote_query("GetQuotation"));
+    let mut fulfilment = build_fulfilment(&server, &llm);
+    let (error_sender, _error_receiver) = mpsc::channel::<Alert>(10);
+    let mut approvals = fulfilment.approvals.subscribe();
+
+    // Well under the threshold - sent straight away
+    fulfilment.quotation_service = fulfilment.quotation_service.with_config(QuotationConfig {
+        approval_threshold: Some(100_000_000.0),
+        ..QuotationConfig::default()
+    });
+    let response = fulfilment
+        .fulfil_query(
+            "quote 100m 3c x 2.5 armoured at 50%",
+            &mut session_context(),
+            &error_sender,
+        )
+        .await
+        .unwrap();
+    let _ = std::fs::remove_file(response.file.expect("quotation should be sent"));
+    assert!(approvals.try_recv().is_err());
+
+    fulfilment.quotation_service = fulfilment.quotation_service.with_config(QuotationConfig {
+        approval_threshold: Some(1.0),
+        ..QuotationConfig::default()
+    });
+    let response = fulfilment
+        .fulfil_query(
+            "quote 100m 3c x 2.5 armoured at 50%",
+            &mut session_context(),
+            &error_sender,
+        )
+        .await
+        .unwrap();
+
+    assert!(response.file.is_none());
+    assert!(response.text.contains("needs a manager's approval"));
+    saved.assert_async().await;
+    let approval = approvals
+        .try_recv()
+        .expect("admin should be asked to approve");
+    assert_eq!(approval.status, ApprovalStatus::Pending);
+    assert_eq!(
+        response.query_metadata.unwrap()[PENDING_APPROVAL_KEY],
+        approval.id.to_string()
+    );
+    assert!(approval.document_reference.starts_with("Q-"));
+    assert!(response.text.starts_with(&approval.document_reference));
+    // The document is kept for release on approval, where the webhook doesn't serve it
+    assert_eq!(approval.files.len(), 1);
+    assert!(approval.files[0].starts_with("held/"));
+    assert!(std::path::Path::new(&approval.files[0]).exists());
+    let _ = std::fs::remove_file(&approval.files[0]);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_dry_parse_shows_query_without_fulfilling_it() {
+    let mut server = Server::new_async().await;
+    mock_database(&mut server).await;
+
+    let llm = ScriptedLLM::new(quote_query("GetQuotation"));
+    let fulfilment = build_fulfilment(&server, &llm);
+    let (error_sender, _error_receiver) = mpsc::channel::<Alert>(10);
+    let mut context = session_context();
+
+    let response = fulfilment
+        .dry_parse("quote 100m 3c x 2.5 armoured", &mut context, &error_sender)
+        .await
+        .unwrap();
+
+    assert!(response.text.starts_with("🔍 Parsed as GetQuotation:"));
+    assert!(response.text.contains("\"sqmm\": \"2.5\""));
+    // Nothing is rendered, and no follow-up buttons are offered
+    assert!(response.file.is_none());
+    assert!(response.query_metadata.is_none());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_quotation_waits_for_confirmation() {
+    let mut server = Server::new_async().await;
+    mock_database(&mut server).await;
+
+    let llm = ScriptedLLM::new(quote_query("GetQuotation"));
+    let mut fulfilment = build_fulfilment(&server, &llm);
+    fulfilment.confirm_intent.users = vec!["test_user".to_string()];
+    let (error_sender, _error_receiver) = mpsc::channel::<Alert>(10);
+
+    let response = fulfilment
+        .fulfil_query(
+            "price 100m 3c x 2.5 armoured at 50%",
+            &mut session_context(),
+            &error_sender,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        response.text,
+        "I'll generate a QUOTATION for 1 item, reply 'go' to proceed"
+    );
+    assert!(response.file.is_none());
+    assert!(response.query_metadata.unwrap()["pending_confirmation"]["GetQuotation"].is_object());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_go_runs_the_pending_quotation() {
+    let mut server = Server::new_async().await;
+    mock_database_with_last_response(
+        &mut server,
+        Some(serde_json::json!({ "pending_confirmation": quote_query("GetQuotation") })),
+    )
+    .await;
+
+    // The LLM must not be asked again - it would parse anything as a metal price query
+    let llm = ScriptedLLM::new(serde_json::json!("MetalPricing"));
+    let mut fulfilment = build_fulfilment(&server, &llm);
+    fulfilment.confirm_intent.users = vec!["test_user".to_string()];
+    let (error_sender, _error_receiver) = mpsc::channel::<Alert>(10);
+    let mut context = session_context();
+
+    let response = fulfilment
+        .fulfil_query("go", &mut context, &error_sender)
+        .await
+        .unwrap();
+
+    assert!(llm.seen().is_empty());
+    assert!(response
+        .text
+        .starts_with("Quotation created for given enquiry"));
+    assert!(context.conversation_id.is_some());
+    let _ = std::fs::remove_file(response.file.expect("confirmed quotation should render"));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_prices_only_lists_discounted_price() {
+    let mut server = Server::new_async().await;
+    mock_database(&mut server).await;
+
+    let llm = ScriptedLLM::new(serde_json::json!({
+        "GetPricesOnly": {
+            "items": [{
+                "product": {"Cable": {"PowerControl": {"LT": {
+                    "conductor": "Copper",
+                    "core_size": "3",
+                    "sqmm": "2.5",
+                    "armoured": true
+                }}}},
+                "discount": 0.5,
+                "quantity": null
+            }]
+        }
+    }));
+    let fulfilment = build_fulfilment(&server, &llm);
+    let (error_sender, _error_receiver) = mpsc::channel::<Alert>(10);
+
+    let response = fulfilment
+        .fulfil_query(
+            "rate of 3c x 2.5 armoured",
+            &mut session_context(),
+            &error_sender,
+        )
+        .await
+        .unwrap();
+
+    assert!(response.text.contains("Rs.200.00/mtr"));
+    assert!(response.file.is_none());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_prices_only_quotes_the_requested_unit() {
+    let mut server = Server::new_async().await;
+    mock_database(&mut server).await;
+
+    let llm = ScriptedLLM::new(serde_json::json!({
+        "GetPricesOnly": {
+            "items": [{
+                "product": {"Cable": {"PowerControl": {"LT": {
+                    "conductor": "Copper",
+                    "core_size": "3",
+                    "sqmm": "2.5",
+                    "armoured": true
+                }}}},
+                "discount": 0.5,
+                "quantity": null,
+                "unit": "Km"
+            }]
+        }
+    }));
+    let fulfilment = build_fulfilment(&server, &llm);
+    let (error_sender, _error_receiver) = mpsc::channel::<Alert>(10);
+
+    let response = fulfilment
+        .fulfil_query(
+            "rate per km of 3c x 2.5 armoured",
+            &mut session_context(),
+            &error_sender,
+        )
+        .await
+        .unwrap();
+
+    assert!(response.text.contains("Rs.200000.00/km"));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_prices_only_names_skipped_items() {
+    let mut server = Server::new_async().await;
+    mock_database(&mut server).await;
+
+    let llm = ScriptedLLM::new(serde_json::json!({
+        "GetPricesOnly": {
+            "items": [
+                {
+                    "product": {"Cable": {"PowerControl": {"LT": {
+                        "conductor": "Copper",
+                        "core_size": "3",
+                        "sqmm": "2.5",
+                        "armoured": true
+                    }}}},
+                    "quantity": null
+                },
+                {
+                    "product": {"Cable": {"Coaxial": "RG11"}},
+                    "quantity": null
+                },
+                {
+                    "product": {"Cable": {"Coaxial": "RG6"}},
+                    "brand": "unknown",
+                    "quantity": null
+                }
+            ]
+        }
+    }));
+    let fulfilment = build_fulfilment(&server, &llm);
+    let (error_sender, _error_receiver) = mpsc::channel::<Alert>(10);
+
+    let response = fulfilment
+        .fulfil_query(
+            "rates of 3c x 2.5 armoured, rg11 and unknown rg6",
+            &mut session_context(),
+            &error_sender,
+        )
+        .await
+        .unwrap();
+
+    let (priced, skipped) = response.text.split_once("\nCouldn't find: ").unwrap();
+    assert!(priced.contains("/mtr"));
+    assert!(skipped.contains("(kei) - size not in pricelist"));
+    assert!(skipped.contains("(unknown) - brand not found"));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_image_query_passes_ocr_text_to_llm() {
+    let mut server = Server::new_async().await;
+    mock_database(&mut server).await;
+
+    let llm = ScriptedLLM::new(quote_query("GetProformaInvoice"));
+    let fulfilment = build_fulfilment(&server, &llm);
+    let (error_sender, _error_receiver) = mpsc::channel::<Alert>(10);
+
+    let response = fulfilment
+        .fulfil_image_query(
+            b"not an image",
+            "PI for this",
+            &mut session_context(),
+            &error_sender,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        llm.seen(),
+        vec!["4C x 2.5 sqmm armoured\nPI for this".to_string()]
+    );
+    assert!(response.text.starts_with("Proforma Invoice created"));
+    let _ = std::fs::remove_file(response.file.unwrap());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_audio_query_passes_transcript_to_llm() {
+    let mut server = Server::new_async().await;
+    mock_database(&mut server).await;
+    server
+        .mock("POST", "/audio/transcriptions")
+        .with_status(200)
+        .with_body(r#"{"text": " tell me a joke ", "duration": 3.0, "language": "english"}"#)
+        .create_async()
+        .await;
+
+    let llm = ScriptedLLM::new(serde_json::json!("UnsupportedQuery"));
+    let fulfilment = build_fulfilment(&server, &llm);
+    let (error_sender, _error_receiver) = mpsc::channel::<Alert>(10);
+
+    let response = fulfilment
+        .fulfil_audio_query(&[0u8; 64], &mut session_context(), &error_sender)
+        .await
+        .unwrap();
+
+    assert_eq!(llm.seen(), vec!["tell me a joke".to_string()]);
+    assert!(response
+        .text
+        .starts_with("I couldn't understand that request"));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_out_of_range_discount_is_refused() {
+    let mut server = Server::new_async().await;
+    mock_database(&mut server).await;
+
+    // "5%" read as 500%
+    let mut query = quote_query("GetQuotation");
+    query["GetQuotation"]["items"][0]["discount"] = serde_json::json!(5.0);
+    let llm = ScriptedLLM::new(query);
+    let fulfilment = build_fulfilment(&server, &llm);
+    let (error_sender, _error_receiver) = mpsc::channel::<Alert>(10);
+
+    let error = fulfilment
+        .fulfil_query(
+            "quote 100m 3c x 2.5 armoured at 5%",
+            &mut session_context(),
+            &error_sender,
+        )
+        .await
+        .unwrap_err();
+
+    assert!(matches!(
+        error,
+        QueryError::QuotationFailed(QuotationError::InvalidItemParameters(_))
+    ));
+    assert!(
+        crate::communication::error_handler::map_query_error_to_user_message(&error)
+            .starts_with("Invalid parameters (discount 5 on")
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_llm_failure_surfaces_as_query_error() {
+    let mut server = Server::new_async().await;
+    mock_database(&mut server).await;
+
+    let llm = ScriptedLLM::new(serde_json::json!("NotAQuery"));
+    let fulfilment = build_fulfilment(&server, &llm);
+    let (error_sender, _error_receiver) = mpsc::channel::<Alert>(10);
+
+    let result = fulfilment
+        .fulfil_query("anything", &mut session_context(), &error_sender)
+        .await;
+
+    assert!(matches!(result, Err(QueryError::LLMError(_))));
+    // Primary provider and its fallback were both tried
+    assert_eq!(llm.seen().len(), 2);
+}
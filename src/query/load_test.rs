@@ -0,0 +1,250 @@
+use crate::configuration::BrandingConfig;
+use crate::pdf::{create_quotation_pdf, DocumentType};
+use crate::prices::item_prices::{Cable, Conductor, PowerControl, Product, LT};
+use crate::quotation::{QuotationRequest, QuotationService, QuoteItem};
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const MAX_LOAD_TEST_REQUESTS: usize = 1000;
+pub const MAX_LOAD_TEST_CONCURRENCY: usize = 32;
+
+// Timings of a single quotation through pricing and PDF rendering
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    pricing: Duration,
+    pdf: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LatencyStats {
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+#[derive(Debug)]
+pub struct LoadTestReport {
+    pub requests: usize,
+    pub concurrency: usize,
+    pub failures: usize,
+    pub elapsed: Duration,
+    pub pricing: LatencyStats,
+    pub pdf: LatencyStats,
+    pub total: LatencyStats,
+}
+
+impl LatencyStats {
+    fn from_durations(mut durations: Vec<Duration>) -> Self {
+        durations.sort();
+        Self {
+            p50: percentile(&durations, 50.0),
+            p95: percentile(&durations, 95.0),
+            p99: percentile(&durations, 99.0),
+            max: durations.last().copied().unwrap_or_default(),
+        }
+    }
+}
+
+// Nearest-rank percentile of an already sorted list
+fn percentile(sorted: &[Duration], pct: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = ((pct / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+impl LoadTestReport {
+    // Completed quotations per second
+    pub fn throughput(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs == 0.0 {
+            return 0.0;
+        }
+        (self.requests - self.failures) as f64 / secs
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "Load test: {} quotations, {} concurrent\nCompleted: {}, failed: {}\nElapsed: {:.2}s, throughput: {:.1}/s\n{}\n{}\n{}",
+            self.requests,
+            self.concurrency,
+            self.requests - self.failures,
+            self.failures,
+            self.elapsed.as_secs_f64(),
+            self.throughput(),
+            format_stats("Pricing", &self.pricing),
+            format_stats("PDF", &self.pdf),
+            format_stats("Total", &self.total),
+        )
+    }
+}
+
+fn format_stats(label: &str, stats: &LatencyStats) -> String {
+    format!(
+        "{}: p50 {}ms, p95 {}ms, p99 {}ms, max {}ms",
+        label,
+        stats.p50.as_millis(),
+        stats.p95.as_millis(),
+        stats.p99.as_millis(),
+        stats.max.as_millis()
+    )
+}
+
+// Ten user-priced LT cables, so the run doesn't depend on which pricelists are loaded
+pub fn default_load_test_request() -> QuotationRequest {
+    let items = ["1.5", "2.5", "4", "6", "10", "16", "25", "35", "50", "70"]
+        .iter()
+        .map(|sqmm| {
+            QuoteItem::builder(Product::Cable(Cable::PowerControl(PowerControl::LT(LT {
+                conductor: Conductor::Copper,
+                core_size: "4".to_string(),
+                sqmm: sqmm.to_string(),
+                armoured: true,
+            }))))
+            .with_quantity(100.0)
+            .with_user_base_price(250.0)
+            .build()
+        })
+        .collect();
+    QuotationRequest::builder()
+        .with_items(items)
+        .with_delivery_charges(500.0)
+        .with_to(vec!["Load Test".to_string()])
+        .build()
+}
+
+// Runs `requests` copies of the request through pricing and PDF rendering on `concurrency`
+// threads. Both steps are synchronous, so they get OS threads rather than tokio tasks. The PDFs
+// are deleted as soon as they are written.
+pub fn run_quotation_load_test(
+    quotation_service: &QuotationService,
+    branding: &BrandingConfig,
+    request: &QuotationRequest,
+    requests: usize,
+    concurrency: usize,
+) -> LoadTestReport {
+    let concurrency = concurrency.clamp(1, requests.max(1));
+    let run_id = uuid::Uuid::new_v4().simple().to_string();
+    let next = AtomicUsize::new(0);
+    let failures = AtomicUsize::new(0);
+    let samples = Mutex::new(Vec::with_capacity(requests));
+
+    let started = Instant::now();
+    thread::scope(|scope| {
+        for _ in 0..concurrency {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                if index >= requests {
+                    break;
+                }
+                let filename = format!("loadtest-{}-{}.pdf", run_id, index);
+                match run_once(quotation_service, branding, request.clone(), &filename) {
+                    Some(sample) => samples.lock().unwrap().push(sample),
+                    None => {
+                        failures.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            });
+        }
+    });
+    let elapsed = started.elapsed();
+
+    let samples = samples.into_inner().unwrap();
+    LoadTestReport {
+        requests,
+        concurrency,
+        failures: failures.into_inner(),
+        elapsed,
+        pricing: LatencyStats::from_durations(samples.iter().map(|s| s.pricing).collect()),
+        pdf: LatencyStats::from_durations(samples.iter().map(|s| s.pdf).collect()),
+        total: LatencyStats::from_durations(samples.iter().map(|s| s.pricing + s.pdf).collect()),
+    }
+}
+
+fn run_once(
+    quotation_service: &QuotationService,
+    branding: &BrandingConfig,
+    request: QuotationRequest,
+    filename: &str,
+) -> Option<Sample> {
+    let started = Instant::now();
+    let response = quotation_service
+        .generate_quotation(request)
+        .map_err(|e| tracing::warn!("Load test quotation failed: {}", e))
+        .ok()?;
+    let pricing = started.elapsed();
+
+    let started = Instant::now();
+    let result = create_quotation_pdf(
+        "LOADTEST",
+        "01/01/2025",
+        &response,
+        filename,
+        DocumentType::Quotation,
+        &quotation_service.table_layout(),
+        branding,
+    );
+    let pdf = started.elapsed();
+    let _ = fs::remove_file(format!("artifacts/{}", filename));
+
+    match result {
+        Ok(_) => Some(Sample { pricing, pdf }),
+        Err(e) => {
+            tracing::warn!("Load test PDF failed: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentiles() {
+        let durations: Vec<Duration> = (1..=100).rev().map(Duration::from_millis).collect();
+        let stats = LatencyStats::from_durations(durations);
+        assert_eq!(stats.p50, Duration::from_millis(50));
+        assert_eq!(stats.p95, Duration::from_millis(95));
+        assert_eq!(stats.p99, Duration::from_millis(99));
+        assert_eq!(stats.max, Duration::from_millis(100));
+
+        let stats = LatencyStats::from_durations(vec![Duration::from_millis(7)]);
+        assert_eq!(
+            (stats.p50, stats.p99),
+            (Duration::from_millis(7), Duration::from_millis(7))
+        );
+        assert_eq!(LatencyStats::from_durations(vec![]).max, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_load_test_runs_every_request() {
+        let quotation_service = QuotationService::new(vec![]).unwrap();
+        let report = run_quotation_load_test(
+            &quotation_service,
+            &BrandingConfig::default(),
+            &default_load_test_request(),
+            6,
+            3,
+        );
+
+        assert_eq!((report.requests, report.concurrency), (6, 3));
+        assert_eq!(report.failures, 0);
+        assert!(report.total.max >= report.total.p50);
+        assert!(report.summary().contains("Completed: 6, failed: 0"));
+        // Generated PDFs are cleaned up
+        let leftover = fs::read_dir("artifacts")
+            .map(|entries| {
+                entries
+                    .filter_map(Result::ok)
+                    .any(|e| e.file_name().to_string_lossy().starts_with("loadtest-"))
+            })
+            .unwrap_or(false);
+        assert!(!leftover);
+    }
+}
@@ -1,24 +1,43 @@
-use crate::communication::telegram::Response;
-use crate::configuration::Context;
-use crate::core::Service;
-use crate::database::{DatabaseService, SessionContext};
+use crate::configuration::{
+    BrandingConfig, ConfirmIntentConfig, Context, FeaturesConfig, MediaConfig,
+};
+use crate::core::{Alert, Service};
+use crate::database::{
+    customer_key, telegram_customer_key, ApprovalStatus, CustomerProfile, DatabaseService,
+    QuotationApproval, ReferencedSession, SessionContext, DOCUMENT_REFERENCE_KEY,
+};
 use crate::llm::{LLMOrchestrator, Query};
 use crate::ocr::OcrService;
-use crate::pdf::{create_quotation_pdf, DocumentType};
+use crate::pdf::{create_quotation_pdf, hold_document, DocumentType, TableLayout};
 use crate::prices::price_list::PriceListService;
 use crate::prices::PriceService;
-use crate::quotation::QuotationService;
-use crate::stock::StockService;
+use crate::quotation::{
+    QuotationError, QuotationRequest, QuotationResponse, QuotationService, SkippedItem,
+};
+use crate::stock::{StockMetrics, StockService};
 use crate::transcription::TranscriptionService;
 use chrono::{Datelike, Local};
 use rand::prelude::*;
 use std::env;
-use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use thiserror::Error;
+use tokio::sync::broadcast;
 use tokio::sync::mpsc::Sender;
+use tokio::sync::RwLock;
 use tracing::info;
 
+#[cfg(test)]
+mod integration_tests;
+mod load_test;
+mod response;
+
+pub use load_test::{
+    default_load_test_request, LoadTestReport, MAX_LOAD_TEST_CONCURRENCY, MAX_LOAD_TEST_REQUESTS,
+};
+pub use response::{QueryResponse, ResponseButton};
+
 #[derive(Error, Debug)]
 pub enum QueryError {
     #[error("Failed to understand query: {0}")]
@@ -42,6 +61,9 @@ pub enum QueryError {
     #[error("Quotation Formation Error")]
     QuotationServiceError,
 
+    #[error("Quotation Formation Error: {0}")]
+    QuotationFailed(#[from] QuotationError),
+
     #[error("PriceList Service Initialization Error: {0}")]
     PriceListServiceInitializationError(String),
 
@@ -50,6 +72,12 @@ pub enum QueryError {
 
     #[error("Audio transcription error: {0}")]
     TranscriptionError(String),
+
+    #[error("Query timed out after {0}s")]
+    Timeout(u64),
+
+    #[error("Media of {0} bytes is over the {1} byte limit")]
+    MediaTooLarge(u64, u64),
 }
 
 pub struct QueryFulfilment {
@@ -61,41 +89,80 @@ pub struct QueryFulfilment {
     stock_service: Arc<StockService>,
     database: Arc<DatabaseService>,
     transcription_service: TranscriptionService,
-    runtime_config: Arc<Mutex<RuntimeConfig>>,
+    runtime_config: SharedRuntimeConfig,
+    confirm_intent: ConfirmIntentConfig,
+    query_timeout: Duration,
+    media_limits: MediaConfig,
+    approvals: broadcast::Sender<QuotationApproval>,
+    branding: BrandingConfig,
 }
 
+// Metadata key holding a document query that is waiting for the user's go-ahead
+const PENDING_CONFIRMATION_KEY: &str = "pending_confirmation";
+// Metadata key holding the id of the approval a high value quotation is waiting on
+pub const PENDING_APPROVAL_KEY: &str = "pending_approval";
+
 #[derive(Debug, Clone)]
 pub struct RuntimeConfig {
     pub primary_llm: String,
+    pub features: FeaturesConfig,
 }
 
 impl Default for RuntimeConfig {
     fn default() -> Self {
         Self {
             primary_llm: "groq".to_string(),
+            features: FeaturesConfig::default(),
         }
     }
 }
 
+// Shared handle to the runtime config. Readers take a snapshot, so no guard is ever held
+// across an await, and the async lock never blocks the runtime thread.
+#[derive(Debug, Clone, Default)]
+pub struct SharedRuntimeConfig(Arc<RwLock<RuntimeConfig>>);
+
+impl SharedRuntimeConfig {
+    pub fn new(config: RuntimeConfig) -> Self {
+        Self(Arc::new(RwLock::new(config)))
+    }
+
+    pub async fn snapshot(&self) -> RuntimeConfig {
+        self.0.read().await.clone()
+    }
+
+    pub async fn update(&self, apply: impl FnOnce(&mut RuntimeConfig)) {
+        let mut config = self.0.write().await;
+        apply(&mut config);
+    }
+}
+
 impl QueryFulfilment {
     pub async fn new(context: Context) -> Result<Self, QueryError> {
-        let runtime_config = Arc::new(Mutex::new(RuntimeConfig::default()));
+        let runtime_config = SharedRuntimeConfig::new(RuntimeConfig {
+            features: context.config.features.clone(),
+            ..RuntimeConfig::default()
+        });
         let price_service = PriceService::new(context.clone()).await;
         let mut llm_service = LLMOrchestrator::new(
-            &context.config.claude.system_prompt,
+            &context.config.claude,
+            &context.config.groq,
             context.database.clone(),
             runtime_config.clone(),
         )
-        .map_err(|e| QueryError::LLMInitializationError(e.to_string()))?;
+        .map_err(|e| QueryError::LLMInitializationError(e.to_string()))?
+        .with_number_locale(context.config.quotation.number_locale)
+        .with_session_cost_limit(context.config.query.max_session_cost_usd);
         let quotation_service = QuotationService::new(context.config.pricelists.clone())
-            .map_err(|e| QueryError::QuotationServiceInitializationError(e.to_string()))?;
+            .map_err(|e| QueryError::QuotationServiceInitializationError(e.to_string()))?
+            .with_config(context.config.quotation.clone());
         let pricelist_service = PriceListService::new(context.config.pdf_pricelists)
             .map_err(|e| QueryError::PriceListServiceInitializationError(e.to_string()))?;
         let pricelist_service_arc = Arc::new(pricelist_service);
 
         // Set the pricelist service on the ClaudeAI instance for multi-step tool calling
         llm_service.set_pricelist_service(Arc::clone(&pricelist_service_arc));
-        let ocr_service = OcrService::new(context.database.clone())
+        let ocr_service = OcrService::new(context.database.clone(), context.config.ocr.clone())
             .await
             .map_err(|_| QueryError::OcrInitializationError)?;
         let groq_api_key = env::var("GROQ_API_KEY").map_err(|_| {
@@ -103,8 +170,11 @@ impl QueryFulfilment {
                 "GROQ_API_KEY not found".to_string(),
             )
         })?;
-        let transcription_service =
-            TranscriptionService::new(groq_api_key, context.database.clone());
+        let transcription_service = TranscriptionService::new(
+            groq_api_key,
+            context.database.clone(),
+            context.config.transcription.clone(),
+        );
         Ok(Self {
             price_service,
             llm_service,
@@ -115,25 +185,139 @@ impl QueryFulfilment {
             database: context.database.clone(),
             transcription_service,
             runtime_config,
+            confirm_intent: context.config.confirm_intent.clone(),
+            query_timeout: Duration::from_secs(context.config.query.timeout_secs),
+            media_limits: context.config.media,
+            approvals: context.approvals.clone(),
+            branding: context.config.branding.clone(),
         })
     }
 
-    pub fn get_help_text() -> String {
-        std::fs::read_to_string("assets/help.txt")
-            .unwrap_or_else(|_| "Could not understand query. Please rephrase".to_string())
+    // Passing a topic (see `help_topic`) returns just that section, falling back to the full text
+    pub fn get_help_text(topic: Option<&str>) -> String {
+        match std::fs::read_to_string("assets/help.txt") {
+            Ok(help) => select_help_section(&help, topic),
+            Err(_) => "Could not understand query. Please rephrase".to_string(),
+        }
+    }
+
+    pub async fn stock_metrics(&self) -> StockMetrics {
+        self.stock_service.metrics().await
     }
 
-    pub fn set_primary_model(&self, model: &str) {
-        let mut config = self.runtime_config.lock().unwrap();
-        config.primary_llm = model.to_string();
+    pub fn max_image_pixels(&self) -> u64 {
+        self.ocr_service.max_image_pixels()
+    }
+
+    pub fn media_limits(&self) -> MediaConfig {
+        self.media_limits
+    }
+
+    pub fn branding(&self) -> &BrandingConfig {
+        &self.branding
+    }
+
+    // Prices and renders a request directly, skipping the LLM - used by the admin /testquote command
+    pub fn test_quotation(&self, request: QuotationRequest) -> Result<QueryResponse, QueryError> {
+        let q_response = self.quotation_service.generate_quotation(request)?;
+        let (quotation_number, quotation_date, filename) =
+            self.generate_document_details(DocumentType::Quotation);
+
+        write_document_pdf(
+            &quotation_number,
+            &quotation_date,
+            &q_response,
+            &filename,
+            DocumentType::Quotation,
+            &self.quotation_service.table_layout(),
+            &self.branding,
+        )?;
+
+        Ok(QueryResponse {
+            text: with_warnings(
+                &format!(
+                    "Test quotation: {} items, grand total {:.2}",
+                    q_response.items.len(),
+                    q_response.grand_total
+                ),
+                &q_response,
+            ),
+            file: Some(format!("artifacts/{}", filename)),
+            query_metadata: with_quotation_totals(None, &q_response),
+            ..Default::default()
+        })
+    }
+
+    // Blocks the calling thread until every quotation has been priced and rendered
+    pub fn load_test_quotations(
+        &self,
+        request: &QuotationRequest,
+        requests: usize,
+        concurrency: usize,
+    ) -> LoadTestReport {
+        load_test::run_quotation_load_test(
+            &self.quotation_service,
+            &self.branding,
+            request,
+            requests,
+            concurrency,
+        )
+    }
+
+    pub async fn set_primary_model(&self, model: &str) {
+        self.runtime_config
+            .update(|config| config.primary_llm = model.to_string())
+            .await;
     }
 
     pub async fn fulfil_audio_query(
         &self,
         audio_data: &[u8],
         context: &mut SessionContext,
-        error_sender: &Sender<String>,
-    ) -> Result<Response, QueryError> {
+        error_sender: &Sender<Alert>,
+    ) -> Result<QueryResponse, QueryError> {
+        check_media_size(audio_data.len() as u64, self.media_limits.max_audio_bytes)?;
+        within_deadline(
+            self.query_timeout,
+            self.run_audio_query(audio_data, context, error_sender),
+        )
+        .await
+    }
+
+    pub async fn fulfil_image_query(
+        &self,
+        image_data: &[u8],
+        user_text: &str,
+        context: &mut SessionContext,
+        error_sender: &Sender<Alert>,
+    ) -> Result<QueryResponse, QueryError> {
+        check_media_size(image_data.len() as u64, self.media_limits.max_image_bytes)?;
+        within_deadline(
+            self.query_timeout,
+            self.run_image_query(image_data, user_text, context, error_sender),
+        )
+        .await
+    }
+
+    pub async fn fulfil_query(
+        &self,
+        query: &str,
+        context: &mut SessionContext,
+        error_sender: &Sender<Alert>,
+    ) -> Result<QueryResponse, QueryError> {
+        within_deadline(
+            self.query_timeout,
+            self.run_query(query, context, error_sender),
+        )
+        .await
+    }
+
+    async fn run_audio_query(
+        &self,
+        audio_data: &[u8],
+        context: &mut SessionContext,
+        error_sender: &Sender<Alert>,
+    ) -> Result<QueryResponse, QueryError> {
         // Transcribe audio to text
         let transcribed_text = self
             .transcription_service
@@ -142,17 +326,17 @@ impl QueryFulfilment {
             .map_err(|e| QueryError::TranscriptionError(e.to_string()))?;
 
         // Use existing text query flow
-        self.fulfil_query(&transcribed_text, context, error_sender)
+        self.run_query(&transcribed_text, context, error_sender)
             .await
     }
 
-    pub async fn fulfil_image_query(
+    async fn run_image_query(
         &self,
         image_data: &[u8],
         user_text: &str,
         context: &mut SessionContext,
-        error_sender: &Sender<String>,
-    ) -> Result<Response, QueryError> {
+        error_sender: &Sender<Alert>,
+    ) -> Result<QueryResponse, QueryError> {
         // Extract text from image
         let image_text = self
             .ocr_service
@@ -169,31 +353,86 @@ impl QueryFulfilment {
             };
         info!("formed combined query:{}", combined_query);
         // Use existing fulfillment logic
-        self.fulfil_query(&combined_query, context, error_sender)
-            .await
+        self.run_query(&combined_query, context, error_sender).await
     }
 
-    pub async fn fulfil_query(
+    async fn run_query(
         &self,
         query: &str,
         context: &mut SessionContext,
-        error_sender: &Sender<String>,
-    ) -> Result<Response, QueryError> {
+        error_sender: &Sender<Alert>,
+    ) -> Result<QueryResponse, QueryError> {
         let original_query_str = query;
-        let query = self.get_query_type(query, context, error_sender).await?;
-        let query_metadata = Some(serde_json::to_value(&query).unwrap_or(serde_json::Value::Null));
+        // What the customer asked for - on a confirmation, the message that was held back
+        let (query, enquiry) = match self.take_confirmed_query(query, context).await {
+            Some(confirmed) => confirmed,
+            None => {
+                let query = self.get_query_type(query, context, error_sender).await?;
+                if let Some(prompt) = self.confirmation_prompt(&query, context) {
+                    let response = QueryResponse {
+                        text: prompt,
+                        file: None,
+                        query_metadata: Some(
+                            serde_json::json!({ PENDING_CONFIRMATION_KEY: query }),
+                        ),
+                        ..Default::default()
+                    };
+                    self.save_conversation_message(context, original_query_str, &response)
+                        .await;
+                    return Ok(response);
+                }
+                (query, original_query_str.to_string())
+            }
+        };
         let response = match query {
+            // Each part is answered, and held for approval, as if it had been asked on its own
+            Query::Batch(queries) => {
+                let mut responses = Vec::new();
+                for query in queries {
+                    let response = self
+                        .fulfil_one(query, context, &enquiry, original_query_str)
+                        .await?;
+                    responses.push(self.hold_for_approval(response, context).await?);
+                }
+                combine_responses(responses)
+            }
+            query => {
+                let response = self
+                    .fulfil_one(query, context, &enquiry, original_query_str)
+                    .await?;
+                self.hold_for_approval(response, context).await?
+            }
+        };
+
+        self.save_conversation_message(context, original_query_str, &response)
+            .await;
+
+        Ok(response)
+    }
+
+    // Answers a single query. `enquiry` is the message it was parsed from.
+    async fn fulfil_one(
+        &self,
+        query: Query,
+        context: &SessionContext,
+        enquiry: &str,
+        original_query_str: &str,
+    ) -> Result<QueryResponse, QueryError> {
+        let query_metadata = Some(serde_json::to_value(&query).unwrap_or(serde_json::Value::Null));
+        Ok(match query {
             Query::GetPriceList { brand, keywords } => {
                 match self.pricelist_service.find_pricelist(&brand, &keywords) {
-                    Some(pdf_path) => Response {
+                    Some(pdf_path) => QueryResponse {
                         text: "Pricelist".to_string(),
                         file: Some(pdf_path),
                         query_metadata,
+                        ..Default::default()
                     },
-                    None => Response {
+                    None => QueryResponse {
                         text: "No matching pricelist found".to_string(),
                         file: None,
                         query_metadata,
+                        ..Default::default()
                     },
                 }
             }
@@ -204,60 +443,87 @@ impl QueryFulfilment {
                     .fetch_formatted_prices()
                     .await
                     .map_err(|e| QueryError::MetalPricingError(e.to_string()))?;
-                Response {
+                QueryResponse {
                     text: response_text,
                     file: None,
                     query_metadata,
+                    ..Default::default()
                 }
             }
 
             Query::GetQuotation(quotation_request) => {
-                let q_response = self.quotation_service.generate_quotation(quotation_request);
-                if q_response.is_none() {
-                    return Err(QueryError::QuotationServiceError);
-                } else {
-                    let (quotation_number, quotation_date, filename) =
-                        self.generate_document_details(DocumentType::Quotation);
-
-                    let _ = create_quotation_pdf(
+                let quotation_request = self
+                    .with_customer_defaults(quotation_request, context)
+                    .await;
+                let mut q_response = self
+                    .quotation_service
+                    .generate_quotation(quotation_request)?;
+                self.quotation_service
+                    .attach_enquiry(&mut q_response, enquiry);
+                let (quotation_number, file) =
+                    self.render_document(&q_response, DocumentType::Quotation)?;
+
+                QueryResponse {
+                    text: with_warnings("Quotation created for given enquiry", &q_response),
+                    file: Some(file),
+                    query_metadata: with_document_reference(
+                        with_quotation_totals(query_metadata, &q_response),
                         &quotation_number,
-                        &quotation_date,
-                        &q_response.unwrap(),
-                        &filename,
-                        DocumentType::Quotation,
-                    )
-                    .unwrap();
-
-                    Response {
-                        text: "Quotation created for given enquiry".to_string(),
-                        file: Some(format!("artifacts/{}", filename)),
-                        query_metadata,
-                    }
+                    ),
+                    ..Default::default()
                 }
             }
 
             Query::GetProformaInvoice(quotation_request) => {
-                let q_response = self.quotation_service.generate_quotation(quotation_request);
-                if q_response.is_none() {
-                    return Err(QueryError::QuotationServiceError);
-                } else {
-                    let (quotation_number, quotation_date, filename) =
-                        self.generate_document_details(DocumentType::ProformaInvoice);
-
-                    let _ = create_quotation_pdf(
+                let quotation_request = self
+                    .with_customer_defaults(quotation_request, context)
+                    .await;
+                let mut q_response = self
+                    .quotation_service
+                    .generate_quotation(quotation_request)?;
+                self.quotation_service
+                    .attach_enquiry(&mut q_response, enquiry);
+                let (quotation_number, file) =
+                    self.render_document(&q_response, DocumentType::ProformaInvoice)?;
+
+                QueryResponse {
+                    text: with_warnings("Proforma Invoice created for given enquiry", &q_response),
+                    file: Some(file),
+                    query_metadata: with_document_reference(
+                        with_quotation_totals(query_metadata, &q_response),
                         &quotation_number,
-                        &quotation_date,
-                        &q_response.unwrap(),
-                        &filename,
-                        DocumentType::ProformaInvoice,
-                    )
-                    .unwrap();
-
-                    Response {
-                        text: "Proforma Invoice created for given enquiry".to_string(),
-                        file: Some(format!("artifacts/{}", filename)),
-                        query_metadata,
-                    }
+                    ),
+                    ..Default::default()
+                }
+            }
+
+            Query::GetQuotationAndProforma(quotation_request) => {
+                let quotation_request = self
+                    .with_customer_defaults(quotation_request, context)
+                    .await;
+                // Priced once, so both documents carry exactly the same items and totals
+                let mut q_response = self
+                    .quotation_service
+                    .generate_quotation(quotation_request)?;
+                self.quotation_service
+                    .attach_enquiry(&mut q_response, enquiry);
+                let (quotation_number, quotation_path) =
+                    self.render_document(&q_response, DocumentType::Quotation)?;
+                let (proforma_number, proforma_path) =
+                    self.render_document(&q_response, DocumentType::ProformaInvoice)?;
+
+                QueryResponse {
+                    text: with_warnings(
+                        "Quotation and Proforma Invoice created for given enquiry",
+                        &q_response,
+                    ),
+                    file: Some(quotation_path),
+                    query_metadata: with_document_reference(
+                        with_quotation_totals(query_metadata, &q_response),
+                        &format!("{}, {}", quotation_number, proforma_number),
+                    ),
+                    attachments: vec![proforma_path],
+                    ..Default::default()
                 }
             }
 
@@ -266,40 +532,187 @@ impl QueryFulfilment {
                 let price_response = self.quotation_service.get_prices_only(price_only_request);
                 match price_response {
                     Some(response) if !response.items.is_empty() => {
-                        Response {
+                        QueryResponse {
                             text: self.format_price_only_response(response),
                             file: None,
-                            query_metadata
+                            query_metadata,
+                            ..Default::default()
                         }
                     }
-                    _ => Response {
+                    Some(response) if !response.skipped_items.is_empty() => QueryResponse {
+                        text: format!(
+                            "I couldn't find prices for: {}",
+                            describe_skipped(&response.skipped_items)
+                        ),
+                        file: None,
+                        query_metadata,
+                        ..Default::default()
+                    },
+                    _ => QueryResponse {
                         text: "No prices found for the requested items. Please check item/specifications".to_string(),
                         file: None,
-                        query_metadata
+                        query_metadata,
+                        ..Default::default()
                     }
                 }
             }
 
+            Query::DescribeProduct(describe_request) => QueryResponse {
+                text: self.quotation_service.describe_product(describe_request),
+                file: None,
+                query_metadata,
+                ..Default::default()
+            },
+
             Query::GetStock { query } => match self.stock_service.request_stock(query).await {
-                Ok(stock_info) => Response {
+                Ok(stock_info) => QueryResponse {
                     text: stock_info,
                     file: None,
                     query_metadata,
+                    ..Default::default()
                 },
-                Err(e) => Response {
+                Err(e) => QueryResponse {
                     text: format!("Stock check failed: {}", e),
                     file: None,
                     query_metadata,
+                    ..Default::default()
                 },
             },
-            _ => Response {
+            Query::GetOrderStatus { reference } => {
+                let text = match self
+                    .database
+                    .get_session_by_reference(context.user_id, &reference)
+                    .await
+                {
+                    Ok(session) => order_status_text(&reference, session.as_ref()),
+                    Err(e) => format!("Order status lookup failed: {}", e),
+                };
+                QueryResponse {
+                    text,
+                    file: None,
+                    query_metadata,
+                    ..Default::default()
+                }
+            }
+            Query::SessionLimitReached {
+                spent_usd,
+                limit_usd,
+            } => QueryResponse {
+                text: format!(
+                    "Conversation limit reached (${:.2} of ${:.2}) - ask again to start fresh.",
+                    spent_usd, limit_usd
+                ),
+                file: None,
+                query_metadata,
+                ..Default::default()
+            },
+            Query::UnsupportedQuery => QueryResponse {
+                text: unsupported_query_text(
+                    &self.llm_service.capabilities(),
+                    help_topic(original_query_str),
+                ),
+                file: None,
+                query_metadata,
+                ..Default::default()
+            },
+            _ => QueryResponse {
                 text: "Cannot fulfil this request at the moment".to_string(),
                 file: None,
                 query_metadata,
+                ..Default::default()
             },
+        })
+    }
+
+    // Documents over the approval threshold are kept back, and the admin is asked (through the
+    // approval events) to release them
+    async fn hold_for_approval(
+        &self,
+        response: QueryResponse,
+        context: &SessionContext,
+    ) -> Result<QueryResponse, QueryError> {
+        let Some(threshold) = self.quotation_service.approval_threshold() else {
+            return Ok(response);
+        };
+        let grand_total = response
+            .query_metadata
+            .as_ref()
+            .and_then(|metadata| {
+                metadata
+                    .get("quotation_totals")?
+                    .get("grand_total")?
+                    .as_f64()
+            })
+            .map(|total| total as f32);
+        let Some(grand_total) = grand_total.filter(|total| *total > threshold) else {
+            return Ok(response);
         };
 
-        // Save conversation message if conversation_id is present
+        let document_reference = response
+            .query_metadata
+            .as_ref()
+            .and_then(|metadata| metadata.get(DOCUMENT_REFERENCE_KEY)?.as_str())
+            .unwrap_or_default()
+            .to_string();
+        // Out of the publicly served artifacts/ until approved
+        let files = response
+            .files()
+            .map(hold_document)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| {
+                tracing::error!("Failed to hold the documents of {}: {}", document_reference, e);
+                QueryError::QuotationServiceError
+            })?;
+        let approval = QuotationApproval {
+            id: uuid::Uuid::new_v4(),
+            user_id: context.user_id,
+            platform: context.platform.clone(),
+            recipient: approval_recipient(context),
+            document_reference: document_reference.clone(),
+            grand_total,
+            text: response.text.clone(),
+            files,
+            status: ApprovalStatus::Pending,
+            decided_by: None,
+        };
+        self.database
+            .create_quotation_approval(&approval)
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to hold {} for approval: {}", document_reference, e);
+                QueryError::QuotationServiceError
+            })?;
+        info!(
+            "Holding {} (grand total {:.2}) for approval {}",
+            document_reference, grand_total, approval.id
+        );
+        if self.approvals.send(approval.clone()).is_err() {
+            tracing::warn!("No service is listening for approval {}", approval.id);
+        }
+
+        let mut metadata = match response.query_metadata {
+            Some(serde_json::Value::Object(map)) => map,
+            _ => serde_json::Map::new(),
+        };
+        metadata.insert(
+            PENDING_APPROVAL_KEY.to_string(),
+            serde_json::Value::String(approval.id.to_string()),
+        );
+        Ok(QueryResponse::new(format!(
+            "{} comes to Rs.{}, which needs a manager's approval. It will be sent here once approved.",
+            document_reference,
+            crate::pdf::format_amount(grand_total)
+        ))
+        .with_metadata(Some(serde_json::Value::Object(metadata))))
+    }
+
+    // Save conversation message if conversation_id is present
+    async fn save_conversation_message(
+        &self,
+        context: &SessionContext,
+        user_query: &str,
+        response: &QueryResponse,
+    ) {
         if let Some(conversation_id) = context.conversation_id {
             let structured_response = self.llm_service.create_structured_response_for_storage(
                 &response.text,
@@ -311,7 +724,7 @@ impl QueryFulfilment {
                 .save_conversation_message(
                     conversation_id,
                     context.session_id,
-                    original_query_str,
+                    user_query,
                     Some(structured_response),
                 )
                 .await
@@ -320,15 +733,89 @@ impl QueryFulfilment {
                 tracing::error!("Failed to save conversation message: {}", e);
             }
         }
+    }
 
-        Ok(response)
+    // Document generating queries are held back with a summary when the user has to confirm
+    fn confirmation_prompt(&self, query: &Query, context: &SessionContext) -> Option<String> {
+        if !self.confirm_intent.applies_to(
+            context.telegram_id.as_deref(),
+            context.user_phone.as_deref(),
+        ) {
+            return None;
+        }
+        describe_document_intent(query)
+            .map(|summary| format!("I'll generate {}, reply 'go' to proceed", summary))
+    }
+
+    // A bare "go" straight after a confirmation prompt runs the query that was held back,
+    // without going through the LLM again. Returns it with the message it was parsed from.
+    async fn take_confirmed_query(
+        &self,
+        query: &str,
+        context: &mut SessionContext,
+    ) -> Option<(Query, String)> {
+        if !is_confirmation(query)
+            || !self.confirm_intent.applies_to(
+                context.telegram_id.as_deref(),
+                context.user_phone.as_deref(),
+            )
+        {
+            return None;
+        }
+        let conversation = self
+            .database
+            .get_recent_conversation(context.user_id)
+            .await
+            .ok()??;
+        let held_back = conversation.messages.last()?;
+        let metadata = held_back
+            .structured_response
+            .as_ref()?
+            .response_metadata
+            .as_ref()?;
+        let pending = serde_json::from_str::<serde_json::Value>(metadata)
+            .ok()?
+            .get(PENDING_CONFIRMATION_KEY)?
+            .clone();
+        let query: Query = serde_json::from_value(pending).ok()?;
+        let enquiry = held_back.user_query.clone();
+
+        info!("Running query confirmed by the user");
+        context.conversation_id = Some(conversation.conversation_id);
+        let _ = self
+            .database
+            .update_session_query_type(context.session_id, query_type_name(&query), None)
+            .await;
+        Some((query, enquiry))
+    }
+
+    pub async fn preview_prompt(&self, sample_query: &str) -> String {
+        self.llm_service.preview_prompt(sample_query).await
+    }
+
+    // Shows what the LLM extracts from a query without acting on it - no prices, stock or PDFs
+    pub async fn dry_parse(
+        &self,
+        query: &str,
+        context: &mut SessionContext,
+        error_sender: &Sender<Alert>,
+    ) -> Result<QueryResponse, QueryError> {
+        let query = self.get_query_type(query, context, error_sender).await?;
+        let parsed = serde_json::to_string_pretty(&query)
+            .unwrap_or_else(|e| format!("Could not serialize query: {}", e));
+        Ok(QueryResponse {
+            text: format!("🔍 Parsed as {}:\n{}", query_type_name(&query), parsed),
+            file: None,
+            query_metadata: None,
+            ..Default::default()
+        })
     }
 
     pub async fn get_query_type(
         &self,
         query: &str,
         context: &mut SessionContext,
-        error_sender: &tokio::sync::mpsc::Sender<String>,
+        error_sender: &tokio::sync::mpsc::Sender<Alert>,
     ) -> Result<Query, QueryError> {
         let start_time = Instant::now();
         let query: Query = self
@@ -339,26 +826,18 @@ impl QueryFulfilment {
         let elapsed = start_time.elapsed();
 
         let timing_message = format!("LLM query parsing took: {:.2}s", elapsed.as_secs_f32());
-        let _ = error_sender.send(timing_message).await;
+        let _ = error_sender.send(Alert::usage(timing_message)).await;
 
         info!("Parsed query successfully");
 
-        // Update the session with the actual query type
-        let query_type = match &query {
-            Query::MetalPricing => "MetalPricing",
-            Query::GetPriceList { .. } => "GetPriceList",
-            Query::GetQuotation(_) => "GetQuotation",
-            Query::GetProformaInvoice(_) => "GetProformaInvoice",
-            Query::GetPricesOnly(_) => "GetPricesOnly",
-            Query::GetStock { .. } => "GetStock",
-            Query::ListAvailablePricelists { .. } => "ListAvailablePricelists",
-            Query::UnsupportedQuery => "UnsupportedQuery",
-        };
-
         // Update the session with actual query type
         let response = self
             .database
-            .update_session_query_type(context.session_id, query_type)
+            .update_session_query_type(
+                context.session_id,
+                query_type_name(&query),
+                context.last_model_used.as_deref(),
+            )
             .await;
         info!("Database query update response:{:#?}", response);
         Ok(query)
@@ -368,14 +847,58 @@ impl QueryFulfilment {
         let mut lines = Vec::new();
 
         for item in response.items {
-            let line = format!("{}: Rs.{:.2}/mtr", item.description, item.price);
+            let line = format!(
+                "{}: Rs.{:.2}/{}",
+                item.description,
+                item.price,
+                item.unit.rate_suffix()
+            );
 
             lines.push(line);
         }
 
+        // Partial results name what was left out, so the user can correct just those
+        if !response.skipped_items.is_empty() {
+            lines.push(format!(
+                "\nCouldn't find: {}",
+                describe_skipped(&response.skipped_items)
+            ));
+        }
+
         lines.join("\n")
     }
 
+    // Looked up by the addressee's name first, then by who sent the query. A failed lookup
+    // only loses the defaults.
+    async fn with_customer_defaults(
+        &self,
+        request: QuotationRequest,
+        context: &SessionContext,
+    ) -> QuotationRequest {
+        let mut keys = Vec::new();
+        if let Some(name) = request.to.as_ref().and_then(|to| to.first()) {
+            keys.push(name.clone());
+        }
+        if let Some(telegram_id) = &context.telegram_id {
+            keys.push(telegram_customer_key(telegram_id));
+        }
+        if let Some(phone) = &context.user_phone {
+            keys.push(phone.clone());
+        }
+
+        match self.database.find_customer_profile(&keys).await {
+            Ok(Some(profile)) => {
+                info!(customer = %profile.customer_key, "Applying customer profile");
+                apply_customer_profile(request, &profile)
+            }
+            Ok(None) => request,
+            Err(e) => {
+                tracing::warn!("Customer profile lookup failed: {}", e);
+                request
+            }
+        }
+    }
+
     fn generate_document_details(&self, document_type: DocumentType) -> (String, String, String) {
         let date = Local::now().date_naive();
         let formatted_date = date.format("%Y%m%d").to_string();
@@ -401,4 +924,718 @@ impl QueryFulfilment {
 
         (quotation_number, quotation_date, filename)
     }
+
+    // Renders a PDF for an already priced request, returning its number and path
+    fn render_document(
+        &self,
+        q_response: &QuotationResponse,
+        document_type: DocumentType,
+    ) -> Result<(String, String), QueryError> {
+        let (number, date, filename) = self.generate_document_details(document_type);
+        write_document_pdf(
+            &number,
+            &date,
+            q_response,
+            &filename,
+            document_type,
+            &self.quotation_service.table_layout(),
+            &self.branding,
+        )?;
+        Ok((number, format!("artifacts/{}", filename)))
+    }
+}
+
+// A letterhead that is missing or can't be decoded fails the query instead of panicking its task
+fn write_document_pdf(
+    number: &str,
+    date: &str,
+    q_response: &QuotationResponse,
+    filename: &str,
+    document_type: DocumentType,
+    layout: &TableLayout,
+    branding: &BrandingConfig,
+) -> Result<(), QueryError> {
+    create_quotation_pdf(
+        number,
+        date,
+        q_response,
+        filename,
+        document_type,
+        layout,
+        branding,
+    )
+    .map_err(|e| {
+        tracing::error!("Failed to create {:?} PDF: {}", document_type, e);
+        QueryError::QuotationServiceError
+    })
+}
+
+// Used at the webhooks with the size the platform reports, so oversized media is never downloaded,
+// and again on the downloaded bytes in case the reported size was missing or wrong
+pub fn check_media_size(size: u64, limit: u64) -> Result<(), QueryError> {
+    if size > limit {
+        tracing::warn!("Rejected {} byte media, limit is {} bytes", size, limit);
+        return Err(QueryError::MediaTooLarge(size, limit));
+    }
+    Ok(())
+}
+
+// Outer guard on a whole query, so a slow stage can't leave the user waiting indefinitely
+async fn within_deadline(
+    timeout: Duration,
+    pipeline: impl Future<Output = Result<QueryResponse, QueryError>>,
+) -> Result<QueryResponse, QueryError> {
+    match tokio::time::timeout(timeout, pipeline).await {
+        Ok(result) => result,
+        Err(_) => {
+            tracing::warn!("Query exceeded the {}s deadline", timeout.as_secs());
+            Err(QueryError::Timeout(timeout.as_secs()))
+        }
+    }
+}
+
+fn query_type_name(query: &Query) -> &'static str {
+    match query {
+        Query::MetalPricing => "MetalPricing",
+        Query::GetPriceList { .. } => "GetPriceList",
+        Query::GetQuotation(_) => "GetQuotation",
+        Query::GetProformaInvoice(_) => "GetProformaInvoice",
+        Query::GetQuotationAndProforma(_) => "GetQuotationAndProforma",
+        Query::GetPricesOnly(_) => "GetPricesOnly",
+        Query::DescribeProduct(_) => "DescribeProduct",
+        Query::GetStock { .. } => "GetStock",
+        Query::ListAvailablePricelists { .. } => "ListAvailablePricelists",
+        Query::GetOrderStatus { .. } => "GetOrderStatus",
+        Query::Batch(_) => "Batch",
+        Query::SessionLimitReached { .. } => "SessionLimitReached",
+        Query::UnsupportedQuery => "UnsupportedQuery",
+    }
+}
+
+// One line summary of a document generating query eg. "a QUOTATION for 3 items"
+fn describe_document_intent(query: &Query) -> Option<String> {
+    let (document, request) = match query {
+        Query::Batch(queries) => {
+            let documents: Vec<String> = queries
+                .iter()
+                .filter_map(describe_document_intent)
+                .collect();
+            return (!documents.is_empty()).then(|| documents.join(" and "));
+        }
+        Query::GetQuotation(request) => ("a QUOTATION", request),
+        Query::GetProformaInvoice(request) => ("a PROFORMA INVOICE", request),
+        Query::GetQuotationAndProforma(request) => ("a QUOTATION and a PROFORMA INVOICE", request),
+        _ => return None,
+    };
+    let count = request.items.len();
+    Some(format!(
+        "{} for {} item{}",
+        document,
+        count,
+        if count == 1 { "" } else { "s" }
+    ))
+}
+
+fn is_confirmation(text: &str) -> bool {
+    let text = text
+        .trim()
+        .trim_end_matches(|c: char| c.is_ascii_punctuation())
+        .to_lowercase();
+    matches!(
+        text.as_str(),
+        "go" | "go ahead" | "yes" | "y" | "ok" | "okay" | "proceed" | "confirm"
+    )
+}
+
+fn with_warnings(text: &str, quotation: &QuotationResponse) -> String {
+    if quotation.warnings.is_empty() {
+        return text.to_string();
+    }
+    format!(
+        "{}\n\nWarning: {}",
+        text,
+        quotation.warnings.join("\nWarning: ")
+    )
+}
+
+fn describe_skipped(skipped_items: &[SkippedItem]) -> String {
+    skipped_items
+        .iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Short reply to an order status enquiry, from what was saved with the document's session
+fn order_status_text(reference: &str, session: Option<&ReferencedSession>) -> String {
+    let reference = reference.trim();
+    let Some(session) = session else {
+        return format!(
+            "I couldn't find {} among your documents. Please check the reference number.",
+            reference
+        );
+    };
+    let mut text = format!(
+        "{} was created on {}",
+        session.document_reference,
+        session.created_at.with_timezone(&Local).format("%d/%m/%Y")
+    );
+    let metadata = session.metadata.as_ref();
+    if let Some(totals) = metadata.and_then(|metadata| metadata.get("quotation_totals")) {
+        let count = totals["item_count"].as_u64().unwrap_or(0);
+        text.push_str(&format!(
+            ": {} item{}, grand total Rs.{}",
+            count,
+            if count == 1 { "" } else { "s" },
+            crate::pdf::format_amount(totals["grand_total"].as_f64().unwrap_or(0.0) as f32)
+        ));
+    }
+    text.push('.');
+    if metadata.is_some_and(|metadata| metadata.get(PENDING_APPROVAL_KEY).is_some()) {
+        text.push_str(" It was held for a manager's approval.");
+    }
+    text
+}
+
+// Adds the computed totals next to the serialized request so analytics can report quote values
+fn with_quotation_totals(
+    metadata: Option<serde_json::Value>,
+    quotation: &QuotationResponse,
+) -> Option<serde_json::Value> {
+    let mut metadata = match metadata {
+        Some(serde_json::Value::Object(map)) => map,
+        _ => serde_json::Map::new(),
+    };
+    metadata.insert(
+        "quotation_totals".to_string(),
+        serde_json::json!({
+            "grand_total": quotation.grand_total,
+            "basic_total": quotation.basic_total,
+            "item_count": quotation.items.len(),
+        }),
+    );
+    Some(serde_json::Value::Object(metadata))
+}
+
+// Fills in a customer's standing terms wherever the request leaves them out. Zero delivery
+// charges count as not given. Items with their own discount or price keep them.
+fn apply_customer_profile(
+    mut request: QuotationRequest,
+    profile: &CustomerProfile,
+) -> QuotationRequest {
+    // A lone "To:" line naming the customer stands for their full address. Anything else,
+    // including names without a saved address, is printed as given.
+    let names_customer = matches!(
+        request.to.as_deref(),
+        Some([name]) if customer_key(name) == profile.customer_key
+    );
+    if names_customer && profile.address.is_some() {
+        request.to = profile.address.clone();
+    }
+    if request.terms_and_conditions.is_none() {
+        request.terms_and_conditions = profile.terms_and_conditions.clone();
+    }
+    if request.estimated_delivery.is_none() {
+        request.estimated_delivery = profile.estimated_delivery.clone();
+    }
+    if request.delivery_charges == 0.0 {
+        request.delivery_charges = profile.delivery_charges.unwrap_or(0.0);
+    }
+    if let Some(discount) = profile.discount {
+        for item in &mut request.items {
+            if item.discount.is_none() && item.user_base_price.is_none() {
+                item.discount = Some(discount);
+            }
+        }
+    }
+    request
+}
+
+// Where the held documents go once approved, on the platform the request came in on
+fn approval_recipient(context: &SessionContext) -> String {
+    match context.platform.as_str() {
+        "whatsapp" => context
+            .user_phone
+            .as_ref()
+            .map(|phone| format!("whatsapp:{}", phone))
+            .unwrap_or_default(),
+        _ => context.telegram_id.clone().unwrap_or_default(),
+    }
+}
+
+fn with_document_reference(
+    metadata: Option<serde_json::Value>,
+    reference: &str,
+) -> Option<serde_json::Value> {
+    let mut metadata = match metadata {
+        Some(serde_json::Value::Object(map)) => map,
+        _ => serde_json::Map::new(),
+    };
+    metadata.insert(
+        DOCUMENT_REFERENCE_KEY.to_string(),
+        serde_json::Value::String(reference.to_string()),
+    );
+    Some(serde_json::Value::Object(metadata))
+}
+
+// One reply for a batch - the texts in order, the first file as the main one and the rest
+// attached. Each part's metadata is kept, with the document references gathered at the top
+// level where the session expects them.
+fn combine_responses(responses: Vec<QueryResponse>) -> QueryResponse {
+    let mut combined = QueryResponse::default();
+    let mut texts = Vec::new();
+    let mut parts = Vec::new();
+    let mut references = Vec::new();
+    for response in responses {
+        texts.push(response.text);
+        for file in response.file.into_iter().chain(response.attachments) {
+            match combined.file {
+                None => combined.file = Some(file),
+                Some(_) => combined.attachments.push(file),
+            }
+        }
+        combined.buttons.extend(response.buttons);
+        let metadata = response.query_metadata.unwrap_or(serde_json::Value::Null);
+        if let Some(reference) = metadata
+            .get(DOCUMENT_REFERENCE_KEY)
+            .and_then(|r| r.as_str())
+        {
+            references.push(reference.to_string());
+        }
+        parts.push(metadata);
+    }
+    combined.text = texts.join("\n\n");
+
+    let metadata = Some(serde_json::json!({ "batch": parts }));
+    combined.query_metadata = if references.is_empty() {
+        metadata
+    } else {
+        with_document_reference(metadata, &references.join(", "))
+    };
+    combined
+}
+
+// Guesses which help section the user was after from keywords in their message
+fn help_topic(query: &str) -> Option<&'static str> {
+    let query = query.to_lowercase();
+    let words: Vec<&str> = query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect();
+
+    if query.contains("proforma") || query.contains("performa") || words.contains(&"pi") {
+        Some("proforma")
+    } else if query.contains("quot") {
+        Some("quotation")
+    } else if query.contains("price list") || query.contains("pricelist") {
+        Some("price_lists")
+    } else if query.contains("metal") || words.contains(&"mcx") {
+        Some("metal_prices")
+    } else if words
+        .iter()
+        .any(|w| matches!(*w, "price" | "prices" | "pricing" | "rate" | "rates"))
+    {
+        Some("item_prices")
+    } else {
+        None
+    }
+}
+
+// Lists what the assistant can do. A query that looks like one of the help topics also gets
+// that topic's section, since the user was probably close.
+fn unsupported_query_text(capabilities: &[String], topic: Option<&str>) -> String {
+    let mut text =
+        String::from("I couldn't understand that request. Here is what I can help with:\n");
+    for capability in capabilities {
+        text.push_str(&format!("\n• {}", capability));
+    }
+    if let Some(topic) = topic {
+        if let Ok(help) = std::fs::read_to_string("assets/help.txt") {
+            text.push_str(&format!("\n\n{}", select_help_section(&help, Some(topic))));
+        }
+    }
+    text.push_str("\n\nSend /help for examples of how to ask.");
+    text
+}
+
+// help.txt is split into sections by `[topic]` marker lines which are never shown to users
+fn select_help_section(help: &str, topic: Option<&str>) -> String {
+    let is_marker = |line: &str| {
+        let line = line.trim();
+        line.starts_with('[') && line.ends_with(']')
+    };
+
+    if let Some(topic) = topic {
+        let marker = format!("[{}]", topic);
+        let section: Vec<&str> = help
+            .lines()
+            .skip_while(|line| line.trim() != marker)
+            .skip(1)
+            .take_while(|line| !is_marker(line))
+            .collect();
+        let section = section.join("\n");
+        if !section.trim().is_empty() {
+            return section.trim().to_string();
+        }
+    }
+
+    help.lines()
+        .filter(|line| !is_marker(line))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prices::item_prices::{Cable, CoaxialType, Product};
+    use crate::quotation::QuoteItem;
+
+    const HELP: &str =
+        "[intro]\nIntro line\n\n[quotation]\nQuote help\n- example\n\n[proforma]\nPI help\n";
+
+    #[test]
+    fn test_help_topic_from_query() {
+        assert_eq!(help_topic("quote for 4c x 2.5 flex"), Some("quotation"));
+        assert_eq!(help_topic("give PI for abc"), Some("proforma"));
+        assert_eq!(help_topic("kei pricelist"), Some("price_lists"));
+        assert_eq!(help_topic("mcx rates"), Some("metal_prices"));
+        assert_eq!(help_topic("rate of 4c cable"), Some("item_prices"));
+        assert_eq!(help_topic("hello there"), None);
+        // Only whole words count - "generate" and "accurate" aren't asking for rates
+        assert_eq!(help_topic("generate an accurate list"), None);
+    }
+
+    #[test]
+    fn test_select_help_section() {
+        assert_eq!(
+            select_help_section(HELP, Some("quotation")),
+            "Quote help\n- example"
+        );
+        assert_eq!(select_help_section(HELP, Some("proforma")), "PI help");
+    }
+
+    #[test]
+    fn test_select_help_section_falls_back_to_full_text() {
+        let full = "Intro line\n\nQuote help\n- example\n\nPI help";
+        assert_eq!(select_help_section(HELP, None), full);
+        assert_eq!(select_help_section(HELP, Some("stock")), full);
+    }
+
+    #[test]
+    fn test_unsupported_query_lists_capabilities() {
+        let capabilities = vec!["Get metal prices".to_string(), "Check stock".to_string()];
+        let text = unsupported_query_text(&capabilities, None);
+        assert!(text.starts_with("I couldn't understand that request"));
+        assert!(text.contains("\n• Get metal prices\n• Check stock"));
+        assert!(text.ends_with("Send /help for examples of how to ask."));
+
+        // A query close to a help topic also gets that topic's section
+        let text = unsupported_query_text(&capabilities, Some("quotation"));
+        assert!(text.contains("**Quotations**"));
+        assert!(text.ends_with("Send /help for examples of how to ask."));
+    }
+
+    #[test]
+    fn test_order_status_text() {
+        let mut session = ReferencedSession {
+            id: uuid::Uuid::new_v4(),
+            document_reference: "Q-20250821-1234".to_string(),
+            metadata: Some(serde_json::json!({
+                "quotation_totals": {"grand_total": 1180.0, "basic_total": 1000.0, "item_count": 2}
+            })),
+            created_at: chrono::DateTime::parse_from_rfc3339("2025-08-21T12:00:00Z")
+                .unwrap()
+                .with_timezone(&chrono::Utc),
+        };
+        let date = session.created_at.with_timezone(&Local).format("%d/%m/%Y");
+        assert_eq!(
+            order_status_text("Q-20250821-1234", Some(&session)),
+            format!(
+                "Q-20250821-1234 was created on {}: 2 items, grand total Rs.1180.00.",
+                date
+            )
+        );
+
+        session.metadata.as_mut().unwrap()[PENDING_APPROVAL_KEY] = serde_json::json!("id");
+        assert!(order_status_text("Q-20250821-1234", Some(&session))
+            .ends_with("Rs.1180.00. It was held for a manager's approval."));
+
+        session.metadata = None;
+        assert_eq!(
+            order_status_text("Q-20250821-1234", Some(&session)),
+            format!("Q-20250821-1234 was created on {}.", date)
+        );
+        assert!(order_status_text(" Q-1 ", None).starts_with("I couldn't find Q-1 among"));
+    }
+
+    #[test]
+    fn test_with_quotation_totals_keeps_request() {
+        let quotation = QuotationResponse {
+            basic_total: 1000.0,
+            total_with_delivery: 1000.0,
+            taxes: 180.0,
+            grand_total: 1180.0,
+            ..QuotationResponse::default()
+        };
+        let metadata = Some(serde_json::json!({"GetQuotation": {"delivery_charges": 0.0}}));
+
+        let metadata = with_quotation_totals(metadata, &quotation).unwrap();
+
+        assert_eq!(metadata["GetQuotation"]["delivery_charges"], 0.0);
+        assert_eq!(metadata["quotation_totals"]["grand_total"], 1180.0);
+        assert_eq!(metadata["quotation_totals"]["basic_total"], 1000.0);
+        assert_eq!(metadata["quotation_totals"]["item_count"], 0);
+
+        let metadata = with_document_reference(Some(metadata), "Q-20250821-1234").unwrap();
+        assert_eq!(metadata[DOCUMENT_REFERENCE_KEY], "Q-20250821-1234");
+        assert_eq!(metadata["quotation_totals"]["grand_total"], 1180.0);
+    }
+
+    #[test]
+    fn test_missing_letterhead_is_a_query_error() {
+        let quotation = QuotationResponse {
+            basic_total: 1000.0,
+            total_with_delivery: 1000.0,
+            taxes: 180.0,
+            grand_total: 1180.0,
+            ..QuotationResponse::default()
+        };
+        let branding = BrandingConfig {
+            header_image_path: "assets/no-such-letterhead.jpg".to_string(),
+            ..BrandingConfig::default()
+        };
+
+        let result = write_document_pdf(
+            "Q-20250821-1234",
+            "21st August, 2025",
+            &quotation,
+            "test_missing_letterhead.pdf",
+            DocumentType::Quotation,
+            &TableLayout::default(),
+            &branding,
+        );
+
+        assert!(matches!(result, Err(QueryError::QuotationServiceError)));
+    }
+
+    #[test]
+    fn test_internal_note_is_kept_in_metadata_only() {
+        let request = QuotationRequest::builder()
+            .with_internal_note("customer haggled, gave extra 2%")
+            .build();
+        let metadata = serde_json::to_value(Query::GetQuotation(request)).ok();
+        let quotation = QuotationResponse::default();
+
+        let metadata = with_quotation_totals(metadata, &quotation).unwrap();
+
+        assert_eq!(
+            metadata["GetQuotation"]["internal_note"],
+            "customer haggled, gave extra 2%"
+        );
+    }
+
+    #[test]
+    fn test_confirmation_replies() {
+        assert!(is_confirmation("go"));
+        assert!(is_confirmation(" Go! "));
+        assert!(is_confirmation("OK."));
+        assert!(!is_confirmation("go for 4c x 2.5 instead"));
+        assert!(!is_confirmation("no"));
+    }
+
+    #[test]
+    fn test_describe_document_intent() {
+        let item = || QuoteItem::builder(Product::Cable(Cable::Coaxial(CoaxialType::RG6))).build();
+        let quotation = Query::GetQuotation(
+            QuotationRequest::builder()
+                .with_items(vec![item(), item(), item()])
+                .build(),
+        );
+        assert_eq!(
+            describe_document_intent(&quotation).unwrap(),
+            "a QUOTATION for 3 items"
+        );
+        let proforma =
+            Query::GetProformaInvoice(QuotationRequest::builder().with_item(item()).build());
+        assert_eq!(
+            describe_document_intent(&proforma).unwrap(),
+            "a PROFORMA INVOICE for 1 item"
+        );
+        assert_eq!(describe_document_intent(&Query::MetalPricing), None);
+
+        let batch = Query::Batch(vec![Query::MetalPricing, proforma, quotation]);
+        assert_eq!(
+            describe_document_intent(&batch).unwrap(),
+            "a PROFORMA INVOICE for 1 item and a QUOTATION for 3 items"
+        );
+        assert_eq!(
+            describe_document_intent(&Query::Batch(vec![Query::MetalPricing])),
+            None
+        );
+    }
+
+    #[test]
+    fn test_combine_responses_keeps_every_part() {
+        let combined = combine_responses(vec![
+            QueryResponse::new("Copper: Rs.900/kg"),
+            QueryResponse::new("Quotation created for given enquiry")
+                .with_file("artifacts/quotation.pdf")
+                .with_attachment("artifacts/proforma.pdf")
+                .with_metadata(Some(serde_json::json!({
+                    DOCUMENT_REFERENCE_KEY: "Q-20250821-1234, PI-20250821-1234"
+                }))),
+            QueryResponse::new("Stock: 200 M").with_file("artifacts/stock.pdf"),
+        ]);
+
+        assert_eq!(
+            combined.text,
+            "Copper: Rs.900/kg\n\nQuotation created for given enquiry\n\nStock: 200 M"
+        );
+        assert_eq!(
+            combined.files().collect::<Vec<_>>(),
+            vec![
+                "artifacts/quotation.pdf",
+                "artifacts/proforma.pdf",
+                "artifacts/stock.pdf"
+            ]
+        );
+        let metadata = combined.query_metadata.unwrap();
+        assert_eq!(
+            metadata[DOCUMENT_REFERENCE_KEY],
+            "Q-20250821-1234, PI-20250821-1234"
+        );
+        assert_eq!(metadata["batch"].as_array().unwrap().len(), 3);
+        assert!(metadata["batch"][0].is_null());
+
+        let without_documents = combine_responses(vec![QueryResponse::new("a")]);
+        assert!(without_documents.query_metadata.unwrap()[DOCUMENT_REFERENCE_KEY].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_shared_runtime_config_updates_are_visible_to_clones() {
+        let config = SharedRuntimeConfig::default();
+        let reader = config.clone();
+        assert_eq!(reader.snapshot().await.primary_llm, "groq");
+
+        config
+            .update(|config| config.primary_llm = "claude".to_string())
+            .await;
+
+        assert_eq!(reader.snapshot().await.primary_llm, "claude");
+    }
+
+    #[tokio::test]
+    async fn test_slow_pipeline_times_out() {
+        let quick = async {
+            Ok(QueryResponse {
+                text: "done".to_string(),
+                file: None,
+                query_metadata: None,
+                ..Default::default()
+            })
+        };
+        let response = within_deadline(Duration::from_secs(5), quick)
+            .await
+            .unwrap();
+        assert_eq!(response.text, "done");
+
+        let slow = async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Err(QueryError::QuotationServiceError)
+        };
+        assert!(matches!(
+            within_deadline(Duration::from_millis(50), slow).await,
+            Err(QueryError::Timeout(_))
+        ));
+    }
+
+    #[test]
+    fn test_customer_profile_fills_only_missing_terms() {
+        let profile = CustomerProfile {
+            customer_key: "btl epc ltd".to_string(),
+            terms_and_conditions: Some(vec!["Payment: 30 days credit".to_string()]),
+            discount: Some(0.05),
+            delivery_charges: Some(500.0),
+            estimated_delivery: Some("Ready stock".to_string()),
+            ..CustomerProfile::default()
+        };
+        let item = || QuoteItem::builder(Product::Cable(Cable::Coaxial(CoaxialType::RG6)));
+
+        let request = QuotationRequest::builder()
+            .with_items(vec![item().build(), item().with_discount(0.1).build()])
+            .build();
+        let request = apply_customer_profile(request, &profile);
+        assert_eq!(request.terms_and_conditions, profile.terms_and_conditions);
+        assert_eq!(request.estimated_delivery.as_deref(), Some("Ready stock"));
+        assert_eq!(request.delivery_charges, 500.0);
+        assert_eq!(request.items[0].discount, Some(0.05));
+        // The request's own values win
+        assert_eq!(request.items[1].discount, Some(0.1));
+
+        let request = QuotationRequest::builder()
+            .with_delivery_charges(200.0)
+            .with_terms_and_conditions(vec!["Advance payment".to_string()])
+            .with_estimated_delivery("2 weeks")
+            .build();
+        let request = apply_customer_profile(request, &profile);
+        assert_eq!(request.delivery_charges, 200.0);
+        assert_eq!(
+            request.terms_and_conditions,
+            Some(vec!["Advance payment".to_string()])
+        );
+        assert_eq!(request.estimated_delivery.as_deref(), Some("2 weeks"));
+    }
+
+    #[test]
+    fn test_customer_name_expands_to_saved_address() {
+        let address = vec![
+            "BTL EPC Ltd.".to_string(),
+            "12 Park Street".to_string(),
+            "Kolkata 700016".to_string(),
+        ];
+        let profile = CustomerProfile {
+            customer_key: "btl epc ltd".to_string(),
+            address: Some(address.clone()),
+            ..CustomerProfile::default()
+        };
+        let addressed_to = |to: &[&str]| {
+            let request = QuotationRequest::builder()
+                .with_to(to.iter().map(|line| line.to_string()).collect())
+                .build();
+            apply_customer_profile(request, &profile).to.unwrap()
+        };
+
+        assert_eq!(addressed_to(&["BTL  EPC Ltd"]), address);
+        // Explicit lines and other names are left as they are
+        assert_eq!(
+            addressed_to(&["BTL EPC Ltd", "Salt Lake"]),
+            vec!["BTL EPC Ltd", "Salt Lake"]
+        );
+        assert_eq!(addressed_to(&["Skipper Ltd"]), vec!["Skipper Ltd"]);
+
+        let without_address = CustomerProfile {
+            address: None,
+            ..profile.clone()
+        };
+        let request = QuotationRequest::builder()
+            .with_to(vec!["BTL EPC Ltd".to_string()])
+            .build();
+        assert_eq!(
+            apply_customer_profile(request, &without_address).to,
+            Some(vec!["BTL EPC Ltd".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_check_media_size() {
+        assert!(check_media_size(1024, 1024).is_ok());
+        assert!(check_media_size(0, 1024).is_ok());
+        assert!(matches!(
+            check_media_size(1025, 1024),
+            Err(QueryError::MediaTooLarge(1025, 1024))
+        ));
+    }
 }
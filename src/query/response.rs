@@ -0,0 +1,81 @@
+// What fulfilling a query produces, independent of the platform it came in on. Telegram and
+// WhatsApp each adapt it to what they can send.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryResponse {
+    pub text: String,
+    // Path of a generated document / pricelist to send with the reply
+    pub file: Option<String>,
+    // Stored on the query session and the conversation history
+    pub query_metadata: Option<serde_json::Value>,
+    // Follow-up actions a platform may offer as buttons
+    pub buttons: Vec<ResponseButton>,
+    // Files beyond `file`, eg. a quotation along with its pricelist
+    pub attachments: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResponseButton {
+    pub label: String,
+    // Sent back as the user's next query when the button is pressed
+    pub query: String,
+}
+
+impl QueryResponse {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_file(mut self, file: impl Into<String>) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    pub fn with_metadata(mut self, query_metadata: Option<serde_json::Value>) -> Self {
+        self.query_metadata = query_metadata;
+        self
+    }
+
+    pub fn with_button(mut self, label: impl Into<String>, query: impl Into<String>) -> Self {
+        self.buttons.push(ResponseButton {
+            label: label.into(),
+            query: query.into(),
+        });
+        self
+    }
+
+    pub fn with_attachment(mut self, file: impl Into<String>) -> Self {
+        self.attachments.push(file.into());
+        self
+    }
+
+    // Every file to send, the main one first
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.file
+            .iter()
+            .chain(self.attachments.iter())
+            .map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_files_lists_main_file_first() {
+        let response = QueryResponse::new("Quotation")
+            .with_attachment("pricelists/cables.pdf")
+            .with_file("artifacts/quotation.pdf")
+            .with_button("Proforma", "make this a proforma");
+
+        assert_eq!(
+            response.files().collect::<Vec<_>>(),
+            vec!["artifacts/quotation.pdf", "pricelists/cables.pdf"]
+        );
+        assert_eq!(response.buttons[0].query, "make this a proforma");
+        assert_eq!(QueryResponse::new("text").files().count(), 0);
+    }
+}
@@ -0,0 +1,277 @@
+use super::{QuotationResponse, QuotedItem};
+use crate::pdf::{format_amount, format_quantity};
+use crate::prices::item_prices::Description;
+
+// Differences below a paisa are rounding noise, not a revision
+const AMOUNT_EPSILON: f32 = 0.005;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ItemChange {
+    Added {
+        description: String,
+        quantity: f32,
+        price: f32,
+    },
+    Removed {
+        description: String,
+        quantity: f32,
+        price: f32,
+    },
+    Changed {
+        description: String,
+        old_quantity: f32,
+        new_quantity: f32,
+        old_price: f32,
+        new_price: f32,
+    },
+}
+
+// What changed between an earlier quotation and its revision
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuotationDiff {
+    pub changes: Vec<ItemChange>,
+    pub old_grand_total: f32,
+    pub new_grand_total: f32,
+}
+
+impl QuotationDiff {
+    pub fn grand_total_delta(&self) -> f32 {
+        self.new_grand_total - self.old_grand_total
+    }
+
+    pub fn is_unchanged(&self) -> bool {
+        self.changes.is_empty() && self.grand_total_delta().abs() < AMOUNT_EPSILON
+    }
+
+    pub fn summary(&self) -> String {
+        if self.is_unchanged() {
+            return "No changes between the two quotations".to_string();
+        }
+        let mut lines = vec!["Changes from the earlier quotation:".to_string()];
+        for change in &self.changes {
+            lines.push(match change {
+                ItemChange::Added {
+                    description,
+                    quantity,
+                    price,
+                } => format!(
+                    "+ {}: {} @ Rs.{}",
+                    description,
+                    format_quantity(*quantity),
+                    format_amount(*price)
+                ),
+                ItemChange::Removed {
+                    description,
+                    quantity,
+                    price,
+                } => format!(
+                    "- {}: {} @ Rs.{}",
+                    description,
+                    format_quantity(*quantity),
+                    format_amount(*price)
+                ),
+                ItemChange::Changed {
+                    description,
+                    old_quantity,
+                    new_quantity,
+                    old_price,
+                    new_price,
+                } => {
+                    let mut parts = Vec::new();
+                    if differs(*old_price, *new_price) {
+                        parts.push(format!(
+                            "Rs.{} -> Rs.{}",
+                            format_amount(*old_price),
+                            format_amount(*new_price)
+                        ));
+                    }
+                    if differs(*old_quantity, *new_quantity) {
+                        parts.push(format!(
+                            "qty {} -> {}",
+                            format_quantity(*old_quantity),
+                            format_quantity(*new_quantity)
+                        ));
+                    }
+                    format!("~ {}: {}", description, parts.join(", "))
+                }
+            });
+        }
+        let delta = self.grand_total_delta();
+        lines.push(format!(
+            "Grand total: Rs.{} -> Rs.{} ({}Rs.{})",
+            format_amount(self.old_grand_total),
+            format_amount(self.new_grand_total),
+            if delta < 0.0 { "-" } else { "+" },
+            format_amount(delta.abs())
+        ));
+        lines.join("\n")
+    }
+}
+
+// Lines are matched on description and brand, in order, so a repeated item pairs up with its
+// counterpart in the other quotation
+pub fn diff_quotations(old: &QuotationResponse, new: &QuotationResponse) -> QuotationDiff {
+    let new_keys: Vec<String> = new.items.iter().map(item_key).collect();
+    let mut matched = vec![false; new.items.len()];
+    let mut changes = Vec::new();
+
+    for old_item in &old.items {
+        let key = item_key(old_item);
+        let counterpart =
+            (0..new_keys.len()).find(|&index| !matched[index] && new_keys[index] == key);
+        match counterpart {
+            Some(index) => {
+                matched[index] = true;
+                let new_item = &new.items[index];
+                if differs(old_item.price, new_item.price)
+                    || differs(old_item.quantity_mtrs, new_item.quantity_mtrs)
+                {
+                    changes.push(ItemChange::Changed {
+                        description: key,
+                        old_quantity: old_item.quantity_mtrs,
+                        new_quantity: new_item.quantity_mtrs,
+                        old_price: old_item.price,
+                        new_price: new_item.price,
+                    });
+                }
+            }
+            None => changes.push(ItemChange::Removed {
+                description: key,
+                quantity: old_item.quantity_mtrs,
+                price: old_item.price,
+            }),
+        }
+    }
+
+    for ((new_item, key), matched) in new.items.iter().zip(new_keys).zip(matched) {
+        if !matched {
+            changes.push(ItemChange::Added {
+                description: key,
+                quantity: new_item.quantity_mtrs,
+                price: new_item.price,
+            });
+        }
+    }
+
+    QuotationDiff {
+        changes,
+        old_grand_total: old.grand_total,
+        new_grand_total: new.grand_total,
+    }
+}
+
+// Same wording as the PDF line, with the brand since a brand swap is a different item
+fn item_key(item: &QuotedItem) -> String {
+    let mut extras = Vec::new();
+    if item.loading_frls > 0.0 {
+        extras.push("frls".to_string());
+    }
+    if item.loading_pvc > 0.0 {
+        extras.push("pvc".to_string());
+    }
+    format!("{} ({})", item.product.get_description(extras), item.brand)
+}
+
+fn differs(old: f32, new: f32) -> bool {
+    (old - new).abs() >= AMOUNT_EPSILON
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prices::item_prices::{Cable, CoaxialType, Product};
+
+    fn item(coaxial: CoaxialType, quantity: f32, price: f32) -> QuotedItem {
+        QuotedItem::new(Product::Cable(Cable::Coaxial(coaxial)), "kei", quantity, price)
+    }
+
+    fn quotation(items: Vec<QuotedItem>, grand_total: f32) -> QuotationResponse {
+        QuotationResponse {
+            items,
+            grand_total,
+            ..QuotationResponse::default()
+        }
+    }
+
+    #[test]
+    fn test_diff_finds_added_removed_and_changed_items() {
+        let old = quotation(
+            vec![
+                item(CoaxialType::RG6, 100.0, 20.0),
+                item(CoaxialType::RG11, 50.0, 30.0),
+            ],
+            4130.0,
+        );
+        let new = quotation(
+            vec![
+                item(CoaxialType::RG6, 150.0, 18.5),
+                item(CoaxialType::RG59, 200.0, 12.0),
+            ],
+            6106.0,
+        );
+
+        let diff = diff_quotations(&old, &new);
+        let rg6 = item_key(&old.items[0]);
+        let rg11 = item_key(&old.items[1]);
+        let rg59 = item_key(&new.items[1]);
+        assert_eq!(
+            diff.changes,
+            vec![
+                ItemChange::Changed {
+                    description: rg6.clone(),
+                    old_quantity: 100.0,
+                    new_quantity: 150.0,
+                    old_price: 20.0,
+                    new_price: 18.5,
+                },
+                ItemChange::Removed {
+                    description: rg11.clone(),
+                    quantity: 50.0,
+                    price: 30.0,
+                },
+                ItemChange::Added {
+                    description: rg59.clone(),
+                    quantity: 200.0,
+                    price: 12.0,
+                },
+            ]
+        );
+        assert_eq!(diff.grand_total_delta(), 1976.0);
+        assert_eq!(
+            diff.summary(),
+            format!(
+                "Changes from the earlier quotation:\n~ {}: Rs.20.00 -> Rs.18.50, qty 100 -> 150\n- {}: 50 @ Rs.30.00\n+ {}: 200 @ Rs.12.00\nGrand total: Rs.4130.00 -> Rs.6106.00 (+Rs.1976.00)",
+                rg6, rg11, rg59
+            )
+        );
+    }
+
+    #[test]
+    fn test_identical_quotations_have_no_diff() {
+        let items = || {
+            vec![
+                item(CoaxialType::RG6, 100.0, 20.0),
+                item(CoaxialType::RG6, 40.0, 20.0),
+            ]
+        };
+        let diff = diff_quotations(&quotation(items(), 3304.0), &quotation(items(), 3304.0));
+        assert!(diff.is_unchanged());
+        assert_eq!(diff.summary(), "No changes between the two quotations");
+
+        // Repeated items pair up in order
+        let revised = vec![
+            item(CoaxialType::RG6, 100.0, 20.0),
+            item(CoaxialType::RG6, 60.0, 20.0),
+        ];
+        let diff = diff_quotations(&quotation(items(), 3304.0), &quotation(revised, 3776.0));
+        assert_eq!(diff.changes.len(), 1);
+        assert!(matches!(
+            diff.changes[0],
+            ItemChange::Changed {
+                old_quantity: 40.0,
+                new_quantity: 60.0,
+                ..
+            }
+        ));
+    }
+}
@@ -1,6 +1,10 @@
 use crate::{
-    configuration::PriceListConfig,
-    prices::item_prices::{Description, PriceList, PricingSystem, Product},
+    configuration::{
+        GrandTotalRounding, PriceListConfig, QuotationConfig, SanityConfig, VolumeDiscountBasis,
+        VolumeDiscountTier,
+    },
+    pdf::TableLayout,
+    prices::item_prices::{Description, PriceList, PricingSystem, Product, QuantityUnit},
 };
 
 use std::collections::HashMap;
@@ -8,7 +12,9 @@ use std::fs;
 use thiserror::Error;
 use tracing::info;
 
+mod diff;
 mod types;
+pub use diff::{diff_quotations, ItemChange, QuotationDiff};
 pub use types::*;
 
 #[derive(Debug, Error)]
@@ -18,15 +24,47 @@ pub enum QuotationError {
 
     #[error("Error parsing pricelist file")]
     PricelistParseError,
+
+    #[error("No items to quote")]
+    NoItems,
+
+    #[error("Could not find prices for any item: {}", .0.join(", "))]
+    NoItemsPriced(Vec<String>),
+
+    #[error("Could not find prices for: {}", .0.join(", "))]
+    UnpricedItems(Vec<String>),
+
+    #[error("Quotation figures look wrong: {0}")]
+    ImplausibleAmount(String),
+
+    #[error("Invalid item parameters: {0}")]
+    InvalidItemParameters(String),
+
+    #[error("Invalid charges: {0}")]
+    InvalidCharges(String),
 }
 
+// Brand for items that don't name one, when no brand preference is configured
+const DEFAULT_BRAND: &str = "kei";
+
 pub struct QuotationService {
     pub pricelists: HashMap<String, Vec<PricingSystem>>,
+    brand_settings: HashMap<String, BrandSettings>,
+    config: QuotationConfig,
+}
+
+// Standing terms for a brand, gathered from its pricelist configs
+#[derive(Debug, Default, Clone)]
+struct BrandSettings {
+    default_discount: Option<f32>,
+    current_tags: Vec<String>,
+    markup: Option<f32>,
 }
 
 impl QuotationService {
     pub fn new(pricelist_configs: Vec<PriceListConfig>) -> Result<Self, QuotationError> {
         let mut pricelists = HashMap::new();
+        let mut brand_settings: HashMap<String, BrandSettings> = HashMap::new();
 
         for pricelist_config in pricelist_configs {
             let json_pricelist = fs::read_to_string(pricelist_config.pricelist)
@@ -35,114 +73,496 @@ impl QuotationService {
                 .map_err(|_| QuotationError::PricelistParseError)?;
             let pricing_system = PricingSystem::from_price_list(pricelist);
             let key = pricelist_config.brand.to_lowercase().trim().to_string();
+            let settings = brand_settings.entry(key.clone()).or_default();
+            if pricelist_config.default_discount.is_some() {
+                settings.default_discount = pricelist_config.default_discount;
+            }
+            if pricelist_config.markup.is_some() {
+                settings.markup = pricelist_config.markup;
+            }
+            for tag in &pricelist_config.current_tags {
+                let tag = tag.trim().to_lowercase();
+                if !settings.current_tags.contains(&tag) {
+                    settings.current_tags.push(tag);
+                }
+            }
             let brand_pricing_systems = pricelists
                 .entry(key)
                 .or_insert_with(|| Vec::<PricingSystem>::new());
             brand_pricing_systems.push(pricing_system);
         }
-        Ok(Self { pricelists })
+        Ok(Self {
+            pricelists,
+            brand_settings,
+            config: QuotationConfig::default(),
+        })
+    }
+
+    pub fn with_config(mut self, config: QuotationConfig) -> Self {
+        // Checked once here rather than on every document drawn with it
+        if let Some(configured) = config.description_column_mm {
+            let width = TableLayout::description_width(configured);
+            if width != configured {
+                tracing::warn!(
+                    "Description column of {}mm is out of range - using {}mm",
+                    configured,
+                    width
+                );
+            }
+        }
+        self.config = config;
+        self
     }
 }
 
+fn loading_extras(loading_frls: f32, loading_pvc: f32) -> Vec<String> {
+    let mut extras = Vec::new();
+    if loading_frls > 0.0 {
+        extras.push("frls".to_string());
+    }
+    if loading_pvc > 0.0 {
+        extras.push("pvc".to_string());
+    }
+    extras
+}
+
+fn describe_unpriced(product: &Product, brand: &str, loading_frls: f32, loading_pvc: f32) -> String {
+    let extras = loading_extras(loading_frls, loading_pvc);
+    format!("{} ({})", product.get_brief_description(extras), brand)
+}
+
+// The unit an item is quoted in, with how many pricelist units make one of it. A unit the
+// pricelist's can't be converted to, eg. pieces of a cable priced per metre, is ignored.
+fn quoted_unit(requested: Option<QuantityUnit>, priced: QuantityUnit) -> (QuantityUnit, f32) {
+    requested
+        .and_then(|unit| unit.in_units_of(priced).map(|per_unit| (unit, per_unit)))
+        .unwrap_or((priced, 1.0))
+}
+
+// Every printed money value goes through this so that the figures on the document add up
+pub(crate) fn round_to_paise(value: f32) -> f32 {
+    (value * 100.0).round() / 100.0
+}
+
+// The largest discount among the tiers the order is above, if any
+fn volume_discount_rate(
+    tiers: &[VolumeDiscountTier],
+    total_quantity: f32,
+    sub_total: f32,
+) -> Option<f32> {
+    tiers
+        .iter()
+        .filter(|tier| {
+            let total = match tier.basis {
+                VolumeDiscountBasis::Quantity => total_quantity,
+                VolumeDiscountBasis::Value => sub_total,
+            };
+            total > tier.above && tier.discount > 0.0
+        })
+        .map(|tier| tier.discount)
+        .reduce(f32::max)
+}
+
+// Discounts, loadings and markups are fractions, so an out of range one is a misread request
+// (eg. 5.0 for a 5% discount) that would otherwise price silently, even negatively
+fn check_item_parameters(item: &QuoteItem) -> Result<(), QuotationError> {
+    let description = item.product.get_brief_description(Vec::new());
+    let problem = if let Some(discount) = item.discount.filter(|d| !(0.0..=1.0).contains(d)) {
+        format!(
+            "discount {} on {} is not between 0 and 1",
+            discount, description
+        )
+    } else if item.loading_frls < 0.0 {
+        format!(
+            "FRLS loading {} on {} is negative",
+            item.loading_frls, description
+        )
+    } else if item.loading_pvc < 0.0 {
+        format!(
+            "PVC loading {} on {} is negative",
+            item.loading_pvc, description
+        )
+    } else if let Some(markup) = item.markup.filter(|markup| *markup < -1.0) {
+        format!("markup {} on {} is below -1", markup, description)
+    } else {
+        return Ok(());
+    };
+    tracing::warn!("Refusing quotation item: {}", problem);
+    Err(QuotationError::InvalidItemParameters(problem))
+}
+
+// A negative charge would lower the total without a printed line to show for it
+fn check_packing_forwarding(charge: Option<PackingCharge>) -> Result<(), QuotationError> {
+    let problem = match charge {
+        Some(PackingCharge::Flat(amount)) if amount < 0.0 => {
+            format!("packing & forwarding of Rs.{} is negative", amount)
+        }
+        Some(PackingCharge::Percent(rate)) if rate < 0.0 => {
+            format!("packing & forwarding of {} is negative", rate)
+        }
+        _ => return Ok(()),
+    };
+    tracing::warn!("Refusing quotation: {}", problem);
+    Err(QuotationError::InvalidCharges(problem))
+}
+
+// Refuses figures no real order would have, so a mis-parsed request never reaches a customer
+fn check_sanity(limits: &SanityConfig, items: &[QuotedItem]) -> Result<(), QuotationError> {
+    let overpriced = items.iter().find_map(|item| {
+        let metres = item.unit.in_units_of(QuantityUnit::Metre)?;
+        let per_metre = item.price / metres;
+        (per_metre > limits.max_price_per_metre).then_some((item, per_metre))
+    });
+    let Some((item, per_metre)) = overpriced else {
+        return Ok(());
+    };
+    let problem = format!(
+        "{} at Rs.{:.2}/m is above the Rs.{:.2}/m ceiling",
+        item.product.get_brief_description(Vec::new()),
+        per_metre,
+        limits.max_price_per_metre
+    );
+    tracing::warn!("Refusing implausible quotation: {}", problem);
+    Err(QuotationError::ImplausibleAmount(problem))
+}
+
 impl QuotationService {
-    pub fn generate_quotation(&self, request: QuotationRequest) -> Option<QuotationResponse> {
+    pub fn generate_quotation(
+        &self,
+        request: QuotationRequest,
+    ) -> Result<QuotationResponse, QuotationError> {
+        // Otherwise we'd produce a delivery + GST only document, which is always a bad parse
+        if request.items.is_empty() {
+            return Err(QuotationError::NoItems);
+        }
+        for item in &request.items {
+            check_item_parameters(item)?;
+        }
+        check_packing_forwarding(request.packing_forwarding)?;
         let mut quoted_items = Vec::new();
+        let mut unpriced_items = Vec::new();
+        let mut warnings = Vec::new();
         let mut basic_total = 0.0;
         const TAX_RATE:f32 = 0.18;
         for item in request.items {
             info!(item = ?item, "Processing quotation item");
 
-            let mut price = if let Some(user_price) = item.user_base_price {
+            let candidates = self.brand_candidates(&item.brand);
+            let mut brand = candidates[0].clone();
+            // The net price, and the list price (with loadings) and discount it comes from
+            let (price, list_price, discount) = if let Some(user_price) = item.user_base_price {
                 // User provided price - apply only markup, skip all lookups/loadings/discounts
                 info!(user_price = %user_price, "Using user-provided price");
-                match item.markup {
+                let price = match item.markup {
                     Some(markup) => {
                         info!(markup = %markup, "Applying markup to user price");
                         user_price * (1.0 + markup)
                     }
                     None => user_price,
-                }
+                };
+                (price, price, 0.0)
             } else {
                 // Existing price lookup logic with loadings/discounts
-                // If price is not found, then we skip creating the quotation and return None
-                let listed_price = self.get_price(&item.product, &item.brand, &item.tag)?;
-                info!(price = %listed_price, "Found item price");
-                listed_price
-                    * (1.0 - item.discount)
+                // Unpriced items are collected so the user can be told exactly which ones failed
+                let Some((priced_brand, listed_price)) =
+                    self.find_price(&item.product, &candidates, &item.tag)
+                else {
+                    unpriced_items.push(describe_unpriced(
+                        &item.product,
+                        &brand,
+                        item.loading_frls,
+                        item.loading_pvc,
+                    ));
+                    continue;
+                };
+                info!(price = %listed_price, brand = %priced_brand, "Found item price");
+                // Only a brand the user actually named counts as substituted
+                if priced_brand != brand && !item.brand.trim().is_empty() {
+                    warnings.push(format!(
+                        "{} is not in the {} pricelist, priced as {} instead",
+                        item.product.get_brief_description(Vec::new()),
+                        brand,
+                        priced_brand
+                    ));
+                }
+                brand = priced_brand;
+                if !self.is_current_tag(&brand, &item.tag) {
+                    let warning = format!(
+                        "{} priced from pricelist '{}', which is not the current {} pricelist",
+                        item.product.get_brief_description(Vec::new()),
+                        item.tag,
+                        brand
+                    );
+                    if !warnings.contains(&warning) {
+                        warnings.push(warning);
+                    }
+                }
+                let discount = self.effective_discount(&brand, item.discount);
+                let price = listed_price
+                    * (1.0 - discount)
+                    * (1.0 + item.loading_frls)
+                    * (1.0 + item.loading_pvc)
+                    * (1.0 + self.brand_markup(&brand));
+                let list_price = listed_price
                     * (1.0 + item.loading_frls)
                     * (1.0 + item.loading_pvc)
+                    * (1.0 + self.brand_markup(&brand));
+                (price, list_price, discount)
             };
 
-            // round prices to 2 decimal places
-            price = (price * 100.0).round() / 100.0;
+            let priced_unit = self.get_unit(&item.product, &brand, &item.tag);
+            // A user price is already per the unit they asked for
+            let (unit, per_unit) = match item.user_base_price {
+                Some(_) => (item.unit.unwrap_or(priced_unit), 1.0),
+                None => quoted_unit(item.unit, priced_unit),
+            };
+            if let Some(requested) = item.unit.filter(|requested| *requested != unit) {
+                warnings.push(format!(
+                    "{} can't be quoted in {}, quoted per {} instead",
+                    item.product.get_brief_description(Vec::new()),
+                    requested.label(),
+                    unit.rate_suffix()
+                ));
+            }
+            let price = round_to_paise(price * per_unit);
+            let list_price = round_to_paise(list_price * per_unit);
 
-            let amount = price * item.quantity;
+            let amount = round_to_paise(price * item.quantity);
             basic_total += amount;
 
+            // A code the user gave wins over the one looked up for the product
+            let hsn_code = item
+                .hsn
+                .or_else(|| item.product.hsn_code().map(|code| code.to_string()));
             quoted_items.push(QuotedItem {
                 product: item.product,
-                brand: item.brand,
+                brand,
                 quantity_mtrs: item.quantity,
+                unit,
                 price,
+                list_price,
+                discount,
                 amount,
                 loading_frls: item.loading_frls,
                 loading_pvc: item.loading_pvc,
+                hsn_code,
             });
         }
 
-        let total_with_delivery = basic_total + request.delivery_charges;
-        let taxes = total_with_delivery * TAX_RATE;
-        let grand_total = (total_with_delivery + taxes).round();
+        if quoted_items.is_empty() && !unpriced_items.is_empty() {
+            return Err(QuotationError::NoItemsPriced(unpriced_items));
+        }
+        if !unpriced_items.is_empty() {
+            return Err(QuotationError::UnpricedItems(unpriced_items));
+        }
 
-        Some(QuotationResponse {
+        // Totals are built from the already rounded line amounts
+        let basic_total = round_to_paise(basic_total);
+        // Items that aren't sold by length, eg. pieces, don't count towards a quantity tier
+        let total_quantity: f32 = quoted_items
+            .iter()
+            .filter_map(|item| {
+                let metres = item.unit.in_units_of(QuantityUnit::Metre)?;
+                Some(item.quantity_mtrs * metres)
+            })
+            .sum();
+        let volume_discount =
+            volume_discount_rate(&self.config.volume_discounts, total_quantity, basic_total).map(
+                |rate| VolumeDiscount {
+                    rate,
+                    amount: round_to_paise(basic_total * rate),
+                },
+            );
+        let discounted_total =
+            basic_total - volume_discount.map_or(0.0, |discount| discount.amount);
+        let packing_forwarding = match request.packing_forwarding {
+            Some(PackingCharge::Flat(amount)) => round_to_paise(amount),
+            Some(PackingCharge::Percent(rate)) => round_to_paise(basic_total * rate),
+            None => 0.0,
+        };
+        let total_with_delivery =
+            round_to_paise(discounted_total + request.delivery_charges + packing_forwarding);
+        let taxes = round_to_paise(total_with_delivery * TAX_RATE);
+        let grand_total = match self.config.rounding.grand_total {
+            GrandTotalRounding::NearestRupee => (total_with_delivery + taxes).round(),
+            GrandTotalRounding::Paise => round_to_paise(total_with_delivery + taxes),
+        };
+        // Printed, so the lines above the total visibly add up to it
+        let round_off = round_to_paise(grand_total - (total_with_delivery + taxes));
+        check_sanity(&self.config.sanity, &quoted_items)?;
+
+        Ok(QuotationResponse {
             items: quoted_items,
             basic_total,
             delivery_charges: request.delivery_charges,
+            packing_forwarding,
             total_with_delivery,
             taxes,
             grand_total,
+            round_off,
             to: request.to,
             terms_and_conditions: self.process_terms_and_conditions(request.terms_and_conditions),
+            estimated_delivery: request
+                .estimated_delivery
+                .map(|delivery| delivery.trim().to_string())
+                .filter(|delivery| !delivery.is_empty()),
+            warnings,
+            enquiry: None,
+            volume_discount,
+            buyer_state: request
+                .buyer_state
+                .map(|state| state.trim().to_string())
+                .filter(|state| !state.is_empty()),
+            seller_state: self.config.seller_state.clone(),
+            draft: request.draft,
+            bank_details: self.config.bank_details.clone(),
         })
     }
 
     pub fn get_prices_only(&self, request: PriceOnlyRequest) -> Option<PriceOnlyResponse> {
         let mut response_items = Vec::new();
+        let mut skipped_items = Vec::new();
 
         for item in request.items {
-            let listed_price = self.get_price(&item.product, &item.brand, &item.tag);
-            if listed_price.is_none() {
+            let candidates = self.brand_candidates(&item.brand);
+            let Some((brand, listed_price)) =
+                self.find_price(&item.product, &candidates, &item.tag)
+            else {
+                skipped_items.push(SkippedItem {
+                    spec: describe_unpriced(
+                        &item.product,
+                        &candidates[0],
+                        item.loading_frls,
+                        item.loading_pvc,
+                    ),
+                    reason: self.skip_reason(&candidates[0]),
+                });
                 continue;
-            }
-            let listed_price = listed_price.unwrap();
+            };
 
+            let discount = self.effective_discount(&brand, item.discount);
             let mut price = listed_price
-                * (1.0 - item.discount)
+                * (1.0 - discount)
                 * (1.0 + item.loading_frls)
-                * (1.0 + item.loading_pvc);
-            // Round prices to 2 decimal places
-            price = (price * 100.0).round() / 100.0;
+                * (1.0 + item.loading_pvc)
+                * (1.0 + self.brand_markup(&brand));
+            let (unit, per_unit) =
+                quoted_unit(item.unit, self.get_unit(&item.product, &brand, &item.tag));
+            price = round_to_paise(price * per_unit);
 
             // Use existing Description trait but make it brief
-            let mut extras = Vec::new();
-            if item.loading_frls > 0.0 {
-                extras.push("frls".to_string());
+            let extras = loading_extras(item.loading_frls, item.loading_pvc);
+            let mut description = format!("{}", item.product.get_brief_description(extras));
+            // Name the brand it was priced from when it isn't the one asked for
+            if brand != candidates[0] && !item.brand.trim().is_empty() {
+                description = format!("{} ({})", description, brand);
             }
-            if item.loading_pvc > 0.0 {
-                extras.push("pvc".to_string());
-            }
-
-            let description = format!("{}", item.product.get_brief_description(extras));
 
             response_items.push(PriceOnlyResponseItem {
                 description,
                 price,
+                unit,
                 quantity: item.quantity,
             });
         }
 
         Some(PriceOnlyResponse {
             items: response_items,
+            skipped_items,
+        })
+    }
+
+    // Same wording that appears on quotation / proforma PDFs for this product
+    pub fn describe_product(&self, request: DescribeProductRequest) -> String {
+        let extras = request
+            .extras
+            .iter()
+            .map(|extra| extra.trim().to_lowercase())
+            .collect();
+        request.product.get_description(extras)
+    }
+
+    // Keeps the customer's request with the quotation when the deployment prints it
+    pub fn attach_enquiry(&self, quotation: &mut QuotationResponse, enquiry: &str) {
+        if self.config.attach_enquiry && !enquiry.trim().is_empty() {
+            quotation.enquiry = Some(enquiry.trim().to_string());
+        }
+    }
+
+    // Grand total above which documents wait for an admin's approval
+    pub fn approval_threshold(&self) -> Option<f32> {
+        self.config.approval_threshold
+    }
+
+    pub fn table_layout(&self) -> TableLayout {
+        self.config
+            .description_column_mm
+            .map(TableLayout::new)
+            .unwrap_or_default()
+    }
+
+    // An explicit item discount (including 0) always wins over the brand's standing discount
+    fn effective_discount(&self, brand: &str, item_discount: Option<f32>) -> f32 {
+        item_discount
+            .or_else(|| {
+                self.brand_settings
+                    .get(brand.to_lowercase().trim())
+                    .and_then(|settings| settings.default_discount)
+            })
+            .unwrap_or(0.0)
+    }
+
+    // Turns listed cost into selling price - items with a user provided price never get this
+    fn brand_markup(&self, brand: &str) -> f32 {
+        self.brand_settings
+            .get(brand.to_lowercase().trim())
+            .and_then(|settings| settings.markup)
+            .unwrap_or(0.0)
+    }
+
+    // Brands without configured current tags are never flagged
+    fn is_current_tag(&self, brand: &str, tag: &str) -> bool {
+        match self.brand_settings.get(brand.to_lowercase().trim()) {
+            Some(settings) if !settings.current_tags.is_empty() => settings
+                .current_tags
+                .contains(&tag.trim().to_lowercase()),
+            _ => true,
+        }
+    }
+
+    // Brands to try, in order, for an item. Unbranded items go by the configured preference, and
+    // unless strict, a named brand is followed by the preferred ones as fallbacks
+    fn brand_candidates(&self, brand: &str) -> Vec<String> {
+        let preference = self
+            .config
+            .brand_preference
+            .iter()
+            .map(|brand| brand.trim().to_lowercase());
+        let brand = brand.trim().to_lowercase();
+
+        let mut candidates = Vec::new();
+        if brand.is_empty() {
+            candidates.extend(preference);
+            if candidates.is_empty() {
+                candidates.push(DEFAULT_BRAND.to_string());
+            }
+        } else {
+            candidates.push(brand);
+            if !self.config.strict_brand {
+                for fallback in preference {
+                    if !candidates.contains(&fallback) {
+                        candidates.push(fallback);
+                    }
+                }
+            }
+        }
+        candidates
+    }
+
+    // First brand, in order, with a listed price for the item
+    fn find_price(&self, product: &Product, brands: &[String], tag: &str) -> Option<(String, f32)> {
+        brands.iter().find_map(|brand| {
+            self.get_price(product, brand, tag).map(|price| (brand.clone(), price))
         })
     }
 
@@ -153,6 +573,27 @@ impl QuotationService {
             .find_map(|pricing_system| pricing_system.get_price(product, tag))
     }
 
+    // Only called once pricing failed - a known brand means the spec itself isn't listed
+    fn skip_reason(&self, brand: &str) -> SkipReason {
+        if self.pricelists.contains_key(&brand.to_lowercase()) {
+            SkipReason::NotInPricelist
+        } else {
+            SkipReason::BrandNotFound
+        }
+    }
+
+    // Falls back to the product's usual unit, also for user priced items not in any pricelist
+    fn get_unit(&self, product: &Product, brand: &str, tag: &str) -> QuantityUnit {
+        self.pricelists
+            .get(&brand.to_lowercase())
+            .and_then(|pricing_systems| {
+                pricing_systems
+                    .iter()
+                    .find_map(|pricing_system| pricing_system.get_unit(product, tag))
+            })
+            .unwrap_or_else(|| product.unit())
+    }
+
     fn process_terms_and_conditions(&self, terms: Option<Vec<String>>) -> Option<Vec<String>> {
         match terms {
             Some(terms_vec) if terms_vec.len() == 1 => match terms_vec[0].to_lowercase().as_str() {
@@ -164,23 +605,26 @@ impl QuotationService {
     }
 
     fn get_standard_terms(&self) -> Vec<String> {
+        let validity = match self.config.validity_days {
+            1 => "1 day".to_string(),
+            days => format!("{} days", days),
+        };
         vec![
-            "Above price is Ex-Godown Kolkata",
-            "Qty. Tolerance: +/-5%",
-            "Payment: Full payment against proforma invoice",
-            "Delivery: Ready stock subject to prior sale",
-            "Validity: 3 days from quotation date",
+            "Above price is Ex-Godown Kolkata".to_string(),
+            "Qty. Tolerance: +/-5%".to_string(),
+            "Payment: Full payment against proforma invoice".to_string(),
+            "Delivery: Ready stock subject to prior sale".to_string(),
+            format!("Validity: {} from quotation date", validity),
         ]
-        .iter()
-        .map(|x| x.to_string())
-        .collect()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::prices::item_prices::{Cable, Conductor, Flexible, FlexibleType, LT, PowerControl};
+    use crate::prices::item_prices::{
+        Cable, CoaxialType, Conductor, Flexible, FlexibleType, LT, PowerControl,
+    };
     use std::collections::HashMap;
 
     // Test helper: create a mock PricingSystem from JSON
@@ -215,27 +659,272 @@ mod tests {
         let mut pricelists = HashMap::new();
         pricelists.insert("kei".to_string(), vec![create_mock_pricing_system()]);
 
-        QuotationService { pricelists }
+        QuotationService {
+            pricelists,
+            brand_settings: HashMap::new(),
+            config: QuotationConfig::default(),
+        }
     }
 
     // Test helper: create a test QuoteItem
     fn create_test_quote_item() -> QuoteItem {
-        QuoteItem {
-            product: Product::Cable(Cable::PowerControl(PowerControl::LT(LT {
+        QuoteItem::builder(Product::Cable(Cable::PowerControl(PowerControl::LT(LT {
+            conductor: Conductor::Copper,
+            core_size: "3".to_string(),
+            sqmm: "2.5".to_string(),
+            armoured: false,
+        }))))
+        .build()
+    }
+
+    #[test]
+    fn test_builders_apply_defaults_and_setters() {
+        let item = create_test_quote_item();
+        // No brand - the service picks one by preference
+        assert_eq!(item.brand, "");
+        assert_eq!(item.tag, "latest");
+        assert_eq!(item.discount, None);
+        assert_eq!((item.loading_frls, item.loading_pvc), (0.0, 0.0));
+        assert_eq!(item.quantity, 1.0);
+        assert_eq!(item.user_base_price, None);
+
+        let item = QuoteItem::builder(item.product)
+            .with_brand("polycab")
+            .with_tag("july 2025")
+            .with_discount(0.4)
+            .with_loading_frls(0.03)
+            .with_quantity(250.0)
+            .with_user_base_price(120.0)
+            .with_markup(0.02)
+            .build();
+        let request = QuotationRequest::builder()
+            .with_item(item)
+            .with_delivery_charges(500.0)
+            .with_to(vec!["ABC Traders".to_string()])
+            .with_estimated_delivery("7-10 days")
+            .with_internal_note("matched competitor rate")
+            .build();
+
+        assert_eq!(request.items.len(), 1);
+        let item = &request.items[0];
+        assert_eq!(item.brand, "polycab");
+        assert_eq!(item.tag, "july 2025");
+        assert_eq!(item.discount, Some(0.4));
+        assert_eq!(item.loading_frls, 0.03);
+        assert_eq!(item.quantity, 250.0);
+        assert_eq!(item.user_base_price, Some(120.0));
+        assert_eq!(item.markup, Some(0.02));
+        assert_eq!(request.delivery_charges, 500.0);
+        assert_eq!(request.to, Some(vec!["ABC Traders".to_string()]));
+        assert_eq!(request.terms_and_conditions, None);
+        assert_eq!(request.estimated_delivery.as_deref(), Some("7-10 days"));
+        assert_eq!(request.internal_note.as_deref(), Some("matched competitor rate"));
+    }
+
+    // kei lists the LT cable, polycab only lists RG6 coaxial
+    fn create_two_brand_service(brand_preference: &[&str], strict_brand: bool) -> QuotationService {
+        let mut service = create_mock_service().with_config(QuotationConfig {
+            brand_preference: brand_preference.iter().map(|b| b.to_string()).collect(),
+            strict_brand,
+            ..QuotationConfig::default()
+        });
+        let price_list: PriceList = serde_json::from_str(
+            r#"{
+                "tags": ["latest"],
+                "prices": [{"product": {"Cable": {"Coaxial": "RG6"}}, "price": 20.0}]
+            }"#,
+        )
+        .unwrap();
+        service.pricelists.insert(
+            "polycab".to_string(),
+            vec![PricingSystem::from_price_list(price_list)],
+        );
+        service
+    }
+
+    #[test]
+    fn test_enquiry_is_attached_only_when_configured() {
+        let request = || {
+            QuotationRequest::builder()
+                .with_item(create_test_quote_item())
+                .build()
+        };
+
+        let service = create_mock_service();
+        let mut quotation = service.generate_quotation(request()).unwrap();
+        service.attach_enquiry(&mut quotation, "quote 3c x 2.5");
+        assert_eq!(quotation.enquiry, None);
+
+        let service = create_mock_service().with_config(QuotationConfig {
+            attach_enquiry: true,
+            ..QuotationConfig::default()
+        });
+        let mut quotation = service.generate_quotation(request()).unwrap();
+        service.attach_enquiry(&mut quotation, "  quote 3c x 2.5\n");
+        assert_eq!(quotation.enquiry.as_deref(), Some("quote 3c x 2.5"));
+    }
+
+    #[test]
+    fn test_unbranded_items_follow_brand_preference() {
+        let service = create_two_brand_service(&["polycab", "kei"], false);
+        let coaxial = QuoteItem::builder(Product::Cable(Cable::Coaxial(CoaxialType::RG6))).build();
+        let request = QuotationRequest::builder()
+            .with_item(create_test_quote_item())
+            .with_item(coaxial)
+            .build();
+
+        let result = service.generate_quotation(request).unwrap();
+        assert_eq!(result.items[0].brand, "kei");
+        assert_eq!(result.items[0].price, 100.0);
+        assert_eq!(result.items[1].brand, "polycab");
+        assert_eq!(result.items[1].price, 20.0);
+        // Nothing was asked for, so nothing was substituted
+        assert!(result.warnings.is_empty());
+
+        // Without a configured preference, unbranded items are priced as kei
+        let request = QuotationRequest::builder()
+            .with_item(create_test_quote_item())
+            .build();
+        let result = create_mock_service().generate_quotation(request).unwrap();
+        assert_eq!(result.items[0].brand, "kei");
+    }
+
+    #[test]
+    fn test_missing_item_falls_back_to_next_brand_unless_strict() {
+        let request = || {
+            let item = QuoteItem::builder(Product::Cable(Cable::PowerControl(PowerControl::LT(LT {
                 conductor: Conductor::Copper,
                 core_size: "3".to_string(),
                 sqmm: "2.5".to_string(),
                 armoured: false,
-            }))),
-            brand: "kei".to_string(),
-            tag: "latest".to_string(),
-            discount: 0.0,
-            loading_frls: 0.0,
-            loading_pvc: 0.0,
-            quantity: 1.0,
-            user_base_price: None,
-            markup: None,
-        }
+            }))))
+            .with_brand("Polycab")
+            .build();
+            QuotationRequest::builder().with_item(item).build()
+        };
+
+        let service = create_two_brand_service(&["polycab", "kei"], false);
+        let result = service.generate_quotation(request()).unwrap();
+        assert_eq!(result.items[0].brand, "kei");
+        assert_eq!(result.warnings.len(), 1);
+        assert!(result.warnings[0].contains("not in the polycab pricelist, priced as kei"));
+
+        let strict_service = create_two_brand_service(&["polycab", "kei"], true);
+        assert!(matches!(
+            strict_service.generate_quotation(request()),
+            Err(QuotationError::NoItemsPriced(_))
+        ));
+    }
+
+    #[test]
+    fn test_get_prices_only_names_substituted_brand() {
+        let service = create_two_brand_service(&["kei"], false);
+        let request = PriceOnlyRequest {
+            items: vec![PriceOnlyItem {
+                product: Product::Cable(Cable::PowerControl(PowerControl::LT(LT {
+                    conductor: Conductor::Copper,
+                    core_size: "3".to_string(),
+                    sqmm: "2.5".to_string(),
+                    armoured: false,
+                }))),
+                brand: "polycab".to_string(),
+                tag: "latest".to_string(),
+                discount: None,
+                quantity: None,
+                unit: None,
+                loading_frls: 0.0,
+                loading_pvc: 0.0,
+            }],
+        };
+
+        let result = service.get_prices_only(request).unwrap();
+        assert_eq!(result.items.len(), 1);
+        assert!(result.items[0].description.ends_with("(kei)"));
+    }
+
+    #[test]
+    fn test_quoted_item_unit_comes_from_pricelist() {
+        let mut service = create_mock_service();
+        let price_list: PriceList = serde_json::from_str(
+            r#"{
+                "tags": ["latest"],
+                "unit": "Piece",
+                "prices": [{"product": {"Cable": {"Coaxial": "RG6"}}, "price": 12.0}]
+            }"#,
+        )
+        .unwrap();
+        service.pricelists.insert(
+            "glands".to_string(),
+            vec![PricingSystem::from_price_list(price_list)],
+        );
+        let per_piece = QuoteItem::builder(Product::Cable(Cable::Coaxial(CoaxialType::RG6)))
+            .with_brand("glands")
+            .with_quantity(10.0)
+            .build();
+
+        let request = QuotationRequest::builder()
+            .with_item(create_test_quote_item())
+            .with_item(per_piece)
+            .build();
+
+        let result = service.generate_quotation(request).unwrap();
+        assert_eq!(result.items[0].unit, QuantityUnit::Metre);
+        assert_eq!(result.items[1].unit, QuantityUnit::Piece);
+    }
+
+    #[test]
+    fn test_requested_unit_converts_the_rate() {
+        let mut service = create_mock_service();
+        let price_list: PriceList = serde_json::from_str(
+            r#"{
+                "tags": ["latest"],
+                "unit": "Piece",
+                "prices": [{"product": {"Cable": {"Coaxial": "RG6"}}, "price": 12.0}]
+            }"#,
+        )
+        .unwrap();
+        service.pricelists.insert(
+            "glands".to_string(),
+            vec![PricingSystem::from_price_list(price_list)],
+        );
+        // 100.0 a metre in the mock pricelist
+        let per_km = QuoteItem::builder(create_test_quote_item().product)
+            .with_unit(QuantityUnit::Kilometre)
+            .with_quantity(2.0)
+            .build();
+        let nos = QuoteItem::builder(Product::Cable(Cable::Coaxial(CoaxialType::RG6)))
+            .with_brand("glands")
+            .with_unit(QuantityUnit::Piece)
+            .with_quantity(10.0)
+            .build();
+        // A cable sold by length can't be counted in pieces
+        let cable_in_nos = QuoteItem::builder(create_test_quote_item().product)
+            .with_unit(QuantityUnit::Piece)
+            .build();
+
+        let request = QuotationRequest::builder()
+            .with_item(per_km)
+            .with_item(nos)
+            .with_item(cable_in_nos)
+            .build();
+        let result = service.generate_quotation(request).unwrap();
+
+        assert_eq!(result.items[0].unit, QuantityUnit::Kilometre);
+        assert_eq!(result.items[0].price, 100000.0);
+        assert_eq!(result.items[0].amount, 200000.0);
+        assert_eq!(result.items[1].unit, QuantityUnit::Piece);
+        assert_eq!(result.items[1].price, 12.0);
+        assert_eq!(result.items[1].amount, 120.0);
+        assert_eq!(result.items[2].unit, QuantityUnit::Metre);
+        assert_eq!(result.items[2].price, 100.0);
+        assert_eq!(result.warnings.len(), 1);
+        assert!(result.warnings[0].contains("can't be quoted in Nos, quoted per mtr instead"));
+
+        assert_eq!(
+            QuantityUnit::Metre.in_units_of(QuantityUnit::Kilometre),
+            Some(0.001)
+        );
+        assert_eq!(QuantityUnit::Piece.in_units_of(QuantityUnit::Metre), None);
     }
 
     #[test]
@@ -243,6 +932,9 @@ mod tests {
         let config = PriceListConfig {
             brand: "test".to_string(),
             pricelist: "/nonexistent/file.json".to_string(),
+            default_discount: None,
+            current_tags: Vec::new(),
+            markup: None,
         };
 
         let result = QuotationService::new(vec![config]);
@@ -250,37 +942,54 @@ mod tests {
     }
 
     #[test]
-    fn test_generate_quotation_returns_none_for_missing_product() {
+    fn test_generate_quotation_reports_when_no_items_priced() {
         let service = create_mock_service();
         let mut item = create_test_quote_item();
         item.brand = "nonexistent_brand".to_string();
 
-        let request = QuotationRequest {
-            items: vec![item],
-            delivery_charges: 0.0,
-            to: None,
-            terms_and_conditions: None,
-        };
+        let request = QuotationRequest::builder().with_item(item).build();
+
+        let result = service.generate_quotation(request);
+        match result {
+            Err(QuotationError::NoItemsPriced(items)) => {
+                assert_eq!(items.len(), 1);
+                assert!(items[0].ends_with("(nonexistent_brand)"));
+            }
+            other => panic!("Expected NoItemsPriced, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_generate_quotation_lists_only_unpriced_items() {
+        let service = create_mock_service();
+        let priced = create_test_quote_item();
+        let mut unpriced = create_test_quote_item();
+        unpriced.brand = "nonexistent_brand".to_string();
+
+        let request = QuotationRequest::builder()
+            .with_items(vec![priced, unpriced])
+            .build();
 
         let result = service.generate_quotation(request);
-        assert!(result.is_none());
+        match result {
+            Err(QuotationError::UnpricedItems(items)) => {
+                assert_eq!(items.len(), 1);
+                assert!(items[0].ends_with("(nonexistent_brand)"));
+            }
+            other => panic!("Expected UnpricedItems, got {:?}", other),
+        }
     }
 
     #[test]
     fn test_price_calculation_with_discount_and_loadings() {
         let service = create_mock_service();
         let mut item = create_test_quote_item();
-        item.discount = 0.1; // 10% discount
+        item.discount = Some(0.1); // 10% discount
         item.loading_frls = 0.03; // 3% FRLS loading
         item.loading_pvc = 0.05; // 5% PVC loading
         item.quantity = 2.0;
 
-        let request = QuotationRequest {
-            items: vec![item],
-            delivery_charges: 0.0,
-            to: None,
-            terms_and_conditions: None,
-        };
+        let request = QuotationRequest::builder().with_item(item).build();
 
         let result = service.generate_quotation(request).unwrap();
 
@@ -294,20 +1003,43 @@ mod tests {
         assert_eq!(result.basic_total, expected_amount);
     }
 
+    #[test]
+    fn test_quoted_item_carries_list_price_and_discount() {
+        let service = create_mock_service();
+        let mut item = create_test_quote_item();
+        item.discount = Some(0.1);
+        item.loading_frls = 0.03;
+        item.loading_pvc = 0.05;
+
+        let request = QuotationRequest::builder().with_item(item).build();
+        let result = service.generate_quotation(request).unwrap();
+
+        // List price keeps the loadings: 100.0 * 1.03 * 1.05 = 108.15
+        let quoted = &result.items[0];
+        assert!((quoted.list_price - 108.15).abs() < 0.001);
+        assert_eq!(quoted.discount, 0.1);
+        // The printed list rate less the printed discount gives the printed rate
+        assert!((quoted.list_price * (1.0 - quoted.discount) - quoted.price).abs() <= 0.01);
+
+        // A user price has nothing to discount
+        let mut item = create_test_quote_item();
+        item.user_base_price = Some(150.0);
+        item.discount = Some(0.5);
+        let request = QuotationRequest::builder().with_item(item).build();
+        let result = service.generate_quotation(request).unwrap();
+        assert_eq!(result.items[0].list_price, 150.0);
+        assert_eq!(result.items[0].discount, 0.0);
+    }
+
     #[test]
     fn test_user_base_price_with_markup() {
         let service = create_mock_service();
         let mut item = create_test_quote_item();
         item.user_base_price = Some(200.0);
         item.markup = Some(0.1); // 10% markup
-        item.discount = 0.5; // Should be ignored when user_base_price is provided
+        item.discount = Some(0.5); // Should be ignored when user_base_price is provided
 
-        let request = QuotationRequest {
-            items: vec![item],
-            delivery_charges: 0.0,
-            to: None,
-            terms_and_conditions: None,
-        };
+        let request = QuotationRequest::builder().with_item(item).build();
 
         let result = service.generate_quotation(request).unwrap();
 
@@ -322,34 +1054,46 @@ mod tests {
         item.user_base_price = Some(150.0);
         item.markup = None;
 
-        let request = QuotationRequest {
-            items: vec![item],
-            delivery_charges: 0.0,
-            to: None,
-            terms_and_conditions: None,
-        };
+        let request = QuotationRequest::builder().with_item(item).build();
 
         let result = service.generate_quotation(request).unwrap();
 
         assert_eq!(result.items[0].price, 150.0);
     }
 
+    #[test]
+    fn test_quoted_item_carries_hsn_code() {
+        let service = create_mock_service();
+        let item = create_test_quote_item();
+
+        let request = QuotationRequest::builder().with_item(item).build();
+
+        let result = service.generate_quotation(request).unwrap();
+
+        assert_eq!(result.items[0].hsn_code.as_deref(), Some("854449"));
+
+        let item = QuoteItem::builder(create_test_quote_item().product)
+            .with_hsn("85444920")
+            .build();
+        let request = QuotationRequest::builder().with_item(item).build();
+        let result = service.generate_quotation(request).unwrap();
+        assert_eq!(result.items[0].hsn_code.as_deref(), Some("85444920"));
+    }
+
     #[test]
     fn test_tax_and_delivery_calculation() {
         let service = create_mock_service();
         let item = create_test_quote_item();
 
-        let request = QuotationRequest {
-            items: vec![item],
-            delivery_charges: 50.0,
-            to: None,
-            terms_and_conditions: None,
-        };
+        let request = QuotationRequest::builder()
+            .with_item(item)
+            .with_delivery_charges(50.0)
+            .build();
 
         let result = service.generate_quotation(request).unwrap();
 
         let expected_total_with_delivery = 100.0_f32 + 50.0; // basic_total + delivery
-        let expected_taxes = expected_total_with_delivery * 0.18; // 27.0
+        let expected_taxes = 27.0; // 18% of 150, rounded to paise
         let expected_grand_total = (expected_total_with_delivery + expected_taxes).round(); // 177.0
 
         assert_eq!(result.total_with_delivery, expected_total_with_delivery);
@@ -358,36 +1102,569 @@ mod tests {
     }
 
     #[test]
-    fn test_price_rounding() {
+    fn test_displayed_item_amounts_sum_to_displayed_subtotal() {
+        let service = create_mock_service();
+        // 10.01 x 2.5 = 25.025 - unrounded amounts would print as 25.03 each but sum to 50.05
+        let items = [(10.01, 2.5), (10.01, 2.5), (33.33, 3.3)]
+            .iter()
+            .map(|&(price, quantity)| {
+                let mut item = create_test_quote_item();
+                item.user_base_price = Some(price);
+                item.quantity = quantity;
+                item
+            })
+            .collect();
+
+        let request = QuotationRequest::builder()
+            .with_items(items)
+            .with_delivery_charges(12.5)
+            .build();
+
+        let result = service.generate_quotation(request).unwrap();
+
+        let to_paise = |printed: String| -> i64 { printed.replace('.', "").parse().unwrap() };
+        let printed_items_sum: i64 = result
+            .items
+            .iter()
+            .map(|item| to_paise(crate::pdf::format_amount(item.amount)))
+            .sum();
+        let printed_subtotal = to_paise(crate::pdf::format_amount(result.basic_total));
+        assert_eq!(printed_items_sum, printed_subtotal);
+
+        let printed_total_with_delivery =
+            to_paise(crate::pdf::format_amount(result.total_with_delivery));
+        assert_eq!(printed_subtotal + 1250, printed_total_with_delivery);
+    }
+
+    #[test]
+    fn test_grand_total_rounding_is_configurable() {
+        let rupee_service = create_mock_service();
+        let paise_service = create_mock_service().with_config(QuotationConfig {
+            rounding: crate::configuration::RoundingConfig {
+                grand_total: GrandTotalRounding::Paise,
+            },
+            ..QuotationConfig::default()
+        });
+
+        let request = || {
+            let mut item = create_test_quote_item();
+            item.user_base_price = Some(10.01);
+            QuotationRequest::builder().with_item(item).build()
+        };
+
+        let rupee_result = rupee_service.generate_quotation(request()).unwrap();
+        let paise_result = paise_service.generate_quotation(request()).unwrap();
+
+        // 10.01 + 1.80 GST = 11.81
+        assert_eq!(rupee_result.grand_total, 12.0);
+        assert_eq!(paise_result.grand_total, 11.81);
+    }
+
+    #[test]
+    fn test_packing_forwarding_is_taxed_with_delivery() {
+        let service = create_mock_service();
+        let quote = |packing_forwarding: Option<PackingCharge>| {
+            let mut item = create_test_quote_item();
+            item.user_base_price = Some(1000.0);
+            let mut request = QuotationRequest::builder()
+                .with_item(item)
+                .with_delivery_charges(100.0);
+            if let Some(packing_forwarding) = packing_forwarding {
+                request = request.with_packing_forwarding(packing_forwarding);
+            }
+            service.generate_quotation(request.build()).unwrap()
+        };
+
+        let flat = quote(Some(PackingCharge::Flat(500.0)));
+        assert_eq!(flat.packing_forwarding, 500.0);
+        // 1000 + 100 delivery + 500 P&F, with GST on all of it
+        assert_eq!(flat.total_with_delivery, 1600.0);
+        assert_eq!(flat.taxes, 288.0);
+        assert_eq!(flat.grand_total, 1888.0);
+
+        // 2% of the 1000 sub total
+        let percent = quote(Some(PackingCharge::Percent(0.02)));
+        assert_eq!(percent.packing_forwarding, 20.0);
+        assert_eq!(percent.total_with_delivery, 1120.0);
+        assert_eq!(percent.taxes, 201.6);
+        assert_eq!(percent.grand_total, 1322.0);
+
+        let none = quote(None);
+        assert_eq!(none.packing_forwarding, 0.0);
+        assert_eq!(none.total_with_delivery, 1100.0);
+    }
+
+    #[test]
+    fn test_negative_packing_forwarding_is_refused() {
+        let service = create_mock_service();
+        for charge in [PackingCharge::Flat(-500.0), PackingCharge::Percent(-0.02)] {
+            let request = QuotationRequest::builder()
+                .with_item(create_test_quote_item())
+                .with_packing_forwarding(charge)
+                .build();
+            assert!(matches!(
+                service.generate_quotation(request),
+                Err(QuotationError::InvalidCharges(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_round_off_takes_components_to_grand_total() {
+        let service = create_mock_service();
+        let quote = |price: f32| {
+            let mut item = create_test_quote_item();
+            item.user_base_price = Some(price);
+            service
+                .generate_quotation(QuotationRequest::builder().with_item(item).build())
+                .unwrap()
+        };
+
+        // 150.03 + 27.01 GST = 177.04, rounded down
+        let result = quote(150.03);
+        assert_eq!(result.total_with_delivery + result.taxes, 177.04);
+        assert_eq!(result.round_off, -0.04);
+        assert_eq!(result.grand_total, 177.0);
+
+        // 10.01 + 1.80 GST = 11.81, rounded up
+        assert_eq!(quote(10.01).round_off, 0.19);
+        // Nothing to round
+        assert_eq!(quote(100.0).round_off, 0.0);
+
+        let paise_service = create_mock_service().with_config(QuotationConfig {
+            rounding: crate::configuration::RoundingConfig {
+                grand_total: GrandTotalRounding::Paise,
+            },
+            ..QuotationConfig::default()
+        });
+        let mut item = create_test_quote_item();
+        item.user_base_price = Some(150.03);
+        let result = paise_service
+            .generate_quotation(QuotationRequest::builder().with_item(item).build())
+            .unwrap();
+        assert_eq!(result.round_off, 0.0);
+        assert_eq!(result.grand_total, 177.04);
+    }
+
+    fn volume_tiers() -> Vec<VolumeDiscountTier> {
+        vec![
+            VolumeDiscountTier {
+                basis: VolumeDiscountBasis::Quantity,
+                above: 1000.0,
+                discount: 0.03,
+            },
+            VolumeDiscountTier {
+                basis: VolumeDiscountBasis::Value,
+                above: 500000.0,
+                discount: 0.05,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_volume_discount_tier_boundaries() {
+        let tiers = volume_tiers();
+        // Tiers apply only above their threshold
+        assert_eq!(volume_discount_rate(&tiers, 1000.0, 1000.0), None);
+        assert_eq!(volume_discount_rate(&tiers, 1000.5, 1000.0), Some(0.03));
+        assert_eq!(volume_discount_rate(&tiers, 10.0, 500000.0), None);
+        // 500000.01 is the same f32 as 500000.0, so test just above with one f32 can hold
+        assert_eq!(volume_discount_rate(&tiers, 10.0, 500000.5), Some(0.05));
+        // The larger discount wins when both are reached
+        assert_eq!(volume_discount_rate(&tiers, 2000.0, 600000.0), Some(0.05));
+        assert_eq!(volume_discount_rate(&[], 2000.0, 600000.0), None);
+    }
+
+    #[test]
+    fn test_volume_discount_applies_after_item_discounts() {
+        let service = create_mock_service().with_config(QuotationConfig {
+            volume_discounts: volume_tiers(),
+            ..QuotationConfig::default()
+        });
+        let quote = |quantity: f32| {
+            let mut item = create_test_quote_item();
+            item.quantity = quantity;
+            item.discount = Some(0.1);
+            service
+                .generate_quotation(
+                    QuotationRequest::builder()
+                        .with_item(item)
+                        .with_delivery_charges(100.0)
+                        .build(),
+                )
+                .unwrap()
+        };
+
+        let result = quote(1000.0);
+        assert_eq!(result.volume_discount, None);
+        assert_eq!(result.total_with_delivery, 90100.0);
+
+        // 1001 x 90.00 = 90090.00, less 3% = 87387.30
+        let result = quote(1001.0);
+        assert_eq!(result.basic_total, 90090.0);
+        assert_eq!(
+            result.volume_discount,
+            Some(VolumeDiscount {
+                rate: 0.03,
+                amount: 2702.7
+            })
+        );
+        assert_eq!(result.total_with_delivery, 87487.3);
+        assert_eq!(result.taxes, round_to_paise(87487.3 * 0.18));
+    }
+
+    #[test]
+    fn test_volume_discount_quantity_counts_only_metres() {
+        let mut service = create_mock_service().with_config(QuotationConfig {
+            volume_discounts: volume_tiers(),
+            ..QuotationConfig::default()
+        });
+        let price_list: PriceList = serde_json::from_str(
+            r#"{
+                "tags": ["latest"],
+                "unit": "Piece",
+                "prices": [{"product": {"Cable": {"Coaxial": "RG6"}}, "price": 12.0}]
+            }"#,
+        )
+        .unwrap();
+        service.pricelists.insert(
+            "glands".to_string(),
+            vec![PricingSystem::from_price_list(price_list)],
+        );
+        let quote = |metres: f32| {
+            let mut cable = create_test_quote_item();
+            cable.quantity = metres;
+            let pieces = QuoteItem::builder(Product::Cable(Cable::Coaxial(CoaxialType::RG6)))
+                .with_brand("glands")
+                .with_quantity(500.0)
+                .build();
+            service
+                .generate_quotation(
+                    QuotationRequest::builder()
+                        .with_item(cable)
+                        .with_item(pieces)
+                        .build(),
+                )
+                .unwrap()
+        };
+
+        // 600 mtrs and 500 Nos is not over 1000 mtrs
+        assert_eq!(quote(600.0).volume_discount, None);
+        assert_eq!(quote(1000.0).volume_discount, None);
+        assert_eq!(quote(1001.0).volume_discount.map(|d| d.rate), Some(0.03));
+    }
+
+    #[test]
+    fn test_out_of_range_item_parameters_are_refused() {
         let service = create_mock_service();
+        let quote = |item: QuoteItem| {
+            service.generate_quotation(QuotationRequest::builder().with_item(item).build())
+        };
+        let refusal = |item: QuoteItem| match quote(item) {
+            Err(QuotationError::InvalidItemParameters(reason)) => reason,
+            other => panic!("expected invalid parameters, got {:?}", other),
+        };
+
+        let mut item = create_test_quote_item();
+        item.discount = Some(5.0);
+        assert!(refusal(item).starts_with("discount 5 on"));
+        let mut item = create_test_quote_item();
+        item.discount = Some(-0.1);
+        assert!(refusal(item).starts_with("discount -0.1 on"));
+        let mut item = create_test_quote_item();
+        item.loading_frls = -0.03;
+        assert!(refusal(item).starts_with("FRLS loading -0.03"));
         let mut item = create_test_quote_item();
-        item.discount = 0.333; // Creates a price that needs rounding: 100 * 0.667 = 66.7
+        item.loading_pvc = -0.05;
+        assert!(refusal(item).starts_with("PVC loading -0.05"));
+        let mut item = create_test_quote_item();
+        item.user_base_price = Some(100.0);
+        item.markup = Some(-1.5);
+        assert!(refusal(item).starts_with("markup -1.5"));
+
+        // One bad item refuses the whole quotation
+        let mut bad = create_test_quote_item();
+        bad.discount = Some(1.5);
+        assert!(matches!(
+            service.generate_quotation(
+                QuotationRequest::builder()
+                    .with_items(vec![create_test_quote_item(), bad])
+                    .build()
+            ),
+            Err(QuotationError::InvalidItemParameters(_))
+        ));
+
+        // The edges of each range are fine
+        let mut item = create_test_quote_item();
+        item.discount = Some(1.0);
+        item.user_base_price = Some(100.0);
+        item.markup = Some(-1.0);
+        assert!(quote(item).is_ok());
+        let mut item = create_test_quote_item();
+        item.discount = Some(0.0);
+        assert!(quote(item).is_ok());
+    }
 
-        let request = QuotationRequest {
-            items: vec![item],
-            delivery_charges: 0.0,
-            to: None,
-            terms_and_conditions: None,
+    #[test]
+    fn test_implausible_quotations_are_refused() {
+        let service = create_mock_service();
+        let quote = |price: f32, delivery_charges: f32| {
+            let mut item = create_test_quote_item();
+            item.user_base_price = Some(price);
+            item.quantity = 10.0;
+            service.generate_quotation(
+                QuotationRequest::builder()
+                    .with_item(item)
+                    .with_delivery_charges(delivery_charges)
+                    .build(),
+            )
         };
 
+        assert!(quote(20000.0, 5000.0).is_ok());
+        // A rate no cable sells at
+        assert!(matches!(
+            quote(20000.01, 0.0),
+            Err(QuotationError::ImplausibleAmount(reason)) if reason.contains("/m ceiling")
+        ));
+
+        let lenient = create_mock_service().with_config(QuotationConfig {
+            sanity: SanityConfig {
+                max_price_per_metre: 50000.0,
+            },
+            ..QuotationConfig::default()
+        });
+        let mut item = create_test_quote_item();
+        item.user_base_price = Some(30000.0);
+        assert!(lenient
+            .generate_quotation(QuotationRequest::builder().with_item(item).build())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_price_rounding() {
+        let service = create_mock_service();
+        let mut item = create_test_quote_item();
+        item.discount = Some(0.333); // Creates a price that needs rounding: 100 * 0.667 = 66.7
+
+        let request = QuotationRequest::builder().with_item(item).build();
+
         let result = service.generate_quotation(request).unwrap();
 
         // Should be rounded to 2 decimal places
         assert_eq!(result.items[0].price, 66.7);
     }
 
+    fn create_service_with_default_discount(discount: f32) -> QuotationService {
+        let mut service = create_mock_service();
+        service.brand_settings.insert(
+            "kei".to_string(),
+            BrandSettings {
+                default_discount: Some(discount),
+                current_tags: Vec::new(),
+                markup: None,
+            },
+        );
+        service
+    }
+
+    #[test]
+    fn test_brand_default_discount_applies_without_item_discount() {
+        let service = create_service_with_default_discount(0.05);
+        let mut item = create_test_quote_item();
+        item.brand = "KEI".to_string();
+
+        let request = QuotationRequest::builder().with_item(item).build();
+
+        let result = service.generate_quotation(request).unwrap();
+        assert_eq!(result.items[0].price, 95.0);
+    }
+
+    #[test]
+    fn test_explicit_item_discount_overrides_brand_default() {
+        let service = create_service_with_default_discount(0.05);
+
+        let mut discounted = create_test_quote_item();
+        discounted.discount = Some(0.1);
+        let mut zero_discount = create_test_quote_item();
+        zero_discount.discount = Some(0.0);
+
+        let request = QuotationRequest::builder()
+            .with_items(vec![discounted, zero_discount])
+            .build();
+
+        let result = service.generate_quotation(request).unwrap();
+        assert_eq!(result.items[0].price, 90.0);
+        // Explicit zero is a real choice, not a missing value
+        assert_eq!(result.items[1].price, 100.0);
+    }
+
+    fn create_service_with_brand_terms(discount: Option<f32>, markup: f32) -> QuotationService {
+        let mut service = create_mock_service();
+        service.brand_settings.insert(
+            "kei".to_string(),
+            BrandSettings {
+                default_discount: discount,
+                current_tags: Vec::new(),
+                markup: Some(markup),
+            },
+        );
+        service
+    }
+
+    #[test]
+    fn test_brand_markup_applies_after_discount_and_loadings() {
+        let service = create_service_with_brand_terms(Some(0.4), 0.1);
+        let plain = create_test_quote_item();
+        let mut loaded = create_test_quote_item();
+        loaded.loading_frls = 0.05;
+
+        let request = QuotationRequest::builder()
+            .with_items(vec![plain, loaded])
+            .build();
+
+        let result = service.generate_quotation(request).unwrap();
+        // 100 * (1 - 0.4) * 1.1
+        assert_eq!(result.items[0].price, 66.0);
+        // 100 * (1 - 0.4) * 1.05 * 1.1
+        assert_eq!(result.items[1].price, 69.3);
+    }
+
+    #[test]
+    fn test_brand_markup_applies_to_prices_only() {
+        let service = create_service_with_brand_terms(None, 0.1);
+        let request = PriceOnlyRequest {
+            items: vec![PriceOnlyItem {
+                product: create_test_quote_item().product,
+                brand: "kei".to_string(),
+                tag: "latest".to_string(),
+                discount: Some(0.5),
+                quantity: None,
+                unit: None,
+                loading_frls: 0.0,
+                loading_pvc: 0.0,
+            }],
+        };
+
+        let result = service.get_prices_only(request).unwrap();
+        assert_eq!(result.items[0].price, 55.0);
+    }
+
+    #[test]
+    fn test_user_price_ignores_brand_markup() {
+        let service = create_service_with_brand_terms(None, 0.1);
+        let mut with_item_markup = create_test_quote_item();
+        with_item_markup.user_base_price = Some(200.0);
+        with_item_markup.markup = Some(0.05);
+        let mut without_markup = create_test_quote_item();
+        without_markup.user_base_price = Some(200.0);
+
+        let request = QuotationRequest::builder()
+            .with_items(vec![with_item_markup, without_markup])
+            .build();
+
+        let result = service.generate_quotation(request).unwrap();
+        assert_eq!(result.items[0].price, 210.0);
+        assert_eq!(result.items[1].price, 200.0);
+    }
+
+    #[test]
+    fn test_warns_when_quoting_from_outdated_tag() {
+        let mut service = create_mock_service();
+        service.pricelists.get_mut("kei").unwrap()[0]
+            .tags
+            .push("july 2025".to_string());
+        service.brand_settings.insert(
+            "kei".to_string(),
+            BrandSettings {
+                default_discount: None,
+                current_tags: vec!["latest".to_string()],
+                markup: None,
+            },
+        );
+
+        let current_item = create_test_quote_item();
+        let mut outdated_item = create_test_quote_item();
+        outdated_item.tag = "July 2025".to_string();
+
+        let current = service
+            .generate_quotation(QuotationRequest::builder().with_item(current_item).build())
+            .unwrap();
+        assert!(current.warnings.is_empty());
+
+        let outdated = service
+            .generate_quotation(QuotationRequest::builder().with_item(outdated_item).build())
+            .unwrap();
+        assert_eq!(outdated.warnings.len(), 1);
+        assert!(outdated.warnings[0].contains("July 2025"));
+    }
+
+    #[test]
+    fn test_gst_split_follows_buyer_state() {
+        let service = create_mock_service().with_config(QuotationConfig {
+            seller_state: Some("Maharashtra".to_string()),
+            ..QuotationConfig::default()
+        });
+        let quote = |buyer_state: Option<&str>| {
+            let mut request = QuotationRequest::builder()
+                .with_item(create_test_quote_item())
+                .with_delivery_charges(123.45);
+            if let Some(buyer_state) = buyer_state {
+                request = request.with_buyer_state(buyer_state);
+            }
+            service.generate_quotation(request.build()).unwrap()
+        };
+
+        let same_state = quote(Some(" maharashtra "));
+        let lines = same_state.tax_lines();
+        assert_eq!(lines.len(), 2);
+        assert_eq!((lines[0].0, lines[1].0), ("CGST @ 9%", "SGST @ 9%"));
+        assert!((lines[0].1 - same_state.taxes / 2.0).abs() <= 0.01);
+        assert_eq!(round_to_paise(lines[0].1 + lines[1].1), same_state.taxes);
+
+        let other_state = quote(Some("Gujarat"));
+        assert_eq!(
+            other_state.tax_lines(),
+            vec![("IGST @ 18%", other_state.taxes)]
+        );
+        assert_eq!(other_state.grand_total, same_state.grand_total);
+
+        let unknown_state = quote(None);
+        assert_eq!(
+            unknown_state.tax_lines(),
+            vec![("GST @ 18%", unknown_state.taxes)]
+        );
+        assert_eq!(unknown_state.grand_total, same_state.grand_total);
+    }
+
+    #[test]
+    fn test_estimated_delivery_is_carried_to_response() {
+        let service = create_mock_service();
+        let request = |delivery: Option<&str>| {
+            let request = QuotationRequest::builder().with_item(create_test_quote_item());
+            match delivery {
+                Some(delivery) => request.with_estimated_delivery(delivery),
+                None => request,
+            }
+            .build()
+        };
+
+        let with_delivery = service.generate_quotation(request(Some(" 2 weeks "))).unwrap();
+        assert_eq!(with_delivery.estimated_delivery.as_deref(), Some("2 weeks"));
+
+        let blank_delivery = service.generate_quotation(request(Some("  "))).unwrap();
+        assert_eq!(blank_delivery.estimated_delivery, None);
+
+        let no_delivery = service.generate_quotation(request(None)).unwrap();
+        assert_eq!(no_delivery.estimated_delivery, None);
+    }
+
     #[test]
     fn test_hundred_percent_discount() {
         let service = create_mock_service();
         let mut item = create_test_quote_item();
-        item.discount = 1.0; // 100% discount
+        item.discount = Some(1.0); // 100% discount
 
-        let request = QuotationRequest {
-            items: vec![item],
-            delivery_charges: 0.0,
-            to: None,
-            terms_and_conditions: None,
-        };
+        let request = QuotationRequest::builder().with_item(item).build();
 
         let result = service.generate_quotation(request).unwrap();
 
@@ -401,12 +1678,7 @@ mod tests {
         let mut item = create_test_quote_item();
         item.quantity = 0.0;
 
-        let request = QuotationRequest {
-            items: vec![item],
-            delivery_charges: 0.0,
-            to: None,
-            terms_and_conditions: None,
-        };
+        let request = QuotationRequest::builder().with_item(item).build();
 
         let result = service.generate_quotation(request).unwrap();
 
@@ -417,18 +1689,14 @@ mod tests {
     #[test]
     fn test_empty_items_list() {
         let service = create_mock_service();
-        let request = QuotationRequest {
-            items: vec![],
-            delivery_charges: 25.0,
-            to: None,
-            terms_and_conditions: None,
-        };
+        let request = QuotationRequest::builder()
+            .with_delivery_charges(25.0)
+            .build();
 
-        let result = service.generate_quotation(request).unwrap();
+        let result = service.generate_quotation(request);
 
-        assert_eq!(result.items.len(), 0);
-        assert_eq!(result.basic_total, 0.0);
-        assert_eq!(result.total_with_delivery, 25.0); // Only delivery charges
+        // Delivery charges alone shouldn't turn into a quotation
+        assert!(matches!(result, Err(QuotationError::NoItems)));
     }
 
     #[test]
@@ -442,6 +1710,38 @@ mod tests {
         assert_eq!(result, Some(standard_terms));
     }
 
+    #[test]
+    fn test_standard_terms_use_configured_validity() {
+        let validity_term = |service: &QuotationService| {
+            service
+                .process_terms_and_conditions(Some(vec!["Standard".to_string()]))
+                .unwrap()
+                .pop()
+                .unwrap()
+        };
+
+        assert_eq!(
+            validity_term(&create_mock_service()),
+            "Validity: 3 days from quotation date"
+        );
+        let service = create_mock_service().with_config(QuotationConfig {
+            validity_days: 5,
+            ..QuotationConfig::default()
+        });
+        assert_eq!(
+            validity_term(&service),
+            "Validity: 5 days from quotation date"
+        );
+        let service = create_mock_service().with_config(QuotationConfig {
+            validity_days: 1,
+            ..QuotationConfig::default()
+        });
+        assert_eq!(
+            validity_term(&service),
+            "Validity: 1 day from quotation date"
+        );
+    }
+
     #[test]
     fn test_process_terms_custom() {
         let service = create_mock_service();
@@ -462,6 +1762,26 @@ mod tests {
         assert_eq!(result, None);
     }
 
+    #[test]
+    fn test_describe_product_matches_document_wording() {
+        let service = create_mock_service();
+
+        let request = DescribeProductRequest {
+            product: Product::Cable(Cable::PowerControl(PowerControl::LT(LT {
+                conductor: Conductor::Copper,
+                core_size: "4".to_string(),
+                sqmm: "2.5".to_string(),
+                armoured: true,
+            }))),
+            extras: vec![" FRLS ".to_string()],
+        };
+
+        assert_eq!(
+            service.describe_product(request),
+            "4 C x 2.5 sq. mm XLPE Insulated, FRLS PVC Sheathed Armoured Copper Cable"
+        );
+    }
+
     #[test]
     fn test_get_prices_only_skips_missing_items() {
         let service = create_mock_service();
@@ -475,8 +1795,9 @@ mod tests {
             }))),
             brand: "kei".to_string(),
             tag: "latest".to_string(),
-            discount: 0.0,
+            discount: None,
             quantity: Some(1.0),
+            unit: None,
             loading_frls: 0.0,
             loading_pvc: 0.0,
         };
@@ -489,8 +1810,9 @@ mod tests {
             }))),
             brand: "nonexistent".to_string(),
             tag: "latest".to_string(),
-            discount: 0.0,
+            discount: None,
             quantity: Some(1.0),
+            unit: None,
             loading_frls: 0.0,
             loading_pvc: 0.0,
         };
@@ -501,9 +1823,38 @@ mod tests {
 
         let result = service.get_prices_only(request).unwrap();
 
-        // Should only include the valid item
+        // Should only include the valid item, and report the missing one
         assert_eq!(result.items.len(), 1);
         assert_eq!(result.items[0].price, 100.0);
+        assert_eq!(result.skipped_items.len(), 1);
+        assert!(result.skipped_items[0].spec.ends_with("(nonexistent)"));
+        assert_eq!(result.skipped_items[0].reason, SkipReason::BrandNotFound);
+    }
+
+    #[test]
+    fn test_get_prices_only_reports_size_not_in_pricelist() {
+        let service = create_mock_service();
+        let request = PriceOnlyRequest {
+            items: vec![PriceOnlyItem {
+                product: Product::Cable(Cable::Coaxial(CoaxialType::RG11)),
+                brand: "KEI".to_string(),
+                tag: "latest".to_string(),
+                discount: None,
+                quantity: None,
+                unit: None,
+                loading_frls: 0.0,
+                loading_pvc: 0.0,
+            }],
+        };
+
+        let result = service.get_prices_only(request).unwrap();
+        assert!(result.items.is_empty());
+        assert_eq!(result.skipped_items.len(), 1);
+        assert_eq!(result.skipped_items[0].reason, SkipReason::NotInPricelist);
+        assert_eq!(
+            result.skipped_items[0].to_string(),
+            format!("{} - size not in pricelist", result.skipped_items[0].spec)
+        );
     }
 
     #[test]
@@ -513,12 +1864,7 @@ mod tests {
         item.loading_frls = 1.0; // 100% loading
         item.loading_pvc = 0.5;  // 50% loading
 
-        let request = QuotationRequest {
-            items: vec![item],
-            delivery_charges: 0.0,
-            to: None,
-            terms_and_conditions: None,
-        };
+        let request = QuotationRequest::builder().with_item(item).build();
 
         let result = service.generate_quotation(request).unwrap();
 
@@ -1,39 +1,255 @@
-use crate::prices::item_prices::Product;
+use super::round_to_paise;
+use crate::configuration::BankDetails;
+use crate::prices::item_prices::{Product, QuantityUnit};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
-#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct QuoteItem {
     /// Specific electrical product for which quotation is required
     pub product: Product,
-    /// Brand name for product
+    /// Brand name for product - leave empty if the user doesn't name one
+    #[serde(default)]
     pub brand: String,
     /// Selects which pricelist to use for pricing the item
     pub tag: String,
-    /// in percentage eg. 0.70 means 70%
-    pub discount: f32,
+    /// Optional, in percentage eg. 0.70 means 70% - leave out to apply the brand's standing discount
+    #[serde(default)]
+    pub discount: Option<f32>,
     /// in percentage eg. 0.03 means 3% - applicable only for LT/HT cable types
     pub loading_frls: f32,
     /// in percentage eg. 0.05 means 5%, - applicable only for LT/HT cable types
     pub loading_pvc: f32,
     /// Quantity required
     pub quantity: f32,
+    /// Optional unit the quantity is given in eg. "Km" for bulk cable, "Nos" for pieces - leave
+    /// out to use the pricelist's unit
+    #[serde(default)]
+    pub unit: Option<QuantityUnit>,
     /// Final price that can optionally be provided by the user - If provided, skip price lookup
     pub user_base_price: Option<f32>,
     /// Optional - Apply markup/margin, if given, to user_base_price (eg. 0.015 means 1.5%)
     pub markup: Option<f32>,
+    /// Optional HSN code, only if the user gives one for the item - leave out otherwise
+    #[serde(default)]
+    pub hsn: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct QuotationRequest {
     /// List of items for which quotation is required
     pub items: Vec<QuoteItem>,
     /// Delivery charges, if provided by user, defaults to 0
     pub delivery_charges: f32,
-    /// Optional addressee for the quotation/proforma invoice
+    /// Optional packing & forwarding charges, only if the user gives them
+    #[serde(default)]
+    pub packing_forwarding: Option<PackingCharge>,
+    /// Optional addressee for the quotation/proforma invoice - the customer's name alone is
+    /// enough for customers with a saved address
     pub to: Option<Vec<String>>,
     /// Optional terms and conditions for the quotation/proforma invoice
     pub terms_and_conditions: Option<Vec<String>>,
+    /// Optional estimated delivery / lead time eg. "2 weeks", "7-10 days", "Ready stock"
+    pub estimated_delivery: Option<String>,
+    /// Optional internal note for staff eg. "customer negotiated extra 2%" - stored with the
+    /// request but never printed on the quotation/proforma invoice
+    pub internal_note: Option<String>,
+    /// Optional full name of the buyer's state eg. "Maharashtra" - decides whether GST is shown
+    /// as CGST + SGST or as IGST
+    pub buyer_state: Option<String>,
+    /// true only if the user asks for a draft / preliminary quote - marks the document "DRAFT"
+    #[serde(default)]
+    pub draft: bool,
+}
+
+/// Packing & forwarding, added with the delivery charges before GST
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub enum PackingCharge {
+    /// Flat amount in rupees eg. 500.0
+    Flat(f32),
+    /// Share of the sub total eg. 0.02 means 2%
+    Percent(f32),
+}
+
+impl QuoteItem {
+    pub fn builder(product: Product) -> QuoteItemBuilder {
+        QuoteItemBuilder::new(product)
+    }
+}
+
+impl QuotationRequest {
+    pub fn builder() -> QuotationRequestBuilder {
+        QuotationRequestBuilder::default()
+    }
+}
+
+// Defaults to the latest pricelist of the preferred brand, quantity 1, no loadings and no user price. The discount
+// is left unset, so the brand's standing discount (zero unless configured) applies.
+pub struct QuoteItemBuilder {
+    item: QuoteItem,
+}
+
+impl QuoteItemBuilder {
+    pub fn new(product: Product) -> Self {
+        Self {
+            item: QuoteItem {
+                product,
+                brand: default_brand(),
+                tag: default_tag(),
+                discount: None,
+                loading_frls: 0.0,
+                loading_pvc: 0.0,
+                quantity: 1.0,
+                unit: None,
+                user_base_price: None,
+                markup: None,
+                hsn: None,
+            },
+        }
+    }
+
+    pub fn with_brand(mut self, brand: &str) -> Self {
+        self.item.brand = brand.to_string();
+        self
+    }
+
+    pub fn with_tag(mut self, tag: &str) -> Self {
+        self.item.tag = tag.to_string();
+        self
+    }
+
+    pub fn with_discount(mut self, discount: f32) -> Self {
+        self.item.discount = Some(discount);
+        self
+    }
+
+    pub fn with_loading_frls(mut self, loading_frls: f32) -> Self {
+        self.item.loading_frls = loading_frls;
+        self
+    }
+
+    pub fn with_loading_pvc(mut self, loading_pvc: f32) -> Self {
+        self.item.loading_pvc = loading_pvc;
+        self
+    }
+
+    pub fn with_quantity(mut self, quantity: f32) -> Self {
+        self.item.quantity = quantity;
+        self
+    }
+
+    pub fn with_unit(mut self, unit: QuantityUnit) -> Self {
+        self.item.unit = Some(unit);
+        self
+    }
+
+    pub fn with_user_base_price(mut self, user_base_price: f32) -> Self {
+        self.item.user_base_price = Some(user_base_price);
+        self
+    }
+
+    pub fn with_markup(mut self, markup: f32) -> Self {
+        self.item.markup = Some(markup);
+        self
+    }
+
+    pub fn with_hsn(mut self, hsn: &str) -> Self {
+        self.item.hsn = Some(hsn.to_string());
+        self
+    }
+
+    pub fn build(self) -> QuoteItem {
+        self.item
+    }
+}
+
+// Starts with no items, no delivery charges and none of the optional document details
+pub struct QuotationRequestBuilder {
+    request: QuotationRequest,
+}
+
+impl Default for QuotationRequestBuilder {
+    fn default() -> Self {
+        Self {
+            request: QuotationRequest {
+                items: Vec::new(),
+                delivery_charges: 0.0,
+                packing_forwarding: None,
+                to: None,
+                terms_and_conditions: None,
+                estimated_delivery: None,
+                internal_note: None,
+                buyer_state: None,
+                draft: false,
+            },
+        }
+    }
+}
+
+impl QuotationRequestBuilder {
+    pub fn with_item(mut self, item: QuoteItem) -> Self {
+        self.request.items.push(item);
+        self
+    }
+
+    pub fn with_items(mut self, items: Vec<QuoteItem>) -> Self {
+        self.request.items.extend(items);
+        self
+    }
+
+    pub fn with_delivery_charges(mut self, delivery_charges: f32) -> Self {
+        self.request.delivery_charges = delivery_charges;
+        self
+    }
+
+    pub fn with_packing_forwarding(mut self, packing_forwarding: PackingCharge) -> Self {
+        self.request.packing_forwarding = Some(packing_forwarding);
+        self
+    }
+
+    pub fn with_to(mut self, to: Vec<String>) -> Self {
+        self.request.to = Some(to);
+        self
+    }
+
+    pub fn with_terms_and_conditions(mut self, terms_and_conditions: Vec<String>) -> Self {
+        self.request.terms_and_conditions = Some(terms_and_conditions);
+        self
+    }
+
+    pub fn with_estimated_delivery(mut self, estimated_delivery: &str) -> Self {
+        self.request.estimated_delivery = Some(estimated_delivery.to_string());
+        self
+    }
+
+    pub fn with_internal_note(mut self, internal_note: &str) -> Self {
+        self.request.internal_note = Some(internal_note.to_string());
+        self
+    }
+
+    pub fn with_buyer_state(mut self, buyer_state: &str) -> Self {
+        self.request.buyer_state = Some(buyer_state.to_string());
+        self
+    }
+
+    pub fn with_draft(mut self, draft: bool) -> Self {
+        self.request.draft = draft;
+        self
+    }
+
+    pub fn build(self) -> QuotationRequest {
+        self.request
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct DescribeProductRequest {
+    /// Product whose document description should be previewed
+    pub product: Product,
+    /// Optional extras that change the wording eg. ["frls"], ["pvc"] or ["pvc", "frls"]
+    #[serde(default)]
+    pub extras: Vec<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, JsonSchema)]
@@ -49,16 +265,19 @@ pub struct PriceOnlyItem {
     #[serde(default = "default_tag")]
     pub tag: String,
     #[serde(default)]
-    pub discount: f32,
+    pub discount: Option<f32>,
     pub quantity: Option<f32>,
     #[serde(default)]
+    pub unit: Option<QuantityUnit>,
+    #[serde(default)]
     pub loading_frls: f32,
     #[serde(default)]
     pub loading_pvc: f32,
 }
 
+// Empty means no brand was named - the quotation service picks one by preference
 fn default_brand() -> String {
-    "kei".to_string()
+    String::new()
 }
 
 fn default_tag() -> String {
@@ -70,32 +289,148 @@ pub struct QuotedItem {
     pub product: Product,
     pub brand: String,
     pub quantity_mtrs: f32,
+    #[serde(default)]
+    pub unit: QuantityUnit, // unit the quantity and price are in
     pub price: f32, // price = listed_price*(1-discount)*(1+loading_frls)*(1+loading_pvc)
+    // Before the discount - price is list_price*(1-discount), give or take a paisa of rounding
+    #[serde(default)]
+    pub list_price: f32,
+    #[serde(default)]
+    pub discount: f32, // fraction eg. 0.1 for 10%, 0 for user priced items
     pub amount: f32, // amount = price*qty
     pub loading_pvc: f32,
     pub loading_frls: f32,
+    pub hsn_code: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 pub struct QuotationResponse {
     pub items: Vec<QuotedItem>,
     pub basic_total: f32,
     pub delivery_charges: f32,
+    // Packing & forwarding in rupees, 0 when not charged
+    #[serde(default)]
+    pub packing_forwarding: f32,
+    // After any volume discount, with the delivery and P&F charges
     pub total_with_delivery: f32,
     pub taxes: f32,       //taxes = total_with_delivery*0.18
-    pub grand_total: f32, // grand_total = total_with_delivery + taxes
+    pub grand_total: f32, // grand_total = total_with_delivery + taxes + round_off
+    // Takes total_with_delivery + taxes to the rounded grand total, negative when rounded down
+    #[serde(default)]
+    pub round_off: f32,
     pub to: Option<Vec<String>>,
     pub terms_and_conditions: Option<Vec<String>>,
+    pub estimated_delivery: Option<String>,
+    pub warnings: Vec<String>, // eg. items priced off a pricelist tag that is not current
+    // Customer's original request, printed on an "Enquiry Reference" page when set
+    pub enquiry: Option<String>,
+    // Taken off basic_total before delivery and taxes
+    pub volume_discount: Option<VolumeDiscount>,
+    // GST is split into CGST + SGST when these are the same state, and is IGST when they differ
+    pub buyer_state: Option<String>,
+    pub seller_state: Option<String>,
+    // Preliminary quote, watermarked "DRAFT" on every page
+    #[serde(default)]
+    pub draft: bool,
+    // Only printed on proforma invoices
+    pub bank_details: Option<BankDetails>,
+}
+
+impl QuotedItem {
+    // A metre line at its list price, without discount or loadings - the rest is set with struct
+    // update syntax eg. `QuotedItem { discount: 0.1, ..QuotedItem::new(..) }`
+    pub fn new(product: Product, brand: &str, quantity_mtrs: f32, price: f32) -> Self {
+        Self {
+            product,
+            brand: brand.to_string(),
+            quantity_mtrs,
+            unit: QuantityUnit::Metre,
+            price,
+            list_price: price,
+            discount: 0.0,
+            amount: round_to_paise(quantity_mtrs * price),
+            loading_pvc: 0.0,
+            loading_frls: 0.0,
+            hsn_code: None,
+        }
+    }
+}
+
+impl QuotationResponse {
+    // The GST lines printed under the sub total, always adding up to `taxes`. Without both
+    // states it stays a single GST line.
+    pub fn tax_lines(&self) -> Vec<(&'static str, f32)> {
+        let same_state = match (&self.buyer_state, &self.seller_state) {
+            (Some(buyer), Some(seller)) => Some(state_key(buyer) == state_key(seller)),
+            _ => None,
+        };
+        match same_state {
+            Some(true) => {
+                // Any odd paisa goes to SGST
+                let cgst = round_to_paise(self.taxes / 2.0);
+                vec![
+                    ("CGST @ 9%", cgst),
+                    ("SGST @ 9%", round_to_paise(self.taxes - cgst)),
+                ]
+            }
+            Some(false) => vec![("IGST @ 18%", self.taxes)],
+            None => vec![("GST @ 18%", self.taxes)],
+        }
+    }
+}
+
+fn state_key(state: &str) -> String {
+    state
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+pub struct VolumeDiscount {
+    pub rate: f32,
+    pub amount: f32,
 }
 
 #[derive(Debug)]
 pub struct PriceOnlyResponse {
     pub items: Vec<PriceOnlyResponseItem>,
+    pub skipped_items: Vec<SkippedItem>,
+}
+
+// An item that couldn't be priced, reported back so the user can correct the query
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkippedItem {
+    pub spec: String,
+    pub reason: SkipReason,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SkipReason {
+    BrandNotFound,
+    NotInPricelist,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::BrandNotFound => write!(f, "brand not found"),
+            SkipReason::NotInPricelist => write!(f, "size not in pricelist"),
+        }
+    }
+}
+
+impl fmt::Display for SkippedItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} - {}", self.spec, self.reason)
+    }
 }
 
 #[derive(Debug)]
 pub struct PriceOnlyResponseItem {
     pub description: String,
     pub price: f32,
+    pub unit: QuantityUnit,
     pub quantity: Option<f32>,
 }
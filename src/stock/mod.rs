@@ -1,7 +1,8 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::sync::{mpsc, oneshot, Mutex};
 use tracing::error;
 use uuid::Uuid;
@@ -10,6 +11,7 @@ use uuid::Uuid;
 pub struct StockRequest {
     pub id: String,
     pub query: String,
+    pub warehouse: String,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -17,24 +19,288 @@ pub struct StockResponse {
     pub id: String,
     pub stock_info: String,
     pub error: Option<String>,
+    #[serde(default)]
+    pub warehouse: Option<String>,
+    // Structured fields sent by newer Tally clients - older ones only send stock_info
+    #[serde(default)]
+    pub quantity: Option<f64>,
+    #[serde(default)]
+    pub unit: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StockQuantity {
+    pub quantity: f64,
+    pub unit: Option<String>,
+}
+
+// Stock reply handed back to query fulfilment
+#[derive(Debug, Clone)]
+pub struct StockInfo {
+    pub text: String,
+    pub quantity: Option<StockQuantity>,
+}
+
+impl StockResponse {
+    // Prefers the structured quantity and falls back to reading it out of the formatted text
+    pub fn available_quantity(&self) -> Option<StockQuantity> {
+        match self.quantity {
+            Some(quantity) => Some(StockQuantity {
+                quantity,
+                unit: self.unit.as_ref().map(|unit| unit.trim().to_lowercase()),
+            }),
+            None => parse_stock_quantity(&self.stock_info),
+        }
+    }
+}
+
+// Best effort for legacy replies like "4 C x 2.5 2XWYL: 1,250.50 mtrs". Item names carry
+// numbers too, so only the text after the last ':' is read when there is one, and the last
+// number in it is taken as the quantity.
+pub fn parse_stock_quantity(stock_info: &str) -> Option<StockQuantity> {
+    let segment = match stock_info.rsplit_once(':') {
+        Some((_, after)) if after.chars().any(|c| c.is_ascii_digit()) => after,
+        _ => stock_info,
+    };
+
+    let chars: Vec<char> = segment.chars().collect();
+    let mut last_number: Option<(f64, usize)> = None;
+    let mut i = 0;
+    while i < chars.len() {
+        let negative = chars[i] == '-' && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit());
+        if chars[i].is_ascii_digit() || negative {
+            let start = i;
+            i += 1;
+            while i < chars.len()
+                && (chars[i].is_ascii_digit()
+                    || (matches!(chars[i], ',' | '.')
+                        && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit())))
+            {
+                i += 1;
+            }
+            let number: String = chars[start..i].iter().filter(|c| **c != ',').collect();
+            if let Ok(value) = number.parse::<f64>() {
+                last_number = Some((value, i));
+            }
+        } else {
+            i += 1;
+        }
+    }
+
+    let (quantity, end) = last_number?;
+    let unit: String = chars[end..]
+        .iter()
+        .skip_while(|c| c.is_whitespace())
+        .take_while(|c| c.is_alphabetic())
+        .collect();
+
+    Some(StockQuantity {
+        quantity,
+        unit: (!unit.is_empty()).then(|| unit.to_lowercase()),
+    })
+}
+
+// Warehouse used by Tally clients that connect without announcing one
+pub const DEFAULT_WAREHOUSE: &str = "default";
+
+// Labels each warehouse's reply. Quantities are only totalled when every warehouse reported
+// one in the same unit.
+fn combine_warehouse_replies(
+    replies: Vec<(String, Result<StockInfo, String>)>,
+) -> Result<StockInfo, String> {
+    if replies.iter().all(|(_, reply)| reply.is_err()) {
+        let errors: Vec<String> = replies
+            .into_iter()
+            .map(|(warehouse, reply)| format!("{}: {}", warehouse, reply.unwrap_err()))
+            .collect();
+        return Err(errors.join("; "));
+    }
+
+    let mut lines = Vec::new();
+    let mut quantities = Vec::new();
+    for (warehouse, reply) in replies {
+        match reply {
+            Ok(info) => {
+                lines.push(format!("[{}]\n{}", warehouse, info.text));
+                quantities.push(info.quantity);
+            }
+            Err(e) => {
+                lines.push(format!("[{}]\nStock check failed: {}", warehouse, e));
+                quantities.push(None);
+            }
+        }
+    }
+
+    Ok(StockInfo {
+        text: lines.join("\n\n"),
+        quantity: sum_quantities(quantities),
+    })
+}
+
+fn sum_quantities(quantities: Vec<Option<StockQuantity>>) -> Option<StockQuantity> {
+    let mut quantities = quantities.into_iter();
+    let mut total = quantities.next()??;
+    for quantity in quantities {
+        let quantity = quantity?;
+        if quantity.unit != total.unit {
+            return None;
+        }
+        total.quantity += quantity.quantity;
+    }
+    Some(total)
+}
+
+// Sender for a connected Tally client, tagged with the websocket connection it belongs to
+#[derive(Clone)]
+pub struct TallyClient {
+    pub connection_id: Uuid,
+    pub sender: mpsc::Sender<String>,
+}
+
+// A stock request waiting on a reply, remembered against the connection it was sent over
+pub struct PendingRequest {
+    pub connection_id: Uuid,
+    pub responder: oneshot::Sender<Result<StockInfo, String>>,
+}
+
+// Running counters, updated around each Tally round trip
+#[derive(Default)]
+struct StockCounters {
+    served: AtomicU64,
+    timeouts: AtomicU64,
+    total_latency_ms: AtomicU64,
+}
+
+// Point in time view of the stock service, for /status and analytics
+#[derive(Debug, Clone, Serialize)]
+pub struct StockMetrics {
+    pub pending_requests: usize,
+    pub total_served: u64,
+    pub timeouts: u64,
+    pub average_latency_ms: Option<f64>,
+    pub connected_warehouses: Vec<String>,
 }
 
 #[derive(Clone)]
 pub struct StockService {
-    pub tally_sender: Arc<Mutex<Option<mpsc::Sender<String>>>>,
-    pub pending_requests: Arc<Mutex<HashMap<String, oneshot::Sender<String>>>>,
+    // One Tally client per warehouse, keyed by the warehouse id it connected with
+    pub tally_senders: Arc<Mutex<HashMap<String, TallyClient>>>,
+    pub pending_requests: Arc<Mutex<HashMap<String, PendingRequest>>>,
+    counters: Arc<StockCounters>,
 }
 
 impl StockService {
     pub fn new() -> Self {
         Self {
-            tally_sender: Arc::new(Mutex::new(None)),
+            tally_senders: Arc::new(Mutex::new(HashMap::new())),
             pending_requests: Arc::new(Mutex::new(HashMap::new())),
+            counters: Arc::new(StockCounters::default()),
+        }
+    }
+
+    pub async fn metrics(&self) -> StockMetrics {
+        let pending_requests = self.pending_requests.lock().await.len();
+        let mut connected_warehouses: Vec<String> =
+            self.tally_senders.lock().await.keys().cloned().collect();
+        connected_warehouses.sort();
+
+        let total_served = self.counters.served.load(Ordering::Relaxed);
+        let total_latency_ms = self.counters.total_latency_ms.load(Ordering::Relaxed);
+        StockMetrics {
+            pending_requests,
+            total_served,
+            timeouts: self.counters.timeouts.load(Ordering::Relaxed),
+            average_latency_ms: (total_served > 0)
+                .then(|| total_latency_ms as f64 / total_served as f64),
+            connected_warehouses,
+        }
+    }
+
+    // Called by the websocket handler whenever a Tally client connects or reconnects - a
+    // reconnect from the same warehouse replaces the old sender
+    pub async fn register_tally_client(&self, warehouse: &str, client: TallyClient) {
+        self.tally_senders
+            .lock()
+            .await
+            .insert(warehouse.to_string(), client);
+    }
+
+    // Called by the websocket handler when a connection drops. Requests still waiting on that
+    // connection are failed right away instead of running into the timeout.
+    pub async fn handle_tally_disconnect(&self, warehouse: &str, connection_id: Uuid) {
+        {
+            let mut senders = self.tally_senders.lock().await;
+            // A quick reconnect may already have replaced this connection's sender
+            if senders
+                .get(warehouse)
+                .is_some_and(|client| client.connection_id == connection_id)
+            {
+                senders.remove(warehouse);
+            }
+        }
+
+        let mut pending = self.pending_requests.lock().await;
+        let dropped: Vec<String> = pending
+            .iter()
+            .filter(|(_, request)| request.connection_id == connection_id)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in dropped {
+            if let Some(request) = pending.remove(&id) {
+                let _ = request.responder.send(Err(
+                    "Tally disconnected - please try again shortly".to_string(),
+                ));
+            }
         }
     }
 
     // Serves user stock queries sent by query fulfilment
     pub async fn request_stock(&self, query: String) -> Result<String, String> {
+        self.request_stock_details(query)
+            .await
+            .map(|info| info.text)
+    }
+
+    // Same as request_stock, but keeps the parsed quantity for quantitative checks.
+    // The query goes to every connected warehouse and the replies are combined.
+    pub async fn request_stock_details(&self, query: String) -> Result<StockInfo, String> {
+        let mut warehouses: Vec<(String, TallyClient)> = self
+            .tally_senders
+            .lock()
+            .await
+            .iter()
+            .map(|(warehouse, client)| (warehouse.clone(), client.clone()))
+            .collect();
+        warehouses.sort_by(|a, b| a.0.cmp(&b.0));
+
+        if warehouses.is_empty() {
+            error!("Tally client not connected at the time of stock request");
+            return Err("Tally client not connected".to_string());
+        }
+
+        let requests = warehouses
+            .iter()
+            .map(|(warehouse, client)| self.request_from_warehouse(warehouse, client, &query));
+        let results = futures::future::join_all(requests).await;
+
+        // Single warehouse setups keep the original, unlabelled response
+        if results.len() == 1 {
+            return results.into_iter().next().unwrap();
+        }
+        let replies: Vec<(String, Result<StockInfo, String>)> = warehouses
+            .into_iter()
+            .map(|(warehouse, _)| warehouse)
+            .zip(results)
+            .collect();
+        combine_warehouse_replies(replies)
+    }
+
+    async fn request_from_warehouse(
+        &self,
+        warehouse: &str,
+        client: &TallyClient,
+        query: &str,
+    ) -> Result<StockInfo, String> {
         let request_id = Uuid::new_v4().to_string();
         // This one-shot channel is used for synchronising request response
         // Any new request is stored in pending_requests with reference to the sender part of this channel
@@ -43,36 +309,55 @@ impl StockService {
         let (tx, rx) = oneshot::channel();
 
         // Store pending request
-        self.pending_requests
-            .lock()
-            .await
-            .insert(request_id.clone(), tx);
+        self.pending_requests.lock().await.insert(
+            request_id.clone(),
+            PendingRequest {
+                connection_id: client.connection_id,
+                responder: tx,
+            },
+        );
 
         // Send request to Tally
         let request = StockRequest {
-            id: request_id,
-            query,
+            id: request_id.clone(),
+            query: query.to_string(),
+            warehouse: warehouse.to_string(),
         };
 
-        // The tally_sender is set by the websocket handler whenever a connection is made or reconnected
-        let sender = self.tally_sender.lock().await;
-        if let Some(sender) = sender.as_ref() {
-            sender
-                .send(serde_json::to_string(&request).unwrap())
-                .await
-                .map_err(|_| "Failed to send request to Tally")?;
-        } else {
-            error!("Tally client not connected at the time of stock request");
-            return Err("Tally client not connected".to_string());
+        if client
+            .sender
+            .send(serde_json::to_string(&request).unwrap())
+            .await
+            .is_err()
+        {
+            self.pending_requests.lock().await.remove(&request_id);
+            error!(
+                "Failed to send stock request to Tally at warehouse {}",
+                warehouse
+            );
+            return Err("Failed to send request to Tally".to_string());
         }
-        drop(sender);
 
         // Wait for response with timeout - and send response to query fulfilment
-        match tokio::time::timeout(Duration::from_secs(10), rx).await {
-            Ok(Ok(response)) => Ok(response),
+        let started = Instant::now();
+        let result = match tokio::time::timeout(Duration::from_secs(10), rx).await {
+            Ok(Ok(response)) => {
+                if response.is_ok() {
+                    self.counters.served.fetch_add(1, Ordering::Relaxed);
+                    self.counters
+                        .total_latency_ms
+                        .fetch_add(started.elapsed().as_millis() as u64, Ordering::Relaxed);
+                }
+                response
+            }
             Ok(Err(_)) => Err("Request cancelled".to_string()),
-            Err(_) => Err("Request timeout".to_string()),
-        }
+            Err(_) => {
+                self.counters.timeouts.fetch_add(1, Ordering::Relaxed);
+                Err("Request timeout".to_string())
+            }
+        };
+        self.pending_requests.lock().await.remove(&request_id);
+        result
     }
 
     // This is called by the websocket module whenever it receives a response from tally client
@@ -83,13 +368,166 @@ impl StockService {
         // Send response to waiting thread using the oneshot channel established when sending the request
         if let Ok(response) = serde_json::from_str::<StockResponse>(response_json) {
             let mut pending = self.pending_requests.lock().await;
-            if let Some(sender) = pending.remove(&response.id) {
-                let result = response.error.unwrap_or(response.stock_info);
+            if let Some(request) = pending.remove(&response.id) {
+                let quantity = response.available_quantity();
+                let result = match response.error {
+                    Some(error) => StockInfo {
+                        text: error,
+                        quantity: None,
+                    },
+                    None => StockInfo {
+                        text: response.stock_info,
+                        quantity,
+                    },
+                };
                 // Calling sender.send actually signals to the tokio::time::timeout function waiting with the receiver
                 // that a response was received - that response is then sent to query fulfilment
                 // it also enables timeout based request processing
-                let _ = sender.send(result);
+                let _ = request.responder.send(Ok(result));
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quantity(value: f64, unit: Option<&str>) -> Option<StockQuantity> {
+        Some(StockQuantity {
+            quantity: value,
+            unit: unit.map(|u| u.to_string()),
+        })
+    }
+
+    #[test]
+    fn test_parse_legacy_stock_strings() {
+        assert_eq!(
+            parse_stock_quantity("4 C x 2.5 2XWYL: 1,250.50 Mtrs"),
+            quantity(1250.5, Some("mtrs"))
+        );
+        assert_eq!(
+            parse_stock_quantity("Closing stock 300 nos"),
+            quantity(300.0, Some("nos"))
+        );
+        assert_eq!(parse_stock_quantity("3C x 1.5 FR: 0"), quantity(0.0, None));
+        assert_eq!(parse_stock_quantity("Item not found"), None);
+    }
+
+    // Stands in for a Tally client - answers every request with the given text
+    fn spawn_fake_tally(service: &StockService, stock_info: &'static str) -> TallyClient {
+        let (tx, mut rx) = mpsc::channel::<String>(10);
+        let service = service.clone();
+        tokio::spawn(async move {
+            while let Some(msg) = rx.recv().await {
+                let request: StockRequest = serde_json::from_str(&msg).unwrap();
+                let response = serde_json::json!({
+                    "id": request.id,
+                    "stock_info": stock_info,
+                    "error": null,
+                    "warehouse": request.warehouse,
+                });
+                service.handle_tally_response(&response.to_string()).await;
+            }
+        });
+        TallyClient {
+            connection_id: Uuid::new_v4(),
+            sender: tx,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request_stock_aggregates_warehouses() {
+        let service = StockService::new();
+        let kolkata = spawn_fake_tally(&service, "4 C x 2.5: 100 mtrs");
+        let howrah = spawn_fake_tally(&service, "4 C x 2.5: 50 mtrs");
+        service.register_tally_client("kolkata", kolkata).await;
+        service.register_tally_client("howrah", howrah).await;
+
+        let info = service
+            .request_stock_details("4 C x 2.5".to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            info.text,
+            "[howrah]\n4 C x 2.5: 50 mtrs\n\n[kolkata]\n4 C x 2.5: 100 mtrs"
+        );
+        assert_eq!(info.quantity, quantity(150.0, Some("mtrs")));
+        assert!(service.pending_requests.lock().await.is_empty());
+
+        let metrics = service.metrics().await;
+        assert_eq!(metrics.total_served, 2);
+        assert_eq!(metrics.timeouts, 0);
+        assert_eq!(metrics.pending_requests, 0);
+        assert!(metrics.average_latency_ms.is_some());
+        assert_eq!(metrics.connected_warehouses, vec!["howrah", "kolkata"]);
+    }
+
+    #[tokio::test]
+    async fn test_request_stock_single_warehouse_unlabelled() {
+        let service = StockService::new();
+        let tally = spawn_fake_tally(&service, "4 C x 2.5: 100 mtrs");
+        service
+            .register_tally_client(DEFAULT_WAREHOUSE, tally)
+            .await;
+
+        let text = service
+            .request_stock("4 C x 2.5".to_string())
+            .await
+            .unwrap();
+        assert_eq!(text, "4 C x 2.5: 100 mtrs");
+
+        let no_clients = StockService::new()
+            .request_stock("4 C x 2.5".to_string())
+            .await;
+        assert_eq!(no_clients, Err("Tally client not connected".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_fails_pending_requests_immediately() {
+        let service = StockService::new();
+        // Client that accepts requests but never answers
+        let (tx, mut rx) = mpsc::channel::<String>(10);
+        let client = TallyClient {
+            connection_id: Uuid::new_v4(),
+            sender: tx,
+        };
+        service
+            .register_tally_client(DEFAULT_WAREHOUSE, client.clone())
+            .await;
+
+        let requester = service.clone();
+        let request =
+            tokio::spawn(async move { requester.request_stock("4 C x 2.5".to_string()).await });
+        rx.recv().await.unwrap();
+
+        let started = std::time::Instant::now();
+        service
+            .handle_tally_disconnect(DEFAULT_WAREHOUSE, client.connection_id)
+            .await;
+        let result = request.await.unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(
+            result,
+            Err("Tally disconnected - please try again shortly".to_string())
+        );
+        assert!(service.tally_senders.lock().await.is_empty());
+        assert!(service.pending_requests.lock().await.is_empty());
+    }
+
+    #[test]
+    fn test_structured_quantity_preferred_over_text() {
+        let response: StockResponse = serde_json::from_str(
+            r#"{"id": "1", "stock_info": "2 C x 4: 10 mtrs", "error": null, "quantity": 42.0, "unit": "Mtrs"}"#,
+        )
+        .unwrap();
+        assert_eq!(response.available_quantity(), quantity(42.0, Some("mtrs")));
+
+        let legacy: StockResponse =
+            serde_json::from_str(r#"{"id": "1", "stock_info": "2 C x 4: 10 mtrs", "error": null}"#)
+                .unwrap();
+        assert_eq!(legacy.available_quantity(), quantity(10.0, Some("mtrs")));
+    }
+}
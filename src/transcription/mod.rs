@@ -1,8 +1,12 @@
-use crate::core::http::RetryableClient;
+use crate::configuration::TranscriptionConfig;
 use crate::database::{CostEventBuilder, DatabaseService, SessionContext};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
+use tokio::process::Command;
 use tracing::info;
+use uuid::Uuid;
 
 #[derive(Debug, Error)]
 pub enum TranscriptionError {
@@ -11,17 +15,34 @@ pub enum TranscriptionError {
 }
 
 pub struct TranscriptionService {
-    client: RetryableClient,
+    // Plain client - a multipart upload can't be cloned for a retry, so each is sent once
+    client: reqwest::Client,
     groq_api_key: String,
     database: Arc<DatabaseService>,
+    config: TranscriptionConfig,
+}
+
+// Result of transcribing a single upload
+struct ChunkTranscript {
+    text: String,
+    language: Option<String>,
+    duration_seconds: f64,
 }
 
 impl TranscriptionService {
-    pub fn new(groq_api_key: String, database: Arc<DatabaseService>) -> Self {
+    pub fn new(
+        groq_api_key: String,
+        database: Arc<DatabaseService>,
+        config: TranscriptionConfig,
+    ) -> Self {
         Self {
-            client: RetryableClient::new(),
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(45))
+                .build()
+                .unwrap(),
             groq_api_key,
             database,
+            config,
         }
     }
 
@@ -31,20 +52,75 @@ impl TranscriptionService {
         context: &SessionContext,
     ) -> Result<String, TranscriptionError> {
         let audio_size = audio_data.len();
+        let requested_language = self.config.language_for(&context.platform);
+
+        let chunks = if audio_size > self.config.max_upload_bytes {
+            info!(
+                "Audio of {} bytes exceeds upload limit, splitting into chunks",
+                audio_size
+            );
+            self.split_audio(&audio_data).await?
+        } else {
+            vec![audio_data]
+        };
+
+        let mut transcripts = Vec::with_capacity(chunks.len());
+        for chunk in chunks {
+            transcripts.push(self.transcribe_chunk(chunk, requested_language).await?);
+        }
+
+        let total_duration_seconds: f64 = transcripts.iter().map(|t| t.duration_seconds).sum();
+        let detected_language = transcripts.iter().find_map(|t| t.language.clone());
+
+        // Log cost (Groq Whisper is typically $0.0001 per second)
+        let billed_seconds = (total_duration_seconds.ceil() as i32).max(10);
+        CostEventBuilder::new(context.clone(), "groq_whisper")
+            .with_cost(0.004 / 3600.0, "per_second", billed_seconds)
+            .with_metadata(serde_json::json!({
+                "audio_size_bytes": audio_size,
+                "estimated_duration_seconds": billed_seconds,
+                "chunks": transcripts.len(),
+                "model": "whisper-large-v3-turbo",
+                "requested_language": requested_language.unwrap_or("auto"),
+                "detected_language": detected_language,
+            }))
+            .log(&self.database)
+            .await
+            .map_err(|_| TranscriptionError::ProcessingError("Failed to log cost".to_string()))?;
+
+        let transcribed_text =
+            join_transcripts(transcripts.into_iter().map(|t| t.text).collect::<Vec<_>>());
+        if transcribed_text.is_empty() {
+            Ok("No speech detected".to_string())
+        } else {
+            info!("Transcribed text: {}", transcribed_text);
+            Ok(transcribed_text)
+        }
+    }
+
+    async fn transcribe_chunk(
+        &self,
+        audio_data: Vec<u8>,
+        language: Option<&str>,
+    ) -> Result<ChunkTranscript, TranscriptionError> {
+        let audio_size = audio_data.len();
 
         // Create multipart form data
-        let form = reqwest::multipart::Form::new()
+        let mut form = reqwest::multipart::Form::new()
             .part(
                 "file",
                 reqwest::multipart::Part::bytes(audio_data).file_name("audio.ogg"),
             )
             .text("model", "whisper-large-v3-turbo")
-            .text("language", "en")
             .text("response_format", "verbose_json");
+        // Leaving the language out lets Whisper detect it
+        if let Some(language) = language {
+            form = form.text("language", language.to_string());
+        }
 
         let response = self
             .client
-            .post("https://api.groq.com/openai/v1/audio/transcriptions")
+            .post(&self.config.api_url)
             .header("Authorization", format!("Bearer {}", self.groq_api_key))
             .multipart(form)
             .send()
@@ -56,36 +132,126 @@ impl TranscriptionService {
             .await
             .map_err(|e| TranscriptionError::ProcessingError(e.to_string()))?;
 
-        let transcribed_text = json_response
+        let text = json_response
             .get("text")
             .and_then(|t| t.as_str())
             .ok_or_else(|| {
                 TranscriptionError::ProcessingError("No text in response".to_string())
             })?;
 
-        // Log cost (Groq Whisper is typically $0.0001 per second)
-        // Estimate duration: ~1 second per 16KB for typical voice messages
-        let estimated_duration_seconds = (audio_size / 16000).max(10);
-        CostEventBuilder::new(context.clone(), "groq_whisper")
-            .with_cost(
-                0.004 / 3600.0,
-                "per_second",
-                estimated_duration_seconds as i32,
-            )
-            .with_metadata(serde_json::json!({
-                "audio_size_bytes": audio_size,
-                "estimated_duration_seconds": estimated_duration_seconds,
-                "model": "whisper-large-v3-turbo"
-            }))
-            .log(&self.database)
+        // Whisper reports the duration in verbose_json; otherwise estimate ~1 second per 16KB
+        let duration_seconds = json_response
+            .get("duration")
+            .and_then(|d| d.as_f64())
+            .unwrap_or((audio_size / 16000) as f64);
+
+        Ok(ChunkTranscript {
+            text: text.to_string(),
+            language: json_response
+                .get("language")
+                .and_then(|l| l.as_str())
+                .map(|l| l.to_string()),
+            duration_seconds,
+        })
+    }
+
+    // Splits on time boundaries with ffmpeg (which must be installed on the host). Segments are
+    // re-encoded so every chunk starts on a valid page and can be decoded on its own.
+    async fn split_audio(&self, audio_data: &[u8]) -> Result<Vec<Vec<u8>>, TranscriptionError> {
+        let work_dir = std::env::temp_dir().join(format!("transcription-{}", Uuid::new_v4()));
+        let result = self.split_audio_in(&work_dir, audio_data).await;
+        let _ = tokio::fs::remove_dir_all(&work_dir).await;
+        result
+    }
+
+    async fn split_audio_in(
+        &self,
+        work_dir: &Path,
+        audio_data: &[u8],
+    ) -> Result<Vec<Vec<u8>>, TranscriptionError> {
+        let processing_error =
+            |e: std::io::Error| TranscriptionError::ProcessingError(e.to_string());
+        tokio::fs::create_dir_all(work_dir)
             .await
-            .map_err(|_| TranscriptionError::ProcessingError("Failed to log cost".to_string()))?;
+            .map_err(processing_error)?;
+        let input_path = work_dir.join("input.ogg");
+        tokio::fs::write(&input_path, audio_data)
+            .await
+            .map_err(processing_error)?;
 
-        if transcribed_text.trim().is_empty() {
-            Ok("No speech detected".to_string())
-        } else {
-            info!("Transcribed text: {}", transcribed_text.trim().to_string());
-            Ok(transcribed_text.trim().to_string())
+        let output = Command::new(&self.config.ffmpeg_command)
+            .arg("-i")
+            .arg(&input_path)
+            .args(["-f", "segment", "-segment_time"])
+            .arg(self.config.chunk_seconds.to_string())
+            .args(["-c:a", "libopus", "-b:a", "32k"])
+            .arg(work_dir.join("chunk_%03d.ogg"))
+            .output()
+            .await
+            .map_err(|e| {
+                TranscriptionError::ProcessingError(format!("Failed to start ffmpeg: {}", e))
+            })?;
+        if !output.status.success() {
+            return Err(TranscriptionError::ProcessingError(format!(
+                "ffmpeg failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        let mut chunk_paths: Vec<PathBuf> = Vec::new();
+        let mut entries = tokio::fs::read_dir(work_dir)
+            .await
+            .map_err(processing_error)?;
+        while let Some(entry) = entries.next_entry().await.map_err(processing_error)? {
+            let path = entry.path();
+            if path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("chunk_"))
+            {
+                chunk_paths.push(path);
+            }
+        }
+        // Zero padded names, so lexical order is playback order
+        chunk_paths.sort();
+
+        let mut chunks = Vec::with_capacity(chunk_paths.len());
+        for path in chunk_paths {
+            chunks.push(tokio::fs::read(&path).await.map_err(processing_error)?);
         }
+        if chunks.is_empty() {
+            return Err(TranscriptionError::ProcessingError(
+                "ffmpeg produced no audio chunks".to_string(),
+            ));
+        }
+        Ok(chunks)
+    }
+}
+
+fn join_transcripts(transcripts: Vec<String>) -> String {
+    transcripts
+        .iter()
+        .map(|text| text.trim())
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_join_transcripts_keeps_order_and_skips_silence() {
+        let transcripts = vec![
+            " quote for 4 core 2.5 ".to_string(),
+            "".to_string(),
+            "100 meters discount 58%".to_string(),
+        ];
+        assert_eq!(
+            join_transcripts(transcripts),
+            "quote for 4 core 2.5 100 meters discount 58%"
+        );
+        assert_eq!(join_transcripts(vec!["  ".to_string()]), "");
     }
 }